@@ -0,0 +1,58 @@
+// Copyright (c) 2018-2021 The MobileCoin Foundation
+
+//! A source of the current time, which can be replaced in tests.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Abstraction for getting the current time.
+pub trait Clock: Send + Sync {
+    /// The current instant.
+    fn now(&self) -> Instant;
+}
+
+/// A clock backed by `Instant::now`.
+#[derive(Clone, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that only moves when told to.
+#[derive(Clone, Debug)]
+pub struct MockClock {
+    now: Arc<Mutex<Instant>>,
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self {
+            now: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+}
+
+impl MockClock {
+    /// Move the clock forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        let mut now = self.now.lock().expect("mutex poisoned");
+        *now += duration;
+    }
+
+    /// Move the clock backward by `duration`, like an NTP adjustment might.
+    pub fn rewind(&self, duration: Duration) {
+        let mut now = self.now.lock().expect("mutex poisoned");
+        *now -= duration;
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().expect("mutex poisoned")
+    }
+}
@@ -1,6 +1,8 @@
 // Copyright (c) 2018-2021 The MobileCoin Foundation
 
 //! Core types for MobileCoin's implementation of SCP.
+use crate::quorum_set::QuorumSet;
+use mc_common::NodeID;
 use mc_crypto_digestible::Digestible;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::{
@@ -11,6 +13,7 @@ use std::{
     fmt::{Debug, Display},
     hash::{Hash, Hasher},
     sync::Arc,
+    time::Duration,
 };
 
 /// A generic node identifier.
@@ -33,12 +36,54 @@ impl<T> GenericNodeId for T where
 {
 }
 
+/// The bytes that determine a node's priority during nomination.
+pub trait NodePriorityKey {
+    /// Bytes hashed, together with the slot and round, to rank this node as a nomination leader.
+    fn priority_key(&self) -> Vec<u8>;
+}
+
+impl NodePriorityKey for NodeID {
+    fn priority_key(&self) -> Vec<u8> {
+        let bytes: &[u8] = self.public_key.as_ref();
+        bytes.to_vec()
+    }
+}
+
+/// Small integer ids, e.g. for large simulations that would rather not generate a key per node.
+impl NodePriorityKey for u32 {
+    fn priority_key(&self) -> Vec<u8> {
+        self.to_be_bytes().to_vec()
+    }
+}
+
 /// Application-specific function for combining multiple values. Must be deterministic.
+///
+/// Values are passed in ascending order, so that nodes with the same confirmed nominated values
+/// combine them identically.
+///
+/// Returning an error signals that no valid combination exists, e.g. because the values
+/// conflict. The slot then does not ballot on (and so cannot externalize) a combination of these
+/// values, and combines again once more values are confirmed nominated or the ballot times out.
 pub type CombineFn<V, E> = Arc<(dyn Fn(&[V]) -> Result<Vec<V>, E> + Sync + Send)>;
 
 /// Application-specific validation of value.
 pub type ValidityFn<V, E> = Arc<(dyn Fn(&V) -> Result<(), E> + Sync + Send)>;
 
+/// Called with a slot index and its values once the slot's externalization is final.
+pub type FinalizeFn<V> = Arc<(dyn Fn(SlotIndex, &[V]) + Sync + Send)>;
+
+/// Called with a peer's ID, a slot index, the values this node externalized in the slot, and
+/// the different values the peer externalized in it.
+pub type ConflictFn<V, ID> = Arc<(dyn Fn(&ID, SlotIndex, &[V], &[V]) + Sync + Send)>;
+
+/// Chooses the nodes whose nominations a slot echoes in its first nomination round, given the
+/// slot index and the local quorum set.
+pub type LeaderFn<ID> = Arc<(dyn Fn(SlotIndex, &QuorumSet<ID>) -> Vec<ID> + Sync + Send)>;
+
+/// Maps a ballot counter to how long the slot waits on a ballot with that counter before timing
+/// out and moving to the next counter.
+pub type TimeoutFn = Arc<(dyn Fn(u32) -> Duration + Sync + Send)>;
+
 /// The node identifier is used when reasoning about messages in federated voting.
 ///
 /// For example, in production SCP, a message is signed by the node that emitted
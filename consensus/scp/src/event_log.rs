@@ -0,0 +1,145 @@
+// Copyright (c) 2018-2021 The MobileCoin Foundation
+
+//! An event-sourced view of a node: the inputs it handled, from which its state can be rebuilt.
+
+use crate::{
+    core_types::{GenericNodeId, Value},
+    msg::Msg,
+    node::ScpNode,
+};
+use mc_common::NodeID;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// An input to a node.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum Event<V: Value, ID: GenericNodeId = NodeID> {
+    /// Values passed to `propose_values`.
+    Nominate(BTreeSet<V>),
+
+    /// Messages passed to `handle_messages`.
+    Handle(Vec<Msg<V, ID>>),
+}
+
+impl<V: Value, ID: GenericNodeId + Send + 'static> Event<V, ID> {
+    /// Applies this event to `node`, returning the messages it emits.
+    pub fn apply<N: ScpNode<V, ID>>(&self, node: &mut N) -> Result<Vec<Msg<V, ID>>, String> {
+        match self {
+            Event::Nominate(values) => {
+                Ok(node.propose_values(values.clone())?.into_iter().collect())
+            }
+            Event::Handle(msgs) => node.handle_messages(msgs.clone()),
+        }
+    }
+}
+
+/// The events handled by a node, oldest first.
+///
+/// Folding the log into a freshly constructed node rebuilds the state of the node that handled
+/// the events. This relies on message handling being a pure function of a node's state and the
+/// event, so the node must be constructed with the same settings, and must not use time-based
+/// behavior such as the emission throttle. Timeouts are not events, so a node that processed
+/// timeouts can't be rebuilt this way.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct EventLog<V: Value, ID: GenericNodeId = NodeID> {
+    events: Vec<Event<V, ID>>,
+}
+
+impl<V: Value, ID: GenericNodeId + Send + 'static> Default for EventLog<V, ID> {
+    fn default() -> Self {
+        Self { events: Vec::new() }
+    }
+}
+
+impl<V: Value, ID: GenericNodeId + Send + 'static> EventLog<V, ID> {
+    /// Create an empty EventLog.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends an event to the log.
+    pub fn append(&mut self, event: Event<V, ID>) {
+        self.events.push(event);
+    }
+
+    /// The logged events, oldest first.
+    pub fn events(&self) -> &[Event<V, ID>] {
+        &self.events
+    }
+
+    /// Applies each logged event to `node`, in order.
+    pub fn replay_into<N: ScpNode<V, ID>>(&self, node: &mut N) -> Result<(), String> {
+        for event in &self.events {
+            event.apply(node)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_utils::*, Node, QuorumSet};
+    use mc_common::logger::{test_with_logger, Logger};
+    use std::iter::FromIterator;
+
+    #[test_with_logger]
+    // A node rebuilt from the log should be in the same state as the node that handled the events.
+    fn test_replay_into(logger: Logger) {
+        let new_node = |node_id: u32, peer_id: u32| {
+            get_node(
+                1,
+                test_node_id(node_id),
+                QuorumSet::new_with_node_ids(1, vec![test_node_id(peer_id)]),
+                logger.clone(),
+            )
+        };
+        let mut node1 = new_node(1, 2);
+        let mut node2 = new_node(2, 1);
+
+        // Drive node 1 with direct calls, logging each of them.
+        let mut log = EventLog::new();
+        let mut drive_node1 = |node1: &mut Node<u32, TransactionValidationError>,
+                               event: Event<u32>| {
+            let emitted = event.apply(node1).expect("error applying event");
+            log.append(event);
+            emitted
+        };
+
+        for values in &[vec![1000, 2000], vec![3000]] {
+            let values = BTreeSet::from_iter(values.iter().cloned());
+            let mut to_node2 = drive_node1(&mut node1, Event::Nominate(values.clone()));
+            let mut to_node1: Vec<Msg<u32>> =
+                node2.propose_values(values).unwrap().into_iter().collect();
+            for _ in 0..100 {
+                if to_node1.is_empty() && to_node2.is_empty() {
+                    break;
+                }
+                let from_node1 = drive_node1(&mut node1, Event::Handle(to_node1));
+                to_node1 = node2.handle_messages(to_node2).unwrap();
+                to_node2 = from_node1;
+            }
+        }
+
+        // Stop partway through slot 3.
+        let to_node1: Vec<Msg<u32>> = node2
+            .propose_values(BTreeSet::from_iter(vec![4000]))
+            .unwrap()
+            .into_iter()
+            .collect();
+        drive_node1(&mut node1, Event::Handle(to_node1));
+        assert_eq!(node1.current_slot_index(), 3);
+
+        let mut replayed = new_node(1, 2);
+        log.replay_into(&mut replayed).expect("error replaying log");
+        assert!(node1.diff_state(&replayed).is_empty());
+        assert_eq!(
+            replayed.get_externalized_values(2),
+            node1.get_externalized_values(2)
+        );
+        assert_eq!(
+            replayed.get_current_slot_metrics().num_voted_nominated,
+            node1.get_current_slot_metrics().num_voted_nominated
+        );
+    }
+}
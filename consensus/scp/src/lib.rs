@@ -5,21 +5,34 @@
 #![allow(non_snake_case)]
 #![deny(missing_docs)]
 
+pub mod clock;
 pub mod core_types;
+pub mod event_log;
 pub mod msg;
 pub mod node;
 pub mod predicates;
+#[cfg(feature = "protobuf")]
+pub mod proto;
 pub mod quorum_set;
 pub mod scp_log;
 pub mod slot;
 pub mod slot_state;
 pub mod test_utils;
+pub mod transcript;
 mod utils;
 
 #[doc(inline)]
 pub use self::{
-    core_types::{CombineFn, GenericNodeId, Identifier, SlotIndex, ValidityFn, Value},
-    msg::{Msg, Topic},
-    node::{MockScpNode, Node, ScpNode},
-    quorum_set::{QuorumSet, QuorumSetMember},
+    core_types::{
+        CombineFn, ConflictFn, FinalizeFn, GenericNodeId, Identifier, LeaderFn, SlotIndex,
+        TimeoutFn, ValidityFn, Value,
+    },
+    msg::{MessageId, Msg, MsgValidationError, SignedMsg, Topic},
+    node::{
+        AnnotatedQuorumSet, AnnotatedQuorumSetMember, BadSignaturePolicy,
+        ExternalizeValidationPolicy, FutureSlotPolicy, LateMessagePolicy, MemberStatus,
+        MockScpNode, NetworkHealth, Node, NodeMetrics, NodeSnapshot, NodeStateDiff, ScpNode,
+        StaleQuorumSetPolicy,
+    },
+    quorum_set::{QuorumSet, QuorumSetMember, QuorumSetParseError, QuorumSetValidationError},
 };
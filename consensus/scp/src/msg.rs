@@ -7,7 +7,8 @@ use crate::{
     quorum_set::QuorumSet,
 };
 use mc_common::NodeID;
-use mc_crypto_digestible::Digestible;
+use mc_crypto_digestible::{DigestTranscript, Digestible, MerlinTranscript};
+use mc_crypto_keys::{Ed25519Pair, Ed25519Signature, Ed25519SignatureError, Signer, Verifier};
 use mc_util_serial::prost::alloc::fmt::Formatter;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::{
@@ -17,6 +18,7 @@ use std::{
     fmt,
     fmt::{Debug, Display},
     hash::{Hash, Hasher},
+    mem,
 };
 
 /// The highest possible ballot counter.
@@ -263,12 +265,150 @@ impl<V: Value> Ord for Topic<V> {
     }
 }
 
+impl<V: Value> Topic<V> {
+    /// True for Nominate topics.
+    pub fn is_nominate(&self) -> bool {
+        matches!(self, Nominate(_))
+    }
+
+    /// True for NominatePrepare topics.
+    pub fn is_nominate_prepare(&self) -> bool {
+        matches!(self, NominatePrepare(_, _))
+    }
+
+    /// True for Prepare topics.
+    pub fn is_prepare(&self) -> bool {
+        matches!(self, Prepare(_))
+    }
+
+    /// True for Commit topics.
+    pub fn is_commit(&self) -> bool {
+        matches!(self, Commit(_))
+    }
+
+    /// True for Externalize topics.
+    pub fn is_externalize(&self) -> bool {
+        matches!(self, Externalize(_))
+    }
+
+    /// The values voted or accepted nominated, for Nominate and NominatePrepare topics.
+    pub fn nominated_values(&self) -> Option<BTreeSet<V>> {
+        match self {
+            Nominate(payload) | NominatePrepare(payload, _) => {
+                Some(payload.X.union(&payload.Y).cloned().collect())
+            }
+            _ => None,
+        }
+    }
+
+    /// The current ballot: `B` for NominatePrepare, Prepare, and Commit topics, and the commit
+    /// ballot `C` for Externalize topics.
+    pub fn ballot(&self) -> Option<&Ballot<V>> {
+        match self {
+            NominatePrepare(_, payload) | Prepare(payload) => Some(&payload.B),
+            Commit(payload) => Some(&payload.B),
+            Externalize(payload) => Some(&payload.C),
+            Nominate(_) => None,
+        }
+    }
+
+    /// The externalized values, for Externalize topics.
+    pub fn externalized_values(&self) -> Option<&[V]> {
+        match self {
+            Externalize(payload) => Some(&payload.C.X),
+            _ => None,
+        }
+    }
+}
+
 impl<V: Value> PartialOrd for Topic<V> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
     }
 }
 
+/// The serialized size of a part of a message, in bytes.
+pub(crate) fn serialized_size<T: Serialize>(part: &T) -> usize {
+    mc_util_serial::serialize(part)
+        .expect("serializing a message part cannot fail")
+        .len()
+}
+
+/// Why a message failed `Msg::validate`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MsgValidationError {
+    /// The sender's quorum set has a threshold larger than its number of members.
+    InvalidQuorumSet,
+
+    /// A value is both voted for and accepted as nominated (X intersects Y).
+    VotedAndAccepted,
+
+    /// A ballot has no values.
+    EmptyBallot,
+
+    /// The current ballot is lower than the prepared ballot (B < P).
+    BallotBelowPrepared,
+
+    /// The second highest prepared ballot is not lower than the highest (PP >= P).
+    PreparedPrimeNotBelowPrepared,
+
+    /// A second highest prepared ballot is given without a highest one (PP without P).
+    PreparedPrimeWithoutPrepared,
+
+    /// The lowest counter to commit exceeds the highest (CN > HN).
+    CommitCounterAboveHighest {
+        /// The lowest counter to commit.
+        CN: u32,
+
+        /// The highest counter to commit.
+        HN: u32,
+    },
+
+    /// The highest counter to commit exceeds the current ballot's counter (HN > B.N).
+    HighestCounterAboveBallot {
+        /// The highest counter to commit.
+        HN: u32,
+
+        /// The current ballot's counter.
+        BN: u32,
+    },
+}
+
+impl fmt::Display for MsgValidationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidQuorumSet => write!(f, "invalid quorum set"),
+            Self::VotedAndAccepted => write!(f, "X intersects Y"),
+            Self::EmptyBallot => write!(f, "ballot has no values"),
+            Self::BallotBelowPrepared => write!(f, "B < P"),
+            Self::PreparedPrimeNotBelowPrepared => write!(f, "PP >= P"),
+            Self::PreparedPrimeWithoutPrepared => write!(f, "PP without P"),
+            Self::CommitCounterAboveHighest { CN, HN } => write!(f, "CN {} > HN {}", CN, HN),
+            Self::HighestCounterAboveBallot { HN, BN } => write!(f, "HN {} > BN {}", HN, BN),
+        }
+    }
+}
+
+impl std::error::Error for MsgValidationError {}
+
+/// A stable identifier for a message, for correlating it across nodes.
+///
+/// The id is a digest of the sender, slot index and topic, so the same logical message has the
+/// same id on every node that sees it. The sender's quorum set is not part of the id.
+#[derive(
+    Clone, Copy, Debug, Eq, PartialEq, Hash, Ord, PartialOrd, Serialize, Deserialize, Digestible,
+)]
+pub struct MessageId(pub [u8; 32]);
+
+impl fmt::Display for MessageId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for byte in self.0.iter() {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
 /// The Messsage type for Consensus.
 #[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq, Hash, Digestible)]
 pub struct Msg<V: Value, ID: GenericNodeId = NodeID> {
@@ -313,38 +453,119 @@ impl<
         }
     }
 
-    /// Basic validation of Msg structure.
-    pub fn validate(&self) -> Result<(), String> {
+    /// A stable identifier for this message. See [MessageId].
+    pub fn message_id(&self) -> MessageId {
+        let mut transcript: MerlinTranscript = DigestTranscript::new();
+        self.sender_id
+            .append_to_transcript(b"sender_id", &mut transcript);
+        self.slot_index
+            .append_to_transcript(b"slot_index", &mut transcript);
+        self.topic.append_to_transcript(b"topic", &mut transcript);
+
+        let mut result = [0u8; 32];
+        transcript.extract_digest(&mut result);
+        MessageId(result)
+    }
+
+    /// An estimate of this message's size on the wire, in bytes: the serialized sizes of the
+    /// sender, quorum set, ballots and value sets, plus the slot index and counters.
+    pub fn estimated_size(&self) -> usize {
+        let counter_size = mem::size_of::<u32>();
+        let nominate_size = |payload: &NominatePayload<V>| {
+            serialized_size(&payload.X) + serialized_size(&payload.Y)
+        };
+        let ballot_size = |ballot: &Ballot<V>| counter_size + serialized_size(&ballot.X);
+        let prepare_size = |payload: &PreparePayload<V>| {
+            ballot_size(&payload.B)
+                + payload.P.as_ref().map_or(0, ballot_size)
+                + payload.PP.as_ref().map_or(0, ballot_size)
+                + 2 * counter_size
+        };
+
+        let topic_size = match &self.topic {
+            Nominate(payload) => nominate_size(payload),
+            NominatePrepare(nominate_payload, prepare_payload) => {
+                nominate_size(nominate_payload) + prepare_size(prepare_payload)
+            }
+            Prepare(payload) => prepare_size(payload),
+            Commit(payload) => ballot_size(&payload.B) + 3 * counter_size,
+            Externalize(payload) => ballot_size(&payload.C) + counter_size,
+        };
+
+        serialized_size(&self.sender_id)
+            + mem::size_of::<SlotIndex>()
+            + serialized_size(&self.quorum_set)
+            + topic_size
+    }
+
+    /// A hint for the transport layer when ordering its send queue. Higher is more urgent.
+    ///
+    /// Messages from later phases carry more progress, so they are sent first:
+    /// * Externalize: 3
+    /// * Commit: 2
+    /// * Prepare, NominatePrepare: 1
+    /// * Nominate: 0
+    pub fn gossip_priority(&self) -> u8 {
+        match self.topic {
+            Topic::Nominate(_) => 0,
+            Topic::NominatePrepare(_, _) | Topic::Prepare(_) => 1,
+            Topic::Commit(_) => 2,
+            Topic::Externalize(_) => 3,
+        }
+    }
+
+    /// Checks that the message is well formed, so that handling it cannot corrupt slot state.
+    pub fn validate(&self) -> Result<(), MsgValidationError> {
         if !self.quorum_set.is_valid() {
-            return Err(format!("Invalid quorum set {:?}", self.quorum_set));
+            return Err(MsgValidationError::InvalidQuorumSet);
         }
 
-        let validate_nominate = |payload: &NominatePayload<V>| -> Result<(), String> {
+        let validate_ballot = |ballot: &Ballot<V>| -> Result<(), MsgValidationError> {
+            if ballot.X.is_empty() {
+                Err(MsgValidationError::EmptyBallot)
+            } else {
+                Ok(())
+            }
+        };
+
+        let validate_nominate = |payload: &NominatePayload<V>| -> Result<(), MsgValidationError> {
             if payload.X.intersection(&payload.Y).next().is_some() {
-                Err(format!("X intersects Y, msg: {}", self))
+                Err(MsgValidationError::VotedAndAccepted)
             } else {
                 Ok(())
             }
         };
 
-        let validate_prepare = |payload: &PreparePayload<V>| -> Result<(), String> {
+        let validate_prepare = |payload: &PreparePayload<V>| -> Result<(), MsgValidationError> {
+            validate_ballot(&payload.B)?;
+
             if let Some(P) = &payload.P {
+                validate_ballot(P)?;
                 if payload.B < *P {
-                    return Err(format!("B < P, msg: {}", self));
+                    return Err(MsgValidationError::BallotBelowPrepared);
                 }
 
                 if let Some(PP) = &payload.PP {
+                    validate_ballot(PP)?;
                     if *PP >= *P {
-                        return Err(format!("PP >= P, msg: {}", self));
+                        return Err(MsgValidationError::PreparedPrimeNotBelowPrepared);
                     }
                 }
+            } else if payload.PP.is_some() {
+                return Err(MsgValidationError::PreparedPrimeWithoutPrepared);
             }
 
             if payload.CN > payload.HN {
-                return Err(format!("CN > HN, msg: {}", self));
+                return Err(MsgValidationError::CommitCounterAboveHighest {
+                    CN: payload.CN,
+                    HN: payload.HN,
+                });
             }
             if payload.HN > payload.B.N {
-                return Err(format!("HN > BN, msg: {}", self));
+                return Err(MsgValidationError::HighestCounterAboveBallot {
+                    HN: payload.HN,
+                    BN: payload.B.N,
+                });
             }
 
             Ok(())
@@ -365,12 +586,18 @@ impl<
             }
 
             Commit(ref payload) => {
+                validate_ballot(&payload.B)?;
                 if payload.CN > payload.HN {
-                    return Err(format!("CN > HN, msg: {}", self));
+                    return Err(MsgValidationError::CommitCounterAboveHighest {
+                        CN: payload.CN,
+                        HN: payload.HN,
+                    });
                 }
             }
 
-            Externalize(_) => {}
+            Externalize(ref payload) => {
+                validate_ballot(&payload.C)?;
+            }
         }
 
         Ok(())
@@ -401,12 +628,7 @@ impl<
 
     /// Returns the set of values that `self` votes or accepts as nominated.
     pub fn votes_or_accepts_nominated(&self) -> Option<BTreeSet<V>> {
-        match self.topic {
-            Nominate(ref payload) | NominatePrepare(ref payload, _) => {
-                Some(payload.X.union(&payload.Y).cloned().collect())
-            }
-            _ => None,
-        }
+        self.topic.nominated_values()
     }
 
     /// Returns the set of values that e accepts as nominated.
@@ -670,14 +892,421 @@ impl<V: Value, ID: GenericNodeId> fmt::Display for Msg<V, ID> {
     }
 }
 
+/// Domain separator for the digest that a message's sender signs.
+const MSG_SIGNATURE_CONTEXT: &[u8] = b"scp_msg_signature";
+
+impl<V: Value> Msg<V> {
+    /// Signs this message with its sender's keypair.
+    pub fn sign(&self, keypair: &Ed25519Pair) -> SignedMsg<V> {
+        let digest = self.digest32::<MerlinTranscript>(MSG_SIGNATURE_CONTEXT);
+        SignedMsg {
+            msg: self.clone(),
+            signature: keypair.sign(&digest),
+        }
+    }
+}
+
+/// A message, and its sender's signature over it.
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq, Digestible)]
+pub struct SignedMsg<V: Value> {
+    /// The signed message.
+    pub msg: Msg<V>,
+
+    /// The signature of `msg.sender_id.public_key` over `msg`.
+    pub signature: Ed25519Signature,
+}
+
+impl<V: Value> SignedMsg<V> {
+    /// Returns the message if it was signed by the key in its `sender_id`.
+    pub fn verify(&self) -> Result<&Msg<V>, Ed25519SignatureError> {
+        let digest = self.msg.digest32::<MerlinTranscript>(MSG_SIGNATURE_CONTEXT);
+        self.msg
+            .sender_id
+            .public_key
+            .verify(&digest, &self.signature)?;
+        Ok(&self.msg)
+    }
+}
+
 #[cfg(test)]
 mod msg_tests {
     use super::*;
-    use crate::test_utils::test_node_id;
+    use crate::test_utils::{test_node_id, test_node_id_and_signer};
     use rand::seq::SliceRandom;
     use std::iter::FromIterator;
     extern crate mc_util_test_helper;
 
+    #[test]
+    // Identical messages constructed independently should share a message id.
+    fn test_message_id() {
+        let make_msg = |sender: u32, slot_index: SlotIndex, values: &[u32]| {
+            Msg::new(
+                test_node_id(sender),
+                QuorumSet::new_with_node_ids(1, vec![test_node_id(2), test_node_id(3)]),
+                slot_index,
+                Nominate(NominatePayload {
+                    X: BTreeSet::from_iter(values.iter().cloned()),
+                    Y: Default::default(),
+                }),
+            )
+        };
+
+        let msg = make_msg(1, 5, &[100, 200]);
+        assert_eq!(msg.message_id(), make_msg(1, 5, &[200, 100]).message_id());
+
+        // The message id depends on the sender, slot, and topic.
+        assert_ne!(msg.message_id(), make_msg(2, 5, &[100, 200]).message_id());
+        assert_ne!(msg.message_id(), make_msg(1, 6, &[100, 200]).message_id());
+        assert_ne!(msg.message_id(), make_msg(1, 5, &[100]).message_id());
+
+        // The message id does not depend on the sender's quorum set.
+        let mut msg_with_other_quorum_set = msg.clone();
+        msg_with_other_quorum_set.quorum_set = QuorumSet::empty();
+        assert_eq!(msg.message_id(), msg_with_other_quorum_set.message_id());
+
+        assert_eq!(msg.message_id().to_string().len(), 64);
+    }
+
+    #[test]
+    // Malformed messages should fail validation with the relevant error.
+    fn test_validate() {
+        let make_msg = |topic: Topic<u32>| {
+            Msg::new(
+                test_node_id(1),
+                QuorumSet::new_with_node_ids(1, vec![test_node_id(2)]),
+                1,
+                topic,
+            )
+        };
+        let prepare = |B: Ballot<u32>, P: Option<Ballot<u32>>, PP: Option<Ballot<u32>>, CN, HN| {
+            Prepare(PreparePayload { B, P, PP, CN, HN })
+        };
+        let ballot = |N: u32, values: &[u32]| Ballot::new(N, values);
+
+        // Well formed messages of each topic.
+        let nominate = NominatePayload {
+            X: BTreeSet::from_iter(vec![100]),
+            Y: BTreeSet::from_iter(vec![200]),
+        };
+        let prepare_payload = PreparePayload {
+            B: ballot(3, &[100]),
+            P: Some(ballot(2, &[100])),
+            PP: Some(ballot(1, &[200])),
+            CN: 1,
+            HN: 2,
+        };
+        let valid_topics = vec![
+            Nominate(nominate.clone()),
+            NominatePrepare(nominate, prepare_payload.clone()),
+            Prepare(prepare_payload),
+            Commit(CommitPayload {
+                B: ballot(3, &[100]),
+                PN: 3,
+                CN: 1,
+                HN: 2,
+            }),
+            Externalize(ExternalizePayload {
+                C: ballot(1, &[100]),
+                HN: 2,
+            }),
+        ];
+        for topic in valid_topics {
+            assert_eq!(make_msg(topic).validate(), Ok(()));
+        }
+
+        let invalid_topics = vec![
+            (
+                Nominate(NominatePayload {
+                    X: BTreeSet::from_iter(vec![100, 200]),
+                    Y: BTreeSet::from_iter(vec![200]),
+                }),
+                MsgValidationError::VotedAndAccepted,
+            ),
+            (
+                prepare(ballot(1, &[]), None, None, 0, 0),
+                MsgValidationError::EmptyBallot,
+            ),
+            (
+                prepare(ballot(2, &[100]), Some(ballot(1, &[])), None, 0, 0),
+                MsgValidationError::EmptyBallot,
+            ),
+            (
+                prepare(ballot(1, &[100]), Some(ballot(2, &[100])), None, 0, 0),
+                MsgValidationError::BallotBelowPrepared,
+            ),
+            (
+                prepare(
+                    ballot(3, &[100]),
+                    Some(ballot(2, &[100])),
+                    Some(ballot(2, &[200])),
+                    0,
+                    0,
+                ),
+                MsgValidationError::PreparedPrimeNotBelowPrepared,
+            ),
+            (
+                prepare(ballot(3, &[100]), None, Some(ballot(2, &[100])), 0, 0),
+                MsgValidationError::PreparedPrimeWithoutPrepared,
+            ),
+            (
+                prepare(ballot(3, &[100]), None, None, 2, 1),
+                MsgValidationError::CommitCounterAboveHighest { CN: 2, HN: 1 },
+            ),
+            (
+                prepare(ballot(3, &[100]), None, None, 0, 4),
+                MsgValidationError::HighestCounterAboveBallot { HN: 4, BN: 3 },
+            ),
+            (
+                Commit(CommitPayload {
+                    B: ballot(3, &[]),
+                    PN: 3,
+                    CN: 1,
+                    HN: 2,
+                }),
+                MsgValidationError::EmptyBallot,
+            ),
+            (
+                Commit(CommitPayload {
+                    B: ballot(3, &[100]),
+                    PN: 3,
+                    CN: 2,
+                    HN: 1,
+                }),
+                MsgValidationError::CommitCounterAboveHighest { CN: 2, HN: 1 },
+            ),
+            (
+                Externalize(ExternalizePayload {
+                    C: ballot(1, &[]),
+                    HN: 2,
+                }),
+                MsgValidationError::EmptyBallot,
+            ),
+        ];
+        for (topic, expected_err) in invalid_topics {
+            let msg = make_msg(topic);
+            assert_eq!(msg.validate(), Err(expected_err), "{}", msg);
+        }
+
+        // A message with an unsatisfiable quorum set is invalid.
+        let mut msg = make_msg(Nominate(NominatePayload {
+            X: BTreeSet::from_iter(vec![100]),
+            Y: Default::default(),
+        }));
+        msg.quorum_set = QuorumSet::new_with_node_ids(2, vec![test_node_id(2)]);
+        assert_eq!(msg.validate(), Err(MsgValidationError::InvalidQuorumSet));
+    }
+
+    #[test]
+    // A signed message should verify, unless it was altered or signed by another key.
+    fn test_sign_and_verify() {
+        let (sender, keypair) = test_node_id_and_signer(1);
+        let (_other, other_keypair) = test_node_id_and_signer(2);
+        let msg = Msg::new(
+            sender,
+            QuorumSet::new_with_node_ids(1, vec![test_node_id(2), test_node_id(3)]),
+            5,
+            Nominate(NominatePayload {
+                X: BTreeSet::from_iter(vec![100, 200]),
+                Y: Default::default(),
+            }),
+        );
+
+        let signed_msg = msg.sign(&keypair);
+        assert_eq!(signed_msg.verify().expect("verification failed"), &msg);
+
+        // Tampering with the topic, the slot, or the quorum set invalidates the signature.
+        let mut tampered = signed_msg.clone();
+        tampered.msg.topic = Nominate(NominatePayload {
+            X: BTreeSet::from_iter(vec![100, 300]),
+            Y: Default::default(),
+        });
+        assert!(tampered.verify().is_err());
+
+        let mut tampered = signed_msg.clone();
+        tampered.msg.slot_index = 6;
+        assert!(tampered.verify().is_err());
+
+        let mut tampered = signed_msg.clone();
+        tampered.msg.quorum_set = QuorumSet::empty();
+        assert!(tampered.verify().is_err());
+
+        // A message signed by someone other than its sender is rejected.
+        assert!(msg.sign(&other_keypair).verify().is_err());
+    }
+
+    #[test]
+    // The estimated size should be close to the serialized size, and grow with the nominated values.
+    fn test_estimated_size() {
+        let make_msg = |num_values: u32| {
+            Msg::new(
+                test_node_id(1),
+                QuorumSet::new_with_node_ids(2, vec![test_node_id(2), test_node_id(3)]),
+                1,
+                NominatePrepare(
+                    NominatePayload {
+                        X: (100_000..100_000 + num_values).collect(),
+                        Y: (200_000..200_000 + num_values).collect(),
+                    },
+                    PreparePayload {
+                        B: Ballot::new(2, &[100_000]),
+                        P: Some(Ballot::new(1, &[100_000])),
+                        PP: None,
+                        CN: 0,
+                        HN: 0,
+                    },
+                ),
+            )
+        };
+
+        let mut previous_size = 0;
+        for num_values in &[0, 10, 100, 1000] {
+            let msg = make_msg(*num_values);
+            let size = msg.estimated_size();
+            let serialized_size = mc_util_serial::serialize(&msg).unwrap().len();
+            // Field tags and lengths of the message framing are not counted.
+            assert!(
+                size <= serialized_size && size + 100 >= serialized_size,
+                "estimated {} bytes, serialized {} bytes",
+                size,
+                serialized_size
+            );
+
+            // Each additional value adds a few bytes.
+            if *num_values >= 100 {
+                assert!(size > previous_size + 2 * *num_values as usize);
+            }
+            previous_size = size;
+        }
+    }
+
+    #[test]
+    // Topic accessors should return the relevant payload fields, and None where inapplicable.
+    fn test_topic_accessors() {
+        let nominate_payload = NominatePayload {
+            X: BTreeSet::from_iter(vec![1000, 2000]),
+            Y: BTreeSet::from_iter(vec![3000]),
+        };
+        let ballot = Ballot::new(2, &[1000, 2000]);
+        let prepare_payload = PreparePayload {
+            B: ballot.clone(),
+            P: None,
+            PP: None,
+            CN: 0,
+            HN: 0,
+        };
+
+        let nominate: Topic<u32> = Nominate(nominate_payload.clone());
+        assert_eq!(
+            nominate.nominated_values(),
+            Some(BTreeSet::from_iter(vec![1000, 2000, 3000]))
+        );
+        assert_eq!(nominate.ballot(), None);
+        assert_eq!(nominate.externalized_values(), None);
+
+        let nominate_prepare = NominatePrepare(nominate_payload, prepare_payload.clone());
+        assert_eq!(
+            nominate_prepare.nominated_values(),
+            Some(BTreeSet::from_iter(vec![1000, 2000, 3000]))
+        );
+        assert_eq!(nominate_prepare.ballot(), Some(&ballot));
+        assert_eq!(nominate_prepare.externalized_values(), None);
+
+        let prepare = Prepare(prepare_payload);
+        assert_eq!(prepare.nominated_values(), None);
+        assert_eq!(prepare.ballot(), Some(&ballot));
+        assert_eq!(prepare.externalized_values(), None);
+
+        let commit = Commit(CommitPayload {
+            B: ballot.clone(),
+            PN: 2,
+            CN: 1,
+            HN: 2,
+        });
+        assert_eq!(commit.nominated_values(), None);
+        assert_eq!(commit.ballot(), Some(&ballot));
+        assert_eq!(commit.externalized_values(), None);
+
+        let externalize = Externalize(ExternalizePayload {
+            C: ballot.clone(),
+            HN: 2,
+        });
+        assert_eq!(externalize.nominated_values(), None);
+        assert_eq!(externalize.ballot(), Some(&ballot));
+        assert_eq!(externalize.externalized_values(), Some(&[1000, 2000][..]));
+    }
+
+    #[test]
+    // Each topic predicate should hold for exactly its own variant.
+    fn test_topic_predicates() {
+        let nominate_payload = NominatePayload {
+            X: BTreeSet::from_iter(vec![1000]),
+            Y: Default::default(),
+        };
+        let ballot = Ballot::new(1, &[1000]);
+        let prepare_payload = PreparePayload {
+            B: ballot.clone(),
+            P: None,
+            PP: None,
+            CN: 0,
+            HN: 0,
+        };
+        let topics: Vec<Topic<u32>> = vec![
+            Nominate(nominate_payload.clone()),
+            NominatePrepare(nominate_payload, prepare_payload.clone()),
+            Prepare(prepare_payload),
+            Commit(CommitPayload {
+                B: ballot.clone(),
+                PN: 1,
+                CN: 1,
+                HN: 1,
+            }),
+            Externalize(ExternalizePayload { C: ballot, HN: 1 }),
+        ];
+
+        for (i, topic) in topics.iter().enumerate() {
+            let predicates = [
+                topic.is_nominate(),
+                topic.is_nominate_prepare(),
+                topic.is_prepare(),
+                topic.is_commit(),
+                topic.is_externalize(),
+            ];
+            for (j, holds) in predicates.iter().enumerate() {
+                assert_eq!(*holds, i == j, "topic {:?}, predicate {}", topic, j);
+            }
+        }
+    }
+
+    #[test]
+    // Messages from later phases should have higher gossip priority.
+    fn test_gossip_priority() {
+        let ballot = Ballot::new(2, &[1000]);
+        let make_msg = |topic: Topic<u32>| Msg::new(test_node_id(1), QuorumSet::empty(), 1, topic);
+
+        let nominate = make_msg(Nominate(NominatePayload {
+            X: BTreeSet::from_iter(vec![1000]),
+            Y: BTreeSet::default(),
+        }));
+        let prepare = make_msg(Prepare(PreparePayload {
+            B: ballot.clone(),
+            P: None,
+            PP: None,
+            CN: 0,
+            HN: 0,
+        }));
+        let commit = make_msg(Commit(CommitPayload {
+            B: ballot.clone(),
+            PN: 2,
+            CN: 1,
+            HN: 2,
+        }));
+        let externalize = make_msg(Externalize(ExternalizePayload { C: ballot, HN: 2 }));
+
+        assert!(externalize.gossip_priority() > commit.gossip_priority());
+        assert!(commit.gossip_priority() > prepare.gossip_priority());
+        assert!(prepare.gossip_priority() > nominate.gossip_priority());
+    }
+
     #[test]
     /// Prepare implies "vote_or_accept prepare" for B, P, and PP.
     fn test_votes_or_accepts_prepared_with_prepare_topic() {
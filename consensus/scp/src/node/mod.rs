@@ -7,5 +7,9 @@
 mod node_impl;
 mod node_trait;
 
-pub use node_impl::Node;
+pub use node_impl::{
+    AnnotatedQuorumSet, AnnotatedQuorumSetMember, BadSignaturePolicy, ExternalizeValidationPolicy,
+    FutureSlotPolicy, LateMessagePolicy, MemberStatus, NetworkHealth, Node, NodeMetrics,
+    NodeSnapshot, NodeStateDiff, StaleQuorumSetPolicy,
+};
 pub use node_trait::{MockScpNode, ScpNode};
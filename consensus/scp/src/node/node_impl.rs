@@ -2,41 +2,330 @@
 
 //! A node determines whether transactions are valid, and participates in voting with the members of its quorum set.
 use crate::{
-    core_types::{CombineFn, SlotIndex, ValidityFn, Value},
-    msg::{ExternalizePayload, Msg, Topic},
-    quorum_set::QuorumSet,
-    slot::{ScpSlot, Slot, SlotMetrics},
+    clock::{Clock, MockClock, SystemClock},
+    core_types::{
+        Ballot, CombineFn, ConflictFn, FinalizeFn, GenericNodeId, LeaderFn, NodePriorityKey,
+        SlotIndex, TimeoutFn, ValidityFn, Value,
+    },
+    msg::{ExternalizePayload, Msg, SignedMsg, Topic, INFINITY},
+    quorum_set::{QuorumSet, QuorumSetMember},
+    slot::{Phase, ScpSlot, Slot, SlotMetrics},
+    slot_state::SlotState,
     ScpNode,
 };
 use mc_common::{
     logger::{log, Logger},
-    NodeID,
+    Hash, LruCache, NodeID,
 };
+use mc_crypto_digestible::{Digestible, MerlinTranscript};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::json;
 use std::{
-    collections::{BTreeSet, HashMap},
+    cmp,
+    collections::{BTreeSet, HashMap, HashSet, VecDeque},
     fmt::Display,
-    time::Duration,
+    mem::{self, Discriminant},
+    ops::{Bound, RangeBounds},
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
 /// Default limit on number of externalized slots to store.
 const MAX_EXTERNALIZED_SLOTS: usize = 1;
 
+/// Default limit on number of buffered messages for future slots.
+const MAX_BUFFERED_FUTURE_MSGS: usize = 1000;
+
+/// Maximum number of recently seen message hashes to remember.
+const LAST_SEEN_HISTORY_SIZE: usize = 1000;
+
+/// Number of recent externalization latencies used when computing network health.
+const NETWORK_HEALTH_LATENCY_SAMPLES: usize = 10;
+
+/// A peer counts as participating if it was heard from within this many `scp_timebase` intervals.
+const NETWORK_HEALTH_PEER_WINDOW: u32 = 10;
+
+/// A current slot open for longer than this many `scp_timebase` intervals is considered stale.
+const NETWORK_HEALTH_STALE_SLOT: u32 = 5;
+
+/// What a node should do when an externalized value fails `validity_fn`.
+///
+/// By the time a slot externalizes, the network has already agreed on its values, so a value
+/// failing local validation indicates either a bug or that this node's validation rules differ
+/// from the rest of the network.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ExternalizeValidationPolicy {
+    /// Refuse to externalize, returning an error and leaving the node on the current slot.
+    ///
+    /// This never records a value the node considers invalid, but the node stops making
+    /// progress with the rest of the network until an operator intervenes.
+    Abort,
+
+    /// Log an error and externalize all values, including invalid ones.
+    ///
+    /// The node stays in agreement with the network, but the application must be prepared to
+    /// handle values that it would not itself have accepted.
+    ProceedWithWarning,
+
+    /// Log an error and externalize only the valid values.
+    ///
+    /// The node keeps making progress and only records values it considers valid, but its
+    /// externalized values may then differ from those of other nodes.
+    ProceedFilteringInvalid,
+}
+
+impl Default for ExternalizeValidationPolicy {
+    fn default() -> Self {
+        Self::ProceedWithWarning
+    }
+}
+
+/// What a node should do with a message whose embedded quorum set differs from the one expected
+/// for its sender, e.g. because the sender has not yet applied a reconfiguration.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StaleQuorumSetPolicy {
+    /// Handle the message as usual.
+    Accept,
+
+    /// Log a warning and handle the message as usual.
+    Warn,
+
+    /// Log a warning and drop the message.
+    Reject,
+}
+
+impl Default for StaleQuorumSetPolicy {
+    fn default() -> Self {
+        Self::Warn
+    }
+}
+
+/// What a node should do with a message for a slot it has not yet reached.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FutureSlotPolicy {
+    /// Drop the message, logging an error. This is the default.
+    Drop,
+
+    /// Buffer the message, and handle it once the node reaches its slot. At most
+    /// `Node::max_buffered_future_msgs` messages are buffered (1000 by default), and the oldest
+    /// are dropped beyond that.
+    Buffer,
+
+    /// Drop the message, logging an error and counting it in `Node::future_msgs_rejected`.
+    /// Frequent future slot messages may indicate clock or configuration skew.
+    Reject,
+}
+
+impl Default for FutureSlotPolicy {
+    fn default() -> Self {
+        Self::Drop
+    }
+}
+
+/// What a node should do with a late message for a slot it has already externalized.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LateMessagePolicy {
+    /// Pass the message to the externalized slot, which only responds if its state changes.
+    Handle,
+
+    /// As `Handle`, but if the slot does not respond and the message's sender has not
+    /// externalized the slot, re-send this node's Externalize message to help it catch up.
+    ResendExternalize,
+}
+
+impl Default for LateMessagePolicy {
+    fn default() -> Self {
+        Self::Handle
+    }
+}
+
+/// What a node should do with a signed message whose signature does not match its sender's
+/// public key, e.g. because the message was forged or altered in transit. Defaults to `Reject`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BadSignaturePolicy {
+    /// Handle the message without verifying its signature, e.g. because the transport layer
+    /// already authenticates peers.
+    Accept,
+
+    /// Log a warning and handle the message as usual.
+    Warn,
+
+    /// Log a warning and return an error without handling the message.
+    Reject,
+}
+
+impl Default for BadSignaturePolicy {
+    fn default() -> Self {
+        Self::Reject
+    }
+}
+
+/// An aggregate estimate of network health, as observed by a single node.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NetworkHealth {
+    /// Number of distinct quorum set members heard from recently.
+    pub peers_heard: usize,
+
+    /// Number of distinct nodes in the local quorum set.
+    pub peers_total: usize,
+
+    /// Mean time taken to externalize recent slots, if any slot has externalized.
+    pub externalize_latency: Option<Duration>,
+
+    /// Time elapsed since the current slot began.
+    pub current_slot_age: Duration,
+
+    /// Health score in [0.0, 1.0], where 1.0 is fully healthy.
+    ///
+    /// The score is a weighted sum of three components, each in [0.0, 1.0]:
+    /// * 0.50 * peer participation: the fraction of quorum set members heard from recently.
+    /// * 0.25 * externalization latency: 1.0 if recent slots externalized within `scp_timebase`,
+    ///   decaying as `scp_timebase / latency` beyond that, and 0.0 if no slot has externalized.
+    /// * 0.25 * current-slot freshness: 1.0 while the current slot is younger than
+    ///   `5 * scp_timebase`, decaying as `5 * scp_timebase / age` beyond that.
+    pub score: f64,
+}
+
+/// Counters accumulated over every slot this node has worked on, including the current one.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NodeMetrics {
+    /// Number of slots externalized.
+    pub num_externalized: u64,
+
+    /// Number of messages received and emitted.
+    pub num_msgs: usize,
+
+    /// Number of nomination and ballot timeouts that have fired.
+    pub num_timeouts: usize,
+
+    /// Number of times `shadow_validity_fn` disagreed with `validity_fn`.
+    pub num_validity_divergences: u64,
+}
+
+/// A quorum set annotated with the reachability of each member, as observed by a single node.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AnnotatedQuorumSet<ID: GenericNodeId = NodeID> {
+    /// Threshold of the quorum set.
+    pub threshold: u32,
+
+    /// Members of the quorum set, in the same order.
+    pub members: Vec<AnnotatedQuorumSetMember<ID>>,
+}
+
+/// A member of an [AnnotatedQuorumSet].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AnnotatedQuorumSetMember<ID: GenericNodeId = NodeID> {
+    /// A single node.
+    Node(MemberStatus<ID>),
+
+    /// An inner quorum set.
+    InnerSet(AnnotatedQuorumSet<ID>),
+}
+
+/// Reachability of a single quorum set member.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MemberStatus<ID: GenericNodeId = NodeID> {
+    /// The member.
+    pub node_id: ID,
+
+    /// Time since a message was last received from the member, if ever.
+    pub last_heard_ago: Option<Duration>,
+
+    /// True if the member was heard from within the same window used by `network_health`.
+    pub reachable: bool,
+}
+
+/// Differences between the state of two nodes, as (ours, theirs) pairs.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NodeStateDiff<V: Value> {
+    /// Current slot indices, if they differ.
+    pub current_slot_index: Option<(SlotIndex, SlotIndex)>,
+
+    /// Phases of the current slot, if they differ. Only compared if both nodes are on the same
+    /// slot.
+    pub phase: Option<(Phase, Phase)>,
+
+    /// Current ballots, if they differ. Only compared if both nodes are on the same slot.
+    pub ballot: Option<(Ballot<V>, Ballot<V>)>,
+
+    /// Externalized values that differ, by slot index. Slots outside either node's retained
+    /// window are not compared.
+    pub externalized: Vec<(SlotIndex, Option<Vec<V>>, Option<Vec<V>>)>,
+}
+
+impl<V: Value> NodeStateDiff<V> {
+    /// True if no differences were found.
+    pub fn is_empty(&self) -> bool {
+        self.current_slot_index.is_none()
+            && self.phase.is_none()
+            && self.ballot.is_none()
+            && self.externalized.is_empty()
+    }
+}
+
+/// The consensus state of a node, for persisting across restarts.
+///
+/// Configuration (policies, callbacks, the clock) and bookkeeping such as the seen-message
+/// history and metrics are not included.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct NodeSnapshot<V: Value, ID: GenericNodeId = NodeID> {
+    /// Local node ID.
+    pub node_id: ID,
+
+    /// Local node quorum set.
+    pub quorum_set: QuorumSet<ID>,
+
+    /// The quorum set the current slot was created with.
+    pub current_slot_quorum_set: QuorumSet<ID>,
+
+    /// State of the current slot.
+    pub current_slot: SlotState<V, ID>,
+
+    /// Values of the retained externalized slots, oldest first.
+    pub externalized: Vec<(SlotIndex, Vec<V>)>,
+
+    /// Maximum number of externalized slots to retain.
+    pub max_externalized_slots: usize,
+}
+
+/// What a node records about each slot it externalizes.
+struct ExternalizedSlotRecord<V: Value> {
+    /// The slot's index.
+    slot_index: SlotIndex,
+
+    /// Number of messages received and emitted to externalize the slot.
+    msg_count: usize,
+
+    /// The slot's values, if invalid values were filtered out of them.
+    filtered_values: Option<Vec<V>>,
+
+    /// Time taken to externalize the slot.
+    latency: Duration,
+}
+
 /// A node participates in federated voting.
-pub struct Node<V: Value, ValidationError: Clone + Display> {
+pub struct Node<V: Value, ValidationError: Clone + Display, ID: GenericNodeId = NodeID> {
     /// Local node ID.
-    pub ID: NodeID,
+    pub ID: ID,
 
     /// Local node quorum set.
-    pub Q: QuorumSet,
+    pub Q: QuorumSet<ID>,
 
     /// The current slot that this node is attempting to reach consensus on.
-    current_slot: Box<dyn ScpSlot<V>>,
+    current_slot: Box<dyn ScpSlot<V, ID>>,
+
+    /// The quorum set the current slot was created with. Changes to `Q` apply from the next
+    /// slot, so messages emitted for the current slot must carry this quorum set.
+    current_slot_quorum_set: QuorumSet<ID>,
+
+    /// The quorum set carried by the most recent message emitted by this node.
+    last_emitted_quorum_set: Option<QuorumSet<ID>>,
 
     /// Maximum number of stored externalized slots.
     max_externalized_slots: usize,
 
     /// A queue of externalized slots, ordered by increasing slot index.
-    externalized_slots: Vec<Box<dyn ScpSlot<V>>>,
+    externalized_slots: Vec<Box<dyn ScpSlot<V, ID>>>,
 
     /// Application-specific validation of value.
     validity_fn: ValidityFn<V, ValidationError>,
@@ -44,15 +333,161 @@ pub struct Node<V: Value, ValidationError: Clone + Display> {
     /// Application-specific function for combining multiple values. Must be deterministic.
     combine_fn: CombineFn<V, ValidationError>,
 
+    /// Combine functions registered for ranges of slot indexes, used instead of `combine_fn`
+    /// for slots in those ranges. Later registrations take precedence.
+    combine_fn_registry: Vec<(
+        (Bound<SlotIndex>, Bound<SlotIndex>),
+        CombineFn<V, ValidationError>,
+    )>,
+
     /// Logger.
     logger: Logger,
 
     /// Sets the 'base round timeout' and the 'base ballot timeout' when creating a slot.
     /// (Defaults to 1 second to match the SCP whitepaper specification.)
     pub scp_timebase: Duration,
+
+    /// The last time a message was received from each peer in this node's quorum set. Other
+    /// senders are not tracked, so that this stays bounded by the quorum set's size.
+    last_heard: HashMap<ID, Instant>,
+
+    /// The time at which the current slot began.
+    current_slot_started_at: Instant,
+
+    /// The last time the current slot emitted a message in response to a proposal or to incoming
+    /// messages, or began if it has not yet done so.
+    last_progress: Instant,
+
+    /// Records of recently externalized slots, ordered by increasing slot index. Kept for the
+    /// retained externalized slots, and for at least `NETWORK_HEALTH_LATENCY_SAMPLES` slots.
+    externalized_records: VecDeque<ExternalizedSlotRecord<V>>,
+
+    /// Hashes of recently handled messages, and the slot index each message belongs to.
+    seen_msg_hashes: LruCache<Hash, SlotIndex>,
+
+    /// An optional validity function that is evaluated alongside `validity_fn` when values are
+    /// proposed or externalized. Its result is only logged and counted, and never affects consensus.
+    pub shadow_validity_fn: Option<ValidityFn<V, ValidationError>>,
+
+    /// Number of times `shadow_validity_fn` disagreed with `validity_fn`.
+    validity_divergences: u64,
+
+    /// Number of messages received and emitted for the current slot.
+    current_slot_msg_count: usize,
+
+    /// Number of slots externalized.
+    num_externalized: u64,
+
+    /// Number of messages received and emitted for slots before the current one.
+    past_slots_msg_count: usize,
+
+    /// Number of timeouts that fired in slots before the current one.
+    past_slots_num_timeouts: usize,
+
+    /// What to do when an externalized value fails `validity_fn`.
+    pub externalize_validation_policy: ExternalizeValidationPolicy,
+
+    /// What to do with messages embedding a stale quorum set.
+    pub stale_quorum_set_policy: StaleQuorumSetPolicy,
+
+    /// Fingerprint of the quorum set expected from each peer, and the slot index from which it
+    /// applies.
+    expected_quorum_sets: HashMap<ID, (SlotIndex, [u8; 32])>,
+
+    /// What to do with messages for future slots.
+    pub future_slot_policy: FutureSlotPolicy,
+
+    /// What to do with late messages for retained externalized slots.
+    pub late_message_policy: LateMessagePolicy,
+
+    /// What to do with signed messages whose signature does not verify.
+    pub bad_signature_policy: BadSignaturePolicy,
+
+    /// Maximum number of distinct ballots tracked by each slot, or `None` for no limit.
+    /// Applies to slots created after it is set.
+    pub max_tracked_ballots: Option<usize>,
+
+    /// Maximum estimated size of an outgoing message, or `None` for no limit. See
+    /// `Slot::max_msg_size`. Applies to slots created after it is set.
+    pub max_msg_size: Option<usize>,
+
+    /// Ballot counter beyond which ballot timeouts no longer increment the counter, or `None` for
+    /// no limit. See `Slot::max_ballot_counter`. Applies to slots created after it is set.
+    pub max_ballot_counter: Option<u32>,
+
+    /// Messages for future slots, oldest first, to be processed once this node reaches their slot.
+    future_msgs: VecDeque<Msg<V, ID>>,
+
+    /// Maximum number of buffered messages for future slots, under `FutureSlotPolicy::Buffer`.
+    /// When exceeded, the oldest buffered messages are dropped.
+    pub max_buffered_future_msgs: usize,
+
+    /// Number of buffered future slot messages dropped because the buffer was full.
+    future_msgs_dropped: u64,
+
+    /// Number of future slot messages rejected by `FutureSlotPolicy::Reject`.
+    future_msgs_rejected: u64,
+
+    /// Values proposed while the current slot could no longer nominate them. They are proposed
+    /// to the next slot when the current one externalizes.
+    pending_values: BTreeSet<V>,
+
+    /// Source of the current time.
+    clock: Arc<dyn Clock>,
+
+    /// The clock passed to `set_mock_clock`, if it is still in use, so that `advance_clock` can
+    /// move it.
+    mock_clock: Option<MockClock>,
+
+    /// Minimum interval between outgoing messages of the same topic for the current slot.
+    /// Messages emitted within the interval are withheld, and the most recent one is sent by
+    /// `process_timeouts` once the interval has elapsed. `None` disables throttling.
+    pub emission_throttle: Option<Duration>,
+
+    /// Slot index, topic and time of the last message emitted for the current slot.
+    last_emission: Option<(SlotIndex, Discriminant<Topic<V>>, Instant)>,
+
+    /// The most recent message withheld by the emission throttle, if any. Sent by
+    /// `process_timeouts`.
+    throttled_msg: Option<Msg<V, ID>>,
+
+    /// The current slot's message nominating pending values, emitted when the previous slot
+    /// externalized. Sent by `process_timeouts`, since `handle_message` returns only one message.
+    pending_values_msg: Option<Msg<V, ID>>,
+
+    /// Maximum serialized size, in bytes, of a value this node will propose. Proposals
+    /// containing a larger value are rejected. `None` disables the check.
+    ///
+    /// This is set per node rather than declared by the value type, because `Value` is
+    /// implemented for every type with the required traits, so a type cannot supply its own
+    /// bound.
+    pub max_value_size: Option<usize>,
+
+    /// Called when this node emits an Externalize message with `HN == INFINITY` for a slot.
+    ///
+    /// Externalizing is provisional until then: the node has confirmed a commit, but has not yet
+    /// confirmed that its whole quorum has done the same. Each slot is finalized at most once.
+    pub on_finalize: Option<FinalizeFn<V>>,
+
+    /// Called when a peer externalizes different values for a retained slot than this node did,
+    /// which means that safety has been violated.
+    pub on_conflict: Option<ConflictFn<V, ID>>,
+
+    /// Chooses the first nomination round's leaders for each new slot. If `None`, the leader is
+    /// chosen by priority.
+    leader_fn: Option<LeaderFn<ID>>,
+
+    /// Computes ballot timeouts for the current and future slots. If `None`, slots use linear
+    /// timeouts.
+    timeout_fn: Option<TimeoutFn>,
 }
 
-impl<V: Value, ValidationError: Clone + Display + 'static> Node<V, ValidationError> {
+impl<
+        V: Value,
+        ValidationError: Clone + Display + 'static,
+        ID: GenericNodeId + NodePriorityKey + Serialize + DeserializeOwned + Send + 'static,
+    > Node<V, ValidationError, ID>
+{
     /// Creates a new Node.
     ///
     /// # Arguments
@@ -63,13 +498,46 @@ impl<V: Value, ValidationError: Clone + Display + 'static> Node<V, ValidationErr
     /// * `current_slot_index` - Index of the slot to begin performing consensus on.
     /// * `logger`
     pub fn new(
-        node_id: NodeID,
-        quorum_set: QuorumSet,
+        node_id: ID,
+        quorum_set: QuorumSet<ID>,
         validity_fn: ValidityFn<V, ValidationError>,
         combine_fn: CombineFn<V, ValidationError>,
         current_slot_index: SlotIndex,
         logger: Logger,
     ) -> Self {
+        Self::with_seen_history_size(
+            node_id,
+            quorum_set,
+            validity_fn,
+            combine_fn,
+            current_slot_index,
+            LAST_SEEN_HISTORY_SIZE,
+            logger,
+        )
+    }
+
+    /// Creates a new Node that remembers the hashes of the given number of recently seen
+    /// messages, and ignores those messages if they are received again.
+    ///
+    /// # Arguments
+    /// * `node_id` - This node's ID.
+    /// * `quorum_set` - This node's quorum set.
+    /// * `validity_fn` - Validates a value.
+    /// * `combine_fn` - Combines a set of values into a composite value (i.e. block).
+    /// * `current_slot_index` - Index of the slot to begin performing consensus on.
+    /// * `seen_history_size` - Number of message hashes to remember. Must be nonzero.
+    /// * `logger`
+    pub fn with_seen_history_size(
+        node_id: ID,
+        quorum_set: QuorumSet<ID>,
+        validity_fn: ValidityFn<V, ValidationError>,
+        combine_fn: CombineFn<V, ValidationError>,
+        current_slot_index: SlotIndex,
+        seen_history_size: usize,
+        logger: Logger,
+    ) -> Self {
+        assert!(seen_history_size > 0, "seen_history_size must be nonzero");
+
         let slot = Slot::new(
             node_id.clone(),
             quorum_set.clone(),
@@ -79,850 +547,4035 @@ impl<V: Value, ValidationError: Clone + Display + 'static> Node<V, ValidationErr
             logger.clone(),
         );
 
+        let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+
         Self {
             ID: node_id,
-            Q: quorum_set,
+            Q: quorum_set.clone(),
             current_slot: Box::new(slot),
+            current_slot_quorum_set: quorum_set,
+            last_emitted_quorum_set: None,
             max_externalized_slots: MAX_EXTERNALIZED_SLOTS,
             externalized_slots: Vec::new(),
             validity_fn,
             combine_fn,
+            combine_fn_registry: Vec::new(),
             logger,
             scp_timebase: Duration::from_millis(1000),
+            last_heard: HashMap::default(),
+            current_slot_started_at: clock.now(),
+            last_progress: clock.now(),
+            externalized_records: VecDeque::new(),
+            seen_msg_hashes: LruCache::new(seen_history_size),
+            shadow_validity_fn: None,
+            validity_divergences: 0,
+            current_slot_msg_count: 0,
+            num_externalized: 0,
+            past_slots_msg_count: 0,
+            past_slots_num_timeouts: 0,
+            externalize_validation_policy: ExternalizeValidationPolicy::default(),
+            stale_quorum_set_policy: StaleQuorumSetPolicy::default(),
+            expected_quorum_sets: HashMap::default(),
+            future_slot_policy: FutureSlotPolicy::default(),
+            late_message_policy: LateMessagePolicy::default(),
+            bad_signature_policy: BadSignaturePolicy::default(),
+            max_tracked_ballots: None,
+            max_msg_size: None,
+            max_ballot_counter: None,
+            future_msgs: VecDeque::new(),
+            max_buffered_future_msgs: MAX_BUFFERED_FUTURE_MSGS,
+            future_msgs_dropped: 0,
+            future_msgs_rejected: 0,
+            pending_values: BTreeSet::new(),
+            clock,
+            mock_clock: None,
+            emission_throttle: None,
+            last_emission: None,
+            throttled_msg: None,
+            pending_values_msg: None,
+            max_value_size: None,
+            on_finalize: None,
+            on_conflict: None,
+            leader_fn: None,
+            timeout_fn: None,
         }
     }
 
-    // Record the values externalized by the current slot and advance the current slot.
-    fn externalize(&mut self, payload: &ExternalizePayload<V>) -> Result<(), String> {
-        let slot_index = self.current_slot.get_index();
-
-        // Log an error if any invalid values were externalized.
-        // This is be redundant, but may be helpful during development.
-        for value in &payload.C.X {
-            if let Err(e) = (self.validity_fn)(value) {
-                log::error!(
-                    self.logger,
-                    "Slot {} externalized invalid value: {:?}, {}",
-                    slot_index,
-                    value,
-                    e
-                );
+    /// Propose values for this node to nominate, collapsing duplicates.
+    ///
+    /// Returns the outgoing message, if any, together with the values that appeared more than
+    /// once in `values`.
+    pub fn nominate_reporting_duplicates(
+        &mut self,
+        values: Vec<V>,
+    ) -> Result<(Option<Msg<V, ID>>, BTreeSet<V>), String> {
+        let mut unique = BTreeSet::new();
+        let mut duplicates = BTreeSet::new();
+        for value in values {
+            if unique.contains(&value) {
+                duplicates.insert(value);
+            } else {
+                unique.insert(value);
             }
         }
 
-        let next_slot = Box::new(Slot::new(
-            self.ID.clone(),
-            self.Q.clone(),
-            slot_index + 1,
-            self.validity_fn.clone(),
-            self.combine_fn.clone(),
-            self.logger.clone(),
-        ));
+        if !duplicates.is_empty() {
+            log::warn!(
+                self.logger,
+                "Nomination contained duplicate values: {:?}",
+                duplicates
+            );
+        }
 
-        // Advance to the next slot.
-        let externalized_slot = std::mem::replace(&mut self.current_slot, next_slot);
+        let msg = self.propose_values(unique)?;
+        Ok((msg, duplicates))
+    }
 
-        self.push_externalized_slot(externalized_slot);
+    /// A digest of the values externalized in the given slot, if it is in the retained window.
+    ///
+    /// Values are digested in canonical (sorted) order, so nodes that externalize the same set
+    /// of values compute the same digest.
+    pub fn externalized_digest(&self, slot_index: SlotIndex) -> Option<[u8; 32]> {
+        self.get_externalized_values(slot_index).map(|values| {
+            let values: BTreeSet<V> = values.into_iter().collect();
+            values.digest32::<MerlinTranscript>(b"scp_externalized_values")
+        })
+    }
 
-        Ok(())
+    /// Indices of the retained externalized slots, in increasing order. Externalized values can
+    /// be queried for these slots only.
+    pub fn externalized_slot_indices(&self) -> Vec<SlotIndex> {
+        self.externalized_slots
+            .iter()
+            .map(|slot| slot.get_index())
+            .collect()
     }
 
-    /// Push an externalized slot into the queue of externalized slots.
-    fn push_externalized_slot(&mut self, slot: Box<dyn ScpSlot<V>>) {
-        self.externalized_slots.push(slot);
-        while self.externalized_slots.len() > self.max_externalized_slots {
-            // Remove the first slot, which is the oldest.
-            self.externalized_slots.remove(0);
+    /// The distinct values externalized in retained slots with index in `[start, end)`.
+    pub fn externalized_values_union(&self, start: SlotIndex, end: SlotIndex) -> BTreeSet<V> {
+        self.externalized_slots
+            .iter()
+            .map(|slot| slot.get_index())
+            .filter(|slot_index| (start..end).contains(slot_index))
+            .filter_map(|slot_index| self.get_externalized_values(slot_index))
+            .flatten()
+            .collect()
+    }
+
+    /// Compare this node's state with another node's.
+    ///
+    /// Intended as a test assertion for nodes that are expected to be in lockstep.
+    pub fn diff_state(&self, other: &Self) -> NodeStateDiff<V> {
+        let mut diff = NodeStateDiff {
+            current_slot_index: None,
+            phase: None,
+            ballot: None,
+            externalized: Vec::new(),
+        };
+
+        let ours = self.current_slot.get_index();
+        let theirs = other.current_slot.get_index();
+        if ours != theirs {
+            diff.current_slot_index = Some((ours, theirs));
+        } else {
+            let our_phase = self.current_slot.get_metrics().phase;
+            let their_phase = other.current_slot.get_metrics().phase;
+            if our_phase != their_phase {
+                diff.phase = Some((our_phase, their_phase));
+            }
+
+            let our_ballot = self.current_slot.get_ballot();
+            let their_ballot = other.current_slot.get_ballot();
+            if our_ballot != their_ballot {
+                diff.ballot = Some((our_ballot, their_ballot));
+            }
+        }
+
+        let lowest = cmp::max(
+            self.lowest_retained_slot_index(),
+            other.lowest_retained_slot_index(),
+        );
+        let slot_indices: BTreeSet<SlotIndex> = self
+            .externalized_slots
+            .iter()
+            .chain(other.externalized_slots.iter())
+            .map(|slot| slot.get_index())
+            .filter(|slot_index| *slot_index >= lowest)
+            .collect();
+        for slot_index in slot_indices {
+            let ours = self.get_externalized_values(slot_index);
+            let theirs = other.get_externalized_values(slot_index);
+            if ours != theirs {
+                diff.externalized.push((slot_index, ours, theirs));
+            }
         }
+
+        diff
     }
 
-    /// Get the externalized slot, if any.
-    fn get_externalized_slot(&self, slot_index: SlotIndex) -> Option<&dyn ScpSlot<V>> {
-        self.externalized_slots
+    /// The Externalize message this node sent for a retained externalized slot, e.g. to help a
+    /// peer that is catching up.
+    pub fn re_externalize(&self, slot_index: SlotIndex) -> Option<Msg<V, ID>> {
+        self.get_externalized_slot(slot_index)
+            .and_then(|slot| slot.get_last_message_sent())
+            .filter(|msg| msg.topic.is_externalize())
+    }
+
+    /// A JSON dump of this node's state, for diagnostics.
+    ///
+    /// Externalized value sets are summarized by their size and digest to keep the dump small.
+    pub fn diagnostic_json(&self) -> String {
+        let now = self.clock.now();
+        let metrics = self.current_slot.get_metrics();
+        let health = self.network_health();
+
+        let externalized: Vec<serde_json::Value> = self
+            .externalized_slots
             .iter()
-            .find(|slot| slot.get_index() == slot_index)
-            .map(|slot| slot.as_ref())
+            .map(|slot| {
+                let slot_index = slot.get_index();
+                let digest = self.externalized_digest(slot_index).map(|digest| {
+                    digest
+                        .iter()
+                        .map(|byte| format!("{:02x}", byte))
+                        .collect::<String>()
+                });
+                let num_values = self
+                    .get_externalized_values(slot_index)
+                    .map_or(0, |values| values.len());
+                json!({
+                    "slot_index": slot_index,
+                    "num_values": num_values,
+                    "digest": digest,
+                })
+            })
+            .collect();
+
+        let peers: Vec<serde_json::Value> =
+            self.Q
+                .nodes()
+                .into_iter()
+                .collect::<BTreeSet<ID>>()
+                .iter()
+                .map(|peer| {
+                    let last_heard_ms_ago = self.last_heard.get(peer).map(|heard_at| {
+                        now.saturating_duration_since(*heard_at).as_millis() as u64
+                    });
+                    json!({
+                        "node_id": peer.to_string(),
+                        "last_heard_ms_ago": last_heard_ms_ago,
+                    })
+                })
+                .collect();
+
+        let externalize_latency_ms = health
+            .externalize_latency
+            .map(|latency| latency.as_millis() as u64);
+
+        json!({
+            "node_id": self.ID.to_string(),
+            "quorum_set": self.Q,
+            "current_slot": {
+                "slot_index": self.current_slot.get_index(),
+                "phase": metrics.phase,
+                "ballot_counter": metrics.bN,
+                "nomination_round": metrics.cur_nomination_round,
+                "num_voted_nominated": metrics.num_voted_nominated,
+                "num_accepted_nominated": metrics.num_accepted_nominated,
+                "num_confirmed_nominated": metrics.num_confirmed_nominated,
+                "age_ms": health.current_slot_age.as_millis() as u64,
+                "num_msgs": self.current_slot_msg_count,
+            },
+            "externalized": externalized,
+            "peers": peers,
+            "metrics": {
+                "health_score": health.score,
+                "externalize_latency_ms": externalize_latency_ms,
+                "validity_divergences": self.validity_divergences,
+                "num_buffered_future_msgs": self.future_msgs.len(),
+                "future_msgs_dropped": self.future_msgs_dropped,
+                "future_msgs_rejected": self.future_msgs_rejected,
+            },
+        })
+        .to_string()
     }
-}
 
-impl<V: Value, ValidationError: Clone + Display + 'static> ScpNode<V> for Node<V, ValidationError> {
-    fn node_id(&self) -> NodeID {
-        self.ID.clone()
+    /// Values waiting to be proposed to the next slot.
+    pub fn pending_values(&self) -> &BTreeSet<V> {
+        &self.pending_values
     }
 
-    fn quorum_set(&self) -> QuorumSet {
-        self.Q.clone()
+    /// Number of buffered messages for future slots.
+    pub fn num_buffered_future_msgs(&self) -> usize {
+        self.future_msgs.len()
     }
 
-    /// Propose values for this node to nominate.
-    fn propose_values(&mut self, values: BTreeSet<V>) -> Result<Option<Msg<V>>, String> {
-        if values.is_empty() {
-            log::error!(self.logger, "propose_values called with 0 values.");
-            return Ok(None);
+    /// Number of future slot messages dropped because the buffer was full.
+    pub fn future_msgs_dropped(&self) -> u64 {
+        self.future_msgs_dropped
+    }
+
+    /// Number of future slot messages rejected by `FutureSlotPolicy::Reject`.
+    pub fn future_msgs_rejected(&self) -> u64 {
+        self.future_msgs_rejected
+    }
+
+    /// Buffer a message for a future slot, dropping the oldest buffered messages if full.
+    fn buffer_future_msg(&mut self, msg: Msg<V, ID>) {
+        self.future_msgs.push_back(msg);
+        while self.future_msgs.len() > self.max_buffered_future_msgs {
+            self.future_msgs.pop_front();
+            self.future_msgs_dropped += 1;
         }
+    }
 
-        match self.current_slot.propose_values(&values)? {
-            None => Ok(None),
-            Some(msg) => {
-                if let Topic::Externalize(ext_payload) = &msg.topic {
-                    self.externalize(ext_payload)?;
-                }
-                Ok(Some(msg))
-            }
+    /// Counters summed over all slots, including the current one and slots that are no longer
+    /// retained.
+    pub fn aggregate_metrics(&self) -> NodeMetrics {
+        NodeMetrics {
+            num_externalized: self.num_externalized,
+            num_msgs: self.past_slots_msg_count + self.current_slot_msg_count,
+            num_timeouts: self.past_slots_num_timeouts
+                + self.current_slot.get_metrics().num_timeouts,
+            num_validity_divergences: self.validity_divergences,
         }
     }
 
-    /// Handle an incoming message from the network.
-    fn handle_message(&mut self, msg: &Msg<V>) -> Result<Option<Msg<V>>, String> {
-        let outgoing_messages = self.handle_messages(vec![msg.clone()])?;
-        Ok(outgoing_messages.get(0).cloned())
+    /// The number of messages received and emitted by this node while externalizing the given
+    /// slot, if the slot is in the retained window of externalized slots.
+    pub fn messages_to_externalize(&self, slot_index: SlotIndex) -> Option<usize> {
+        self.retained_externalized_record(slot_index)
+            .map(|record| record.msg_count)
     }
 
-    /// Handle incoming message from the network.
-    fn handle_messages(&mut self, msgs: Vec<Msg<V>>) -> Result<Vec<Msg<V>>, String> {
-        // Omit messages from self.
-        let (msgs_from_peers, msgs_from_self): (Vec<_>, Vec<_>) =
-            msgs.into_iter().partition(|msg| msg.sender_id != self.ID);
+    /// The record of the given externalized slot, if the slot is retained.
+    fn retained_externalized_record(
+        &self,
+        slot_index: SlotIndex,
+    ) -> Option<&ExternalizedSlotRecord<V>> {
+        self.get_externalized_slot(slot_index)?;
+        // The newest record, in case the slot index was reset and the slot externalized again.
+        self.externalized_records
+            .iter()
+            .rev()
+            .find(|record| record.slot_index == slot_index)
+    }
 
-        if !msgs_from_self.is_empty() {
-            log::error!(
-                self.logger,
-                "Received {} messages from self.",
-                msgs_from_self.len()
-            );
-        }
+    /// Number of times `shadow_validity_fn` disagreed with `validity_fn`.
+    pub fn validity_divergences(&self) -> u64 {
+        self.validity_divergences
+    }
 
-        // Omit messages for future slots.
-        let (msgs_to_process, future_msgs): (Vec<_>, Vec<_>) = msgs_from_peers
-            .into_iter()
-            .partition(|msg| msg.slot_index <= self.current_slot.get_index());
+    // Compare `validity_fn` and `shadow_validity_fn` on a value, logging any divergence.
+    fn check_shadow_validity(&mut self, value: &V, result: &Result<(), ValidationError>) {
+        let shadow_validity_fn = match &self.shadow_validity_fn {
+            Some(shadow_validity_fn) => shadow_validity_fn,
+            None => return,
+        };
 
-        if !future_msgs.is_empty() {
-            log::error!(
+        let shadow_result = shadow_validity_fn(value);
+        if shadow_result.is_ok() != result.is_ok() {
+            self.validity_divergences += 1;
+            log::warn!(
                 self.logger,
-                "Received {} messages for future slots.",
-                future_msgs.len()
+                "Shadow validity diverged on slot {} for value {:?}: active: {}, shadow: {}",
+                self.current_slot.get_index(),
+                value,
+                result
+                    .as_ref()
+                    .map_or_else(|e| e.to_string(), |_| "valid".to_string()),
+                shadow_result
+                    .as_ref()
+                    .map_or_else(|e| e.to_string(), |_| "valid".to_string()),
             );
         }
+    }
 
-        // Group messages by slot index.
-        let mut slot_index_to_msgs: HashMap<SlotIndex, Vec<Msg<V>>> = Default::default();
-        for msg in msgs_to_process {
-            slot_index_to_msgs
-                .entry(msg.slot_index)
-                .or_insert_with(Vec::new)
-                .push(msg);
+    /// Forget the hashes of seen messages for slots lower than `slot_index`.
+    ///
+    /// This is called automatically whenever the node advances to a new slot, retaining only
+    /// hashes for the current slot and the retained externalized slots.
+    pub fn prune_seen_msg_hashes(&mut self, slot_index: SlotIndex) {
+        let stale_hashes: Vec<Hash> = self
+            .seen_msg_hashes
+            .iter()
+            .filter(|(_hash, msg_slot_index)| **msg_slot_index < slot_index)
+            .map(|(hash, _msg_slot_index)| *hash)
+            .collect();
+
+        for hash in stale_hashes {
+            self.seen_msg_hashes.pop(&hash);
         }
+    }
 
-        // Messages emitted by this node that should be sent to the network.
-        let mut outbound_msgs: Vec<_> = Vec::new();
+    /// The hash identifying `msg` in `seen_msg_hashes`. The quorum set is canonicalized first, so
+    /// that messages differing only in the order of quorum set members are duplicates.
+    fn seen_msg_hash(msg: &Msg<V, ID>) -> Hash {
+        let mut msg = msg.clone();
+        msg.quorum_set = msg.quorum_set.canonical();
+        msg.digest32::<MerlinTranscript>(b"scp_msg")
+    }
 
-        // Handle messages for recent externalized slots. Messages for older slots are ignored.
-        for slot in self.externalized_slots.iter_mut() {
-            if let Some(msgs) = slot_index_to_msgs.get(&slot.get_index()) {
-                if let Some(response) = slot.handle_messages(msgs)? {
-                    outbound_msgs.push(response);
-                }
-            }
+    /// Forgets the hashes of seen messages for the given slot, so that they are handled again if
+    /// they are received again.
+    fn forget_seen_msg_hashes(&mut self, slot_index: SlotIndex) {
+        let hashes: Vec<Hash> = self
+            .seen_msg_hashes
+            .iter()
+            .filter(|(_hash, msg_slot_index)| **msg_slot_index == slot_index)
+            .map(|(hash, _msg_slot_index)| *hash)
+            .collect();
+
+        for hash in hashes {
+            self.seen_msg_hashes.pop(&hash);
         }
+    }
 
-        // Handle messages for current slot.
-        if let Some(msgs) = slot_index_to_msgs.get(&self.current_slot.get_index()) {
-            if let Some(response) = self.current_slot.handle_messages(msgs)? {
-                if let Topic::Externalize(ext_payload) = &response.topic {
-                    self.externalize(&ext_payload)?;
-                }
-                outbound_msgs.push(response);
+    /// The lowest slot index for which messages are still processed.
+    fn lowest_retained_slot_index(&self) -> SlotIndex {
+        self.current_slot
+            .get_index()
+            .saturating_sub(self.max_externalized_slots as SlotIndex)
+    }
+
+    /// Creates a new Node, as if it had already externalized the given slots.
+    ///
+    /// # Arguments
+    /// * `node_id` - This node's ID.
+    /// * `quorum_set` - This node's quorum set.
+    /// * `validity_fn` - Validates a value.
+    /// * `combine_fn` - Combines a set of values into a composite value (i.e. block).
+    /// * `externalized` - Externalized values of consecutive slots, in increasing slot order.
+    ///   The node begins performing consensus on the slot following the last of these.
+    /// * `logger`
+    pub fn with_history(
+        node_id: ID,
+        quorum_set: QuorumSet<ID>,
+        validity_fn: ValidityFn<V, ValidationError>,
+        combine_fn: CombineFn<V, ValidationError>,
+        externalized: Vec<(SlotIndex, Vec<V>)>,
+        logger: Logger,
+    ) -> Result<Self, String> {
+        let last_slot_index = match externalized.last() {
+            Some((slot_index, _values)) => *slot_index,
+            None => return Err("Externalized history must not be empty".to_string()),
+        };
+
+        for pair in externalized.windows(2) {
+            if pair[1].0 != pair[0].0 + 1 {
+                return Err(format!(
+                    "Externalized history has a gap between slots {} and {}",
+                    pair[0].0, pair[1].0
+                ));
             }
         }
 
-        Ok(outbound_msgs)
-    }
+        let mut node = Self::new(
+            node_id,
+            quorum_set,
+            validity_fn,
+            combine_fn,
+            last_slot_index + 1,
+            logger,
+        );
 
-    /// Maximum number of stored externalized slots.
-    fn max_externalized_slots(&self) -> usize {
-        self.max_externalized_slots
-    }
+        for (slot_index, values) in externalized {
+            let slot = Slot::new_externalized(
+                node.ID.clone(),
+                node.Q.clone(),
+                slot_index,
+                &values,
+                node.validity_fn.clone(),
+                node.combine_fn_for(slot_index),
+                node.logger.clone(),
+            );
+            node.push_externalized_slot(Box::new(slot));
+        }
 
-    /// Set the maximum number of stored externalized slots. Must be non-zero.
-    fn set_max_externalized_slots(&mut self, n: usize) {
-        debug_assert!(n > 0);
-        self.max_externalized_slots = n;
+        Ok(node)
     }
 
-    /// Get externalized values for a given slot index, if any.
-    fn get_externalized_values(&self, slot_index: SlotIndex) -> Option<Vec<V>> {
-        self.get_externalized_slot(slot_index).map(|slot| {
-            if let Topic::Externalize(payload) = slot
-                .get_last_message_sent()
-                .expect("Previous slots must have a message")
-                .topic
-            {
-                payload.C.X
-            } else {
-                panic!("Previous slot has not externalized?");
-            }
-        })
+    /// The state needed to resume consensus after a restart. See `from_snapshot`.
+    pub fn to_snapshot(&self) -> NodeSnapshot<V, ID> {
+        let externalized = self
+            .externalized_slots
+            .iter()
+            .map(|slot| {
+                let values = slot
+                    .get_last_message_sent()
+                    .expect("Previous slots must have a message")
+                    .topic
+                    .externalized_values()
+                    .expect("Previous slot has not externalized?")
+                    .to_vec();
+                (slot.get_index(), values)
+            })
+            .collect();
+
+        NodeSnapshot {
+            node_id: self.ID.clone(),
+            quorum_set: self.Q.clone(),
+            current_slot_quorum_set: self.current_slot_quorum_set.clone(),
+            current_slot: self.current_slot.get_state(),
+            externalized,
+            max_externalized_slots: self.max_externalized_slots,
+        }
     }
 
-    /// Process pending timeouts.
-    fn process_timeouts(&mut self) -> Vec<Msg<V>> {
-        self.current_slot.process_timeouts()
+    /// Creates a node from a snapshot taken with `to_snapshot`.
+    ///
+    /// Functions are not serializable, so `validity_fn` and `combine_fn` must be supplied again.
+    /// Everything else not in the snapshot starts out with its default.
+    pub fn from_snapshot(
+        snapshot: NodeSnapshot<V, ID>,
+        validity_fn: ValidityFn<V, ValidationError>,
+        combine_fn: CombineFn<V, ValidationError>,
+        logger: Logger,
+    ) -> Result<Self, String> {
+        let current_slot_index = snapshot.current_slot.slot_index;
+        if snapshot.current_slot.node_id != snapshot.node_id {
+            return Err(format!(
+                "Current slot belongs to {}, not {}",
+                snapshot.current_slot.node_id, snapshot.node_id
+            ));
+        }
+        if let Some((slot_index, _values)) = snapshot
+            .externalized
+            .iter()
+            .find(|(slot_index, _values)| *slot_index >= current_slot_index)
+        {
+            return Err(format!(
+                "Externalized slot {} is not before current slot {}",
+                slot_index, current_slot_index
+            ));
+        }
+
+        let mut node = Self::new(
+            snapshot.node_id,
+            snapshot.quorum_set,
+            validity_fn,
+            combine_fn,
+            current_slot_index,
+            logger,
+        );
+        node.max_externalized_slots = snapshot.max_externalized_slots;
+
+        for (slot_index, values) in snapshot.externalized {
+            let slot = Slot::new_externalized(
+                node.ID.clone(),
+                node.Q.clone(),
+                slot_index,
+                &values,
+                node.validity_fn.clone(),
+                node.combine_fn_for(slot_index),
+                node.logger.clone(),
+            );
+            node.push_externalized_slot(Box::new(slot));
+        }
+
+        let mut slot = Slot::from_state(
+            snapshot.current_slot,
+            snapshot.current_slot_quorum_set.clone(),
+            node.validity_fn.clone(),
+            node.combine_fn_for(current_slot_index),
+            node.logger.clone(),
+        );
+        node.configure_slot(&mut slot);
+        node.current_slot = Box::new(slot);
+        node.current_slot_quorum_set = snapshot.current_slot_quorum_set;
+
+        Ok(node)
     }
 
-    /// Get the current slot's index.
-    fn current_slot_index(&self) -> SlotIndex {
-        self.current_slot.get_index()
+    /// This node's quorum set, with each member annotated with its reachability.
+    ///
+    /// The local node always counts as reachable.
+    pub fn annotated_quorum_set(&self) -> AnnotatedQuorumSet<ID> {
+        self.annotate_quorum_set(&self.Q, self.clock.now())
     }
 
-    /// Get metrics for the current slot.
-    fn get_current_slot_metrics(&mut self) -> SlotMetrics {
-        self.current_slot.get_metrics()
+    fn annotate_quorum_set(
+        &self,
+        quorum_set: &QuorumSet<ID>,
+        now: Instant,
+    ) -> AnnotatedQuorumSet<ID> {
+        let peer_window = self.scp_timebase * NETWORK_HEALTH_PEER_WINDOW;
+        let members = quorum_set
+            .members
+            .iter()
+            .map(|member| match member {
+                QuorumSetMember::Node(node_id) => {
+                    let last_heard_ago = if *node_id == self.ID {
+                        Some(Duration::from_secs(0))
+                    } else {
+                        self.last_heard
+                            .get(node_id)
+                            .map(|heard_at| now.saturating_duration_since(*heard_at))
+                    };
+                    AnnotatedQuorumSetMember::Node(MemberStatus {
+                        node_id: node_id.clone(),
+                        last_heard_ago,
+                        reachable: last_heard_ago.map_or(false, |ago| ago <= peer_window),
+                    })
+                }
+                QuorumSetMember::InnerSet(inner) => {
+                    AnnotatedQuorumSetMember::InnerSet(self.annotate_quorum_set(inner, now))
+                }
+            })
+            .collect();
+
+        AnnotatedQuorumSet {
+            threshold: quorum_set.threshold,
+            members,
+        }
     }
 
-    /// Get the slot internal state (for debug purposes).
-    fn get_slot_debug_snapshot(&mut self, slot_index: SlotIndex) -> Option<String> {
-        if slot_index == self.current_slot_index() {
-            Some(self.current_slot.get_debug_snapshot())
+    /// An aggregate estimate of network health, based on recently observed messages.
+    ///
+    /// See [NetworkHealth::score] for how the score is computed.
+    pub fn network_health(&self) -> NetworkHealth {
+        let now = self.clock.now();
+        let peer_window = self.scp_timebase * NETWORK_HEALTH_PEER_WINDOW;
+
+        let peers: Vec<ID> = self
+            .Q
+            .nodes()
+            .into_iter()
+            .filter(|node_id| *node_id != self.ID)
+            .collect();
+        let peers_total = peers.len();
+        let peers_heard = peers
+            .iter()
+            .filter(|node_id| match self.last_heard.get(node_id) {
+                Some(heard_at) => now.saturating_duration_since(*heard_at) <= peer_window,
+                None => false,
+            })
+            .count();
+
+        let latencies: Vec<Duration> = self
+            .externalized_records
+            .iter()
+            .rev()
+            .take(NETWORK_HEALTH_LATENCY_SAMPLES)
+            .map(|record| record.latency)
+            .collect();
+        let externalize_latency = if latencies.is_empty() {
+            None
         } else {
-            self.get_externalized_slot(slot_index)
-                .map(|slot| slot.get_debug_snapshot())
+            let total: Duration = latencies.iter().sum();
+            Some(total / latencies.len() as u32)
+        };
+
+        let current_slot_age = now.saturating_duration_since(self.current_slot_started_at);
+
+        let participation_score = if peers_total == 0 {
+            1.0
+        } else {
+            peers_heard as f64 / peers_total as f64
+        };
+
+        let latency_score = match externalize_latency {
+            None => 0.0,
+            Some(latency) => ratio_score(self.scp_timebase, latency),
+        };
+
+        let freshness_score = ratio_score(
+            self.scp_timebase * NETWORK_HEALTH_STALE_SLOT,
+            current_slot_age,
+        );
+
+        NetworkHealth {
+            peers_heard,
+            peers_total,
+            externalize_latency,
+            current_slot_age,
+            score: 0.5 * participation_score + 0.25 * latency_score + 0.25 * freshness_score,
         }
     }
 
-    /// Set the node's current slot index, abandoning any current and externalized slots.
-    fn reset_slot_index(&mut self, slot_index: SlotIndex) {
-        // The slot index should only increase.
-        debug_assert!(slot_index > self.current_slot_index());
+    // Record the values externalized by the current slot and advance the current slot.
+    fn externalize(&mut self, payload: &ExternalizePayload<V>) -> Result<(), String> {
+        let slot_index = self.current_slot.get_index();
 
-        self.current_slot = Box::new(Slot::new(
-            self.ID.clone(),
-            self.Q.clone(),
+        // Check for invalid values. This should be redundant, since the network has already
+        // agreed on these values, but may indicate a bug or a validation mismatch.
+        let mut invalid_values: Vec<V> = Vec::new();
+        for value in &payload.C.X {
+            let result = (self.validity_fn)(value);
+            if let Err(e) = &result {
+                log::error!(
+                    self.logger,
+                    "Slot {} externalized invalid value: {:?}, {}",
+                    slot_index,
+                    value,
+                    e
+                );
+                invalid_values.push(value.clone());
+            }
+            self.check_shadow_validity(value, &result);
+        }
+
+        let mut filtered_values = None;
+        if !invalid_values.is_empty() {
+            match self.externalize_validation_policy {
+                ExternalizeValidationPolicy::Abort => {
+                    return Err(format!(
+                        "Slot {} externalized {} invalid values",
+                        slot_index,
+                        invalid_values.len()
+                    ));
+                }
+                ExternalizeValidationPolicy::ProceedWithWarning => {}
+                ExternalizeValidationPolicy::ProceedFilteringInvalid => {
+                    filtered_values = Some(
+                        payload
+                            .C
+                            .X
+                            .iter()
+                            .filter(|value| !invalid_values.contains(value))
+                            .cloned()
+                            .collect(),
+                    );
+                }
+            }
+        }
+
+        let now = self.clock.now();
+        self.externalized_records.push_back(ExternalizedSlotRecord {
             slot_index,
-            self.validity_fn.clone(),
-            self.combine_fn.clone(),
-            self.logger.clone(),
-        ));
+            msg_count: self.current_slot_msg_count,
+            filtered_values,
+            latency: now.saturating_duration_since(self.current_slot_started_at),
+        });
+        let max_records = cmp::max(self.max_externalized_slots, NETWORK_HEALTH_LATENCY_SAMPLES);
+        while self.externalized_records.len() > max_records {
+            self.externalized_records.pop_front();
+        }
+        self.current_slot_started_at = now;
 
-        self.externalized_slots.clear();
-    }
-}
+        self.num_externalized += 1;
+        self.past_slots_msg_count += self.current_slot_msg_count;
+        self.past_slots_num_timeouts += self.current_slot.get_metrics().num_timeouts;
+        self.current_slot_msg_count = 0;
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::{core_types::Ballot, msg::*, slot::MockScpSlot, test_utils::*};
-    use maplit::btreeset;
-    use mc_common::logger::test_with_logger;
-    use std::{iter::FromIterator, sync::Arc};
+        let next_slot = self.new_slot(slot_index + 1);
+        self.current_slot_quorum_set = self.Q.clone();
+        self.throttled_msg = None;
 
-    fn get_node(
-        slot_index: SlotIndex,
-        logger: Logger,
-    ) -> Node<&'static str, TransactionValidationError> {
-        let node_id = test_node_id(1);
-        let quorum_set = QuorumSet::new_with_node_ids(1, vec![test_node_id(2)]);
-        Node::<&'static str, TransactionValidationError>::new(
-            node_id.clone(),
-            quorum_set.clone(),
-            Arc::new(trivial_validity_fn),
-            Arc::new(trivial_combine_fn),
-            slot_index,
-            logger,
-        )
-    }
+        // Advance to the next slot.
+        let externalized_slot = std::mem::replace(&mut self.current_slot, next_slot);
 
-    #[test_with_logger]
-    // Node::new should correctly initialize current_slot and externalized_slots.
-    fn test_initialization(logger: Logger) {
-        let node_id = test_node_id(1);
-        let quorum_set = QuorumSet::new_with_node_ids(1, vec![test_node_id(2)]);
-        let slot_index = 6;
-        let node = Node::<u32, TransactionValidationError>::new(
-            node_id.clone(),
-            quorum_set.clone(),
-            Arc::new(trivial_validity_fn),
-            Arc::new(trivial_combine_fn),
-            slot_index,
-            logger,
-        );
+        self.push_externalized_slot(externalized_slot);
+        self.prune_seen_msg_hashes(self.lowest_retained_slot_index());
 
-        assert_eq!(node.current_slot.get_index(), slot_index);
-        assert_eq!(node.node_id(), node_id);
-        assert_eq!(node.quorum_set(), quorum_set);
+        // Values externalized in this slot need not be proposed again.
+        let values: BTreeSet<V> = mem::take(&mut self.pending_values)
+            .into_iter()
+            .filter(|value| !payload.C.X.contains(value))
+            .collect();
+        self.pending_values_msg = None;
+        if !values.is_empty() {
+            if let Some(msg) = self.current_slot.propose_values(&values)? {
+                self.check_emitted(&msg);
+                self.pending_values_msg = Some(msg);
+            }
+        }
 
-        // Initially, `externalized_slots` should be empty.
-        assert!(node.externalized_slots.is_empty());
+        Ok(())
     }
 
-    #[test_with_logger]
-    // Should pass values to the appropriate slot.
-    fn test_propose_values_no_outgoing_message(logger: Logger) {
-        // type V = &'static str;
-        let mut node = get_node(0, logger);
+    /// Replace the clock used by this node and its current slot, e.g. with a mock in tests.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.current_slot.set_clock(clock.clone());
+        self.current_slot_started_at = clock.now();
+        self.last_progress = clock.now();
+        self.clock = clock;
+        self.mock_clock = None;
+    }
 
-        // Should call `propose_values` on the current slot.
-        let mut slot = MockScpSlot::new();
-        slot.expect_propose_values().times(1).return_const(Ok(None)); // No outgoing Msg.
-        node.current_slot = Box::new(slot);
+    /// Replace the clock used by this node and its current slot with `clock`, which can then be
+    /// moved forward with `advance_clock`.
+    pub fn set_mock_clock(&mut self, clock: MockClock) {
+        self.set_clock(Arc::new(clock.clone()));
+        self.mock_clock = Some(clock);
+    }
 
-        // Should not call anything on an externalized slot.
-        let externalized_slot = MockScpSlot::new();
-        node.push_externalized_slot(Box::new(externalized_slot));
+    /// Move the mock clock forward by `by`, and process any timeouts that became due.
+    /// Returns the messages emitted by the timeouts.
+    ///
+    /// # Panics
+    /// * If the node's clock was not set with `set_mock_clock`.
+    pub fn advance_clock(&mut self, by: Duration) -> Vec<Msg<V, ID>> {
+        self.mock_clock
+            .as_ref()
+            .expect("advance_clock requires a clock set with set_mock_clock")
+            .advance(by);
+        self.process_timeouts()
+    }
 
-        let values = btreeset!["a", "b", "c"];
-        assert_eq!(node.propose_values(values), Ok(None));
+    /// Expect messages from `node_id` for slot `from_slot_index` onwards to embed `quorum_set`.
+    /// Messages that embed a different quorum set are handled according to
+    /// `stale_quorum_set_policy`.
+    pub fn expect_quorum_set(
+        &mut self,
+        node_id: ID,
+        quorum_set: &QuorumSet<ID>,
+        from_slot_index: SlotIndex,
+    ) {
+        self.expected_quorum_sets
+            .insert(node_id, (from_slot_index, quorum_set.fingerprint()));
     }
 
-    #[test_with_logger]
-    // Should pass values to the appropriate slot and return the outgoing msg.
-    fn test_propose_values_with_outgoing_message(logger: Logger) {
-        let slot_index = 1;
-        let mut node = get_node(slot_index, logger);
+    /// True if `msg` embeds a quorum set other than the one expected from its sender for its
+    /// slot.
+    pub fn has_stale_quorum_set(&self, msg: &Msg<V, ID>) -> bool {
+        match self.expected_quorum_sets.get(&msg.sender_id) {
+            Some((from_slot_index, fingerprint)) => {
+                msg.slot_index >= *from_slot_index && msg.quorum_set.fingerprint() != *fingerprint
+            }
+            None => false,
+        }
+    }
 
-        // Should call `propose_values` on the current slot, which returns a Msg.
-        let msg = Msg::new(
-            test_node_id(2),
-            QuorumSet::new_with_node_ids(1, vec![test_node_id(3)]),
-            slot_index,
-            Topic::Nominate(NominatePayload {
-                X: Default::default(),
-                Y: Default::default(),
-            }),
+    /// Use `combine_fn` for slots whose index is in `range`, e.g. when block-building rules
+    /// change at a scheduled slot. Only affects slots created after registration.
+    ///
+    /// Every node in the network must register the same functions: nodes that combine values
+    /// differently will not agree on what to externalize.
+    pub fn register_combine_fn<R: RangeBounds<SlotIndex>>(
+        &mut self,
+        range: R,
+        combine_fn: CombineFn<V, ValidationError>,
+    ) {
+        let clone_bound = |bound: Bound<&SlotIndex>| match bound {
+            Bound::Included(index) => Bound::Included(*index),
+            Bound::Excluded(index) => Bound::Excluded(*index),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        let bounds = (
+            clone_bound(range.start_bound()),
+            clone_bound(range.end_bound()),
         );
-        let mut slot = MockScpSlot::new();
-        slot.expect_propose_values()
-            .times(1)
-            .return_const(Ok(Some(msg.clone()))); //  Outgoing Msg, not an Externalize.
-        node.current_slot = Box::new(slot);
+        self.combine_fn_registry.push((bounds, combine_fn));
+    }
 
-        let values = btreeset!["a", "b", "c"];
-        assert_eq!(node.propose_values(values), Ok(Some(msg)));
+    /// The combine function for the given slot.
+    fn combine_fn_for(&self, slot_index: SlotIndex) -> CombineFn<V, ValidationError> {
+        self.combine_fn_registry
+            .iter()
+            .rev()
+            .find(|(bounds, _)| bounds.contains(&slot_index))
+            .map(|(_, combine_fn)| combine_fn.clone())
+            .unwrap_or_else(|| self.combine_fn.clone())
     }
 
-    #[test_with_logger]
-    // Should pass values to the appropriate slot, externalize the slot,  and return the outgoing msg.
-    fn test_propose_values_with_externalize(logger: Logger) {
-        let slot_index = 4;
-        let mut node = get_node(slot_index, logger);
+    /// Progress of the current slot towards externalizing, in [0.0, 1.0].
+    ///
+    /// See [ScpSlot::progress]. Once the slot externalizes, the node moves on to the next slot,
+    /// whose progress starts again from 0.0.
+    pub fn slot_progress(&self) -> f64 {
+        self.current_slot.progress()
+    }
 
-        // Should call `propose_values` on the current slot, which returns a Msg.
+    /// The nodes from which `value` was taken when this node voted to nominate it for
+    /// `slot_index`, including this node for its own proposals.
+    ///
+    /// Only the current slot and retained externalized slots are tracked. Returns `None` if the
+    /// slot is not retained, or this node never voted to nominate `value` for it.
+    pub fn value_provenance(&self, slot_index: SlotIndex, value: &V) -> Option<HashSet<ID>> {
+        if self.current_slot.get_index() == slot_index {
+            return self.current_slot.value_provenance(value);
+        }
+        self.get_externalized_slot(slot_index)
+            .and_then(|slot| slot.value_provenance(value))
+    }
+
+    /// Replace this node's quorum set.
+    ///
+    /// Changing the quorum set mid-slot is unsafe, so the current slot keeps the quorum set it
+    /// was created with. The new quorum set takes effect on the next `externalize`, from the
+    /// slot that follows the current one.
+    pub fn set_quorum_set(&mut self, quorum_set: QuorumSet<ID>) {
+        let quorum_set_nodes = quorum_set.nodes();
+        self.last_heard
+            .retain(|node_id, _heard_at| quorum_set_nodes.contains(node_id));
+        self.Q = quorum_set;
+    }
+
+    /// Choose the first nomination round's leaders with `leader_fn` instead of by priority.
+    ///
+    /// Like a quorum set change, this takes effect from the slot that follows the current one.
+    pub fn set_leader_fn(&mut self, leader_fn: LeaderFn<ID>) {
+        self.leader_fn = Some(leader_fn);
+    }
+
+    /// Compute ballot timeouts with `timeout_fn`, e.g. to back off exponentially or to cap the
+    /// timeout, instead of linearly in the ballot counter. Applies to the current slot as well as
+    /// future ones.
+    pub fn set_timeout_fn(&mut self, timeout_fn: TimeoutFn) {
+        self.current_slot.set_timeout_fn(timeout_fn.clone());
+        self.timeout_fn = Some(timeout_fn);
+    }
+
+    /// The quorum set carried by the most recent message emitted by this node.
+    pub fn last_emitted_quorum_set(&self) -> Option<&QuorumSet<ID>> {
+        self.last_emitted_quorum_set.as_ref()
+    }
+
+    /// Checks that a message emitted by the current slot identifies this node and carries the
+    /// current slot's quorum set.
+    fn check_emitted(&mut self, msg: &Msg<V, ID>) {
+        if msg.sender_id != self.ID || msg.quorum_set != self.current_slot_quorum_set {
+            log::error!(
+                self.logger,
+                "Emitted message {} does not carry this node's ID and quorum set {:?}.",
+                msg,
+                self.current_slot_quorum_set
+            );
+        }
+        self.last_emitted_quorum_set = Some(msg.quorum_set.clone());
+    }
+
+    /// Calls `on_finalize` if `msg` is a final Externalize message.
+    fn notify_if_finalized(&self, msg: &Msg<V, ID>) {
+        if let (Some(on_finalize), Topic::Externalize(payload)) = (&self.on_finalize, &msg.topic) {
+            if payload.HN == INFINITY {
+                let values = self
+                    .retained_externalized_record(msg.slot_index)
+                    .and_then(|record| record.filtered_values.as_ref())
+                    .unwrap_or(&payload.C.X);
+                on_finalize(msg.slot_index, values);
+            }
+        }
+    }
+
+    /// Reports an Externalize message whose values differ from the values this node
+    /// externalized for the same retained slot.
+    ///
+    /// Values are compared with the ballot this node externalized, before any filtering by the
+    /// externalize validation policy, since filtering is local to this node.
+    fn check_externalize_conflict(&self, msg: &Msg<V, ID>) {
+        let theirs = match msg.topic.externalized_values() {
+            Some(values) => values,
+            None => return,
+        };
+        let ours = match self
+            .get_externalized_slot(msg.slot_index)
+            .and_then(|slot| slot.get_last_message_sent())
+        {
+            Some(ours) => ours,
+            None => return,
+        };
+        let ours = match ours.topic.externalized_values() {
+            Some(values) => values,
+            None => return,
+        };
+
+        if ours != theirs {
+            log::error!(
+                self.logger,
+                "Node {} externalized {:?} in slot {}, but this node externalized {:?}.",
+                msg.sender_id,
+                theirs,
+                msg.slot_index,
+                ours
+            );
+            if let Some(on_conflict) = &self.on_conflict {
+                on_conflict(&msg.sender_id, msg.slot_index, ours, theirs);
+            }
+        }
+    }
+
+    /// Create a slot for this node.
+    fn new_slot(&self, slot_index: SlotIndex) -> Box<dyn ScpSlot<V, ID>> {
+        let mut slot = Slot::new(
+            self.ID.clone(),
+            self.Q.clone(),
+            slot_index,
+            self.validity_fn.clone(),
+            self.combine_fn_for(slot_index),
+            self.logger.clone(),
+        );
+        self.configure_slot(&mut slot);
+        if let Some(leader_fn) = &self.leader_fn {
+            slot.set_leaders(leader_fn(slot_index, &self.Q));
+        }
+        Box::new(slot)
+    }
+
+    /// Applies this node's per-slot settings to a new or restored current slot.
+    ///
+    /// Leaders are only set by `new_slot`, since a restored slot may already be nominating.
+    fn configure_slot(&self, slot: &mut Slot<V, ValidationError, ID>) {
+        slot.set_clock(self.clock.clone());
+        slot.max_tracked_ballots = self.max_tracked_ballots;
+        slot.max_msg_size = self.max_msg_size;
+        slot.max_ballot_counter = self.max_ballot_counter;
+        if let Some(timeout_fn) = &self.timeout_fn {
+            slot.set_timeout_fn(timeout_fn.clone());
+        }
+    }
+
+    /// Applies the emission throttle to a message emitted by the current slot.
+    ///
+    /// Returns the message if it should be sent now, or `None` if it is withheld because a
+    /// message with the same topic was recently emitted for this slot.
+    fn throttle_outgoing(&mut self, msg: Msg<V, ID>) -> Option<Msg<V, ID>> {
+        let now = self.clock.now();
+        let topic = mem::discriminant(&msg.topic);
+
+        if let (Some(interval), Some((slot_index, last_topic, emitted_at))) =
+            (self.emission_throttle, &self.last_emission)
+        {
+            if *slot_index == msg.slot_index
+                && *last_topic == topic
+                && now < *emitted_at + interval
+                && !msg.topic.is_externalize()
+            {
+                self.throttled_msg = Some(msg);
+                return None;
+            }
+        }
+
+        self.last_emission = Some((msg.slot_index, topic, now));
+        self.throttled_msg = None;
+        Some(msg)
+    }
+
+    /// Push an externalized slot into the queue of externalized slots.
+    fn push_externalized_slot(&mut self, slot: Box<dyn ScpSlot<V, ID>>) {
+        self.externalized_slots.push(slot);
+        while self.externalized_slots.len() > self.max_externalized_slots {
+            // Remove the first slot, which is the oldest.
+            self.externalized_slots.remove(0);
+        }
+    }
+
+    /// Get the externalized slot, if any.
+    fn get_externalized_slot(&self, slot_index: SlotIndex) -> Option<&dyn ScpSlot<V, ID>> {
+        self.externalized_slots
+            .iter()
+            .find(|slot| slot.get_index() == slot_index)
+            .map(|slot| slot.as_ref())
+    }
+}
+
+impl<V: Value, ValidationError: Clone + Display + 'static> Node<V, ValidationError> {
+    /// Handle a signed incoming message from the network, applying `bad_signature_policy`.
+    ///
+    /// Only nodes identified by `NodeID` can verify signatures, since verification needs the
+    /// sender's public key. This is therefore not part of `ScpNode`, which is generic over the
+    /// node id type.
+    pub fn handle_signed_message(
+        &mut self,
+        signed_msg: &SignedMsg<V>,
+    ) -> Result<Option<Msg<V>>, String> {
+        if self.bad_signature_policy != BadSignaturePolicy::Accept && signed_msg.verify().is_err() {
+            log::warn!(
+                self.logger,
+                "Message from {} for slot {} has a bad signature.",
+                signed_msg.msg.sender_id,
+                signed_msg.msg.slot_index
+            );
+            if self.bad_signature_policy == BadSignaturePolicy::Reject {
+                return Err(format!(
+                    "Message from {} for slot {} has a bad signature.",
+                    signed_msg.msg.sender_id, signed_msg.msg.slot_index
+                ));
+            }
+        }
+        self.handle_message(&signed_msg.msg)
+    }
+}
+
+/// 1.0 if `actual` is within `expected`, otherwise `expected / actual`.
+fn ratio_score(expected: Duration, actual: Duration) -> f64 {
+    if actual <= expected {
+        1.0
+    } else {
+        expected.as_secs_f64() / actual.as_secs_f64()
+    }
+}
+
+impl<
+        V: Value,
+        ValidationError: Clone + Display + 'static,
+        ID: GenericNodeId + NodePriorityKey + Serialize + DeserializeOwned + Send + 'static,
+    > ScpNode<V, ID> for Node<V, ValidationError, ID>
+{
+    fn node_id(&self) -> ID {
+        self.ID.clone()
+    }
+
+    fn quorum_set(&self) -> QuorumSet<ID> {
+        self.Q.clone()
+    }
+
+    /// Propose values for this node to nominate.
+    fn propose_values(&mut self, values: BTreeSet<V>) -> Result<Option<Msg<V, ID>>, String> {
+        if values.is_empty() {
+            log::error!(self.logger, "propose_values called with 0 values.");
+            return Ok(None);
+        }
+
+        if let Some(max_value_size) = self.max_value_size {
+            for value in &values {
+                let size = mc_util_serial::serialize(value)
+                    .map_err(|e| format!("Failed serializing value {:?}: {:?}", value, e))?
+                    .len();
+                if size > max_value_size {
+                    return Err(format!(
+                        "Value {:?} is {} bytes, exceeding the maximum of {}",
+                        value, size, max_value_size
+                    ));
+                }
+            }
+        }
+
+        // With a shadow validity function, validate values here to compare the two, and pass
+        // the slot only the valid values so that they are not validated again.
+        let valid_values = if self.shadow_validity_fn.is_some() {
+            let mut valid_values = BTreeSet::new();
+            for value in &values {
+                let result = (self.validity_fn)(value);
+                self.check_shadow_validity(value, &result);
+                if result.is_ok() {
+                    valid_values.insert(value.clone());
+                }
+            }
+            Some(valid_values)
+        } else {
+            None
+        };
+
+        // A slot only takes new values while nominating and before any value is confirmed
+        // nominated. Keep later values for the next slot.
+        let metrics = self.current_slot.get_metrics();
+        if metrics.phase != Phase::NominatePrepare || metrics.num_confirmed_nominated > 0 {
+            log::debug!(
+                self.logger,
+                "Slot {} is no longer nominating, queueing {} values for the next slot.",
+                self.current_slot.get_index(),
+                values.len()
+            );
+            self.pending_values.extend(values.iter().cloned());
+        }
+
+        let msg = match &valid_values {
+            Some(valid_values) => self.current_slot.propose_valid_values(valid_values)?,
+            None => self.current_slot.propose_values(&values)?,
+        };
+        match msg {
+            None => Ok(None),
+            Some(msg) => {
+                self.check_emitted(&msg);
+                self.last_progress = self.clock.now();
+                self.current_slot_msg_count += 1;
+                if let Topic::Externalize(ext_payload) = &msg.topic {
+                    self.externalize(ext_payload)?;
+                }
+                self.notify_if_finalized(&msg);
+                Ok(Some(msg))
+            }
+        }
+    }
+
+    /// Handle an incoming message from the network.
+    fn handle_message(&mut self, msg: &Msg<V, ID>) -> Result<Option<Msg<V, ID>>, String> {
+        if let Err(err) = msg.validate() {
+            return Err(format!(
+                "Malformed message from {} for slot {}: {}",
+                msg.sender_id, msg.slot_index, err
+            ));
+        }
+
+        let outgoing_messages = self.handle_messages(vec![msg.clone()])?;
+        Ok(outgoing_messages.get(0).cloned())
+    }
+
+    /// Handle incoming messages from the network.
+    ///
+    /// Messages that have already been handled successfully are ignored. A message is not
+    /// recorded as seen if handling it fails, or if it was evicted by the slot's ballot cap.
+    fn handle_messages(&mut self, msgs: Vec<Msg<V, ID>>) -> Result<Vec<Msg<V, ID>>, String> {
+        // Omit messages from self.
+        let (mut msgs_from_peers, msgs_from_self): (Vec<_>, Vec<_>) =
+            msgs.into_iter().partition(|msg| msg.sender_id != self.ID);
+
+        if !msgs_from_self.is_empty() {
+            log::error!(
+                self.logger,
+                "Received {} messages from self.",
+                msgs_from_self.len()
+            );
+        }
+
+        // Drop malformed messages.
+        msgs_from_peers.retain(|msg| match msg.validate() {
+            Ok(()) => true,
+            Err(err) => {
+                log::warn!(
+                    self.logger,
+                    "Dropping malformed message from {} for slot {}: {}",
+                    msg.sender_id,
+                    msg.slot_index,
+                    err
+                );
+                false
+            }
+        });
+
+        let now = self.clock.now();
+        let quorum_set_nodes = self.Q.nodes();
+        for msg in &msgs_from_peers {
+            if quorum_set_nodes.contains(&msg.sender_id) {
+                self.last_heard.insert(msg.sender_id.clone(), now);
+            }
+        }
+
+        // Apply the stale quorum set policy.
+        if self.stale_quorum_set_policy != StaleQuorumSetPolicy::Accept {
+            let (stale_msgs, current_msgs): (Vec<_>, Vec<_>) = msgs_from_peers
+                .into_iter()
+                .partition(|msg| self.has_stale_quorum_set(msg));
+            msgs_from_peers = current_msgs;
+
+            for msg in stale_msgs {
+                log::warn!(
+                    self.logger,
+                    "Message from {} for slot {} has a stale quorum set.",
+                    msg.sender_id,
+                    msg.slot_index
+                );
+                if self.stale_quorum_set_policy == StaleQuorumSetPolicy::Warn {
+                    msgs_from_peers.push(msg);
+                }
+            }
+        }
+
+        // Include previously buffered messages for slots this node has since reached.
+        let current_slot_index = self.current_slot.get_index();
+        let (ready_msgs, still_future_msgs): (VecDeque<_>, VecDeque<_>) = self
+            .future_msgs
+            .drain(..)
+            .partition(|msg| msg.slot_index <= current_slot_index);
+        self.future_msgs = still_future_msgs;
+
+        // Apply the future slot policy.
+        let (msgs_to_process, future_msgs): (Vec<_>, Vec<_>) = ready_msgs
+            .into_iter()
+            .chain(msgs_from_peers.into_iter())
+            .partition(|msg| msg.slot_index <= current_slot_index);
+
+        if !future_msgs.is_empty() {
+            match self.future_slot_policy {
+                FutureSlotPolicy::Drop => {
+                    log::error!(
+                        self.logger,
+                        "Received {} messages for future slots.",
+                        future_msgs.len()
+                    );
+                }
+                FutureSlotPolicy::Buffer => {
+                    log::debug!(
+                        self.logger,
+                        "Buffering {} messages for future slots.",
+                        future_msgs.len()
+                    );
+                    for msg in future_msgs {
+                        self.buffer_future_msg(msg);
+                    }
+                }
+                FutureSlotPolicy::Reject => {
+                    log::error!(
+                        self.logger,
+                        "Rejected {} messages for future slots; current slot is {}.",
+                        future_msgs.len(),
+                        current_slot_index
+                    );
+                    self.future_msgs_rejected += future_msgs.len() as u64;
+                }
+            }
+        }
+
+        // Omit messages that have already been seen. A message is only recorded as seen once it
+        // has been handled successfully, so that it is handled again if it is re-sent.
+        let mut batch_hashes: HashSet<Hash> = HashSet::default();
+        let mut slot_index_to_hashes: HashMap<SlotIndex, Vec<Hash>> = Default::default();
+        let mut unseen_msgs: Vec<Msg<V, ID>> = Vec::with_capacity(msgs_to_process.len());
+        for msg in msgs_to_process {
+            let msg_hash = Self::seen_msg_hash(&msg);
+            if self.seen_msg_hashes.contains(&msg_hash) || !batch_hashes.insert(msg_hash) {
+                continue;
+            }
+            slot_index_to_hashes
+                .entry(msg.slot_index)
+                .or_insert_with(Vec::new)
+                .push(msg_hash);
+            unseen_msgs.push(msg);
+        }
+
+        // Report peers that externalized different values for a retained slot.
+        for msg in &unseen_msgs {
+            self.check_externalize_conflict(msg);
+        }
+
+        // Group messages by slot index.
+        let mut slot_index_to_msgs: HashMap<SlotIndex, Vec<Msg<V, ID>>> = Default::default();
+        for msg in unseen_msgs {
+            slot_index_to_msgs
+                .entry(msg.slot_index)
+                .or_insert_with(Vec::new)
+                .push(msg);
+        }
+
+        // Messages emitted by this node that should be sent to the network.
+        let mut outbound_msgs: Vec<_> = Vec::new();
+
+        // Previously sent Externalize messages to re-send to peers that are behind.
+        let mut resent_msgs: Vec<_> = Vec::new();
+
+        // Handle messages for recent externalized slots. Messages for older slots are ignored.
+        for slot in self.externalized_slots.iter_mut() {
+            if let Some(msgs) = slot_index_to_msgs.get(&slot.get_index()) {
+                let response = slot.handle_messages(msgs)?;
+                for hash in slot_index_to_hashes
+                    .remove(&slot.get_index())
+                    .unwrap_or_default()
+                {
+                    self.seen_msg_hashes.put(hash, slot.get_index());
+                }
+
+                if let Some(response) = response {
+                    outbound_msgs.push(response);
+                } else if self.late_message_policy == LateMessagePolicy::ResendExternalize
+                    && msgs.iter().any(|msg| !msg.topic.is_externalize())
+                {
+                    resent_msgs.extend(slot.get_last_message_sent());
+                }
+            }
+        }
+
+        // Handle messages for current slot.
+        if let Some(msgs) = slot_index_to_msgs.get(&self.current_slot.get_index()) {
+            self.current_slot_msg_count += msgs.len();
+            let num_evictions = self.current_slot.get_metrics().num_ballot_evictions;
+            let response = self.current_slot.handle_messages(msgs)?;
+
+            // Messages evicted by the slot's ballot cap must be handled again if they are resent.
+            let hashes = slot_index_to_hashes
+                .remove(&self.current_slot.get_index())
+                .unwrap_or_default();
+            if self.current_slot.get_metrics().num_ballot_evictions > num_evictions {
+                self.forget_seen_msg_hashes(self.current_slot.get_index());
+            } else {
+                for hash in hashes {
+                    self.seen_msg_hashes
+                        .put(hash, self.current_slot.get_index());
+                }
+            }
+
+            let response = response.and_then(|response| self.throttle_outgoing(response));
+            if let Some(response) = response {
+                self.check_emitted(&response);
+                self.last_progress = self.clock.now();
+                self.current_slot_msg_count += 1;
+                if let Topic::Externalize(ext_payload) = &response.topic {
+                    self.externalize(&ext_payload)?;
+                }
+                outbound_msgs.push(response);
+            }
+        }
+
+        // Messages for older slots are ignored, and need not be handled again.
+        for (slot_index, hashes) in slot_index_to_hashes {
+            for hash in hashes {
+                self.seen_msg_hashes.put(hash, slot_index);
+            }
+        }
+
+        for msg in &outbound_msgs {
+            self.notify_if_finalized(msg);
+        }
+        outbound_msgs.extend(resent_msgs);
+
+        Ok(outbound_msgs)
+    }
+
+    /// Maximum number of stored externalized slots.
+    fn max_externalized_slots(&self) -> usize {
+        self.max_externalized_slots
+    }
+
+    /// Set the maximum number of stored externalized slots. Zero disables retention.
+    fn set_max_externalized_slots(&mut self, n: usize) {
+        self.max_externalized_slots = n;
+    }
+
+    /// Get externalized values for a given slot index, if any.
+    fn get_externalized_values(&self, slot_index: SlotIndex) -> Option<Vec<V>> {
+        let filtered_values = self
+            .retained_externalized_record(slot_index)
+            .and_then(|record| record.filtered_values.clone());
+
+        self.get_externalized_slot(slot_index).map(|slot| {
+            if let Some(values) = filtered_values {
+                return values;
+            }
+
+            slot.get_last_message_sent()
+                .expect("Previous slots must have a message")
+                .topic
+                .externalized_values()
+                .expect("Previous slot has not externalized?")
+                .to_vec()
+        })
+    }
+
+    /// Process pending timeouts.
+    fn process_timeouts(&mut self) -> Vec<Msg<V, ID>> {
+        let mut msgs = self.current_slot.process_timeouts();
+        for msg in &msgs {
+            self.check_emitted(msg);
+        }
+
+        // A message from the slot supersedes any withheld message. Otherwise, send the withheld
+        // message once the throttle interval has elapsed.
+        if !msgs.is_empty() {
+            self.throttled_msg = None;
+        } else if let Some(msg) = self
+            .throttled_msg
+            .take()
+            .and_then(|msg| self.throttle_outgoing(msg))
+        {
+            msgs.push(msg);
+        }
+
+        // Peers ignore this message if they have since received a newer one from this node.
+        if let Some(msg) = self.pending_values_msg.take() {
+            msgs.insert(0, msg);
+        }
+
+        self.current_slot_msg_count += msgs.len();
+        msgs
+    }
+
+    /// Get the current slot's index.
+    fn current_slot_index(&self) -> SlotIndex {
+        self.current_slot.get_index()
+    }
+
+    /// Get metrics for the current slot.
+    fn get_current_slot_metrics(&mut self) -> SlotMetrics {
+        self.current_slot.get_metrics()
+    }
+
+    fn is_stuck(&self, since: Duration) -> bool {
+        // The current slot is replaced as soon as it externalizes.
+        self.clock
+            .now()
+            .saturating_duration_since(self.last_progress)
+            > since
+    }
+
+    /// Get the slot internal state (for debug purposes).
+    fn get_slot_debug_snapshot(&mut self, slot_index: SlotIndex) -> Option<String> {
+        if slot_index == self.current_slot_index() {
+            Some(self.current_slot.get_debug_snapshot())
+        } else {
+            self.get_externalized_slot(slot_index)
+                .map(|slot| slot.get_debug_snapshot())
+        }
+    }
+
+    /// Set the node's current slot index, abandoning any current and externalized slots.
+    fn reset_slot_index(&mut self, slot_index: SlotIndex) {
+        // The slot index should only increase.
+        debug_assert!(slot_index > self.current_slot_index());
+
+        self.past_slots_msg_count += self.current_slot_msg_count;
+        self.past_slots_num_timeouts += self.current_slot.get_metrics().num_timeouts;
+        self.current_slot = self.new_slot(slot_index);
+        self.current_slot_quorum_set = self.Q.clone();
+        self.current_slot_started_at = self.clock.now();
+        self.last_progress = self.clock.now();
+        self.current_slot_msg_count = 0;
+        self.throttled_msg = None;
+        self.pending_values.clear();
+        self.pending_values_msg = None;
+
+        self.externalized_slots.clear();
+        self.future_msgs.retain(|msg| msg.slot_index >= slot_index);
+        self.prune_seen_msg_hashes(slot_index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        clock::MockClock,
+        core_types::{Ballot, CombineFn},
+        msg::*,
+        slot::MockScpSlot,
+        test_utils,
+        test_utils::*,
+    };
+    use maplit::btreeset;
+    use mc_common::logger::test_with_logger;
+    use std::{
+        collections::HashSet,
+        iter::FromIterator,
+        sync::{Arc, Mutex},
+    };
+
+    fn get_node(
+        slot_index: SlotIndex,
+        logger: Logger,
+    ) -> Node<&'static str, TransactionValidationError> {
+        let node_id = test_node_id(1);
+        let quorum_set = QuorumSet::new_with_node_ids(1, vec![test_node_id(2)]);
+        Node::<&'static str, TransactionValidationError>::new(
+            node_id.clone(),
+            quorum_set.clone(),
+            Arc::new(trivial_validity_fn),
+            Arc::new(trivial_combine_fn),
+            slot_index,
+            logger,
+        )
+    }
+
+    /// Metrics for a mock slot that is still nominating.
+    fn nominating_slot_metrics() -> SlotMetrics {
+        SlotMetrics {
+            phase: Phase::NominatePrepare,
+            num_voted_nominated: 0,
+            num_accepted_nominated: 0,
+            num_confirmed_nominated: 0,
+            cur_nomination_round: 1,
+            bN: 0,
+            num_validity_fn_calls: 0,
+            num_combine_fn_calls: 0,
+            num_tracked_ballots: 0,
+            num_ballot_evictions: 0,
+            num_timeouts: 0,
+            counter_capped: false,
+        }
+    }
+
+    #[test_with_logger]
+    // Node::new should correctly initialize current_slot and externalized_slots.
+    fn test_initialization(logger: Logger) {
+        let node_id = test_node_id(1);
+        let quorum_set = QuorumSet::new_with_node_ids(1, vec![test_node_id(2)]);
+        let slot_index = 6;
+        let node = Node::<u32, TransactionValidationError>::new(
+            node_id.clone(),
+            quorum_set.clone(),
+            Arc::new(trivial_validity_fn),
+            Arc::new(trivial_combine_fn),
+            slot_index,
+            logger,
+        );
+
+        assert_eq!(node.current_slot.get_index(), slot_index);
+        assert_eq!(node.node_id(), node_id);
+        assert_eq!(node.quorum_set(), quorum_set);
+
+        // Initially, `externalized_slots` should be empty.
+        assert!(node.externalized_slots.is_empty());
+    }
+
+    #[test_with_logger]
+    // Should pass values to the appropriate slot.
+    fn test_propose_values_no_outgoing_message(logger: Logger) {
+        // type V = &'static str;
+        let mut node = get_node(0, logger);
+
+        // Should call `propose_values` on the current slot.
+        let mut slot = MockScpSlot::new();
+        slot.expect_propose_values().times(1).return_const(Ok(None)); // No outgoing Msg.
+        slot.expect_get_metrics().returning(nominating_slot_metrics);
+        node.current_slot = Box::new(slot);
+
+        // Should not call anything on an externalized slot.
+        let externalized_slot = MockScpSlot::new();
+        node.push_externalized_slot(Box::new(externalized_slot));
+
+        let values = btreeset!["a", "b", "c"];
+        assert_eq!(node.propose_values(values), Ok(None));
+    }
+
+    #[test_with_logger]
+    // Should pass values to the appropriate slot and return the outgoing msg.
+    fn test_propose_values_with_outgoing_message(logger: Logger) {
+        let slot_index = 1;
+        let mut node = get_node(slot_index, logger);
+
+        // Should call `propose_values` on the current slot, which returns a Msg.
+        let msg = Msg::new(
+            test_node_id(2),
+            QuorumSet::new_with_node_ids(1, vec![test_node_id(3)]),
+            slot_index,
+            Topic::Nominate(NominatePayload {
+                X: Default::default(),
+                Y: Default::default(),
+            }),
+        );
+        let mut slot = MockScpSlot::new();
+        slot.expect_propose_values()
+            .times(1)
+            .return_const(Ok(Some(msg.clone()))); //  Outgoing Msg, not an Externalize.
+        slot.expect_get_metrics().returning(nominating_slot_metrics);
+        node.current_slot = Box::new(slot);
+
+        let values = btreeset!["a", "b", "c"];
+        assert_eq!(node.propose_values(values), Ok(Some(msg)));
+    }
+
+    #[test_with_logger]
+    // Duplicate values should be collapsed before nominating, and reported to the caller.
+    fn test_nominate_reporting_duplicates(logger: Logger) {
+        let mut node = test_utils::get_node(
+            1,
+            test_node_id(1),
+            QuorumSet::new_with_node_ids(1, vec![test_node_id(2)]),
+            logger,
+        );
+
+        let mut slot = MockScpSlot::new();
+        slot.expect_propose_values()
+            .withf(|values| *values == btreeset![1, 2, 3])
+            .times(1)
+            .return_const(Ok(None));
+        slot.expect_get_metrics().returning(nominating_slot_metrics);
+        node.current_slot = Box::new(slot);
+
+        let (msg, duplicates) = node
+            .nominate_reporting_duplicates(vec![1, 2, 2, 3])
+            .expect("error nominating values");
+        assert_eq!(msg, None);
+        assert_eq!(duplicates, btreeset![2]);
+    }
+
+    #[test_with_logger]
+    // Should pass values to the appropriate slot, externalize the slot,  and return the outgoing msg.
+    fn test_propose_values_with_externalize(logger: Logger) {
+        let slot_index = 4;
+        let mut node = get_node(slot_index, logger);
+
+        // Should call `propose_values` on the current slot, which returns a Msg.
+        let msg = Msg::new(
+            test_node_id(2),
+            QuorumSet::new_with_node_ids(1, vec![test_node_id(3)]),
+            slot_index,
+            Topic::Externalize(ExternalizePayload {
+                C: Ballot::new(4, &[]),
+                HN: 3,
+            }),
+        );
+
+        let mut slot = MockScpSlot::new();
+        slot.expect_propose_values()
+            .times(1)
+            .return_const(Ok(Some(msg.clone()))); //  Outgoing Msg, not an Externalize.
+        slot.expect_get_index().return_const(slot_index);
+        slot.expect_get_metrics().returning(nominating_slot_metrics);
+        node.current_slot = Box::new(slot);
+
+        let values = btreeset!["a", "b", "c"];
+        assert_eq!(node.propose_values(values), Ok(Some(msg)));
+
+        // The `slot_index` slot should now be extnalized, and current_slot should be at `slot_index + 1`.
+        assert_eq!(node.current_slot.get_index(), slot_index + 1);
+        assert_eq!(node.externalized_slots.len(), 1);
+        assert_eq!(node.externalized_slots[0].get_index(), slot_index)
+    }
+
+    #[test_with_logger]
+    // Should omit messages from self.
+    fn test_handle_messages_omit_from_self(logger: Logger) {
+        let slot_index = 1985;
+        let mut node = get_node(slot_index, logger);
+
+        // The current slot should not be called.
+        let mut slot = MockScpSlot::new();
+        slot.expect_get_index().return_const(slot_index);
+        node.current_slot = Box::new(slot);
+
+        // The recent externalized slot should not be called.
+        let mut externalized_slot = MockScpSlot::new();
+        externalized_slot
+            .expect_get_index()
+            .return_const(slot_index - 1);
+        node.push_externalized_slot(Box::new(externalized_slot));
+
+        let msg_from_self = Msg::new(
+            node.ID.clone(),
+            node.quorum_set(),
+            slot_index,
+            Topic::Externalize(ExternalizePayload {
+                C: Ballot::new(4, &[]),
+                HN: 3,
+            }),
+        );
+
+        match node.handle_messages(vec![msg_from_self.clone(), msg_from_self.clone()]) {
+            Ok(outgoing_msgs) => assert_eq!(outgoing_msgs.len(), 0),
+            Err(e) => panic!("Unexpected error {:?}", e),
+        }
+    }
+
+    #[test_with_logger]
+    // Should omit messages for future slots.
+    fn test_handle_messages_omit_from_future(logger: Logger) {
+        let slot_index = 1985;
+        let mut node = get_node(slot_index, logger);
+
+        // The current slot should not be called.
+        let mut slot = MockScpSlot::new();
+        slot.expect_get_index().return_const(slot_index);
+        node.current_slot = Box::new(slot);
+
+        // The recent externalized slot should not be called.
+        let mut externalized_slot = MockScpSlot::new();
+        externalized_slot
+            .expect_get_index()
+            .return_const(slot_index - 1);
+        node.push_externalized_slot(Box::new(externalized_slot));
+
+        // A message from a peer for a future slot index.
+        let msg_for_future_slot = Msg::new(
+            test_node_id(2),
+            QuorumSet::new_with_node_ids(1, vec![test_node_id(3)]),
+            2015, // Where we're going, we don't need roads.
+            Topic::Externalize(ExternalizePayload {
+                C: Ballot::new(4, &[]),
+                HN: 3,
+            }),
+        );
+
+        match node.handle_messages(vec![msg_for_future_slot]) {
+            Ok(outgoing_msgs) => assert_eq!(outgoing_msgs.len(), 0),
+            Err(e) => panic!("Unexpected error {:?}", e),
+        }
+    }
+
+    #[test_with_logger]
+    // Should omit messages that are too old.
+    fn test_handle_messages_omit_old(logger: Logger) {
+        let slot_index = 1985;
+        let mut node = get_node(slot_index, logger);
+
+        // The current slot should not be called.
+        let mut slot = MockScpSlot::new();
+        slot.expect_get_index().return_const(slot_index);
+        node.current_slot = Box::new(slot);
+
+        // The recent externalized slot should not be called.
+        let mut externalized_slot = MockScpSlot::new();
+        externalized_slot
+            .expect_get_index()
+            .return_const(slot_index - 1);
+        node.push_externalized_slot(Box::new(externalized_slot));
+
+        // A message from an old slot.
+        let msg_for_old_slot = Msg::new(
+            test_node_id(2),
+            QuorumSet::new_with_node_ids(1, vec![test_node_id(3)]),
+            1885, // Too old
+            Topic::Nominate(NominatePayload {
+                X: Default::default(),
+                Y: Default::default(),
+            }),
+        );
+
+        match node.handle_messages(vec![msg_for_old_slot]) {
+            Ok(outgoing_msgs) => assert_eq!(outgoing_msgs.len(), 0),
+            Err(e) => panic!("Unexpected error {:?}", e),
+        }
+    }
+
+    #[test_with_logger]
+    // Should pass messages to the current slot.
+    fn test_handle_messages_current_slot(logger: Logger) {
+        let slot_index = 1985;
+        let mut node = get_node(slot_index, logger);
+
+        // The current slot should be called, and should return a message.
+        let mut slot = MockScpSlot::new();
+        {
+            slot.expect_get_index().return_const(slot_index);
+
+            let msg = Msg::new(
+                node.ID.clone(),
+                node.quorum_set(),
+                slot_index,
+                Topic::Externalize(ExternalizePayload {
+                    C: Ballot::new(4, &[]),
+                    HN: 3,
+                }),
+            );
+
+            slot.expect_handle_messages()
+                .times(1)
+                .return_const(Ok(Some(msg)));
+            slot.expect_get_metrics().returning(nominating_slot_metrics);
+        }
+        node.current_slot = Box::new(slot);
+
+        // The recent externalized slot should not be called.
+        let mut externalized_slot = MockScpSlot::new();
+        externalized_slot
+            .expect_get_index()
+            .return_const(slot_index - 1);
+        node.push_externalized_slot(Box::new(externalized_slot));
+
+        let msg_for_current_slot = Msg::new(
+            test_node_id(2),
+            QuorumSet::new_with_node_ids(1, vec![test_node_id(3)]),
+            slot_index,
+            Topic::Nominate(NominatePayload {
+                X: Default::default(),
+                Y: Default::default(),
+            }),
+        );
+
+        match node.handle_messages(vec![msg_for_current_slot]) {
+            Ok(outgoing_msgs) => assert_eq!(outgoing_msgs.len(), 1), // Should return a message.
+            Err(e) => panic!("Unexpected error {:?}", e),
+        }
+    }
+
+    #[test_with_logger]
+    // Should pass messages to the correct externalized slot.
+    fn test_handle_messages_externalized_slots(logger: Logger) {
+        let slot_index = 1985;
+        let mut node = get_node(slot_index, logger);
+
+        // The current slot should not be called.
+        let mut slot = MockScpSlot::new();
+        slot.expect_get_index().return_const(slot_index);
+        node.current_slot = Box::new(slot);
+
+        // The recently externalized slot should be called.
+        let mut externalized_slot = MockScpSlot::new();
+        {
+            externalized_slot
+                .expect_get_index()
+                .return_const(slot_index - 1);
+
+            let msg = Msg::new(
+                node.ID.clone(),
+                node.quorum_set(),
+                slot_index - 1,
+                Topic::Externalize(ExternalizePayload {
+                    C: Ballot::new(4, &[]),
+                    HN: 3,
+                }),
+            );
+
+            externalized_slot
+                .expect_handle_messages()
+                .times(1)
+                .return_const(Ok(Some(msg)));
+        }
+        node.push_externalized_slot(Box::new(externalized_slot));
+
+        let msg_for_recent_slot = Msg::new(
+            test_node_id(2),
+            QuorumSet::new_with_node_ids(1, vec![test_node_id(3)]),
+            slot_index - 1,
+            Topic::Nominate(NominatePayload {
+                X: Default::default(),
+                Y: Default::default(),
+            }),
+        );
+
+        match node.handle_messages(vec![msg_for_recent_slot]) {
+            Ok(outgoing_msgs) => assert_eq!(outgoing_msgs.len(), 1), // Should return a message.
+            Err(e) => panic!("Unexpected error {:?}", e),
+        }
+    }
+
+    #[test_with_logger]
+    // Should get externalized values from the correct externalized slot.
+    fn test_get_externalized_values(logger: Logger) {
+        let slot_index = 56;
+        let mut node = get_node(slot_index, logger);
+        node.set_max_externalized_slots(2);
+
+        // push externalized slots for 51, 52, ..., 55
+        for i in 51..slot_index {
+            let mut externalized_slot = MockScpSlot::new();
+            externalized_slot.expect_get_index().return_const(i);
+
+            let msg = Msg::new(
+                test_node_id(2),
+                QuorumSet::new_with_node_ids(1, vec![test_node_id(3)]),
+                i,
+                Topic::Externalize(ExternalizePayload {
+                    C: Ballot::new(4, &[]),
+                    HN: 3,
+                }),
+            );
+
+            externalized_slot
+                .expect_get_last_message_sent()
+                .return_const(Some(msg));
+
+            node.push_externalized_slot(Box::new(externalized_slot));
+        }
+
+        // These slots are too old, and are no longer maintained.
+        for i in 51..=53 {
+            assert_eq!(node.get_externalized_values(i), None)
+        }
+
+        // Slots 54 and 55 should still be maintained.
+        for i in 54..=55 {
+            assert!(node.get_externalized_values(i).is_some());
+        }
+    }
+
+    #[test_with_logger]
+    fn test_process_timeouts(logger: Logger) {
+        let mut node = get_node(0, logger);
+
+        // Should call `propose_values` on the current slot.
+        let mut slot = MockScpSlot::new();
+        let messages: Vec<Msg<&'static str>> = vec![];
+        slot.expect_process_timeouts()
+            .times(1)
+            .return_const(messages.clone());
+        node.current_slot = Box::new(slot);
+
+        // Should not call anything on an externalized slot, which no longer have timeouts.
+        let externalized_slot = MockScpSlot::new();
+        node.push_externalized_slot(Box::new(externalized_slot));
+
+        assert_eq!(node.process_timeouts(), messages);
+    }
+
+    #[test_with_logger]
+    // Should reset `current_slot` to a new Slot for the given index.
+    fn test_reset_slot_index(logger: Logger) {
+        let slot_index = 14;
+        let mut node = get_node(slot_index, logger);
+
+        node.set_max_externalized_slots(2);
+        for _i in 12..slot_index {
+            let externalized_slot = MockScpSlot::new();
+            node.push_externalized_slot(Box::new(externalized_slot));
+        }
+
+        assert_eq!(node.current_slot_index(), slot_index);
+        assert_eq!(node.externalized_slots.len(), 2);
+
+        let new_slot_index = 987;
+        node.reset_slot_index(new_slot_index);
+        assert_eq!(node.current_slot_index(), new_slot_index);
+        assert_eq!(node.current_slot.get_index(), new_slot_index);
+
+        // externalized_slots should be empty
+        assert_eq!(node.externalized_slots.len(), 0);
+    }
+
+    #[test_with_logger]
+    /// Steps through a sequence of messages that allow a two-node network to reach consensus.
+    fn basic_two_node_consensus(logger: Logger) {
+        let slot_index = 1;
+
+        // A two-node network, where the only quorum is both nodes.
+        let mut node1 = Node::<u32, TransactionValidationError>::new(
+            test_node_id(1),
+            QuorumSet::new_with_node_ids(1, vec![test_node_id(2)]),
+            Arc::new(trivial_validity_fn),
+            Arc::new(trivial_combine_fn),
+            slot_index,
+            logger.clone(),
+        );
+        let mut node2 = Node::<u32, TransactionValidationError>::new(
+            test_node_id(2),
+            QuorumSet::new_with_node_ids(1, vec![test_node_id(1)]),
+            Arc::new(trivial_validity_fn),
+            Arc::new(trivial_combine_fn),
+            slot_index,
+            logger.clone(),
+        );
+
+        // Client(s) submits some values to node 2.
+        let values = vec![1000, 2000];
+        let msg = node2
+            .propose_values(BTreeSet::from_iter(values.clone()))
+            .expect("error handling msg")
+            .expect("no msg?");
+
+        // Node 2 should emit "vote nominate([1000, 2000])"
+        assert_eq!(
+            msg,
+            Msg::new(
+                node2.node_id(),
+                node2.quorum_set(),
+                slot_index,
+                Topic::Nominate(NominatePayload {
+                    X: BTreeSet::from_iter(values.clone()),
+                    Y: Default::default(),
+                }),
+            )
+        );
+
+        // Node 1 handles Node 2's message. It may accept nominate [1000, 2000]
+        let msg = node1
+            .handle_message(&msg)
+            .expect("error handling msg")
+            .expect("no msg?");
+
+        assert_eq!(
+            msg,
+            Msg::new(
+                node1.node_id(),
+                node1.quorum_set(),
+                slot_index,
+                Topic::Nominate(NominatePayload {
+                    X: Default::default(),
+                    Y: BTreeSet::from_iter(values.clone()),
+                }),
+            )
+        );
+
+        // Node 2 may "confirm nominate", and issue "vote prepare(<1, [1000,2000]>)
+        let msg = node2
+            .handle_message(&msg)
+            .expect("error handling msg")
+            .expect("no msg?");
+
+        assert_eq!(
+            msg,
+            Msg::new(
+                node2.node_id(),
+                node2.quorum_set(),
+                slot_index,
+                Topic::NominatePrepare(
+                    NominatePayload {
+                        X: Default::default(),
+                        Y: BTreeSet::from_iter(values.clone())
+                    },
+                    PreparePayload {
+                        B: Ballot::new(1, &[1000, 2000]),
+                        P: None,
+                        PP: None,
+                        CN: 0,
+                        HN: 0
+                    }
+                ),
+            )
+        );
+
+        // Node 1 issues "accept prepare(<1, [1000,2000])
+        let msg = node1
+            .handle_message(&msg)
+            .expect("error handling msg")
+            .expect("no msg?");
+
+        assert_eq!(
+            msg,
+            Msg::new(
+                node1.node_id(),
+                node1.quorum_set(),
+                slot_index,
+                Topic::NominatePrepare(
+                    NominatePayload {
+                        X: Default::default(),
+                        Y: BTreeSet::from_iter(values.clone())
+                    },
+                    PreparePayload {
+                        B: Ballot::new(1, &values),
+                        P: Some(Ballot::new(1, &values)),
+                        PP: None,
+                        CN: 0,
+                        HN: 0
+                    }
+                ),
+            )
+        );
+
+        // Node 2 issues "vote commit"
+        let msg = node2
+            .handle_message(&msg)
+            .expect("error handling msg")
+            .expect("no msg?");
+
+        assert_eq!(
+            msg,
+            Msg::new(
+                node2.node_id(),
+                node2.quorum_set(),
+                slot_index,
+                Topic::Prepare(PreparePayload {
+                    B: Ballot::new(1, &values),
+                    P: Some(Ballot::new(1, &values)),
+                    PP: None,
+                    CN: 1,
+                    HN: 1,
+                },),
+            )
+        );
+
+        // Node 1 issues "accept commit".
+        let msg = node1
+            .handle_message(&msg)
+            .expect("error handling msg")
+            .expect("no msg?");
+
+        assert_eq!(
+            msg,
+            Msg::new(
+                node1.node_id(),
+                node1.quorum_set(),
+                slot_index,
+                Topic::Commit(CommitPayload {
+                    B: Ballot::new(1, &values),
+                    PN: 1,
+                    CN: 1,
+                    HN: 1
+                }),
+            )
+        );
+
+        // Node 2 externalizes.
+        let msg = node2
+            .handle_message(&msg)
+            .expect("error handling msg")
+            .expect("no msg?");
+
+        assert_eq!(
+            msg,
+            Msg::new(
+                node2.node_id(),
+                node2.quorum_set(),
+                slot_index,
+                Topic::Externalize(ExternalizePayload {
+                    C: Ballot::new(1, &values),
+                    HN: 1,
+                }),
+            )
+        );
+
+        // Node 1 externalizes.
+        let msg = node1
+            .handle_message(&msg)
+            .expect("error handling msg")
+            .expect("no msg?");
+
+        // Both nodes have issued Externalize, which implies
+        // "accept prepare(<infinity, commit.value>)".
+        // That means node 1 can also state "confirm prepare(<infinity, commit.value>)",
+        // so HN is now INFINITY.
+        assert_eq!(
+            msg,
+            Msg::new(
+                node1.node_id(),
+                node1.quorum_set(),
+                slot_index,
+                Topic::Externalize(ExternalizePayload {
+                    C: Ballot::new(1, &values),
+                    HN: INFINITY,
+                }),
+            )
+        );
+    }
+
+    /// A pair of nodes whose only quorum is both nodes.
+    fn get_two_nodes(
+        slot_index: SlotIndex,
+        logger: Logger,
+    ) -> (
+        Node<u32, TransactionValidationError>,
+        Node<u32, TransactionValidationError>,
+    ) {
+        let node1 = test_utils::get_node(
+            slot_index,
+            test_node_id(1),
+            QuorumSet::new_with_node_ids(1, vec![test_node_id(2)]),
+            logger.clone(),
+        );
+        let node2 = test_utils::get_node(
+            slot_index,
+            test_node_id(2),
+            QuorumSet::new_with_node_ids(1, vec![test_node_id(1)]),
+            logger,
+        );
+        (node1, node2)
+    }
+
+    /// Proposes `values` to `node2`, then passes messages between the two nodes until neither
+    /// node has anything further to say.
+    fn run_two_node_consensus(
+        node1: &mut Node<u32, TransactionValidationError>,
+        node2: &mut Node<u32, TransactionValidationError>,
+        values: &[u32],
+    ) {
+        let msg = node2
+            .propose_values(BTreeSet::from_iter(values.iter().cloned()))
+            .expect("error handling msg");
+        exchange_messages(node1, node2, msg);
+    }
+
+    /// Like `run_two_node_consensus`, but proposes `values` to `node1` instead if `node2` is not
+    /// the nomination leader for the current slot.
+    fn run_two_node_consensus_with_any_leader(
+        node1: &mut Node<u32, TransactionValidationError>,
+        node2: &mut Node<u32, TransactionValidationError>,
+        values: &[u32],
+    ) {
+        let values = BTreeSet::from_iter(values.iter().cloned());
+        match node2
+            .propose_values(values.clone())
+            .expect("error handling msg")
+        {
+            Some(msg) => exchange_messages(node1, node2, Some(msg)),
+            None => {
+                let msg = node1.propose_values(values).expect("error handling msg");
+                exchange_messages(node2, node1, msg);
+            }
+        }
+    }
+
+    /// Passes messages between the two nodes, starting with node 1 handling `msg` from node 2,
+    /// until neither node has anything further to say.
+    fn exchange_messages(
+        node1: &mut Node<u32, TransactionValidationError>,
+        node2: &mut Node<u32, TransactionValidationError>,
+        mut msg: Option<Msg<u32>>,
+    ) {
+        let mut receiver_is_node1 = true;
+        for _ in 0..100 {
+            let outgoing = match &msg {
+                None => return,
+                Some(msg) if receiver_is_node1 => node1.handle_message(msg),
+                Some(msg) => node2.handle_message(msg),
+            };
+            msg = outgoing.expect("error handling msg");
+            receiver_is_node1 = !receiver_is_node1;
+        }
+        panic!("Nodes did not stop exchanging messages.");
+    }
+
+    #[test_with_logger]
+    // Nodes identified by plain integers should reach consensus like nodes identified by NodeID.
+    fn test_consensus_with_u32_node_ids(logger: Logger) {
+        let mut node1 = test_utils::get_node(
+            1,
+            1u32,
+            QuorumSet::new_with_node_ids(1, vec![2]),
+            logger.clone(),
+        );
+        let mut node2 =
+            test_utils::get_node(1, 2u32, QuorumSet::new_with_node_ids(1, vec![1]), logger);
+
+        // Only the nomination leader emits a message for its proposal.
+        let values = BTreeSet::from_iter(vec![1000, 2000]);
+        let mut to_node1: Vec<Msg<u32, u32>> = node2
+            .propose_values(values.clone())
+            .expect("error proposing values")
+            .into_iter()
+            .collect();
+        let mut to_node2: Vec<Msg<u32, u32>> = node1
+            .propose_values(values)
+            .expect("error proposing values")
+            .into_iter()
+            .collect();
+
+        for _ in 0..100 {
+            if to_node1.is_empty() && to_node2.is_empty() {
+                break;
+            }
+            let from_node1 = node1
+                .handle_messages(std::mem::take(&mut to_node1))
+                .expect("error handling msgs");
+            let from_node2 = node2
+                .handle_messages(std::mem::take(&mut to_node2))
+                .expect("error handling msgs");
+            to_node2 = from_node1;
+            to_node1 = from_node2;
+        }
+
+        for node in &[node1, node2] {
+            assert_eq!(node.current_slot_index(), 2);
+            assert_eq!(node.get_externalized_values(1), Some(vec![1000, 2000]));
+        }
+    }
+
+    #[test_with_logger]
+    // Five nodes in a symmetric network with threshold 3 should reach consensus.
+    fn test_symmetric_network_consensus(logger: Logger) {
+        let mut nodes: Vec<_> = test_utils::symmetric_network(5, 3)
+            .into_iter()
+            .map(|(node_id, quorum_set)| {
+                test_utils::get_node(1, node_id, quorum_set, logger.clone())
+            })
+            .collect();
+
+        // Every node proposes the same values; each emitted message is broadcast to all peers.
+        let values = BTreeSet::from_iter(vec![1000, 2000]);
+        let mut in_flight: Vec<(usize, Msg<u32>)> = Vec::new();
+        for (i, node) in nodes.iter_mut().enumerate() {
+            if let Some(msg) = node
+                .propose_values(values.clone())
+                .expect("error proposing values")
+            {
+                in_flight.push((i, msg));
+            }
+        }
+
+        for _ in 0..100 {
+            if in_flight.is_empty() {
+                break;
+            }
+            let mut next = Vec::new();
+            for (i, node) in nodes.iter_mut().enumerate() {
+                let inbound: Vec<Msg<u32>> = in_flight
+                    .iter()
+                    .filter(|(sender, _)| *sender != i)
+                    .map(|(_, msg)| msg.clone())
+                    .collect();
+                let outbound = node.handle_messages(inbound).expect("error handling msgs");
+                next.extend(outbound.into_iter().map(|msg| (i, msg)));
+            }
+            in_flight = next;
+        }
+        assert!(
+            in_flight.is_empty(),
+            "Nodes did not stop exchanging messages."
+        );
+
+        for node in &nodes {
+            assert_eq!(node.current_slot_index(), 2);
+            assert_eq!(node.get_externalized_values(1), Some(vec![1000, 2000]));
+        }
+    }
+
+    #[test_with_logger]
+    // A node that is participating in a healthy network should report a high health score.
+    fn test_network_health_healthy(logger: Logger) {
+        let (mut node1, mut node2) = get_two_nodes(1, logger);
+        let clock = MockClock::default();
+        node1.set_mock_clock(clock.clone());
+        node2.set_mock_clock(clock.clone());
+
+        // Consensus takes a tenth of the timebase.
+        clock.advance(node1.scp_timebase / 10);
+        run_two_node_consensus(&mut node1, &mut node2, &[1000, 2000]);
+
+        assert_eq!(node1.current_slot_index(), 2);
+        assert_eq!(node2.current_slot_index(), 2);
+
+        for node in &[node1, node2] {
+            let health = node.network_health();
+            assert_eq!(health.peers_heard, 1);
+            assert_eq!(health.peers_total, 1);
+            assert_eq!(health.externalize_latency, Some(node.scp_timebase / 10));
+            assert!(health.score > 0.9, "score: {}", health.score);
+        }
+    }
+
+    #[test_with_logger]
+    // Only senders in the node's quorum set should be tracked as heard from.
+    fn test_last_heard_is_limited_to_quorum_set(logger: Logger) {
+        let mut node = get_node(1, logger);
+        let msgs: Vec<Msg<&'static str>> = (2..10)
+            .map(|i| {
+                Msg::new(
+                    test_node_id(i),
+                    QuorumSet::new_with_node_ids(1, vec![test_node_id(1)]),
+                    1,
+                    Topic::Nominate(NominatePayload {
+                        X: btreeset!["a"],
+                        Y: Default::default(),
+                    }),
+                )
+            })
+            .collect();
+        node.handle_messages(msgs).expect("error handling msgs");
+
+        // Node 1's quorum set only contains node 2.
+        assert_eq!(
+            node.last_heard.keys().cloned().collect::<Vec<_>>(),
+            vec![test_node_id(2)]
+        );
+
+        // Peers removed from the quorum set are forgotten.
+        node.set_quorum_set(QuorumSet::new_with_node_ids(1, vec![test_node_id(3)]));
+        assert!(node.last_heard.is_empty());
+    }
+
+    #[test_with_logger]
+    // A node that has not heard from its peers or externalized should report a low health score.
+    fn test_network_health_stalled(logger: Logger) {
+        let (_node1, mut node2) = get_two_nodes(1, logger);
+
+        // Node 2 proposes values, but never hears back from node 1.
+        node2
+            .propose_values(btreeset![1000, 2000])
+            .expect("error handling msg")
+            .expect("no msg?");
+        assert_eq!(node2.current_slot_index(), 1);
+
+        let health = node2.network_health();
+        assert_eq!(health.peers_heard, 0);
+        assert_eq!(health.peers_total, 1);
+        assert_eq!(health.externalize_latency, None);
+        assert!(health.score < 0.5, "score: {}", health.score);
+    }
+
+    #[test_with_logger]
+    // Members that have not sent messages recently should be annotated as unreachable.
+    fn test_annotated_quorum_set(logger: Logger) {
+        let clock = Arc::new(MockClock::default());
+        let mut node = Node::<u32, TransactionValidationError>::new(
+            test_node_id(1),
+            QuorumSet::new(
+                2,
+                vec![
+                    QuorumSetMember::Node(test_node_id(2)),
+                    QuorumSetMember::InnerSet(QuorumSet::new_with_node_ids(
+                        1,
+                        vec![test_node_id(3), test_node_id(4)],
+                    )),
+                ],
+            ),
+            Arc::new(trivial_validity_fn),
+            Arc::new(trivial_combine_fn),
+            1,
+            logger,
+        );
+        node.set_clock(clock.clone());
+
+        // Nodes 2 and 3 send messages, node 4 does not.
+        let msgs = vec![2, 3]
+            .into_iter()
+            .map(|id| {
+                Msg::new(
+                    test_node_id(id),
+                    QuorumSet::new_with_node_ids(1, vec![test_node_id(1)]),
+                    1,
+                    Topic::Nominate(NominatePayload {
+                        X: btreeset![1000],
+                        Y: Default::default(),
+                    }),
+                )
+            })
+            .collect();
+        node.handle_messages(msgs).expect("error handling msgs");
+        clock.advance(Duration::from_secs(1));
+
+        let status = |id: u32, ago: Option<u64>, reachable: bool| MemberStatus {
+            node_id: test_node_id(id),
+            last_heard_ago: ago.map(Duration::from_secs),
+            reachable,
+        };
+        assert_eq!(
+            node.annotated_quorum_set(),
+            AnnotatedQuorumSet {
+                threshold: 2,
+                members: vec![
+                    AnnotatedQuorumSetMember::Node(status(2, Some(1), true)),
+                    AnnotatedQuorumSetMember::InnerSet(AnnotatedQuorumSet {
+                        threshold: 1,
+                        members: vec![
+                            AnnotatedQuorumSetMember::Node(status(3, Some(1), true)),
+                            AnnotatedQuorumSetMember::Node(status(4, None, false)),
+                        ],
+                    }),
+                ],
+            }
+        );
+
+        // Once the window has passed without further messages, nobody is reachable.
+        clock.advance(node.scp_timebase * NETWORK_HEALTH_PEER_WINDOW);
+        let annotated = node.annotated_quorum_set();
+        assert_eq!(
+            annotated.members[0],
+            AnnotatedQuorumSetMember::Node(status(2, Some(11), false))
+        );
+    }
+
+    #[test_with_logger]
+    // Advancing slots should forget message hashes for slots below the externalized window.
+    fn test_prune_seen_msg_hashes(logger: Logger) {
+        let (mut node1, mut node2) = get_two_nodes(1, logger);
+
+        // Externalize slots 1 and 2. Only slot 2 remains in the externalized window.
+        run_two_node_consensus(&mut node1, &mut node2, &[1000, 2000]);
+        run_two_node_consensus(&mut node1, &mut node2, &[3000]);
+        assert_eq!(node1.current_slot_index(), 3);
+        assert_eq!(node2.current_slot_index(), 3);
+        assert_eq!(node1.max_externalized_slots(), 1);
+
+        // Node 1 sees a message for the current slot.
         let msg = Msg::new(
-            test_node_id(2),
-            QuorumSet::new_with_node_ids(1, vec![test_node_id(3)]),
+            node2.node_id(),
+            node2.quorum_set(),
+            3,
+            Topic::Nominate(NominatePayload {
+                X: btreeset![4000],
+                Y: Default::default(),
+            }),
+        );
+        node1.handle_message(&msg).expect("error handling msg");
+
+        let seen_slot_indexes: HashSet<SlotIndex> = node1
+            .seen_msg_hashes
+            .iter()
+            .map(|(_hash, slot_index)| *slot_index)
+            .collect();
+        assert_eq!(seen_slot_indexes, HashSet::from_iter(vec![2, 3]));
+
+        // Pruning explicitly drops the older slot and retains current-slot hashes.
+        node1.prune_seen_msg_hashes(3);
+        let seen_slot_indexes: HashSet<SlotIndex> = node1
+            .seen_msg_hashes
+            .iter()
+            .map(|(_hash, slot_index)| *slot_index)
+            .collect();
+        assert_eq!(seen_slot_indexes, HashSet::from_iter(vec![3]));
+
+        // A repeated message is ignored.
+        assert_eq!(node1.handle_messages(vec![msg]), Ok(vec![]));
+    }
+
+    #[test_with_logger]
+    // A shadow validity function should be compared against, but never affect, consensus.
+    fn test_shadow_validity_fn(logger: Logger) {
+        let (mut node1, mut node2) = get_two_nodes(1, logger);
+
+        // The shadow validity function rejects 2000.
+        node2.shadow_validity_fn = Some(Arc::new(|value: &u32| {
+            if *value == 2000 {
+                Err(TransactionValidationError)
+            } else {
+                Ok(())
+            }
+        }));
+
+        let msg = node2
+            .propose_values(btreeset![1000, 2000])
+            .expect("error handling msg")
+            .expect("no msg?");
+        assert_eq!(node2.validity_divergences(), 1);
+        assert_eq!(node2.aggregate_metrics().num_validity_divergences, 1);
+
+        // The slot reuses the node's validity results rather than validating the values again.
+        assert_eq!(node2.get_current_slot_metrics().num_validity_fn_calls, 0);
+
+        // The value rejected by the shadow validity function is still nominated.
+        match &msg.topic {
+            Topic::Nominate(payload) => assert_eq!(payload.X, btreeset![1000, 2000]),
+            _ => panic!("Unexpected topic {:?}", msg.topic),
+        }
+
+        // ...and externalized.
+        exchange_messages(&mut node1, &mut node2, Some(msg));
+        assert_eq!(node2.get_externalized_values(1), Some(vec![1000, 2000]));
+
+        // Externalizing the value counts as a second divergence.
+        assert_eq!(node2.validity_divergences(), 2);
+        assert_eq!(node2.aggregate_metrics().num_validity_divergences, 2);
+    }
+
+    #[test_with_logger]
+    // Should count the messages received and emitted while externalizing a slot.
+    fn test_messages_to_externalize(logger: Logger) {
+        let (mut node1, mut node2) = get_two_nodes(1, logger);
+        assert_eq!(node1.messages_to_externalize(1), None);
+
+        // This is the same exchange as `basic_two_node_consensus`:
+        // node 2 emits Nominate, NominatePrepare, Prepare and Externalize, and
+        // node 1 emits Nominate, NominatePrepare, Commit and Externalize.
+        run_two_node_consensus(&mut node1, &mut node2, &[1000, 2000]);
+
+        // Node 2 externalized after receiving node 1's Nominate, NominatePrepare and Commit.
+        assert_eq!(node2.messages_to_externalize(1), Some(4 + 3));
+
+        // Node 1 externalized after receiving all four of node 2's messages.
+        assert_eq!(node1.messages_to_externalize(1), Some(4 + 4));
+
+        // Only the retained window of externalized slots is available.
+        assert_eq!(node1.messages_to_externalize(2), None);
+        run_two_node_consensus(&mut node1, &mut node2, &[3000]);
+        assert_eq!(node1.messages_to_externalize(1), None);
+        assert!(node1.messages_to_externalize(2).is_some());
+    }
+
+    #[test_with_logger]
+    // Each externalize validation policy should handle an invalid externalized value.
+    fn test_externalize_validation_policy(logger: Logger) {
+        let slot_index = 4;
+        let msg = Msg::new(
+            test_node_id(1),
+            QuorumSet::new_with_node_ids(1, vec![test_node_id(2)]),
             slot_index,
             Topic::Externalize(ExternalizePayload {
-                C: Ballot::new(4, &[]),
-                HN: 3,
+                C: Ballot::new(4, &["a", "bad", "c"]),
+                HN: 4,
+            }),
+        );
+
+        let run = |policy: ExternalizeValidationPolicy| {
+            let mut node = Node::<&'static str, TransactionValidationError>::new(
+                test_node_id(1),
+                QuorumSet::new_with_node_ids(1, vec![test_node_id(2)]),
+                Arc::new(|value: &&'static str| {
+                    if *value == "bad" {
+                        Err(TransactionValidationError)
+                    } else {
+                        Ok(())
+                    }
+                }),
+                Arc::new(trivial_combine_fn),
+                slot_index,
+                logger.clone(),
+            );
+            node.externalize_validation_policy = policy;
+
+            // The current slot externalizes a value set containing an invalid value.
+            let mut slot = MockScpSlot::new();
+            slot.expect_propose_values()
+                .return_const(Ok(Some(msg.clone())));
+            slot.expect_get_index().return_const(slot_index);
+            slot.expect_get_metrics().returning(nominating_slot_metrics);
+            slot.expect_get_last_message_sent()
+                .return_const(Some(msg.clone()));
+            node.current_slot = Box::new(slot);
+
+            let result = node.propose_values(btreeset!["a"]);
+            (node, result)
+        };
+
+        // Abort: the node refuses to externalize and stays on the current slot.
+        let (node, result) = run(ExternalizeValidationPolicy::Abort);
+        assert!(result.is_err());
+        assert_eq!(node.current_slot_index(), slot_index);
+        assert_eq!(node.get_externalized_values(slot_index), None);
+
+        // Proceed with warning: all values are externalized.
+        let (node, result) = run(ExternalizeValidationPolicy::ProceedWithWarning);
+        assert_eq!(result, Ok(Some(msg.clone())));
+        assert_eq!(node.current_slot_index(), slot_index + 1);
+        assert_eq!(
+            node.get_externalized_values(slot_index),
+            Some(vec!["a", "bad", "c"])
+        );
+
+        // Proceed filtering invalid: only valid values are externalized.
+        let (node, result) = run(ExternalizeValidationPolicy::ProceedFilteringInvalid);
+        assert_eq!(result, Ok(Some(msg.clone())));
+        assert_eq!(node.current_slot_index(), slot_index + 1);
+        assert_eq!(
+            node.get_externalized_values(slot_index),
+            Some(vec!["a", "c"])
+        );
+    }
+
+    #[test_with_logger]
+    // Should initialize a node as if it had externalized the given history.
+    fn test_with_history(logger: Logger) {
+        let history: Vec<(SlotIndex, Vec<u32>)> =
+            (1..=10).map(|i| (i, vec![i as u32 * 100])).collect();
+
+        let node = Node::<u32, TransactionValidationError>::with_history(
+            test_node_id(1),
+            QuorumSet::new_with_node_ids(1, vec![test_node_id(2)]),
+            Arc::new(trivial_validity_fn),
+            Arc::new(trivial_combine_fn),
+            history,
+            logger.clone(),
+        )
+        .expect("with_history failed");
+
+        assert_eq!(node.current_slot_index(), 11);
+
+        // The retained window of externalized slots is available.
+        assert_eq!(node.get_externalized_values(10), Some(vec![1000]));
+        assert_eq!(node.get_externalized_values(9), None);
+
+        // History with a gap is rejected.
+        let result = Node::<u32, TransactionValidationError>::with_history(
+            test_node_id(1),
+            QuorumSet::new_with_node_ids(1, vec![test_node_id(2)]),
+            Arc::new(trivial_validity_fn),
+            Arc::new(trivial_combine_fn),
+            vec![(1, vec![100]), (3, vec![300])],
+            logger.clone(),
+        );
+        assert!(result.is_err());
+
+        // Empty history is rejected.
+        let result = Node::<u32, TransactionValidationError>::with_history(
+            test_node_id(1),
+            QuorumSet::new_with_node_ids(1, vec![test_node_id(2)]),
+            Arc::new(trivial_validity_fn),
+            Arc::new(trivial_combine_fn),
+            vec![],
+            logger,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test_with_logger]
+    // The buffer of future slot messages should stay bounded, recording dropped messages.
+    fn test_future_msgs_buffer_is_bounded(logger: Logger) {
+        let slot_index = 7;
+        let mut node = get_node(slot_index, logger);
+        node.future_slot_policy = FutureSlotPolicy::Buffer;
+        node.max_buffered_future_msgs = 10;
+
+        let values = ["a", "b", "c", "d", "e"];
+        let msgs: Vec<Msg<&'static str>> = (0..25)
+            .map(|i| {
+                Msg::new(
+                    test_node_id(2),
+                    QuorumSet::new_with_node_ids(1, vec![test_node_id(1)]),
+                    slot_index + 1 + i / values.len() as SlotIndex,
+                    Topic::Nominate(NominatePayload {
+                        X: btreeset![values[i as usize % values.len()]],
+                        Y: Default::default(),
+                    }),
+                )
+            })
+            .collect();
+
+        assert_eq!(node.handle_messages(msgs), Ok(vec![]));
+        assert_eq!(node.num_buffered_future_msgs(), 10);
+        assert_eq!(node.future_msgs_dropped(), 15);
+
+        // The oldest messages were dropped. Once the node reaches their slots, the remaining
+        // buffered messages are processed.
+        node.reset_slot_index(slot_index + 5);
+        assert_eq!(node.num_buffered_future_msgs(), 5);
+        node.handle_messages(vec![])
+            .expect("handle_messages failed");
+        assert_eq!(node.num_buffered_future_msgs(), 0);
+        assert_eq!(node.future_msgs_dropped(), 15);
+    }
+
+    #[test_with_logger]
+    // A message one slot in the future should be dropped, buffered, or rejected per the policy.
+    fn test_future_slot_policy(logger: Logger) {
+        let slot_index = 7;
+        let msg: Msg<&'static str> = Msg::new(
+            test_node_id(2),
+            QuorumSet::new_with_node_ids(1, vec![test_node_id(1)]),
+            slot_index + 1,
+            Topic::Nominate(NominatePayload {
+                X: btreeset!["a"],
+                Y: Default::default(),
             }),
         );
 
+        let mut node = get_node(slot_index, logger.clone());
+        assert_eq!(node.future_slot_policy, FutureSlotPolicy::Drop);
+        assert_eq!(node.handle_messages(vec![msg.clone()]), Ok(vec![]));
+        assert_eq!(node.num_buffered_future_msgs(), 0);
+
+        let mut node = get_node(slot_index, logger.clone());
+        node.future_slot_policy = FutureSlotPolicy::Buffer;
+        assert_eq!(node.handle_messages(vec![msg.clone()]), Ok(vec![]));
+        assert_eq!(node.num_buffered_future_msgs(), 1);
+
+        let mut node = get_node(slot_index, logger);
+        node.future_slot_policy = FutureSlotPolicy::Reject;
+        assert_eq!(node.handle_messages(vec![msg]), Ok(vec![]));
+        assert_eq!(node.future_msgs_rejected(), 1);
+        assert_eq!(node.num_buffered_future_msgs(), 0);
+    }
+
+    #[test_with_logger]
+    // Rejecting a future slot message should not discard the rest of its batch.
+    fn test_future_slot_policy_reject_handles_batch(logger: Logger) {
+        let (_, mut node2) = get_two_nodes(1, logger.clone());
+        let msg = node2
+            .propose_values(btreeset! {1000})
+            .expect("error proposing values")
+            .expect("node 2 leads slot 1");
+        let mut future_msg = msg.clone();
+        future_msg.slot_index = 3;
+
+        let (mut reference, _) = get_two_nodes(1, logger.clone());
+        let expected = reference.handle_messages(vec![msg.clone()]).unwrap();
+        assert_eq!(expected.len(), 1);
+
+        let (mut node1, _) = get_two_nodes(1, logger);
+        node1.future_slot_policy = FutureSlotPolicy::Reject;
+        assert_eq!(
+            node1.handle_messages(vec![future_msg.clone(), msg]),
+            Ok(expected)
+        );
+        assert!(node1.diff_state(&reference).is_empty());
+        assert_eq!(node1.future_msgs_rejected(), 1);
+
+        // Through `handle_message`, the response is to the message itself.
+        assert_eq!(node1.handle_message(&future_msg), Ok(None));
+        assert_eq!(node1.future_msgs_rejected(), 2);
+        assert_eq!(node1.handle_messages(vec![]), Ok(vec![]));
+    }
+
+    #[test_with_logger]
+    // The union should contain each value externalized in the range exactly once.
+    fn test_externalized_values_union(logger: Logger) {
+        let (mut node1, mut node2) = get_two_nodes(1, logger);
+        node1.set_max_externalized_slots(3);
+        run_two_node_consensus_with_any_leader(&mut node1, &mut node2, &[1000, 2000]);
+        run_two_node_consensus_with_any_leader(&mut node1, &mut node2, &[2000, 3000]);
+        run_two_node_consensus_with_any_leader(&mut node1, &mut node2, &[3000, 4000]);
+        assert_eq!(node1.current_slot_index(), 4);
+
+        assert_eq!(
+            node1.externalized_values_union(1, 3),
+            BTreeSet::from_iter(vec![1000, 2000, 3000])
+        );
+        assert_eq!(
+            node1.externalized_values_union(2, 10),
+            BTreeSet::from_iter(vec![2000, 3000, 4000])
+        );
+        assert_eq!(node1.externalized_values_union(3, 3), BTreeSet::new());
+        assert_eq!(node1.externalized_values_union(4, 10), BTreeSet::new());
+    }
+
+    #[test_with_logger]
+    // Only the configured number of most recent externalized slots should be retained.
+    fn test_externalized_slot_indices(logger: Logger) {
+        let (mut node1, mut node2) = get_two_nodes(1, logger);
+        node1.set_max_externalized_slots(5);
+        assert!(node1.externalized_slot_indices().is_empty());
+
+        for i in 1..=15 {
+            run_two_node_consensus_with_any_leader(&mut node1, &mut node2, &[1000 * i]);
+        }
+        assert_eq!(node1.current_slot_index(), 16);
+
+        assert_eq!(node1.externalized_slot_indices(), vec![11, 12, 13, 14, 15]);
+        assert_eq!(node1.get_externalized_values(10), None);
+        assert_eq!(node1.get_externalized_values(11), Some(vec![11000]));
+        assert_eq!(node1.get_externalized_values(15), Some(vec![15000]));
+    }
+
+    #[test_with_logger]
+    // After a quorum set change, messages for later slots should carry the new quorum set.
+    fn test_last_emitted_quorum_set(logger: Logger) {
+        let (mut node1, mut node2) = get_two_nodes(1, logger);
+        assert_eq!(node2.last_emitted_quorum_set(), None);
+
+        let old_quorum_set = node2.Q.clone();
+        run_two_node_consensus_with_any_leader(&mut node1, &mut node2, &[1000]);
+        assert_eq!(node2.last_emitted_quorum_set(), Some(&old_quorum_set));
+
+        // Slot 2 already exists, so the new quorum set applies from slot 3.
+        let new_quorum_set =
+            QuorumSet::new_with_node_ids(1, vec![test_node_id(1), test_node_id(3)]);
+        node2.Q = new_quorum_set.clone();
+        run_two_node_consensus_with_any_leader(&mut node1, &mut node2, &[2000]);
+        assert_eq!(node2.last_emitted_quorum_set(), Some(&old_quorum_set));
+
+        run_two_node_consensus_with_any_leader(&mut node1, &mut node2, &[3000]);
+        assert_eq!(node2.current_slot_index(), 4);
+        assert_eq!(node2.last_emitted_quorum_set(), Some(&new_quorum_set));
+        let slot = node2
+            .get_externalized_slot(3)
+            .expect("slot 3 should be retained");
+        assert_eq!(
+            slot.get_last_message_sent().map(|msg| msg.quorum_set),
+            Some(new_quorum_set)
+        );
+    }
+
+    #[test_with_logger]
+    // A late Prepare for an externalized slot should trigger a re-sent Externalize, if enabled.
+    fn test_late_message_policy(logger: Logger) {
+        for policy in &[
+            LateMessagePolicy::Handle,
+            LateMessagePolicy::ResendExternalize,
+        ] {
+            let (mut node1, mut node2) = get_two_nodes(1, logger.clone());
+            node1.late_message_policy = *policy;
+            run_two_node_consensus(&mut node1, &mut node2, &[1000, 2000]);
+            assert_eq!(node1.current_slot_index(), 2);
+
+            // A peer that is still preparing slot 1.
+            let late_prepare = Msg::new(
+                test_node_id(2),
+                QuorumSet::new_with_node_ids(1, vec![test_node_id(1)]),
+                1,
+                Topic::Prepare(PreparePayload {
+                    B: Ballot::new(1, &[1000, 2000]),
+                    P: None,
+                    PP: None,
+                    CN: 0,
+                    HN: 0,
+                }),
+            );
+            let outgoing = node1
+                .handle_messages(vec![late_prepare])
+                .expect("error handling msgs");
+
+            match policy {
+                LateMessagePolicy::Handle => assert_eq!(outgoing, vec![]),
+                LateMessagePolicy::ResendExternalize => {
+                    let externalize = node1
+                        .get_externalized_slot(1)
+                        .and_then(|slot| slot.get_last_message_sent())
+                        .expect("slot 1 should have sent a message");
+                    assert!(externalize.topic.is_externalize());
+                    assert_eq!(outgoing, vec![externalize]);
+                }
+            }
+        }
+    }
+
+    #[test_with_logger]
+    // on_conflict should fire when a peer externalizes different values for a retained slot.
+    fn test_on_conflict(logger: Logger) {
+        let (mut node1, mut node2) = get_two_nodes(1, logger);
+        let conflicts = Arc::new(Mutex::new(Vec::new()));
+        let conflicts_clone = conflicts.clone();
+        node1.on_conflict = Some(Arc::new(
+            move |node_id: &NodeID, slot_index, ours: &[u32], theirs: &[u32]| {
+                conflicts_clone.lock().unwrap().push((
+                    node_id.clone(),
+                    slot_index,
+                    ours.to_vec(),
+                    theirs.to_vec(),
+                ));
+            },
+        ));
+
+        run_two_node_consensus(&mut node1, &mut node2, &[1000, 2000]);
+        assert_eq!(node1.current_slot_index(), 2);
+        assert!(conflicts.lock().unwrap().is_empty());
+
+        let externalize = |node_id: NodeID, values: &[u32]| {
+            Msg::new(
+                node_id,
+                QuorumSet::new_with_node_ids(1, vec![test_node_id(1)]),
+                1,
+                Topic::Externalize(ExternalizePayload {
+                    C: Ballot::new(1, values),
+                    HN: INFINITY,
+                }),
+            )
+        };
+
+        // Externalizing the same values is not a conflict.
+        node1
+            .handle_messages(vec![externalize(test_node_id(3), &[1000, 2000])])
+            .expect("error handling msgs");
+        assert!(conflicts.lock().unwrap().is_empty());
+
+        node1
+            .handle_messages(vec![externalize(test_node_id(3), &[1000, 3000])])
+            .expect("error handling msgs");
+        assert_eq!(
+            *conflicts.lock().unwrap(),
+            vec![(test_node_id(3), 1, vec![1000, 2000], vec![1000, 3000])]
+        );
+    }
+
+    #[test_with_logger]
+    // handle_many should behave like calling handle_messages on each message in turn.
+    fn test_handle_many(logger: Logger) {
+        // Record the messages node 2 sends while reaching consensus with node 1.
+        let (mut node1, mut node2) = get_two_nodes(1, logger.clone());
+        let mut msgs_from_node2 = Vec::new();
+        let mut msg = node2
+            .propose_values(BTreeSet::from_iter(vec![1000, 2000]))
+            .unwrap();
+        while let Some(incoming) = msg {
+            msgs_from_node2.push(incoming.clone());
+            msg = node1
+                .handle_message(&incoming)
+                .unwrap()
+                .and_then(|response| node2.handle_message(&response).unwrap());
+        }
+        assert_eq!(node1.current_slot_index(), 2);
+
+        // Include a message from node 1 itself, which should be skipped.
+        let mut batch = msgs_from_node2.clone();
+        batch.insert(
+            1,
+            node1
+                .get_externalized_slot(1)
+                .unwrap()
+                .get_last_message_sent()
+                .unwrap(),
+        );
+
+        let (mut one_at_a_time, _) = get_two_nodes(1, logger.clone());
+        let mut expected = Vec::new();
+        for msg in &msgs_from_node2 {
+            expected.extend(one_at_a_time.handle_messages(vec![msg.clone()]).unwrap());
+        }
+
+        let (mut batched, _) = get_two_nodes(1, logger.clone());
+        assert_eq!(batched.handle_many(&batch).unwrap(), expected);
+        assert!(batched.diff_state(&one_at_a_time).is_empty());
+        assert_eq!(batched.get_externalized_values(1), Some(vec![1000, 2000]));
+
+        // An error stops the batch, so the slot only sees the first message.
+        let mut node = get_node(1, logger);
         let mut slot = MockScpSlot::new();
-        slot.expect_propose_values()
+        slot.expect_get_index().return_const(1 as SlotIndex);
+        slot.expect_get_metrics().returning(nominating_slot_metrics);
+        slot.expect_handle_messages()
             .times(1)
-            .return_const(Ok(Some(msg.clone()))); //  Outgoing Msg, not an Externalize.
-        slot.expect_get_index().return_const(slot_index);
+            .returning(|_msgs| Err("failed".to_string()));
         node.current_slot = Box::new(slot);
 
-        let values = btreeset!["a", "b", "c"];
-        assert_eq!(node.propose_values(values), Ok(Some(msg)));
+        let msgs: Vec<Msg<&'static str>> = ["a", "b"]
+            .iter()
+            .map(|value| {
+                Msg::new(
+                    test_node_id(2),
+                    QuorumSet::new_with_node_ids(1, vec![test_node_id(1)]),
+                    1,
+                    Topic::Nominate(NominatePayload {
+                        X: btreeset![*value],
+                        Y: Default::default(),
+                    }),
+                )
+            })
+            .collect();
+        assert!(node.handle_many(&msgs).is_err());
+    }
 
-        // The `slot_index` slot should now be extnalized, and current_slot should be at `slot_index + 1`.
-        assert_eq!(node.current_slot.get_index(), slot_index + 1);
-        assert_eq!(node.externalized_slots.len(), 1);
-        assert_eq!(node.externalized_slots[0].get_index(), slot_index)
+    #[test_with_logger]
+    // A new quorum set should only apply from the slot after the one in flight.
+    fn test_set_quorum_set(logger: Logger) {
+        let (mut node1, mut node2) = get_two_nodes(1, logger);
+        let old_quorum_set = node2.quorum_set();
+        let msg = node2
+            .propose_values(btreeset! {1000})
+            .expect("error proposing values")
+            .expect("node 2 leads slot 1");
+        assert_eq!(msg.quorum_set, old_quorum_set);
+
+        let new_quorum_set =
+            QuorumSet::new_with_node_ids(1, vec![test_node_id(1), test_node_id(3)]);
+        node2.set_quorum_set(new_quorum_set.clone());
+        assert_eq!(node2.quorum_set(), new_quorum_set);
+
+        // The in-flight slot keeps the old quorum set.
+        exchange_messages(&mut node1, &mut node2, Some(msg));
+        assert_eq!(node2.current_slot_index(), 2);
+        let slot = node2
+            .get_externalized_slot(1)
+            .expect("slot 1 should be retained");
+        assert_eq!(
+            slot.get_last_message_sent().map(|msg| msg.quorum_set),
+            Some(old_quorum_set)
+        );
+
+        // The next slot uses the new one.
+        run_two_node_consensus_with_any_leader(&mut node1, &mut node2, &[2000]);
+        assert_eq!(node2.current_slot_index(), 3);
+        assert_eq!(node2.last_emitted_quorum_set(), Some(&new_quorum_set));
+    }
+
+    #[test_with_logger]
+    // A node should report being stuck once its current slot has emitted nothing for longer than
+    // the threshold, and stop once it emits a message again.
+    fn test_is_stuck(logger: Logger) {
+        let (mut node1, mut node2) = get_two_nodes(1, logger);
+        node1.set_mock_clock(MockClock::default());
+        let threshold = Duration::from_secs(5);
+        assert!(!node1.is_stuck(threshold));
+
+        node1.advance_clock(Duration::from_secs(4));
+        assert!(!node1.is_stuck(threshold));
+        node1.advance_clock(Duration::from_secs(2));
+        assert!(node1.is_stuck(threshold));
+
+        let msg = node2
+            .propose_values(btreeset! {1000})
+            .expect("error proposing values");
+        let response = node1
+            .handle_message(&msg.expect("node 2 leads slot 1"))
+            .expect("error handling msg");
+        assert!(response.is_some());
+        assert!(!node1.is_stuck(threshold));
+
+        node1.advance_clock(Duration::from_secs(6));
+        assert!(node1.is_stuck(threshold));
+    }
+
+    #[test_with_logger]
+    // Aggregate metrics should include slots that are no longer retained.
+    fn test_aggregate_metrics(logger: Logger) {
+        let (mut node1, mut node2) = get_two_nodes(1, logger);
+        node1.set_max_externalized_slots(2);
+        node1.set_mock_clock(MockClock::default());
+        assert_eq!(
+            node1.aggregate_metrics(),
+            NodeMetrics {
+                num_externalized: 0,
+                num_msgs: 0,
+                num_timeouts: 0,
+                num_validity_divergences: 0,
+            }
+        );
+
+        // Node 2 leads the first round, so node 1 nominates after the round times out.
+        assert_eq!(node1.propose_values(btreeset! {1000}), Ok(None));
+        assert_eq!(node1.advance_clock(Duration::from_millis(1100)).len(), 1);
+        let metrics = node1.aggregate_metrics();
+        assert_eq!(metrics.num_timeouts, 1);
+        assert_eq!(metrics.num_msgs, 1);
+
+        let mut expected_msgs = 0;
+        for slot_index in 1..=5 {
+            run_two_node_consensus_with_any_leader(&mut node1, &mut node2, &[1000 * slot_index]);
+            expected_msgs += node1
+                .messages_to_externalize(slot_index as SlotIndex)
+                .expect("latest slot should be retained");
+        }
+        assert_eq!(node1.current_slot_index(), 6);
+        assert_eq!(
+            node1.aggregate_metrics(),
+            NodeMetrics {
+                num_externalized: 5,
+                num_msgs: expected_msgs,
+                num_timeouts: 1,
+                num_validity_divergences: 0,
+            }
+        );
+    }
+
+    #[test_with_logger]
+    // With a leader function, a node should initially echo only the nominations of its leaders.
+    fn test_set_leader_fn(logger: Logger) {
+        let mut node = test_utils::get_node(
+            1,
+            test_node_id(1),
+            QuorumSet::new_with_node_ids(
+                2,
+                vec![test_node_id(2), test_node_id(3), test_node_id(4)],
+            ),
+            logger,
+        );
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let calls_clone = calls.clone();
+        node.set_leader_fn(Arc::new(move |slot_index, quorum_set| {
+            calls_clone.lock().unwrap().push(slot_index);
+            assert_eq!(quorum_set.threshold, 2);
+            vec![test_node_id(2), test_node_id(3)]
+        }));
+        node.reset_slot_index(2);
+        assert_eq!(*calls.lock().unwrap(), vec![2]);
+
+        // Node 1 is not a leader, so it does not nominate its own values.
+        assert_eq!(node.propose_values(btreeset! {100}), Ok(None));
+
+        let msgs: Vec<Msg<u32>> = (2..=4)
+            .map(|i| {
+                Msg::new(
+                    test_node_id(i),
+                    QuorumSet::new_with_node_ids(1, vec![test_node_id(1)]),
+                    2,
+                    Topic::Nominate(NominatePayload {
+                        X: btreeset! {i * 1000},
+                        Y: Default::default(),
+                    }),
+                )
+            })
+            .collect();
+        let outgoing = node.handle_messages(msgs).expect("error handling msgs");
+        let voted = outgoing
+            .last()
+            .and_then(|msg| msg.votes_or_accepts_nominated())
+            .expect("node 1 should echo its leaders' values");
+        assert_eq!(voted, btreeset! {2000, 3000});
+    }
+
+    #[test_with_logger]
+    // Values proposed after the current slot stops nominating should be proposed to the next slot.
+    fn test_pending_values(logger: Logger) {
+        let (mut node1, mut node2) = get_two_nodes(1, logger);
+        node2.set_leader_fn(Arc::new(|_slot_index, _quorum_set| vec![test_node_id(2)]));
+
+        // Run slot 1 until node 2 has confirmed a value nominated.
+        let mut to_node1 = node2
+            .propose_values(btreeset! {1000})
+            .expect("error proposing values");
+        while node2.get_current_slot_metrics().num_confirmed_nominated == 0 {
+            let to_node2 = node1
+                .handle_message(&to_node1.expect("node 2 should send a message"))
+                .expect("error handling msg");
+            to_node1 = node2
+                .handle_message(&to_node2.expect("node 1 should send a message"))
+                .expect("error handling msg");
+        }
+        assert_eq!(node2.current_slot_index(), 1);
+
+        node2
+            .propose_values(btreeset! {1000, 2000})
+            .expect("error proposing values");
+        assert_eq!(node2.pending_values(), &btreeset! {1000, 2000});
+
+        // Slot 1 externalizes while node 2 handles messages one at a time, so its response is the
+        // Externalize message.
+        let mut response = None;
+        while node2.current_slot_index() == 1 {
+            let to_node2 = node1
+                .handle_message(&to_node1.expect("node 2 should send a message"))
+                .expect("error handling msg");
+            response = node2
+                .handle_message(&to_node2.expect("node 1 should send a message"))
+                .expect("error handling msg");
+            to_node1 = response.clone();
+        }
+        assert!(response
+            .expect("node 2 should externalize")
+            .topic
+            .is_externalize());
+        assert_eq!(node2.get_externalized_values(1), Some(vec![1000]));
+        assert!(node2.pending_values().is_empty());
+
+        // Node 2 leads slot 2, so it nominates the pending values that were not externalized.
+        // The message is sent by `process_timeouts`, exactly once.
+        let msgs = node2.process_timeouts();
+        assert_eq!(msgs.len(), 1);
+        assert_eq!(msgs[0].slot_index, 2);
+        assert_eq!(msgs[0].votes_or_accepts_nominated(), Some(btreeset! {2000}));
+        assert!(node2.process_timeouts().is_empty());
+    }
+
+    #[test_with_logger]
+    // Resetting the slot index should discard pending values.
+    fn test_reset_slot_index_clears_pending_values(logger: Logger) {
+        let (mut node1, mut node2) = get_two_nodes(1, logger);
+        node2.set_leader_fn(Arc::new(|_slot_index, _quorum_set| vec![test_node_id(2)]));
+
+        let mut to_node1 = node2
+            .propose_values(btreeset! {1000})
+            .expect("error proposing values");
+        while node2.get_current_slot_metrics().num_confirmed_nominated == 0 {
+            let to_node2 = node1
+                .handle_message(&to_node1.expect("node 2 should send a message"))
+                .expect("error handling msg");
+            to_node1 = node2
+                .handle_message(&to_node2.expect("node 1 should send a message"))
+                .expect("error handling msg");
+        }
+        node2
+            .propose_values(btreeset! {2000})
+            .expect("error proposing values");
+        assert_eq!(node2.pending_values(), &btreeset! {2000});
+
+        node2.reset_slot_index(5);
+        assert!(node2.pending_values().is_empty());
+    }
+
+    #[test_with_logger]
+    // A node restored from a snapshot taken mid-consensus should carry on where it left off.
+    fn test_snapshot_round_trip(logger: Logger) {
+        let (mut node1, mut node2) = get_two_nodes(1, logger.clone());
+        node1.set_max_externalized_slots(5);
+        run_two_node_consensus(&mut node1, &mut node2, &[1000]);
+
+        // Only the nomination leader for slot 2 emits a message for its proposal.
+        let values = btreeset! {2000};
+        let to_node1 = node2
+            .propose_values(values.clone())
+            .expect("error proposing values");
+        let to_node2 = node1
+            .propose_values(values)
+            .expect("error proposing values");
+        let to_node2 = match to_node1 {
+            Some(msg) => node1.handle_message(&msg).expect("error handling msg"),
+            None => to_node2,
+        };
+        assert!(to_node2.is_some());
+        assert_eq!(node1.current_slot_index(), 2);
+
+        let bytes = mc_util_serial::serialize(&node1.to_snapshot()).unwrap();
+        let snapshot: NodeSnapshot<u32> = mc_util_serial::deserialize(&bytes).unwrap();
+        let mut restored = Node::<u32, TransactionValidationError>::from_snapshot(
+            snapshot,
+            Arc::new(trivial_validity_fn),
+            Arc::new(trivial_combine_fn),
+            logger,
+        )
+        .expect("snapshot should be valid");
+
+        assert!(node1.diff_state(&restored).is_empty());
+        assert_eq!(restored.get_externalized_values(1), Some(vec![1000]));
+        assert_eq!(
+            restored.current_slot.get_last_message_sent(),
+            node1.current_slot.get_last_message_sent()
+        );
+
+        exchange_messages(&mut node2, &mut restored, to_node2);
+        assert_eq!(restored.current_slot_index(), 3);
+        assert_eq!(restored.get_externalized_values(2), Some(vec![2000]));
+        assert_eq!(node2.get_externalized_values(2), Some(vec![2000]));
     }
 
     #[test_with_logger]
-    // Should omit messages from self.
-    fn test_handle_messages_omit_from_self(logger: Logger) {
-        let slot_index = 1985;
-        let mut node = get_node(slot_index, logger);
+    // A snapshot whose externalized slots are not before its current slot should be rejected.
+    fn test_from_snapshot_rejects_inconsistent_history(logger: Logger) {
+        let (mut node1, mut node2) = get_two_nodes(1, logger.clone());
+        run_two_node_consensus(&mut node1, &mut node2, &[1000]);
 
-        // The current slot should not be called.
-        let mut slot = MockScpSlot::new();
-        slot.expect_get_index().return_const(slot_index);
-        node.current_slot = Box::new(slot);
+        let mut snapshot = node1.to_snapshot();
+        snapshot.externalized.push((2, vec![2000]));
+        assert!(Node::<u32, TransactionValidationError>::from_snapshot(
+            snapshot,
+            Arc::new(trivial_validity_fn),
+            Arc::new(trivial_combine_fn),
+            logger,
+        )
+        .is_err());
+    }
 
-        // The recent externalized slot should not be called.
-        let mut externalized_slot = MockScpSlot::new();
-        externalized_slot
-            .expect_get_index()
-            .return_const(slot_index - 1);
-        node.push_externalized_slot(Box::new(externalized_slot));
+    #[test_with_logger]
+    // Nodes that externalize the same values should compute the same digest.
+    fn test_externalized_digest(logger: Logger) {
+        let (mut node1, mut node2) = get_two_nodes(1, logger);
+        assert_eq!(node1.externalized_digest(1), None);
 
-        let msg_from_self = Msg::new(
-            node.ID.clone(),
-            node.quorum_set(),
-            slot_index,
-            Topic::Externalize(ExternalizePayload {
-                C: Ballot::new(4, &[]),
-                HN: 3,
-            }),
-        );
+        run_two_node_consensus(&mut node1, &mut node2, &[2000, 1000]);
 
-        match node.handle_messages(vec![msg_from_self.clone(), msg_from_self.clone()]) {
-            Ok(outgoing_msgs) => assert_eq!(outgoing_msgs.len(), 0),
-            Err(e) => panic!("Unexpected error {:?}", e),
-        }
+        let digest = node1.externalized_digest(1).expect("no digest?");
+        assert_eq!(node2.externalized_digest(1), Some(digest));
+
+        // A different set of values has a different digest.
+        run_two_node_consensus(&mut node1, &mut node2, &[3000]);
+        assert_ne!(node1.externalized_digest(2), Some(digest));
+        assert_eq!(node1.externalized_digest(2), node2.externalized_digest(2));
     }
 
     #[test_with_logger]
-    // Should omit messages for future slots.
-    fn test_handle_messages_omit_from_future(logger: Logger) {
-        let slot_index = 1985;
-        let mut node = get_node(slot_index, logger);
-
-        // The current slot should not be called.
-        let mut slot = MockScpSlot::new();
-        slot.expect_get_index().return_const(slot_index);
-        node.current_slot = Box::new(slot);
+    // Nodes in lockstep should report no differences, and divergent nodes should report them.
+    fn test_diff_state(logger: Logger) {
+        let (mut node1, mut node2) = get_two_nodes(1, logger.clone());
+        run_two_node_consensus(&mut node1, &mut node2, &[1000, 2000]);
+        assert!(node1.diff_state(&node2).is_empty());
 
-        // The recent externalized slot should not be called.
-        let mut externalized_slot = MockScpSlot::new();
-        externalized_slot
-            .expect_get_index()
-            .return_const(slot_index - 1);
-        node.push_externalized_slot(Box::new(externalized_slot));
+        // A node that is behind differs in its current slot index.
+        let (node3, _) = get_two_nodes(1, logger.clone());
+        let diff = node1.diff_state(&node3);
+        assert_eq!(diff.current_slot_index, Some((2, 1)));
+        assert_eq!(diff.phase, None);
+        assert_eq!(diff.ballot, None);
+        assert_eq!(diff.externalized, vec![(1, Some(vec![1000, 2000]), None)]);
 
-        // A message from a peer for a future slot index.
-        let msg_for_future_slot = Msg::new(
-            test_node_id(2),
-            QuorumSet::new_with_node_ids(1, vec![test_node_id(3)]),
-            2015, // Where we're going, we don't need roads.
-            Topic::Externalize(ExternalizePayload {
-                C: Ballot::new(4, &[]),
-                HN: 3,
-            }),
+        // Nodes on the same slot that externalized different values.
+        let with_history = |values: Vec<u32>| {
+            Node::<u32, TransactionValidationError>::with_history(
+                test_node_id(1),
+                QuorumSet::new_with_node_ids(1, vec![test_node_id(2)]),
+                Arc::new(trivial_validity_fn),
+                Arc::new(trivial_combine_fn),
+                vec![(1, values)],
+                logger.clone(),
+            )
+            .expect("with_history failed")
+        };
+        let diff = with_history(vec![1000]).diff_state(&with_history(vec![2000]));
+        assert_eq!(diff.current_slot_index, None);
+        assert_eq!(
+            diff.externalized,
+            vec![(1, Some(vec![1000]), Some(vec![2000]))]
         );
+    }
 
-        match node.handle_messages(vec![msg_for_future_slot]) {
-            Ok(outgoing_msgs) => assert_eq!(outgoing_msgs.len(), 0),
-            Err(e) => panic!("Unexpected error {:?}", e),
+    #[test_with_logger]
+    // Slot progress should increase with each step towards externalizing.
+    fn test_slot_progress(logger: Logger) {
+        let (mut node1, mut node2) = get_two_nodes(1, logger);
+        assert_eq!(node1.slot_progress(), 0.0);
+
+        let mut progress = vec![node1.slot_progress()];
+        let mut msg = node2
+            .propose_values(BTreeSet::from_iter(vec![1000, 2000]))
+            .unwrap();
+        let mut receiver_is_node1 = true;
+        while let Some(incoming) = msg {
+            msg = if receiver_is_node1 {
+                let outgoing = node1.handle_message(&incoming).unwrap();
+                if node1.current_slot_index() == 1 {
+                    progress.push(node1.slot_progress());
+                }
+                outgoing
+            } else {
+                node2.handle_message(&incoming).unwrap()
+            };
+            receiver_is_node1 = !receiver_is_node1;
         }
+
+        // Node 1 passed through several phases before externalizing, progressing at each step.
+        assert!(progress.len() > 2, "progress: {:?}", progress);
+        for pair in progress.windows(2) {
+            assert!(pair[0] < pair[1], "progress: {:?}", progress);
+        }
+
+        // The externalized slot is complete, and the next slot starts from scratch.
+        let slot = node1
+            .get_externalized_slot(1)
+            .expect("slot 1 should be retained");
+        assert_eq!(slot.progress(), 1.0);
+        assert_eq!(node1.current_slot_index(), 2);
+        assert_eq!(node1.slot_progress(), 0.0);
     }
 
     #[test_with_logger]
-    // Should omit messages that are too old.
-    fn test_handle_messages_omit_old(logger: Logger) {
-        let slot_index = 1985;
-        let mut node = get_node(slot_index, logger);
+    // The provenance of an externalized value should list the peers it was nominated by.
+    fn test_value_provenance(logger: Logger) {
+        let (node_1, node_2, node_3) = three_node_dense_graph();
+        let mut node = test_utils::get_node(1, node_1.0, node_1.1.clone(), logger.clone());
 
-        // The current slot should not be called.
-        let mut slot = MockScpSlot::new();
-        slot.expect_get_index().return_const(slot_index);
+        // Listen to nominations from both peers.
+        let mut slot = test_utils::get_slot(1, &node.ID, &node_1.1, logger);
+        slot.max_priority_peers = HashSet::from_iter(vec![node_2.0.clone(), node_3.0.clone()]);
         node.current_slot = Box::new(slot);
 
-        // The recent externalized slot should not be called.
-        let mut externalized_slot = MockScpSlot::new();
-        externalized_slot
-            .expect_get_index()
-            .return_const(slot_index - 1);
-        node.push_externalized_slot(Box::new(externalized_slot));
+        let nominate = |(node_id, quorum_set): &(NodeID, QuorumSet)| {
+            Msg::new(
+                node_id.clone(),
+                quorum_set.clone(),
+                1,
+                Topic::Nominate(NominatePayload {
+                    X: BTreeSet::from_iter(vec![1000]),
+                    Y: Default::default(),
+                }),
+            )
+        };
+        node.handle_messages(vec![nominate(&node_2), nominate(&node_3)])
+            .expect("error handling msgs");
+        assert_eq!(node.value_provenance(1, &2000), None);
 
-        // A message from an old slot.
-        let msg_for_old_slot = Msg::new(
-            test_node_id(2),
-            QuorumSet::new_with_node_ids(1, vec![test_node_id(3)]),
-            1885, // Too old
-            Topic::Nominate(NominatePayload {
-                X: Default::default(),
-                Y: Default::default(),
-            }),
+        let externalize = |(node_id, quorum_set): &(NodeID, QuorumSet)| {
+            Msg::new(
+                node_id.clone(),
+                quorum_set.clone(),
+                1,
+                Topic::Externalize(ExternalizePayload {
+                    C: Ballot::new(1, &[1000]),
+                    HN: INFINITY,
+                }),
+            )
+        };
+        node.handle_messages(vec![externalize(&node_2), externalize(&node_3)])
+            .expect("error handling msgs");
+        assert_eq!(node.current_slot_index(), 2);
+        assert_eq!(node.get_externalized_values(1), Some(vec![1000]));
+
+        assert_eq!(
+            node.value_provenance(1, &1000),
+            Some(HashSet::from_iter(vec![node_2.0, node_3.0]))
         );
+        assert_eq!(node.value_provenance(2, &1000), None);
+    }
 
-        match node.handle_messages(vec![msg_for_old_slot]) {
-            Ok(outgoing_msgs) => assert_eq!(outgoing_msgs.len(), 0),
-            Err(e) => panic!("Unexpected error {:?}", e),
+    #[test_with_logger]
+    // on_finalize should fire once per slot, when externalizing becomes final.
+    fn test_on_finalize(logger: Logger) {
+        let (mut node1, mut node2) = get_two_nodes(1, logger);
+        let finalized_by_node1 = Arc::new(Mutex::new(Vec::new()));
+        let finalized_by_node2 = Arc::new(Mutex::new(Vec::new()));
+        for (node, finalized) in &mut [
+            (&mut node1, finalized_by_node1.clone()),
+            (&mut node2, finalized_by_node2.clone()),
+        ] {
+            let finalized = finalized.clone();
+            node.on_finalize = Some(Arc::new(move |slot_index, values: &[u32]| {
+                finalized
+                    .lock()
+                    .unwrap()
+                    .push((slot_index, values.to_vec()));
+            }));
+        }
+
+        let mut msg = node2
+            .propose_values(BTreeSet::from_iter(vec![1000, 2000]))
+            .unwrap();
+        let mut receiver_is_node1 = true;
+        let mut node2_provisional = false;
+        while let Some(incoming) = msg {
+            msg = if receiver_is_node1 {
+                node1.handle_message(&incoming).unwrap()
+            } else {
+                let outgoing = node2.handle_message(&incoming).unwrap();
+                // Node 2 externalizes first, before it knows that node 1 has also committed.
+                if let Some(Topic::Externalize(payload)) = outgoing.as_ref().map(|m| &m.topic) {
+                    if payload.HN < INFINITY {
+                        node2_provisional = true;
+                        assert!(finalized_by_node2.lock().unwrap().is_empty());
+                    }
+                }
+                outgoing
+            };
+            receiver_is_node1 = !receiver_is_node1;
+        }
+        assert!(node2_provisional);
+
+        // Both nodes finalize slot 1 exactly once.
+        for finalized in &[finalized_by_node1, finalized_by_node2] {
+            assert_eq!(*finalized.lock().unwrap(), vec![(1, vec![1000, 2000])]);
         }
     }
 
     #[test_with_logger]
-    // Should pass messages to the current slot.
-    fn test_handle_messages_current_slot(logger: Logger) {
-        let slot_index = 1985;
-        let mut node = get_node(slot_index, logger);
+    // Messages embedding a stale quorum set should be handled according to the policy.
+    fn test_stale_quorum_set_policy(logger: Logger) {
+        for policy in &[
+            StaleQuorumSetPolicy::Accept,
+            StaleQuorumSetPolicy::Warn,
+            StaleQuorumSetPolicy::Reject,
+        ] {
+            let (mut node1, node2) = get_two_nodes(1, logger.clone());
+            node1.stale_quorum_set_policy = *policy;
 
-        // The current slot should be called, and should return a message.
-        let mut slot = MockScpSlot::new();
-        {
-            slot.expect_get_index().return_const(slot_index);
+            // Node 2 is expected to have added node 3 to its quorum set as of slot 1, but still
+            // sends its old one.
+            let new_quorum_set =
+                QuorumSet::new_with_node_ids(1, vec![test_node_id(1), test_node_id(3)]);
+            node1.expect_quorum_set(node2.ID.clone(), &new_quorum_set, 1);
 
             let msg = Msg::new(
-                node.ID.clone(),
-                node.quorum_set(),
-                slot_index,
-                Topic::Externalize(ExternalizePayload {
-                    C: Ballot::new(4, &[]),
-                    HN: 3,
+                node2.ID.clone(),
+                node2.Q.clone(),
+                1,
+                Topic::Nominate(NominatePayload {
+                    X: btreeset![1000],
+                    Y: Default::default(),
                 }),
             );
+            assert!(node1.has_stale_quorum_set(&msg));
 
-            slot.expect_handle_messages()
-                .times(1)
-                .return_const(Ok(Some(msg)));
+            // Messages for earlier slots are not checked.
+            let mut old_msg = msg.clone();
+            old_msg.slot_index = 0;
+            assert!(!node1.has_stale_quorum_set(&old_msg));
+
+            let response = node1.handle_message(&msg).unwrap();
+            match policy {
+                StaleQuorumSetPolicy::Accept | StaleQuorumSetPolicy::Warn => {
+                    assert!(response.is_some())
+                }
+                StaleQuorumSetPolicy::Reject => assert_eq!(response, None),
+            }
+
+            // A message with the new quorum set is always handled.
+            let mut current_msg = msg.clone();
+            current_msg.quorum_set = new_quorum_set;
+            assert!(!node1.has_stale_quorum_set(&current_msg));
         }
-        node.current_slot = Box::new(slot);
+    }
 
-        // The recent externalized slot should not be called.
-        let mut externalized_slot = MockScpSlot::new();
-        externalized_slot
-            .expect_get_index()
-            .return_const(slot_index - 1);
-        node.push_externalized_slot(Box::new(externalized_slot));
+    #[test_with_logger]
+    // Malformed messages should be rejected before they reach the slot.
+    fn test_handle_malformed_message(logger: Logger) {
+        let (mut node1, node2) = get_two_nodes(1, logger);
 
-        let msg_for_current_slot = Msg::new(
-            test_node_id(2),
-            QuorumSet::new_with_node_ids(1, vec![test_node_id(3)]),
-            slot_index,
-            Topic::Nominate(NominatePayload {
-                X: Default::default(),
-                Y: Default::default(),
+        let msg = Msg::new(
+            node2.ID.clone(),
+            node2.Q.clone(),
+            1,
+            Topic::Prepare(PreparePayload {
+                B: Ballot::new(1, &[1000]),
+                P: None,
+                PP: None,
+                CN: 1,
+                HN: 0,
             }),
         );
+        assert!(node1.handle_message(&msg).is_err());
 
-        match node.handle_messages(vec![msg_for_current_slot]) {
-            Ok(outgoing_msgs) => assert_eq!(outgoing_msgs.len(), 1), // Should return a message.
-            Err(e) => panic!("Unexpected error {:?}", e),
-        }
+        // Within a batch, malformed messages are dropped and the rest are handled.
+        let mut empty_ballot_msg = msg.clone();
+        empty_ballot_msg.topic = Topic::Prepare(PreparePayload {
+            B: Ballot::new(1, &[]),
+            P: None,
+            PP: None,
+            CN: 0,
+            HN: 0,
+        });
+        let mut valid_msg = msg.clone();
+        valid_msg.topic = Topic::Nominate(NominatePayload {
+            X: btreeset![1000],
+            Y: Default::default(),
+        });
+        assert_eq!(
+            node1
+                .handle_messages(vec![msg, empty_ballot_msg, valid_msg])
+                .unwrap()
+                .len(),
+            1
+        );
     }
 
     #[test_with_logger]
-    // Should pass messages to the correct externalized slot.
-    fn test_handle_messages_externalized_slots(logger: Logger) {
-        let slot_index = 1985;
-        let mut node = get_node(slot_index, logger);
-
-        // The current slot should not be called.
-        let mut slot = MockScpSlot::new();
-        slot.expect_get_index().return_const(slot_index);
-        node.current_slot = Box::new(slot);
+    // Signed messages with a bad signature should be handled according to the policy.
+    fn test_bad_signature_policy(logger: Logger) {
+        // Messages with a bad signature are rejected by default.
+        let (node1, _node2) = get_two_nodes(1, logger.clone());
+        assert_eq!(node1.bad_signature_policy, BadSignaturePolicy::Reject);
 
-        // The recently externalized slot should be called.
-        let mut externalized_slot = MockScpSlot::new();
-        {
-            externalized_slot
-                .expect_get_index()
-                .return_const(slot_index - 1);
+        let (_node2_id, node2_keypair) = test_node_id_and_signer(2);
+        for policy in &[
+            BadSignaturePolicy::Accept,
+            BadSignaturePolicy::Warn,
+            BadSignaturePolicy::Reject,
+        ] {
+            let (mut node1, node2) = get_two_nodes(1, logger.clone());
+            node1.bad_signature_policy = *policy;
 
             let msg = Msg::new(
-                node.ID.clone(),
-                node.quorum_set(),
-                slot_index - 1,
-                Topic::Externalize(ExternalizePayload {
-                    C: Ballot::new(4, &[]),
-                    HN: 3,
+                node2.ID.clone(),
+                node2.Q.clone(),
+                1,
+                Topic::Nominate(NominatePayload {
+                    X: btreeset![1000],
+                    Y: Default::default(),
                 }),
             );
+            let signed_msg = msg.sign(&node2_keypair);
 
-            externalized_slot
-                .expect_handle_messages()
-                .times(1)
-                .return_const(Ok(Some(msg)));
-        }
-        node.push_externalized_slot(Box::new(externalized_slot));
+            // A correctly signed message is always handled.
+            let (mut node1_copy, _) = get_two_nodes(1, logger.clone());
+            node1_copy.bad_signature_policy = *policy;
+            assert!(node1_copy
+                .handle_signed_message(&signed_msg)
+                .unwrap()
+                .is_some());
 
-        let msg_for_recent_slot = Msg::new(
-            test_node_id(2),
-            QuorumSet::new_with_node_ids(1, vec![test_node_id(3)]),
-            slot_index - 1,
-            Topic::Nominate(NominatePayload {
-                X: Default::default(),
+            // A message altered after it was signed has a bad signature.
+            let mut tampered = signed_msg.clone();
+            tampered.msg.topic = Topic::Nominate(NominatePayload {
+                X: btreeset![2000],
                 Y: Default::default(),
-            }),
-        );
-
-        match node.handle_messages(vec![msg_for_recent_slot]) {
-            Ok(outgoing_msgs) => assert_eq!(outgoing_msgs.len(), 1), // Should return a message.
-            Err(e) => panic!("Unexpected error {:?}", e),
+            });
+            let result = node1.handle_signed_message(&tampered);
+            match policy {
+                BadSignaturePolicy::Accept | BadSignaturePolicy::Warn => {
+                    assert!(result.unwrap().is_some())
+                }
+                BadSignaturePolicy::Reject => assert!(result.is_err()),
+            }
         }
     }
 
     #[test_with_logger]
-    // Should get externalized values from the correct externalized slot.
-    fn test_get_externalized_values(logger: Logger) {
-        let slot_index = 56;
-        let mut node = get_node(slot_index, logger);
-        node.set_max_externalized_slots(2);
+    // Proposals containing a value larger than max_value_size should be rejected.
+    fn test_max_value_size(logger: Logger) {
+        let (_, mut node2) = get_two_nodes(1, logger);
+        // 10 serializes to one byte, 1000 to three.
+        node2.max_value_size = Some(2);
 
-        // push externalized slots for 51, 52, ..., 55
-        for i in 51..slot_index {
-            let mut externalized_slot = MockScpSlot::new();
-            externalized_slot.expect_get_index().return_const(i);
+        assert!(node2
+            .propose_values(BTreeSet::from_iter(vec![10, 1000]))
+            .is_err());
 
-            let msg = Msg::new(
-                test_node_id(2),
-                QuorumSet::new_with_node_ids(1, vec![test_node_id(3)]),
-                i,
-                Topic::Externalize(ExternalizePayload {
-                    C: Ballot::new(4, &[]),
-                    HN: 3,
-                }),
-            );
+        // The rejected proposal left nothing behind, so only the valid value is nominated.
+        let msg = node2
+            .propose_values(BTreeSet::from_iter(vec![10]))
+            .unwrap()
+            .expect("node 2 should nominate");
+        assert_eq!(
+            msg.votes_or_accepts_nominated(),
+            Some(BTreeSet::from_iter(vec![10]))
+        );
+    }
 
-            externalized_slot
-                .expect_get_last_message_sent()
-                .return_const(Some(msg));
+    #[test_with_logger]
+    // Rapid state changes within the throttle interval should be coalesced into one message.
+    fn test_emission_throttle(logger: Logger) {
+        let clock = Arc::new(MockClock::default());
+        let (mut node1, node2) = get_two_nodes(1, logger);
+        node1.set_clock(clock.clone());
+        node1.emission_throttle = Some(Duration::from_millis(100));
 
-            node.push_externalized_slot(Box::new(externalized_slot));
-        }
+        let nominate = |values: Vec<u32>| {
+            Msg::new(
+                node2.ID.clone(),
+                node2.Q.clone(),
+                1,
+                Topic::Nominate(NominatePayload {
+                    X: BTreeSet::from_iter(values),
+                    Y: BTreeSet::default(),
+                }),
+            )
+        };
 
-        // These slots are too old, and are no longer maintained.
-        for i in 51..=53 {
-            assert_eq!(node.get_externalized_values(i), None)
-        }
+        // The first message is emitted.
+        let msgs = node1.handle_messages(vec![nominate(vec![1000])]).unwrap();
+        assert_eq!(msgs.len(), 1);
 
-        // Slots 54 and 55 should still be maintained.
-        for i in 54..=55 {
-            assert!(node.get_externalized_values(i).is_some());
-        }
+        // The second state change is tracked, but its message is withheld.
+        let msgs = node1
+            .handle_messages(vec![nominate(vec![1000, 2000])])
+            .unwrap();
+        assert!(msgs.is_empty());
+        let latest = node1.current_slot.get_last_message_sent().unwrap();
+        assert!(latest.values().contains(&2000));
+        assert!(node1.process_timeouts().is_empty());
+
+        // Once the interval has elapsed, the latest state is sent.
+        clock.advance(Duration::from_millis(200));
+        assert_eq!(node1.process_timeouts(), vec![latest]);
+        assert!(node1.process_timeouts().is_empty());
     }
 
     #[test_with_logger]
-    fn test_process_timeouts(logger: Logger) {
-        let mut node = get_node(0, logger);
+    // Advancing the mock clock past the nomination timeout should start a new round, in which
+    // node 1 nominates its proposed values.
+    fn test_advance_clock(logger: Logger) {
+        let (mut node1, _node2) = get_two_nodes(1, logger);
+        node1.set_mock_clock(MockClock::default());
+
+        // Node 2 leads the first round, so node 1 waits to nominate its values.
+        let msg = node1
+            .propose_values(BTreeSet::from_iter(vec![1000]))
+            .unwrap();
+        assert_eq!(msg, None);
+        assert!(node1.advance_clock(Duration::from_millis(500)).is_empty());
+
+        let msgs = node1.advance_clock(Duration::from_millis(600));
+        assert_eq!(msgs.len(), 1);
+        assert_eq!(msgs[0].sender_id, node1.ID);
+        assert_eq!(
+            msgs[0].votes_or_accepts_nominated(),
+            Some(BTreeSet::from_iter(vec![1000]))
+        );
+        assert_eq!(node1.get_current_slot_metrics().cur_nomination_round, 2);
+    }
+
+    #[test_with_logger]
+    // A message evicted by the current slot's ballot cap should be handled again if it is resent.
+    fn test_resend_evicted_msg(logger: Logger) {
+        let slot_index = 1;
+        let mut node = get_node(slot_index, logger);
 
-        // Should call `propose_values` on the current slot.
+        // The slot evicts the message each time it handles it.
+        let num_evictions = Arc::new(std::sync::Mutex::new(0));
         let mut slot = MockScpSlot::new();
-        let messages: Vec<Msg<&'static str>> = vec![];
-        slot.expect_process_timeouts()
-            .times(1)
-            .return_const(messages.clone());
+        slot.expect_get_index().return_const(slot_index);
+        let evictions = num_evictions.clone();
+        slot.expect_get_metrics().returning(move || SlotMetrics {
+            num_ballot_evictions: *evictions.lock().unwrap(),
+            ..nominating_slot_metrics()
+        });
+        slot.expect_handle_messages()
+            .times(2)
+            .returning(move |_msgs| {
+                *num_evictions.lock().unwrap() += 1;
+                Ok(None)
+            });
         node.current_slot = Box::new(slot);
 
-        // Should not call anything on an externalized slot, which no longer have timeouts.
-        let externalized_slot = MockScpSlot::new();
-        node.push_externalized_slot(Box::new(externalized_slot));
-
-        assert_eq!(node.process_timeouts(), messages);
+        let msg = Msg::new(
+            test_node_id(3),
+            QuorumSet::new_with_node_ids(1, vec![test_node_id(1)]),
+            slot_index,
+            Topic::Prepare(PreparePayload {
+                B: Ballot::new(1, &["a"]),
+                P: None,
+                PP: None,
+                CN: 0,
+                HN: 0,
+            }),
+        );
+        assert_eq!(node.handle_messages(vec![msg.clone()]), Ok(vec![]));
+        assert_eq!(node.handle_messages(vec![msg]), Ok(vec![]));
     }
 
     #[test_with_logger]
-    // Should reset `current_slot` to a new Slot for the given index.
-    fn test_reset_slot_index(logger: Logger) {
-        let slot_index = 14;
+    // A message whose handling fails should be handled again if it is re-sent.
+    fn test_resend_failed_msg(logger: Logger) {
+        let slot_index = 1;
         let mut node = get_node(slot_index, logger);
 
-        node.set_max_externalized_slots(2);
-        for _i in 12..slot_index {
-            let externalized_slot = MockScpSlot::new();
-            node.push_externalized_slot(Box::new(externalized_slot));
-        }
-
-        assert_eq!(node.current_slot_index(), slot_index);
-        assert_eq!(node.externalized_slots.len(), 2);
+        // The slot fails to handle the message the first time.
+        let num_calls = Arc::new(std::sync::Mutex::new(0));
+        let mut slot = MockScpSlot::new();
+        slot.expect_get_index().return_const(slot_index);
+        slot.expect_get_metrics().returning(nominating_slot_metrics);
+        slot.expect_handle_messages()
+            .times(2)
+            .returning(move |_msgs| {
+                let mut num_calls = num_calls.lock().unwrap();
+                *num_calls += 1;
+                if *num_calls == 1 {
+                    Err("failed".to_string())
+                } else {
+                    Ok(None)
+                }
+            });
+        node.current_slot = Box::new(slot);
 
-        let new_slot_index = 987;
-        node.reset_slot_index(new_slot_index);
-        assert_eq!(node.current_slot_index(), new_slot_index);
-        assert_eq!(node.current_slot.get_index(), new_slot_index);
+        let msg = Msg::new(
+            test_node_id(3),
+            QuorumSet::new_with_node_ids(1, vec![test_node_id(1)]),
+            slot_index,
+            Topic::Nominate(NominatePayload {
+                X: BTreeSet::from_iter(vec!["a"]),
+                Y: BTreeSet::default(),
+            }),
+        );
+        assert!(node.handle_messages(vec![msg.clone()]).is_err());
+        assert_eq!(node.handle_messages(vec![msg.clone()]), Ok(vec![]));
 
-        // externalized_slots should be empty
-        assert_eq!(node.externalized_slots.len(), 0);
+        // Once handled, the message is ignored.
+        assert_eq!(node.handle_messages(vec![msg]), Ok(vec![]));
     }
 
     #[test_with_logger]
-    /// Steps through a sequence of messages that allow a two-node network to reach consensus.
-    fn basic_two_node_consensus(logger: Logger) {
-        let slot_index = 1;
-
-        // A two-node network, where the only quorum is both nodes.
-        let mut node1 = Node::<u32, TransactionValidationError>::new(
+    // A node with a small seen-message history should forget the oldest message, and process it
+    // again if it is re-sent.
+    fn test_seen_history_size(logger: Logger) {
+        let mut node = Node::<u32, TransactionValidationError>::with_seen_history_size(
             test_node_id(1),
             QuorumSet::new_with_node_ids(1, vec![test_node_id(2)]),
             Arc::new(trivial_validity_fn),
             Arc::new(trivial_combine_fn),
-            slot_index,
-            logger.clone(),
-        );
-        let mut node2 = Node::<u32, TransactionValidationError>::new(
-            test_node_id(2),
-            QuorumSet::new_with_node_ids(1, vec![test_node_id(1)]),
-            Arc::new(trivial_validity_fn),
-            Arc::new(trivial_combine_fn),
-            slot_index,
-            logger.clone(),
+            1,
+            2,
+            logger,
         );
 
-        // Client(s) submits some values to node 2.
-        let values = vec![1000, 2000];
-        let msg = node2
-            .propose_values(BTreeSet::from_iter(values.clone()))
-            .expect("error handling msg")
-            .expect("no msg?");
+        let msgs: Vec<Msg<u32>> = (1..=3)
+            .map(|i| {
+                Msg::new(
+                    test_node_id(2),
+                    QuorumSet::new_with_node_ids(1, vec![test_node_id(1)]),
+                    1,
+                    Topic::Nominate(NominatePayload {
+                        X: BTreeSet::from_iter(1..=i),
+                        Y: BTreeSet::default(),
+                    }),
+                )
+            })
+            .collect();
+        let is_seen = |node: &Node<u32, TransactionValidationError>, msg: &Msg<u32>| {
+            node.seen_msg_hashes
+                .contains(&Node::<u32, TransactionValidationError>::seen_msg_hash(msg))
+        };
 
-        // Node 2 should emit "vote nominate([1000, 2000])"
-        assert_eq!(
-            msg,
+        for msg in &msgs {
+            node.handle_message(msg).expect("error handling msg");
+        }
+        assert!(!is_seen(&node, &msgs[0]));
+        assert!(is_seen(&node, &msgs[1]));
+        assert!(is_seen(&node, &msgs[2]));
+
+        // The oldest message is no longer filtered out, so it is processed again and evicts the
+        // next oldest.
+        node.handle_message(&msgs[0]).expect("error handling msg");
+        assert!(is_seen(&node, &msgs[0]));
+        assert!(!is_seen(&node, &msgs[1]));
+    }
+
+    #[test_with_logger]
+    // Messages that differ only in the order of their quorum set members should be deduplicated.
+    fn test_seen_msg_hash_ignores_quorum_set_order(logger: Logger) {
+        let mut node = get_node(1, logger);
+        let msg = |quorum_set: QuorumSet| {
             Msg::new(
-                node2.node_id(),
-                node2.quorum_set(),
-                slot_index,
+                test_node_id(2),
+                quorum_set,
+                1,
                 Topic::Nominate(NominatePayload {
-                    X: BTreeSet::from_iter(values.clone()),
+                    X: btreeset!["a"],
                     Y: Default::default(),
                 }),
             )
+        };
+        let msg_1 = msg(QuorumSet::new_with_node_ids(
+            1,
+            vec![test_node_id(1), test_node_id(3)],
+        ));
+        let msg_2 = msg(QuorumSet::new_with_node_ids(
+            1,
+            vec![test_node_id(3), test_node_id(1)],
+        ));
+        assert_ne!(
+            msg_1.digest32::<MerlinTranscript>(b"scp_msg"),
+            msg_2.digest32::<MerlinTranscript>(b"scp_msg")
         );
 
-        // Node 1 handles Node 2's message. It may accept nominate [1000, 2000]
-        let msg = node1
-            .handle_message(&msg)
-            .expect("error handling msg")
-            .expect("no msg?");
+        node.handle_messages(vec![msg_1, msg_2.clone()])
+            .expect("error handling msgs");
+        assert_eq!(node.seen_msg_hashes.len(), 1);
+        assert!(node
+            .seen_msg_hashes
+            .contains(&Node::<&'static str, TransactionValidationError>::seen_msg_hash(&msg_2)));
+    }
 
-        assert_eq!(
-            msg,
-            Msg::new(
-                node1.node_id(),
-                node1.quorum_set(),
-                slot_index,
-                Topic::Nominate(NominatePayload {
-                    X: Default::default(),
-                    Y: BTreeSet::from_iter(values.clone()),
-                }),
-            )
-        );
+    #[test_with_logger]
+    // `Node::new` should remember the default number of message hashes.
+    fn test_default_seen_history_size(logger: Logger) {
+        let mut node = get_node(1, logger);
+        for i in 0..1000u32 {
+            assert!(!node.seen_msg_hashes.is_full());
+            let mut hash = [0u8; 32];
+            hash[..4].copy_from_slice(&i.to_le_bytes());
+            node.seen_msg_hashes.put(hash, 1);
+        }
+        assert!(node.seen_msg_hashes.is_full());
+        assert_eq!(node.seen_msg_hashes.len(), 1000);
+    }
 
-        // Node 2 may "confirm nominate", and issue "vote prepare(<1, [1000,2000]>)
-        let msg = node2
-            .handle_message(&msg)
-            .expect("error handling msg")
-            .expect("no msg?");
+    #[test_with_logger]
+    // A retained externalized slot's Externalize message can be re-sent to a catching-up peer.
+    fn test_re_externalize(logger: Logger) {
+        let (mut node1, mut node2) = get_two_nodes(1, logger.clone());
 
-        assert_eq!(
-            msg,
-            Msg::new(
-                node2.node_id(),
-                node2.quorum_set(),
-                slot_index,
-                Topic::NominatePrepare(
-                    NominatePayload {
-                        X: Default::default(),
-                        Y: BTreeSet::from_iter(values.clone())
-                    },
-                    PreparePayload {
-                        B: Ballot::new(1, &[1000, 2000]),
-                        P: None,
-                        PP: None,
-                        CN: 0,
-                        HN: 0
-                    }
-                ),
-            )
-        );
+        // Run consensus, remembering the Externalize message emitted by node 1.
+        let mut original = None;
+        let mut msg = node2
+            .propose_values(BTreeSet::from_iter(vec![1000, 2000]))
+            .unwrap();
+        let mut receiver_is_node1 = true;
+        while let Some(incoming) = msg {
+            msg = if receiver_is_node1 {
+                let outgoing = node1.handle_message(&incoming).unwrap();
+                if matches!(outgoing, Some(ref msg) if msg.topic.is_externalize()) {
+                    original = outgoing.clone();
+                }
+                outgoing
+            } else {
+                node2.handle_message(&incoming).unwrap()
+            };
+            receiver_is_node1 = !receiver_is_node1;
+        }
+        assert!(original.is_some());
 
-        // Node 1 issues "accept prepare(<1, [1000,2000])
-        let msg = node1
-            .handle_message(&msg)
-            .expect("error handling msg")
-            .expect("no msg?");
+        let msg = node1.re_externalize(1).expect("slot 1 should be retained");
+        assert_eq!(Some(msg.clone()), original);
+        assert_eq!(msg.validate(), Ok(()));
+
+        // Slots that have not externalized have nothing to re-send.
+        assert_eq!(node1.re_externalize(2), None);
 
+        // A peer that missed the slot can externalize from the re-sent message.
+        let (_, mut catching_up) = get_two_nodes(1, logger);
+        catching_up.handle_message(&msg).unwrap();
         assert_eq!(
-            msg,
-            Msg::new(
-                node1.node_id(),
-                node1.quorum_set(),
-                slot_index,
-                Topic::NominatePrepare(
-                    NominatePayload {
-                        X: Default::default(),
-                        Y: BTreeSet::from_iter(values.clone())
-                    },
-                    PreparePayload {
-                        B: Ballot::new(1, &values),
-                        P: Some(Ballot::new(1, &values)),
-                        PP: None,
-                        CN: 0,
-                        HN: 0
-                    }
-                ),
-            )
+            catching_up.get_externalized_values(1),
+            Some(vec![1000, 2000])
         );
+    }
 
-        // Node 2 issues "vote commit"
-        let msg = node2
-            .handle_message(&msg)
-            .expect("error handling msg")
-            .expect("no msg?");
+    #[test_with_logger]
+    // With retention disabled, nodes should still advance but keep no externalized slots.
+    fn test_zero_max_externalized_slots(logger: Logger) {
+        let (mut node1, mut node2) = get_two_nodes(1, logger);
+        node1.set_max_externalized_slots(0);
+        node2.set_max_externalized_slots(0);
 
-        assert_eq!(
-            msg,
-            Msg::new(
-                node2.node_id(),
-                node2.quorum_set(),
-                slot_index,
-                Topic::Prepare(PreparePayload {
-                    B: Ballot::new(1, &values),
-                    P: Some(Ballot::new(1, &values)),
-                    PP: None,
-                    CN: 1,
-                    HN: 1,
-                },),
-            )
-        );
+        run_two_node_consensus(&mut node1, &mut node2, &[1000, 2000]);
 
-        // Node 1 issues "accept commit".
-        let msg = node1
-            .handle_message(&msg)
-            .expect("error handling msg")
-            .expect("no msg?");
+        for node in &[&node1, &node2] {
+            assert_eq!(node.current_slot_index(), 2);
+            assert!(node.externalized_slots.is_empty());
+            assert_eq!(node.messages_to_externalize(1), None);
+            assert_eq!(node.get_externalized_values(1), None);
+            assert_eq!(node.re_externalize(1), None);
+        }
+    }
 
-        assert_eq!(
-            msg,
-            Msg::new(
-                node1.node_id(),
-                node1.quorum_set(),
-                slot_index,
-                Topic::Commit(CommitPayload {
-                    B: Ballot::new(1, &values),
-                    PN: 1,
-                    CN: 1,
-                    HN: 1
-                }),
-            )
-        );
+    #[test_with_logger]
+    // The diagnostic dump should be valid JSON containing the expected sections.
+    fn test_diagnostic_json(logger: Logger) {
+        let (mut node1, mut node2) = get_two_nodes(1, logger);
+        run_two_node_consensus(&mut node1, &mut node2, &[1000, 2000]);
 
-        // Node 2 externalizes.
-        let msg = node2
-            .handle_message(&msg)
-            .expect("error handling msg")
-            .expect("no msg?");
+        let json: serde_json::Value =
+            serde_json::from_str(&node1.diagnostic_json()).expect("invalid JSON");
+        for key in &[
+            "node_id",
+            "quorum_set",
+            "current_slot",
+            "externalized",
+            "peers",
+            "metrics",
+        ] {
+            assert!(json.get(key).is_some(), "missing key: {}", key);
+        }
 
-        assert_eq!(
-            msg,
-            Msg::new(
-                node2.node_id(),
-                node2.quorum_set(),
-                slot_index,
-                Topic::Externalize(ExternalizePayload {
-                    C: Ballot::new(1, &values),
-                    HN: 1,
-                }),
+        assert_eq!(json["current_slot"]["slot_index"], 2);
+        assert_eq!(json["externalized"][0]["slot_index"], 1);
+        assert_eq!(json["externalized"][0]["num_values"], 2);
+        assert_eq!(json["peers"].as_array().unwrap().len(), 1);
+        assert!(json["peers"][0]["last_heard_ms_ago"].is_u64());
+    }
+
+    #[test_with_logger]
+    // A combine function that fails on conflicting values should prevent the nodes from
+    // externalizing them.
+    fn test_combine_error_prevents_externalize(logger: Logger) {
+        let combine_fn: CombineFn<u32, TransactionValidationError> =
+            Arc::new(|values: &[u32]| {
+                if values.contains(&1000) && values.contains(&2000) {
+                    Err(TransactionValidationError)
+                } else {
+                    trivial_combine_fn(values)
+                }
+            });
+        let new_node = |node_id: u32, peer_id: u32, logger: Logger| {
+            Node::<u32, TransactionValidationError>::new(
+                test_node_id(node_id),
+                QuorumSet::new_with_node_ids(1, vec![test_node_id(peer_id)]),
+                Arc::new(trivial_validity_fn),
+                combine_fn.clone(),
+                1,
+                logger,
             )
-        );
+        };
+        let mut node1 = new_node(1, 2, logger.clone());
+        let mut node2 = new_node(2, 1, logger);
 
-        // Node 1 externalizes.
-        let msg = node1
-            .handle_message(&msg)
-            .expect("error handling msg")
-            .expect("no msg?");
+        run_two_node_consensus(&mut node1, &mut node2, &[1000, 2000]);
 
-        // Both nodes have issued Externalize, which implies
-        // "accept prepare(<infinity, commit.value>)".
-        // That means node 1 can also state "confirm prepare(<infinity, commit.value>)",
-        // so HN is now INFINITY.
-        assert_eq!(
-            msg,
-            Msg::new(
-                node1.node_id(),
-                node1.quorum_set(),
-                slot_index,
-                Topic::Externalize(ExternalizePayload {
-                    C: Ballot::new(1, &values),
-                    HN: INFINITY,
-                }),
-            )
-        );
+        for node in &mut [node1, node2] {
+            assert_eq!(node.current_slot_index(), 1);
+            assert_eq!(node.get_current_slot_metrics().bN, 0);
+            assert_eq!(node.get_current_slot_metrics().num_confirmed_nominated, 2);
+        }
+    }
+
+    #[test_with_logger]
+    // New slots should use the combine function registered for their slot index.
+    fn test_register_combine_fn(logger: Logger) {
+        let combine_min: CombineFn<u32, TransactionValidationError> =
+            Arc::new(|values: &[u32]| Ok(values.iter().min().into_iter().cloned().collect()));
+        let combine_max: CombineFn<u32, TransactionValidationError> =
+            Arc::new(|values: &[u32]| Ok(values.iter().max().into_iter().cloned().collect()));
+
+        let (mut node1, mut node2) = get_two_nodes(1, logger);
+        for node in &mut [&mut node1, &mut node2] {
+            node.register_combine_fn(..10, combine_min.clone());
+            node.register_combine_fn(10.., combine_max.clone());
+            node.reset_slot_index(9);
+            node.set_max_externalized_slots(2);
+        }
+
+        for slot_index in 9..=10 {
+            // Either node may lead nomination for this slot.
+            let msg1 = node1
+                .propose_values(BTreeSet::from_iter(vec![1000, 2000]))
+                .unwrap();
+            let msg2 = node2
+                .propose_values(BTreeSet::from_iter(vec![1000, 2000]))
+                .unwrap();
+            exchange_messages(&mut node1, &mut node2, msg2);
+            exchange_messages(&mut node2, &mut node1, msg1);
+            assert_eq!(node1.current_slot_index(), slot_index + 1);
+            assert_eq!(node2.current_slot_index(), slot_index + 1);
+        }
+
+        assert_eq!(node1.get_externalized_values(9), Some(vec![1000]));
+        assert_eq!(node1.get_externalized_values(10), Some(vec![2000]));
     }
 }
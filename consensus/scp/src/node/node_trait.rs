@@ -1,39 +1,54 @@
 // Copyright (c) 2018-2021 The MobileCoin Foundation
 
-use crate::{slot::SlotMetrics, Msg, QuorumSet, SlotIndex, Value};
+use crate::{slot::SlotMetrics, GenericNodeId, Msg, QuorumSet, SlotIndex, Value};
 use mc_common::NodeID;
 use mockall::*;
-use std::collections::BTreeSet;
+use std::{collections::BTreeSet, time::Duration};
 
 /// A node capable of participating in SCP.
 #[automock]
-pub trait ScpNode<V: Value>: Send {
+pub trait ScpNode<V: Value, ID: GenericNodeId + Send + 'static = NodeID>: Send {
     /// Get local node ID.
-    fn node_id(&self) -> NodeID;
+    fn node_id(&self) -> ID;
 
     /// Get local node quorum set.
-    fn quorum_set(&self) -> QuorumSet;
+    fn quorum_set(&self) -> QuorumSet<ID>;
 
     /// Propose values for this node to nominate.
-    fn propose_values(&mut self, values: BTreeSet<V>) -> Result<Option<Msg<V>>, String>;
+    fn propose_values(&mut self, values: BTreeSet<V>) -> Result<Option<Msg<V, ID>>, String>;
 
     /// Handle incoming message from the network.
-    fn handle_message(&mut self, msg: &Msg<V>) -> Result<Option<Msg<V>>, String>;
+    fn handle_message(&mut self, msg: &Msg<V, ID>) -> Result<Option<Msg<V, ID>>, String>;
 
     /// Handle incoming messages from the network.
-    fn handle_messages(&mut self, msgs: Vec<Msg<V>>) -> Result<Vec<Msg<V>>, String>;
+    ///
+    /// A node may ignore a message that it has already handled successfully, so re-sending a
+    /// message only has an effect if handling it failed.
+    fn handle_messages(&mut self, msgs: Vec<Msg<V, ID>>) -> Result<Vec<Msg<V, ID>>, String>;
+
+    /// Handle incoming messages from the network one at a time, in order, as if by calling
+    /// `handle_messages` with each on its own, and return every message emitted. Messages sent
+    /// by this node are skipped. Stops at the first error.
+    fn handle_many(&mut self, msgs: &[Msg<V, ID>]) -> Result<Vec<Msg<V, ID>>, String> {
+        let node_id = self.node_id();
+        let mut outgoing = Vec::new();
+        for msg in msgs.iter().filter(|msg| msg.sender_id != node_id) {
+            outgoing.extend(self.handle_messages(vec![msg.clone()])?);
+        }
+        Ok(outgoing)
+    }
 
     /// Maximum number of stored externalized slots.
     fn max_externalized_slots(&self) -> usize;
 
-    /// Set the maximum number of stored externalized slots. Must be non-zero.
+    /// Set the maximum number of stored externalized slots. Zero disables retention.
     fn set_max_externalized_slots(&mut self, n: usize);
 
     /// Get externalized values (or an empty vector) for a given slot index.
     fn get_externalized_values(&self, slot_index: SlotIndex) -> Option<Vec<V>>;
 
     /// Process pending timeouts.
-    fn process_timeouts(&mut self) -> Vec<Msg<V>>;
+    fn process_timeouts(&mut self) -> Vec<Msg<V, ID>>;
 
     /// Get the current slot's index.
     fn current_slot_index(&self) -> SlotIndex;
@@ -41,6 +56,11 @@ pub trait ScpNode<V: Value>: Send {
     /// Get metrics for the current slot.
     fn get_current_slot_metrics(&mut self) -> SlotMetrics;
 
+    /// True if the current slot has not externalized and has emitted no message in response to
+    /// `propose_values` or incoming messages for longer than `since`, e.g. because it cannot
+    /// find a quorum.
+    fn is_stuck(&self, since: Duration) -> bool;
+
     /// Additional debug info, e.g. a JSON representation of the Slot's state.
     fn get_slot_debug_snapshot(&mut self, slot_index: SlotIndex) -> Option<String>;
 
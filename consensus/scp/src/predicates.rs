@@ -1,10 +1,13 @@
 // Copyright (c) 2018-2021 The MobileCoin Foundation
 
 //! Predicates for use in trust decisions for SCP.
-use mc_common::NodeID;
+use mc_common::{Hash, NodeID};
+use mc_crypto_digestible::{Digestible, MerlinTranscript};
+use serde::{de::DeserializeOwned, Serialize};
 use std::{
+    cmp::Ordering,
     collections::{BTreeSet, HashMap, HashSet},
-    sync::Arc,
+    sync::{Arc, Mutex},
 };
 
 use crate::{
@@ -25,22 +28,29 @@ pub trait Predicate<V: Value, ID: GenericNodeId = NodeID>: Clone {
 
     /// Returns the result stored inside the predicate.
     fn result(&self) -> Self::Result;
+
+    /// Tests a batch of messages, as if by calling `test` on each of them in order.
+    /// Returns `None` as soon as a message does not satisfy the predicate.
+    fn test_many(&self, msgs: &[&Msg<V, ID>]) -> Option<Self> {
+        msgs.iter()
+            .try_fold(self.clone(), |pred, msg| pred.test(msg))
+    }
 }
 
 /// A predicate for narrowing down a set of ballots.
 #[derive(Clone)]
-pub struct BallotSetPredicate<V: Value> {
+pub struct BallotSetPredicate<V: Value, ID: GenericNodeId = NodeID> {
     /// The ballots to consider for the evaluation of this predicate.
     pub ballots: HashSet<Ballot<V>>,
 
     /// The test function to apply to the ballots in this predicate.
-    pub test_fn: Arc<dyn Fn(&Msg<V>, &HashSet<Ballot<V>>) -> HashSet<Ballot<V>>>,
+    pub test_fn: Arc<dyn Fn(&Msg<V, ID>, &HashSet<Ballot<V>>) -> HashSet<Ballot<V>>>,
 }
 
-impl<V: Value> Predicate<V> for BallotSetPredicate<V> {
+impl<V: Value, ID: GenericNodeId> Predicate<V, ID> for BallotSetPredicate<V, ID> {
     type Result = HashSet<Ballot<V>>;
 
-    fn test(&self, msg: &Msg<V>) -> Option<Self> {
+    fn test(&self, msg: &Msg<V, ID>) -> Option<Self> {
         if self.ballots.is_empty() {
             return None;
         }
@@ -59,22 +69,47 @@ impl<V: Value> Predicate<V> for BallotSetPredicate<V> {
     fn result(&self) -> Self::Result {
         self.ballots.clone()
     }
+
+    /// Narrows the ballots across the whole batch before building a predicate, so the ballot set
+    /// is not copied up front.
+    fn test_many(&self, msgs: &[&Msg<V, ID>]) -> Option<Self> {
+        let mut ballots: Option<HashSet<Ballot<V>>> = None;
+        for msg in msgs {
+            let current = ballots.as_ref().unwrap_or(&self.ballots);
+            if current.is_empty() {
+                return None;
+            }
+
+            let nextBallots = (self.test_fn)(msg, current);
+            if nextBallots.is_empty() {
+                return None;
+            }
+            ballots = Some(nextBallots);
+        }
+
+        Some(Self {
+            ballots: ballots.unwrap_or_else(|| self.ballots.clone()),
+            test_fn: self.test_fn.clone(),
+        })
+    }
 }
 
 /// A predicate for ranges of ballots, where the range is over the counter.
 #[derive(Clone)]
-pub struct BallotRangePredicate<V: Value> {
-    /// Map of value to counter ranges, representing ballot ranges.
+pub struct BallotRangePredicate<V: Value, ID: GenericNodeId = NodeID> {
+    /// Map of value to counter ranges, representing ballot ranges. Empty ranges, where
+    /// `min > max`, are never kept.
     pub ballot_ranges: HashMap<Vec<V>, (u32, u32)>,
 
     /// The test function to apply to the ballot ranges in this predicate.
-    pub test_fn: Arc<dyn Fn(&Msg<V>, &HashMap<Vec<V>, (u32, u32)>) -> HashMap<Vec<V>, (u32, u32)>>,
+    pub test_fn:
+        Arc<dyn Fn(&Msg<V, ID>, &HashMap<Vec<V>, (u32, u32)>) -> HashMap<Vec<V>, (u32, u32)>>,
 }
 
-impl<V: Value> Predicate<V> for BallotRangePredicate<V> {
+impl<V: Value, ID: GenericNodeId> Predicate<V, ID> for BallotRangePredicate<V, ID> {
     type Result = HashMap<Vec<V>, (u32, u32)>;
 
-    fn test(&self, msg: &Msg<V>) -> Option<Self> {
+    fn test(&self, msg: &Msg<V, ID>) -> Option<Self> {
         if self.ballot_ranges.is_empty() {
             return None;
         }
@@ -95,20 +130,64 @@ impl<V: Value> Predicate<V> for BallotRangePredicate<V> {
     }
 }
 
+impl<V: Value, ID: GenericNodeId + Serialize + DeserializeOwned + 'static>
+    BallotRangePredicate<V, ID>
+{
+    /// Create a predicate for ballots with values `value` and counters in `[min, max]`.
+    ///
+    /// If `min > max` the range is empty, and the predicate is never satisfied.
+    pub fn for_value(
+        value: Vec<V>,
+        min: u32,
+        max: u32,
+        test_fn: Arc<
+            dyn Fn(&Msg<V, ID>, &HashMap<Vec<V>, (u32, u32)>) -> HashMap<Vec<V>, (u32, u32)>,
+        >,
+    ) -> Self {
+        let mut ballot_ranges = HashMap::default();
+        if min <= max {
+            ballot_ranges.insert(value, (min, max));
+        }
+        Self {
+            ballot_ranges,
+            test_fn,
+        }
+    }
+
+    /// A test function that narrows each ballot range to the part a message accepts committed.
+    /// Empty ranges, where `min > max`, are dropped.
+    pub fn intersect_commits(
+    ) -> Arc<dyn Fn(&Msg<V, ID>, &HashMap<Vec<V>, (u32, u32)>) -> HashMap<Vec<V>, (u32, u32)>> {
+        Arc::new(|msg, ballot_ranges| {
+            let mut intersection: HashMap<Vec<V>, (u32, u32)> = Default::default();
+            for (values, &(min, max)) in ballot_ranges {
+                if min > max {
+                    continue;
+                }
+                if let Some((a, b)) = msg.accepts_commits(values, min, max) {
+                    assert!(a <= b);
+                    intersection.insert(values.clone(), (a, b));
+                }
+            }
+            intersection
+        })
+    }
+}
+
 /// A predicate for narrowing down a set of values.
 #[derive(Clone)]
-pub struct ValueSetPredicate<V: Value> {
+pub struct ValueSetPredicate<V: Value, ID: GenericNodeId = NodeID> {
     /// The values over which to apply the test function.
     pub values: BTreeSet<V>,
 
     /// The test function to narrow down the values in this predicate.
-    pub test_fn: Arc<dyn Fn(&Msg<V>, &BTreeSet<V>) -> BTreeSet<V>>,
+    pub test_fn: Arc<dyn Fn(&Msg<V, ID>, &BTreeSet<V>) -> BTreeSet<V>>,
 }
 
-impl<V: Value> Predicate<V> for ValueSetPredicate<V> {
+impl<V: Value, ID: GenericNodeId> Predicate<V, ID> for ValueSetPredicate<V, ID> {
     type Result = BTreeSet<V>;
 
-    fn test(&self, msg: &Msg<V>) -> Option<Self> {
+    fn test(&self, msg: &Msg<V, ID>) -> Option<Self> {
         if self.values.is_empty() {
             return None;
         }
@@ -129,30 +208,34 @@ impl<V: Value> Predicate<V> for ValueSetPredicate<V> {
     }
 }
 
-impl<V: Value> ValueSetPredicate<V> {
+impl<V: Value, ID: GenericNodeId> ValueSetPredicate<V, ID> {
     /// Given a list of results, each containg a set of values, find the "biggest" set of values.
     /// Sets of values are sorted by their length, and if the lenght matches then by their values.
-    pub fn filter_to_max_values(
-        results: Vec<(HashSet<NodeID>, BTreeSet<V>)>,
-    ) -> Option<BTreeSet<V>> {
-        if results.is_empty() {
-            return None;
-        }
+    pub fn filter_to_max_values(results: Vec<(HashSet<ID>, BTreeSet<V>)>) -> Option<BTreeSet<V>> {
+        results
+            .into_iter()
+            .max_by(Self::cmp_results)
+            .map(|(_node_ids, max_values)| max_values)
+    }
 
-        let (_node_ids, max_values) = results
+    /// Given a list of results, each containg a set of values, find the "smallest" set of values.
+    /// Results are ordered the same way as in `filter_to_max_values`.
+    pub fn filter_to_min_values(results: Vec<(HashSet<ID>, BTreeSet<V>)>) -> Option<BTreeSet<V>> {
+        results
             .into_iter()
-            .max_by(|a, b| {
-                let (a_node_ids, a_values) = a;
-                let (b_node_ids, b_values) = b;
-                if a_node_ids.len() != b_node_ids.len() {
-                    a_node_ids.len().cmp(&b_node_ids.len())
-                } else {
-                    a_values.cmp(&b_values)
-                }
-            })
-            .unwrap();
+            .min_by(Self::cmp_results)
+            .map(|(_node_ids, min_values)| min_values)
+    }
 
-        Some(max_values)
+    /// Orders results by the number of node IDs, and then by their values.
+    fn cmp_results(a: &(HashSet<ID>, BTreeSet<V>), b: &(HashSet<ID>, BTreeSet<V>)) -> Ordering {
+        let (a_node_ids, a_values) = a;
+        let (b_node_ids, b_values) = b;
+        if a_node_ids.len() != b_node_ids.len() {
+            a_node_ids.len().cmp(&b_node_ids.len())
+        } else {
+            a_values.cmp(b_values)
+        }
     }
 }
 
@@ -177,11 +260,212 @@ impl<'a, V: Value, ID: GenericNodeId> Predicate<V, ID> for FuncPredicate<'a, V,
     fn result(&self) -> Self::Result {}
 }
 
+/// A predicate for determining whether a message does not match a certain condition.
+#[derive(Clone)]
+pub struct NotPredicate<'a, V: Value, ID: GenericNodeId = NodeID> {
+    /// The predicate to invert.
+    pub inner: FuncPredicate<'a, V, ID>,
+}
+
+impl<'a, V: Value, ID: GenericNodeId> Predicate<V, ID> for NotPredicate<'a, V, ID> {
+    type Result = ();
+
+    fn test(&self, msg: &Msg<V, ID>) -> Option<Self> {
+        if (self.inner.test_fn)(msg) {
+            None
+        } else {
+            Some(self.clone())
+        }
+    }
+
+    fn result(&self) -> Self::Result {}
+}
+
+/// A predicate that is satisfied by every message, and counts the messages that match a certain
+/// condition.
+#[derive(Clone)]
+pub struct CountingPredicate<'a, V: Value, ID: GenericNodeId = NodeID> {
+    /// The condition to count.
+    pub test_fn: &'a dyn Fn(&Msg<V, ID>) -> bool,
+
+    /// The number of messages tested so far that match the condition.
+    pub count: usize,
+}
+
+impl<'a, V: Value, ID: GenericNodeId> Predicate<V, ID> for CountingPredicate<'a, V, ID> {
+    type Result = usize;
+
+    fn test(&self, msg: &Msg<V, ID>) -> Option<Self> {
+        let mut next = self.clone();
+        if (self.test_fn)(msg) {
+            next.count += 1;
+        }
+        Some(next)
+    }
+
+    fn result(&self) -> Self::Result {
+        self.count
+    }
+}
+
+/// A predicate that caches the outcome of its inner predicate for each message it tests.
+///
+/// Clones share the cache. An outcome depends on the state of the inner predicate as well as on
+/// the message, so each predicate returned by `test` has a cache of its own.
+#[derive(Clone)]
+pub struct MemoPredicate<P> {
+    /// The predicate whose outcomes are cached.
+    pub inner: P,
+
+    /// Outcomes of testing `inner`, by message hash.
+    cache: Arc<Mutex<HashMap<Hash, Option<MemoPredicate<P>>>>>,
+}
+
+impl<P> MemoPredicate<P> {
+    /// Create a new MemoPredicate with an empty cache.
+    pub fn new(inner: P) -> Self {
+        Self {
+            inner,
+            cache: Arc::new(Mutex::new(HashMap::default())),
+        }
+    }
+}
+
+impl<V: Value, ID: GenericNodeId, P: Predicate<V, ID>> Predicate<V, ID> for MemoPredicate<P> {
+    type Result = P::Result;
+
+    fn test(&self, msg: &Msg<V, ID>) -> Option<Self> {
+        let msg_hash = msg.digest32::<MerlinTranscript>(b"scp_msg");
+        if let Some(outcome) = self.cache.lock().expect("mutex poisoned").get(&msg_hash) {
+            return outcome.clone();
+        }
+
+        let outcome = self.inner.test(msg).map(Self::new);
+        self.cache
+            .lock()
+            .expect("mutex poisoned")
+            .insert(msg_hash, outcome.clone());
+        outcome
+    }
+
+    fn result(&self) -> Self::Result {
+        self.inner.result()
+    }
+}
+
+/// A predicate for matching messages whose ballot counter falls within a range of rounds.
+#[derive(Clone)]
+pub struct RoundPredicate {
+    /// The lowest matching ballot counter.
+    pub min_round: u32,
+
+    /// The highest matching ballot counter (inclusive).
+    pub max_round: u32,
+}
+
+impl<V: Value, ID: GenericNodeId + Serialize + DeserializeOwned> Predicate<V, ID>
+    for RoundPredicate
+{
+    type Result = ();
+
+    fn test(&self, msg: &Msg<V, ID>) -> Option<Self> {
+        let round = msg.bN();
+        if self.min_round <= round && round <= self.max_round {
+            Some(self.clone())
+        } else {
+            None
+        }
+    }
+
+    fn result(&self) -> Self::Result {}
+}
+
+/// A predicate that is satisfied when both of its inner predicates are satisfied.
+#[derive(Clone)]
+pub struct AndPredicate<P1, P2> {
+    /// The first inner predicate.
+    pub first: P1,
+
+    /// The second inner predicate.
+    pub second: P2,
+}
+
+impl<V: Value, ID: GenericNodeId, P1: Predicate<V, ID>, P2: Predicate<V, ID>> Predicate<V, ID>
+    for AndPredicate<P1, P2>
+{
+    type Result = (P1::Result, P2::Result);
+
+    fn test(&self, msg: &Msg<V, ID>) -> Option<Self> {
+        let first = self.first.test(msg)?;
+        let second = self.second.test(msg)?;
+        Some(Self { first, second })
+    }
+
+    fn result(&self) -> Self::Result {
+        (self.first.result(), self.second.result())
+    }
+}
+
+/// A predicate that is satisfied while either of its inner predicates is satisfied.
+///
+/// An inner predicate that fails a message is dropped, so every message tested must satisfy
+/// the same surviving inner predicate.
+#[derive(Clone)]
+pub struct OrPredicate<P1, P2> {
+    /// The first inner predicate, unless it has failed.
+    pub first: Option<P1>,
+
+    /// The second inner predicate, unless it has failed.
+    pub second: Option<P2>,
+}
+
+impl<P1, P2> OrPredicate<P1, P2> {
+    /// Create a new OrPredicate.
+    pub fn new(first: P1, second: P2) -> Self {
+        Self {
+            first: Some(first),
+            second: Some(second),
+        }
+    }
+}
+
+impl<V: Value, ID: GenericNodeId, P1: Predicate<V, ID>, P2: Predicate<V, ID>> Predicate<V, ID>
+    for OrPredicate<P1, P2>
+{
+    type Result = (Option<P1::Result>, Option<P2::Result>);
+
+    fn test(&self, msg: &Msg<V, ID>) -> Option<Self> {
+        let first = self.first.as_ref().and_then(|pred| pred.test(msg));
+        let second = self.second.as_ref().and_then(|pred| pred.test(msg));
+        if first.is_none() && second.is_none() {
+            return None;
+        }
+        Some(Self { first, second })
+    }
+
+    fn result(&self) -> Self::Result {
+        (
+            self.first.as_ref().map(|pred| pred.result()),
+            self.second.as_ref().map(|pred| pred.result()),
+        )
+    }
+}
+
 #[cfg(test)]
 mod predicates_tests {
     use super::*;
-    use crate::{core_types::*, msg::*, quorum_set::*, test_utils::test_node_id};
-    use std::iter::FromIterator;
+    use crate::{
+        core_types::*,
+        msg::*,
+        quorum_set::*,
+        test_utils::{test_node_id, test_responder_id},
+    };
+    use mc_common::ResponderId;
+    use mc_crypto_digestible::Digestible;
+    use std::{
+        iter::FromIterator,
+        sync::atomic::{AtomicUsize, Ordering as AtomicOrdering},
+    };
 
     #[test]
     // BallotSetPredicate can be used to pick a quorum that intersects with a given set of ballots.
@@ -295,6 +579,99 @@ mod predicates_tests {
         assert_eq!(pred.result(), HashSet::from_iter(vec![ballot_1]));
     }
 
+    /// A value that counts how many times it is cloned. Only used by one test, so the count is
+    /// not disturbed by tests running in parallel.
+    #[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Digestible)]
+    struct CountedValue(u32);
+
+    static NUM_CLONES: AtomicUsize = AtomicUsize::new(0);
+
+    impl Clone for CountedValue {
+        fn clone(&self) -> Self {
+            NUM_CLONES.fetch_add(1, AtomicOrdering::SeqCst);
+            CountedValue(self.0)
+        }
+    }
+
+    fn num_clones() -> usize {
+        NUM_CLONES.load(AtomicOrdering::SeqCst)
+    }
+
+    #[test]
+    // BallotSetPredicate::test_many should narrow a large ballot set with fewer clones than
+    // testing messages one at a time.
+    pub fn test_ballot_set_predicate_test_many() {
+        let ballots: HashSet<Ballot<CountedValue>> = (0..100)
+            .map(|i| Ballot::new(1, &[CountedValue(i)]))
+            .collect();
+        let msgs: Vec<Msg<CountedValue>> = (2..=6)
+            .map(|node_index| {
+                Msg::new(
+                    test_node_id(node_index),
+                    QuorumSet::new_with_node_ids(1, vec![test_node_id(1)]),
+                    1,
+                    Topic::Prepare(PreparePayload {
+                        B: Ballot::new(1, &[CountedValue(7)]),
+                        P: None,
+                        PP: None,
+                        CN: 0,
+                        HN: 0,
+                    }),
+                )
+            })
+            .collect();
+        let msg_refs: Vec<&Msg<CountedValue>> = msgs.iter().collect();
+        let pred = BallotSetPredicate::<CountedValue> {
+            ballots,
+            test_fn: Arc::new(|msg, ballots| {
+                ballots
+                    .intersection(&msg.votes_or_accepts_prepared())
+                    .cloned()
+                    .collect()
+            }),
+        };
+
+        let before = num_clones();
+        let one_at_a_time = msg_refs
+            .iter()
+            .try_fold(pred.clone(), |pred, msg| pred.test(msg))
+            .expect("all messages vote for the ballot");
+        let one_at_a_time_clones = num_clones() - before;
+
+        let before = num_clones();
+        let batched = pred
+            .test_many(&msg_refs)
+            .expect("all messages vote for the ballot");
+        let batched_clones = num_clones() - before;
+
+        assert_eq!(batched.result(), one_at_a_time.result());
+        assert_eq!(
+            batched.result(),
+            HashSet::from_iter(vec![Ballot::new(1, &[CountedValue(7)])])
+        );
+        assert!(
+            batched_clones + 100 <= one_at_a_time_clones,
+            "batched: {}, one at a time: {}",
+            batched_clones,
+            one_at_a_time_clones
+        );
+
+        // A message that does not vote for any of the ballots fails the batch.
+        let other = Msg::new(
+            test_node_id(7),
+            QuorumSet::new_with_node_ids(1, vec![test_node_id(1)]),
+            1,
+            Topic::Prepare(PreparePayload {
+                B: Ballot::new(1, &[CountedValue(1000)]),
+                P: None,
+                PP: None,
+                CN: 0,
+                HN: 0,
+            }),
+        );
+        assert!(pred.test_many(&[&msgs[0], &other, &msgs[1]]).is_none());
+    }
+
     #[test]
     // BallotSetPredicate can be used to pick a blocking set that intersects with a given set of ballots.
     pub fn test_ballot_set_predicate_blocking_set() {
@@ -345,7 +722,7 @@ mod predicates_tests {
 
         // Node 5 and 6 also form a blocking set, voting on ballot_2
         let topic = Topic::Prepare(PreparePayload::<u32> {
-            B: ballot_2,
+            B: ballot_2.clone(),
             P: None,
             PP: None,
             CN: 0,
@@ -377,7 +754,34 @@ mod predicates_tests {
             node_ids,
             HashSet::from_iter(vec![test_node_id(2), test_node_id(3)])
         );
-        assert_eq!(pred.result(), HashSet::from_iter(vec![ballot_1]));
+        assert_eq!(pred.result(), HashSet::from_iter(vec![ballot_1.clone()]));
+
+        // Both blocking sets should be found, each with the ballots its members share.
+        let blocking_sets = local_node_quorum_set.findAllBlockingSetsWithPredicates(
+            &msgs,
+            BallotSetPredicate {
+                ballots: HashSet::from_iter(vec![ballot_1.clone(), ballot_2.clone()]),
+                test_fn: Arc::new(|msg, ballots| {
+                    ballots
+                        .intersection(&msg.votes_or_accepts_prepared())
+                        .cloned()
+                        .collect()
+                }),
+            },
+        );
+        let blocking_sets: Vec<(BTreeSet<NodeID>, HashSet<Ballot<u32>>)> = blocking_sets
+            .into_iter()
+            .map(|(node_ids, pred)| (node_ids, pred.result()))
+            .collect();
+        assert_eq!(blocking_sets.len(), 2);
+        assert!(blocking_sets.contains(&(
+            BTreeSet::from_iter(vec![test_node_id(2), test_node_id(3)]),
+            HashSet::from_iter(vec![ballot_1])
+        )));
+        assert!(blocking_sets.contains(&(
+            BTreeSet::from_iter(vec![test_node_id(5), test_node_id(6)]),
+            HashSet::from_iter(vec![ballot_2])
+        )));
     }
 
     #[test]
@@ -488,6 +892,151 @@ mod predicates_tests {
         assert_eq!(pred.result(), values_1);
     }
 
+    #[test]
+    // filter_to_min_values should pick the smallest set of values among quorum results.
+    pub fn test_value_set_predicate_filter_to_min_values() {
+        let local_node_id = test_node_id(1);
+        let local_node_quorum_set = QuorumSet::new_with_node_ids(
+            2,
+            vec![
+                test_node_id(2),
+                test_node_id(3),
+                test_node_id(4),
+                test_node_id(5),
+            ],
+        );
+        let peer_quorum_set = |node_index: u32| {
+            QuorumSet::new_with_node_ids(
+                1,
+                (1..=5)
+                    .filter(|i| *i != node_index)
+                    .map(test_node_id)
+                    .collect(),
+            )
+        };
+
+        let values_1 = BTreeSet::from_iter(vec!["a".to_string(), "A".to_string()]);
+        let values_2 = BTreeSet::from_iter(vec!["b".to_string(), "B".to_string()]);
+
+        // Node 2 and 3 form a quorum voting on values_1, and nodes 4 and 5 on values_2.
+        let mut msgs = HashMap::<NodeID, Msg<String>>::default();
+        for node_index in 2..=5 {
+            let values = if node_index <= 3 {
+                values_1.clone()
+            } else {
+                values_2.clone()
+            };
+            let topic = Topic::Nominate(NominatePayload {
+                X: values,
+                Y: BTreeSet::default(),
+            });
+            msgs.insert(
+                test_node_id(node_index),
+                Msg::new(
+                    test_node_id(node_index),
+                    peer_quorum_set(node_index),
+                    1,
+                    topic,
+                ),
+            );
+        }
+
+        // Look for quorums agreeing on each of the value sets.
+        let results: Vec<(HashSet<NodeID>, BTreeSet<String>)> =
+            vec![values_2.clone(), values_1.clone()]
+                .into_iter()
+                .map(|values| {
+                    let (node_ids, pred) = local_node_quorum_set.findQuorum(
+                        &local_node_id,
+                        &msgs,
+                        ValueSetPredicate {
+                            values,
+                            test_fn: Arc::new(|msg, values| {
+                                match msg.votes_or_accepts_nominated() {
+                                    None => BTreeSet::default(),
+                                    Some(values2) => {
+                                        values.intersection(&values2).cloned().collect()
+                                    }
+                                }
+                            }),
+                        },
+                    );
+                    assert_eq!(node_ids.len(), 3);
+                    (node_ids, pred.result())
+                })
+                .collect();
+
+        assert_eq!(
+            ValueSetPredicate::<String>::filter_to_min_values(results.clone()),
+            Some(values_1)
+        );
+        assert_eq!(
+            ValueSetPredicate::<String>::filter_to_max_values(results),
+            Some(values_2)
+        );
+        assert_eq!(
+            ValueSetPredicate::<String>::filter_to_min_values(vec![]),
+            None
+        );
+    }
+
+    #[test]
+    // ValueSetPredicate should work with node ID types other than NodeID.
+    pub fn test_value_set_predicate_with_responder_ids() {
+        let local_node_id = test_responder_id(1);
+        let local_node_quorum_set =
+            QuorumSet::new_with_node_ids(2, (2..=4).map(test_responder_id).collect());
+
+        let values = BTreeSet::from_iter(vec!["a".to_string(), "A".to_string()]);
+        let mut msgs = HashMap::<ResponderId, Msg<String, ResponderId>>::default();
+        for node_index in 2..=3 {
+            msgs.insert(
+                test_responder_id(node_index),
+                Msg::new(
+                    test_responder_id(node_index),
+                    QuorumSet::new_with_node_ids(1, vec![test_responder_id(1)]),
+                    1,
+                    Topic::Nominate(NominatePayload {
+                        X: values.clone(),
+                        Y: BTreeSet::default(),
+                    }),
+                ),
+            );
+        }
+
+        let (node_ids, pred) = local_node_quorum_set.findQuorum(
+            &local_node_id,
+            &msgs,
+            ValueSetPredicate::<String, ResponderId> {
+                values: BTreeSet::from_iter(vec![
+                    "a".to_string(),
+                    "A".to_string(),
+                    "c".to_string(),
+                ]),
+                test_fn: Arc::new(|msg, values| match msg.votes_or_accepts_nominated() {
+                    None => BTreeSet::default(),
+                    Some(values2) => values.intersection(&values2).cloned().collect(),
+                }),
+            },
+        );
+        assert_eq!(
+            node_ids,
+            HashSet::from_iter(vec![
+                test_responder_id(1),
+                test_responder_id(2),
+                test_responder_id(3)
+            ])
+        );
+        assert_eq!(pred.result(), values);
+        assert_eq!(
+            ValueSetPredicate::<String, ResponderId>::filter_to_max_values(vec![(
+                node_ids,
+                pred.result()
+            )]),
+            Some(values)
+        );
+    }
+
     #[test]
     // ValueSetPredicate can be used to pick a set values that has reached blocking threshold.
     pub fn test_value_set_predicate_blocking_set() {
@@ -568,4 +1117,502 @@ mod predicates_tests {
         );
         assert_eq!(pred.result(), values_1);
     }
+
+    #[test]
+    // RoundPredicate can be used to find a quorum among messages from a range of rounds.
+    pub fn test_round_predicate_quorum() {
+        let local_node_id = test_node_id(1);
+        let local_node_quorum_set = QuorumSet::new_with_node_ids(
+            2,
+            vec![
+                test_node_id(2),
+                test_node_id(3),
+                test_node_id(4),
+                test_node_id(5),
+            ],
+        );
+
+        let peer_quorum_set = |node_index: u32| {
+            QuorumSet::new_with_node_ids(
+                1,
+                (1..=5)
+                    .filter(|i| *i != node_index)
+                    .map(test_node_id)
+                    .collect(),
+            )
+        };
+
+        let prepare = |round: u32| {
+            Topic::Prepare(PreparePayload::<u32> {
+                B: Ballot::new(round, &[1111]),
+                P: None,
+                PP: None,
+                CN: 0,
+                HN: 0,
+            })
+        };
+
+        let mut msgs = HashMap::<NodeID, Msg<u32>>::default();
+
+        // Nodes 2 and 3 vote in round 1.
+        for node_index in 2..=3 {
+            msgs.insert(
+                test_node_id(node_index),
+                Msg::new(
+                    test_node_id(node_index),
+                    peer_quorum_set(node_index),
+                    1,
+                    prepare(1),
+                ),
+            );
+        }
+
+        // Nodes 4 and 5 have re-voted in round 2.
+        for node_index in 4..=5 {
+            msgs.insert(
+                test_node_id(node_index),
+                Msg::new(
+                    test_node_id(node_index),
+                    peer_quorum_set(node_index),
+                    1,
+                    prepare(2),
+                ),
+            );
+        }
+
+        let (node_ids, _pred) = local_node_quorum_set.findQuorum(
+            &local_node_id,
+            &msgs,
+            RoundPredicate {
+                min_round: 1,
+                max_round: 1,
+            },
+        );
+        assert_eq!(
+            node_ids,
+            HashSet::from_iter(vec![test_node_id(1), test_node_id(2), test_node_id(3)])
+        );
+
+        // No quorum exists among round 3 messages.
+        let (node_ids, _pred) = local_node_quorum_set.findQuorum(
+            &local_node_id,
+            &msgs,
+            RoundPredicate {
+                min_round: 3,
+                max_round: 3,
+            },
+        );
+        assert!(node_ids.is_empty());
+    }
+    /// Nodes 2 and 3 are preparing in round 1, and nodes 4 and 5 accept commit <1..=2, [1111]>.
+    fn mixed_prepare_commit_msgs() -> HashMap<NodeID, Msg<u32>> {
+        let peer_quorum_set = |node_index: u32| {
+            QuorumSet::new_with_node_ids(
+                1,
+                (1..=5)
+                    .filter(|i| *i != node_index)
+                    .map(test_node_id)
+                    .collect(),
+            )
+        };
+
+        let mut msgs = HashMap::<NodeID, Msg<u32>>::default();
+        for node_index in 2..=5 {
+            let topic = if node_index <= 3 {
+                Topic::Prepare(PreparePayload::<u32> {
+                    B: Ballot::new(1, &[1111]),
+                    P: None,
+                    PP: None,
+                    CN: 0,
+                    HN: 0,
+                })
+            } else {
+                Topic::Commit(CommitPayload::<u32> {
+                    B: Ballot::new(2, &[1111]),
+                    PN: 2,
+                    CN: 1,
+                    HN: 2,
+                })
+            };
+            msgs.insert(
+                test_node_id(node_index),
+                Msg::new(
+                    test_node_id(node_index),
+                    peer_quorum_set(node_index),
+                    1,
+                    topic,
+                ),
+            );
+        }
+        msgs
+    }
+
+    /// Narrows ranges of ballot counters to those the message accepts committed.
+    fn accepts_commits_predicate(min: u32, max: u32) -> BallotRangePredicate<u32> {
+        BallotRangePredicate::for_value(
+            vec![1111],
+            min,
+            max,
+            BallotRangePredicate::intersect_commits(),
+        )
+    }
+
+    #[test]
+    // BallotRangePredicate::for_value should narrow a single ballot range across a quorum.
+    pub fn test_ballot_range_predicate_for_value() {
+        let local_node_id = test_node_id(1);
+        let local_node_quorum_set =
+            QuorumSet::new_with_node_ids(2, (2..=5).map(test_node_id).collect());
+        let msgs = mixed_prepare_commit_msgs();
+
+        let pred = BallotRangePredicate::for_value(
+            vec![1111],
+            1,
+            10,
+            BallotRangePredicate::intersect_commits(),
+        );
+        assert_eq!(
+            pred.result(),
+            HashMap::from_iter(vec![(vec![1111], (1, 10))])
+        );
+
+        // Nodes 4 and 5 accept commit for counters 1 through 2.
+        let (node_ids, pred) = local_node_quorum_set.findQuorum(&local_node_id, &msgs, pred);
+        assert_eq!(
+            node_ids,
+            HashSet::from_iter(vec![test_node_id(1), test_node_id(4), test_node_id(5)])
+        );
+        assert_eq!(
+            pred.result(),
+            HashMap::from_iter(vec![(vec![1111], (1, 2))])
+        );
+
+        // No quorum accepts commit for other values.
+        let (node_ids, _pred) = local_node_quorum_set.findQuorum(
+            &local_node_id,
+            &msgs,
+            BallotRangePredicate::for_value(
+                vec![2222],
+                1,
+                10,
+                BallotRangePredicate::intersect_commits(),
+            ),
+        );
+        assert!(node_ids.is_empty());
+    }
+
+    #[test]
+    // A BallotRangePredicate with an empty range should never be satisfied.
+    pub fn test_ballot_range_predicate_empty_range() {
+        let local_node_id = test_node_id(1);
+        let local_node_quorum_set =
+            QuorumSet::new_with_node_ids(2, (2..=5).map(test_node_id).collect());
+        let msgs = mixed_prepare_commit_msgs();
+
+        let pred = accepts_commits_predicate(10, 1);
+        assert!(pred.result().is_empty());
+        for msg in msgs.values() {
+            assert!(pred.test(msg).is_none());
+        }
+        let (node_ids, _pred) = local_node_quorum_set.findQuorum(&local_node_id, &msgs, pred);
+        assert!(node_ids.is_empty());
+
+        // An empty range set directly is skipped by `intersect_commits`.
+        let mut pred = accepts_commits_predicate(1, 10);
+        pred.ballot_ranges.insert(vec![2222], (10, 1));
+        let narrowed = pred
+            .test(&msgs[&test_node_id(4)])
+            .expect("commit should satisfy the non-empty range");
+        assert_eq!(
+            narrowed.result(),
+            HashMap::from_iter(vec![(vec![1111], (1, 2))])
+        );
+    }
+
+    #[test]
+    // AndPredicate should only be satisfied by messages that satisfy both inner predicates.
+    pub fn test_and_predicate() {
+        let local_node_id = test_node_id(1);
+        let local_node_quorum_set =
+            QuorumSet::new_with_node_ids(2, (2..=5).map(test_node_id).collect());
+        let msgs = mixed_prepare_commit_msgs();
+
+        // Prepare messages do not accept commit, so fail the combined predicate.
+        let pred = AndPredicate {
+            first: accepts_commits_predicate(1, 10),
+            second: RoundPredicate {
+                min_round: 1,
+                max_round: 2,
+            },
+        };
+        assert!(pred.test(&msgs[&test_node_id(2)]).is_none());
+        let narrowed = pred
+            .test(&msgs[&test_node_id(4)])
+            .expect("commit should satisfy both predicates");
+        assert_eq!(
+            narrowed.result().0,
+            HashMap::from_iter(vec![(vec![1111], (1, 2))])
+        );
+
+        // Nodes 4 and 5 accept commit, and are in a round in range.
+        let (node_ids, pred) = local_node_quorum_set.findQuorum(&local_node_id, &msgs, pred);
+        assert_eq!(
+            node_ids,
+            HashSet::from_iter(vec![test_node_id(1), test_node_id(4), test_node_id(5)])
+        );
+        assert_eq!(
+            pred.result().0,
+            HashMap::from_iter(vec![(vec![1111], (1, 2))])
+        );
+
+        // No quorum accepts commit in round 1.
+        let (node_ids, _pred) = local_node_quorum_set.findQuorum(
+            &local_node_id,
+            &msgs,
+            AndPredicate {
+                first: accepts_commits_predicate(1, 10),
+                second: RoundPredicate {
+                    min_round: 1,
+                    max_round: 1,
+                },
+            },
+        );
+        assert!(node_ids.is_empty());
+    }
+
+    #[test]
+    // OrPredicate should keep whichever inner predicates messages continue to satisfy.
+    pub fn test_or_predicate() {
+        let local_node_id = test_node_id(1);
+        let local_node_quorum_set =
+            QuorumSet::new_with_node_ids(2, (2..=5).map(test_node_id).collect());
+        let msgs = mixed_prepare_commit_msgs();
+        let pred = OrPredicate::new(
+            RoundPredicate {
+                min_round: 1,
+                max_round: 1,
+            },
+            accepts_commits_predicate(2, 10),
+        );
+
+        // A Prepare message only satisfies the round predicate, and a Commit message only
+        // satisfies the accepts commit predicate.
+        let after_prepare = pred
+            .test(&msgs[&test_node_id(2)])
+            .expect("prepare should satisfy the round predicate");
+        assert_eq!(after_prepare.result(), (Some(()), None));
+        let after_commit = pred
+            .test(&msgs[&test_node_id(4)])
+            .expect("commit should satisfy the accepts commit predicate");
+        assert_eq!(
+            after_commit.result(),
+            (None, Some(HashMap::from_iter(vec![(vec![1111], (2, 2))])))
+        );
+        assert!(after_prepare.test(&msgs[&test_node_id(4)]).is_none());
+        assert!(after_commit.test(&msgs[&test_node_id(2)]).is_none());
+
+        // Either kind of message can form a quorum, but not a mix of both.
+        let (node_ids, pred) = local_node_quorum_set.findQuorum(&local_node_id, &msgs, pred);
+        match pred.result() {
+            (Some(()), None) => assert_eq!(
+                node_ids,
+                HashSet::from_iter(vec![test_node_id(1), test_node_id(2), test_node_id(3)])
+            ),
+            (None, Some(ranges)) => {
+                assert_eq!(
+                    node_ids,
+                    HashSet::from_iter(vec![test_node_id(1), test_node_id(4), test_node_id(5)])
+                );
+                assert_eq!(ranges, HashMap::from_iter(vec![(vec![1111], (2, 2))]));
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    // NotPredicate can be used to find a blocking set of nodes that have not externalized.
+    pub fn test_not_predicate_blocking_set() {
+        let local_node_quorum_set =
+            QuorumSet::new_with_node_ids(3, (2..=5).map(test_node_id).collect());
+
+        let mut msgs = HashMap::<NodeID, Msg<u32>>::default();
+        for node_index in 2..=5 {
+            let topic = if node_index <= 3 {
+                Topic::Prepare(PreparePayload::<u32> {
+                    B: Ballot::new(1, &[1111]),
+                    P: None,
+                    PP: None,
+                    CN: 0,
+                    HN: 0,
+                })
+            } else {
+                Topic::Externalize(ExternalizePayload::<u32> {
+                    C: Ballot::new(1, &[1111]),
+                    HN: 1,
+                })
+            };
+            msgs.insert(
+                test_node_id(node_index),
+                Msg::new(
+                    test_node_id(node_index),
+                    QuorumSet::new_with_node_ids(1, vec![test_node_id(1)]),
+                    1,
+                    topic,
+                ),
+            );
+        }
+
+        let is_externalize = |msg: &Msg<u32>| msg.topic.is_externalize();
+        let pred = NotPredicate {
+            inner: FuncPredicate {
+                test_fn: &is_externalize,
+            },
+        };
+        assert!(pred.test(&msgs[&test_node_id(2)]).is_some());
+        assert!(pred.test(&msgs[&test_node_id(4)]).is_none());
+
+        let (node_ids, _pred) = local_node_quorum_set.findBlockingSet(&msgs, pred);
+        assert_eq!(
+            node_ids,
+            HashSet::from_iter(vec![test_node_id(2), test_node_id(3)])
+        );
+    }
+
+    #[test]
+    // CountingPredicate should count the messages that match its condition.
+    pub fn test_counting_predicate() {
+        let mut msgs = HashMap::<NodeID, Msg<u32>>::default();
+        for node_index in 2..=6 {
+            // Nodes 2, 3 and 4 nominate, nodes 5 and 6 have moved on to balloting.
+            let topic = if node_index <= 4 {
+                Topic::Nominate(NominatePayload {
+                    X: BTreeSet::from_iter(vec![1111]),
+                    Y: BTreeSet::default(),
+                })
+            } else {
+                Topic::Prepare(PreparePayload {
+                    B: Ballot::new(1, &[1111]),
+                    P: None,
+                    PP: None,
+                    CN: 0,
+                    HN: 0,
+                })
+            };
+            msgs.insert(
+                test_node_id(node_index),
+                Msg::new(
+                    test_node_id(node_index),
+                    QuorumSet::new_with_node_ids(1, vec![test_node_id(1)]),
+                    1,
+                    topic,
+                ),
+            );
+        }
+
+        let votes_or_accepts_nominated =
+            |msg: &Msg<u32>| msg.votes_or_accepts_nominated().is_some();
+        let pred = CountingPredicate {
+            test_fn: &votes_or_accepts_nominated,
+            count: 0,
+        };
+        assert_eq!(pred.result(), 0);
+
+        // A message that doesn't match carries the count forward.
+        let pred = pred
+            .test(&msgs[&test_node_id(5)])
+            .expect("counting predicate is always satisfied");
+        assert_eq!(pred.result(), 0);
+
+        let msg_refs: Vec<&Msg<u32>> = msgs.values().collect();
+        let pred = pred
+            .test_many(&msg_refs)
+            .expect("counting predicate is always satisfied");
+        assert_eq!(pred.result(), 3);
+    }
+
+    #[test]
+    // MemoPredicate should only run the inner predicate once per distinct message.
+    pub fn test_memo_predicate() {
+        let local_node_id = test_node_id(1);
+        let local_node_quorum_set =
+            QuorumSet::new_with_node_ids(3, (2..=5).map(test_node_id).collect());
+
+        // Nodes 2 and 3 nominate, and nodes 4 and 5 are balloting.
+        let mut msgs = HashMap::<NodeID, Msg<u32>>::default();
+        for node_index in 2..=5 {
+            let topic = if node_index <= 3 {
+                Topic::Nominate(NominatePayload {
+                    X: BTreeSet::from_iter(vec![1111]),
+                    Y: BTreeSet::default(),
+                })
+            } else {
+                Topic::Prepare(PreparePayload {
+                    B: Ballot::new(1, &[1111]),
+                    P: None,
+                    PP: None,
+                    CN: 0,
+                    HN: 0,
+                })
+            };
+            let peer_quorum_set = QuorumSet::new_with_node_ids(
+                3,
+                (1..=5)
+                    .filter(|i| *i != node_index)
+                    .map(test_node_id)
+                    .collect(),
+            );
+            msgs.insert(
+                test_node_id(node_index),
+                Msg::new(test_node_id(node_index), peer_quorum_set, 1, topic),
+            );
+        }
+
+        let num_calls = std::cell::Cell::new(0);
+        let is_nominate = |msg: &Msg<u32>| {
+            num_calls.set(num_calls.get() + 1);
+            msg.topic.is_nominate()
+        };
+
+        let (node_ids, _pred) = local_node_quorum_set.findQuorum(
+            &local_node_id,
+            &msgs,
+            FuncPredicate {
+                test_fn: &is_nominate,
+            },
+        );
+        let num_uncached_calls = num_calls.replace(0);
+
+        // Wrapping the predicate doesn't change the outcome of the search.
+        let (memo_node_ids, _pred) = local_node_quorum_set.findQuorum(
+            &local_node_id,
+            &msgs,
+            MemoPredicate::new(FuncPredicate {
+                test_fn: &is_nominate,
+            }),
+        );
+        assert_eq!(memo_node_ids, node_ids);
+        assert!(node_ids.is_empty());
+        assert!(num_calls.get() <= num_uncached_calls);
+
+        // Testing the same messages again, including from a clone, is answered from the cache.
+        let pred = MemoPredicate::new(FuncPredicate {
+            test_fn: &is_nominate,
+        });
+        num_calls.set(0);
+        for _ in 0..3 {
+            for node_index in 2..=5 {
+                let outcome = pred.clone().test(&msgs[&test_node_id(node_index)]);
+                assert_eq!(outcome.is_some(), node_index <= 3);
+            }
+        }
+        assert_eq!(num_calls.get(), msgs.len());
+
+        // The predicate returned by a successful test caches its own outcomes, and is returned
+        // again on a cache hit.
+        let next = pred.test(&msgs[&test_node_id(2)]).unwrap();
+        assert!(next.test(&msgs[&test_node_id(4)]).is_none());
+        let next = pred.test(&msgs[&test_node_id(2)]).unwrap();
+        assert!(next.test(&msgs[&test_node_id(4)]).is_none());
+        assert_eq!(num_calls.get(), msgs.len() + 1);
+    }
 }
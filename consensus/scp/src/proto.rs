@@ -0,0 +1,506 @@
+// Copyright (c) 2018-2021 The MobileCoin Foundation
+
+//! A stable protobuf encoding of SCP messages, for sending them over the wire.
+//!
+//! Messages are converted to and from private prost message types that mirror `Msg` and its
+//! payloads. Values are encoded as opaque bytes by `ProstValue`.
+use crate::{
+    core_types::{Ballot, SlotIndex, Value},
+    msg::{CommitPayload, ExternalizePayload, Msg, NominatePayload, PreparePayload, Topic},
+    quorum_set::{QuorumSet, QuorumSetMember},
+};
+use mc_common::{NodeID, ResponderId};
+use mc_crypto_keys::Ed25519Public;
+use prost::{DecodeError, Message, Oneof};
+use std::{collections::BTreeSet, convert::TryFrom};
+
+/// A value with a stable binary encoding, so that messages carrying it can be encoded.
+pub trait ProstValue: Value {
+    /// Encodes the value.
+    fn encode_value(&self) -> Vec<u8>;
+
+    /// Decodes a value encoded by `encode_value`.
+    fn decode_value(bytes: &[u8]) -> Result<Self, DecodeError>;
+}
+
+impl ProstValue for u32 {
+    fn encode_value(&self) -> Vec<u8> {
+        self.to_be_bytes().to_vec()
+    }
+
+    fn decode_value(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let bytes = <[u8; 4]>::try_from(bytes)
+            .map_err(|_| DecodeError::new("u32 value must be 4 bytes"))?;
+        Ok(u32::from_be_bytes(bytes))
+    }
+}
+
+impl ProstValue for u64 {
+    fn encode_value(&self) -> Vec<u8> {
+        self.to_be_bytes().to_vec()
+    }
+
+    fn decode_value(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let bytes = <[u8; 8]>::try_from(bytes)
+            .map_err(|_| DecodeError::new("u64 value must be 8 bytes"))?;
+        Ok(u64::from_be_bytes(bytes))
+    }
+}
+
+impl ProstValue for String {
+    fn encode_value(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+
+    fn decode_value(bytes: &[u8]) -> Result<Self, DecodeError> {
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| DecodeError::new("string value must be UTF-8"))
+    }
+}
+
+impl<V: ProstValue> Msg<V> {
+    /// Encodes this message as protobuf.
+    pub fn to_protobuf(&self) -> Vec<u8> {
+        let proto_msg = ProtoMsg {
+            sender_id: Some(ProtoNodeId::from(&self.sender_id)),
+            slot_index: self.slot_index,
+            quorum_set: Some(ProtoQuorumSet::from(&self.quorum_set)),
+            topic: Some(ProtoTopic::from(&self.topic)),
+        };
+        let mut bytes = Vec::with_capacity(proto_msg.encoded_len());
+        proto_msg
+            .encode(&mut bytes)
+            .expect("encoding into a Vec cannot fail");
+        bytes
+    }
+
+    /// Decodes a message encoded by `to_protobuf`.
+    pub fn from_protobuf(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let proto_msg = ProtoMsg::decode(bytes)?;
+        Ok(Msg::new(
+            required(proto_msg.sender_id, "sender_id")?.into_node_id()?,
+            required(proto_msg.quorum_set, "quorum_set")?.into_quorum_set()?,
+            proto_msg.slot_index,
+            required(proto_msg.topic, "topic")?.into_topic()?,
+        ))
+    }
+}
+
+/// Returns the value of a message field that prost decodes as optional.
+fn required<T>(field: Option<T>, name: &'static str) -> Result<T, DecodeError> {
+    field.ok_or_else(|| DecodeError::new(format!("missing {}", name)))
+}
+
+fn encode_values<'a, V: ProstValue + 'a>(values: impl IntoIterator<Item = &'a V>) -> Vec<Vec<u8>> {
+    values.into_iter().map(ProstValue::encode_value).collect()
+}
+
+fn decode_values<V: ProstValue>(values: &[Vec<u8>]) -> Result<Vec<V>, DecodeError> {
+    values.iter().map(|bytes| V::decode_value(bytes)).collect()
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ProtoMsg {
+    #[prost(message, optional, tag = "1")]
+    sender_id: Option<ProtoNodeId>,
+
+    #[prost(uint64, tag = "2")]
+    slot_index: SlotIndex,
+
+    #[prost(message, optional, tag = "3")]
+    quorum_set: Option<ProtoQuorumSet>,
+
+    #[prost(oneof = "ProtoTopic", tags = "4, 5, 6, 7, 8")]
+    topic: Option<ProtoTopic>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ProtoNodeId {
+    #[prost(string, tag = "1")]
+    responder_id: String,
+
+    #[prost(bytes, tag = "2")]
+    public_key: Vec<u8>,
+}
+
+impl From<&NodeID> for ProtoNodeId {
+    fn from(node_id: &NodeID) -> Self {
+        Self {
+            responder_id: node_id.responder_id.0.clone(),
+            public_key: AsRef::<[u8]>::as_ref(&node_id.public_key).to_vec(),
+        }
+    }
+}
+
+impl ProtoNodeId {
+    fn into_node_id(self) -> Result<NodeID, DecodeError> {
+        let public_key = Ed25519Public::try_from(&self.public_key[..])
+            .map_err(|_| DecodeError::new("invalid node public key"))?;
+        Ok(NodeID {
+            responder_id: ResponderId(self.responder_id),
+            public_key,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ProtoQuorumSet {
+    #[prost(uint32, tag = "1")]
+    threshold: u32,
+
+    #[prost(message, repeated, tag = "2")]
+    members: Vec<ProtoQuorumSetMember>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ProtoQuorumSetMember {
+    #[prost(oneof = "ProtoMember", tags = "1, 2")]
+    member: Option<ProtoMember>,
+}
+
+#[derive(Clone, PartialEq, Oneof)]
+enum ProtoMember {
+    #[prost(message, tag = "1")]
+    Node(ProtoNodeId),
+
+    #[prost(message, tag = "2")]
+    InnerSet(ProtoQuorumSet),
+}
+
+impl From<&QuorumSet> for ProtoQuorumSet {
+    fn from(quorum_set: &QuorumSet) -> Self {
+        Self {
+            threshold: quorum_set.threshold,
+            members: quorum_set
+                .members
+                .iter()
+                .map(|member| ProtoQuorumSetMember {
+                    member: Some(match member {
+                        QuorumSetMember::Node(node_id) => ProtoMember::Node(node_id.into()),
+                        QuorumSetMember::InnerSet(inner_set) => {
+                            ProtoMember::InnerSet(inner_set.into())
+                        }
+                    }),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl ProtoQuorumSet {
+    fn into_quorum_set(self) -> Result<QuorumSet, DecodeError> {
+        let members = self
+            .members
+            .into_iter()
+            .map(
+                |member| match required(member.member, "quorum set member")? {
+                    ProtoMember::Node(node_id) => {
+                        Ok(QuorumSetMember::Node(node_id.into_node_id()?))
+                    }
+                    ProtoMember::InnerSet(inner_set) => {
+                        Ok(QuorumSetMember::InnerSet(inner_set.into_quorum_set()?))
+                    }
+                },
+            )
+            .collect::<Result<Vec<_>, DecodeError>>()?;
+        Ok(QuorumSet::new(self.threshold, members))
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ProtoBallot {
+    #[prost(uint32, tag = "1")]
+    counter: u32,
+
+    #[prost(bytes, repeated, tag = "2")]
+    values: Vec<Vec<u8>>,
+}
+
+impl<V: ProstValue> From<&Ballot<V>> for ProtoBallot {
+    fn from(ballot: &Ballot<V>) -> Self {
+        Self {
+            counter: ballot.N,
+            values: encode_values(&ballot.X),
+        }
+    }
+}
+
+impl ProtoBallot {
+    fn into_ballot<V: ProstValue>(self) -> Result<Ballot<V>, DecodeError> {
+        Ok(Ballot {
+            N: self.counter,
+            X: decode_values(&self.values)?,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Oneof)]
+enum ProtoTopic {
+    #[prost(message, tag = "4")]
+    Nominate(ProtoNominatePayload),
+
+    #[prost(message, tag = "5")]
+    NominatePrepare(ProtoNominatePreparePayload),
+
+    #[prost(message, tag = "6")]
+    Prepare(ProtoPreparePayload),
+
+    #[prost(message, tag = "7")]
+    Commit(ProtoCommitPayload),
+
+    #[prost(message, tag = "8")]
+    Externalize(ProtoExternalizePayload),
+}
+
+impl<V: ProstValue> From<&Topic<V>> for ProtoTopic {
+    fn from(topic: &Topic<V>) -> Self {
+        match topic {
+            Topic::Nominate(payload) => ProtoTopic::Nominate(payload.into()),
+            Topic::NominatePrepare(nominate_payload, prepare_payload) => {
+                ProtoTopic::NominatePrepare(ProtoNominatePreparePayload {
+                    nominate: Some(nominate_payload.into()),
+                    prepare: Some(prepare_payload.into()),
+                })
+            }
+            Topic::Prepare(payload) => ProtoTopic::Prepare(payload.into()),
+            Topic::Commit(payload) => ProtoTopic::Commit(ProtoCommitPayload {
+                ballot: Some((&payload.B).into()),
+                prepared_counter: payload.PN,
+                commit_counter: payload.CN,
+                highest_counter: payload.HN,
+            }),
+            Topic::Externalize(payload) => ProtoTopic::Externalize(ProtoExternalizePayload {
+                commit: Some((&payload.C).into()),
+                highest_counter: payload.HN,
+            }),
+        }
+    }
+}
+
+impl ProtoTopic {
+    fn into_topic<V: ProstValue>(self) -> Result<Topic<V>, DecodeError> {
+        Ok(match self {
+            ProtoTopic::Nominate(payload) => Topic::Nominate(payload.into_payload()?),
+            ProtoTopic::NominatePrepare(payload) => Topic::NominatePrepare(
+                required(payload.nominate, "nominate")?.into_payload()?,
+                required(payload.prepare, "prepare")?.into_payload()?,
+            ),
+            ProtoTopic::Prepare(payload) => Topic::Prepare(payload.into_payload()?),
+            ProtoTopic::Commit(payload) => Topic::Commit(CommitPayload {
+                B: required(payload.ballot, "ballot")?.into_ballot()?,
+                PN: payload.prepared_counter,
+                CN: payload.commit_counter,
+                HN: payload.highest_counter,
+            }),
+            ProtoTopic::Externalize(payload) => Topic::Externalize(ExternalizePayload {
+                C: required(payload.commit, "commit")?.into_ballot()?,
+                HN: payload.highest_counter,
+            }),
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ProtoNominatePayload {
+    #[prost(bytes, repeated, tag = "1")]
+    voted: Vec<Vec<u8>>,
+
+    #[prost(bytes, repeated, tag = "2")]
+    accepted: Vec<Vec<u8>>,
+}
+
+impl<V: ProstValue> From<&NominatePayload<V>> for ProtoNominatePayload {
+    fn from(payload: &NominatePayload<V>) -> Self {
+        Self {
+            voted: encode_values(&payload.X),
+            accepted: encode_values(&payload.Y),
+        }
+    }
+}
+
+impl ProtoNominatePayload {
+    fn into_payload<V: ProstValue>(self) -> Result<NominatePayload<V>, DecodeError> {
+        Ok(NominatePayload {
+            X: decode_values::<V>(&self.voted)?
+                .into_iter()
+                .collect::<BTreeSet<V>>(),
+            Y: decode_values::<V>(&self.accepted)?
+                .into_iter()
+                .collect::<BTreeSet<V>>(),
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ProtoNominatePreparePayload {
+    #[prost(message, optional, tag = "1")]
+    nominate: Option<ProtoNominatePayload>,
+
+    #[prost(message, optional, tag = "2")]
+    prepare: Option<ProtoPreparePayload>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ProtoPreparePayload {
+    #[prost(message, optional, tag = "1")]
+    ballot: Option<ProtoBallot>,
+
+    #[prost(message, optional, tag = "2")]
+    prepared: Option<ProtoBallot>,
+
+    #[prost(message, optional, tag = "3")]
+    prepared_prime: Option<ProtoBallot>,
+
+    #[prost(uint32, tag = "4")]
+    commit_counter: u32,
+
+    #[prost(uint32, tag = "5")]
+    highest_counter: u32,
+}
+
+impl<V: ProstValue> From<&PreparePayload<V>> for ProtoPreparePayload {
+    fn from(payload: &PreparePayload<V>) -> Self {
+        Self {
+            ballot: Some((&payload.B).into()),
+            prepared: payload.P.as_ref().map(ProtoBallot::from),
+            prepared_prime: payload.PP.as_ref().map(ProtoBallot::from),
+            commit_counter: payload.CN,
+            highest_counter: payload.HN,
+        }
+    }
+}
+
+impl ProtoPreparePayload {
+    fn into_payload<V: ProstValue>(self) -> Result<PreparePayload<V>, DecodeError> {
+        Ok(PreparePayload {
+            B: required(self.ballot, "ballot")?.into_ballot()?,
+            P: self.prepared.map(ProtoBallot::into_ballot).transpose()?,
+            PP: self
+                .prepared_prime
+                .map(ProtoBallot::into_ballot)
+                .transpose()?,
+            CN: self.commit_counter,
+            HN: self.highest_counter,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ProtoCommitPayload {
+    #[prost(message, optional, tag = "1")]
+    ballot: Option<ProtoBallot>,
+
+    #[prost(uint32, tag = "2")]
+    prepared_counter: u32,
+
+    #[prost(uint32, tag = "3")]
+    commit_counter: u32,
+
+    #[prost(uint32, tag = "4")]
+    highest_counter: u32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ProtoExternalizePayload {
+    #[prost(message, optional, tag = "1")]
+    commit: Option<ProtoBallot>,
+
+    #[prost(uint32, tag = "2")]
+    highest_counter: u32,
+}
+
+#[cfg(test)]
+mod proto_tests {
+    use super::*;
+    use crate::test_utils::test_node_id;
+    use std::iter::FromIterator;
+
+    #[test]
+    // Messages of every topic should round-trip through the protobuf encoding.
+    fn test_protobuf_round_trip() {
+        // A nested quorum set.
+        let quorum_set = QuorumSet::new(
+            2,
+            vec![
+                QuorumSetMember::Node(test_node_id(2)),
+                QuorumSetMember::InnerSet(QuorumSet::new(
+                    1,
+                    vec![
+                        QuorumSetMember::Node(test_node_id(3)),
+                        QuorumSetMember::InnerSet(QuorumSet::new_with_node_ids(
+                            2,
+                            vec![test_node_id(4), test_node_id(5)],
+                        )),
+                    ],
+                )),
+            ],
+        );
+
+        let nominate_payload = NominatePayload {
+            X: BTreeSet::from_iter(vec![1000, 2000]),
+            Y: BTreeSet::from_iter(vec![3000]),
+        };
+        let prepare_payload = PreparePayload {
+            B: Ballot::new(3, &[1000, 2000]),
+            P: Some(Ballot::new(2, &[1000, 2000])),
+            PP: Some(Ballot::new(1, &[1000])),
+            CN: 1,
+            HN: 2,
+        };
+        let topics: Vec<Topic<u32>> = vec![
+            Topic::Nominate(nominate_payload.clone()),
+            Topic::NominatePrepare(nominate_payload, prepare_payload.clone()),
+            Topic::Prepare(prepare_payload),
+            Topic::Prepare(PreparePayload {
+                B: Ballot::new(1, &[1000]),
+                P: None,
+                PP: None,
+                CN: 0,
+                HN: 0,
+            }),
+            Topic::Commit(CommitPayload {
+                B: Ballot::new(3, &[1000, 2000]),
+                PN: 3,
+                CN: 2,
+                HN: 3,
+            }),
+            Topic::Externalize(ExternalizePayload {
+                C: Ballot::new(2, &[1000, 2000]),
+                HN: 3,
+            }),
+        ];
+
+        for topic in topics {
+            let msg = Msg::new(test_node_id(1), quorum_set.clone(), 7, topic);
+            let decoded = Msg::<u32>::from_protobuf(&msg.to_protobuf()).expect("decoding failed");
+            assert_eq!(decoded, msg);
+            // QuorumSet equality ignores member order, so compare the nesting exactly.
+            assert_eq!(decoded.quorum_set.members, msg.quorum_set.members);
+        }
+    }
+
+    #[test]
+    // Malformed encodings should fail to decode.
+    fn test_protobuf_decode_errors() {
+        let msg = Msg::new(
+            test_node_id(1),
+            QuorumSet::new_with_node_ids(1, vec![test_node_id(2)]),
+            7,
+            Topic::Nominate(NominatePayload {
+                X: BTreeSet::from_iter(vec![1000u32]),
+                Y: Default::default(),
+            }),
+        );
+        let bytes = msg.to_protobuf();
+
+        // Values must decode as the expected type.
+        assert!(Msg::<u64>::from_protobuf(&bytes).is_err());
+
+        // A message without a topic is rejected.
+        let mut proto_msg = ProtoMsg::decode(&bytes[..]).unwrap();
+        proto_msg.topic = None;
+        let mut bytes = Vec::new();
+        proto_msg.encode(&mut bytes).unwrap();
+        assert!(Msg::<u32>::from_protobuf(&bytes).is_err());
+    }
+}
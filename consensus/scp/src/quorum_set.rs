@@ -4,13 +4,15 @@
 //!
 //! A quorum set includes the members of the network, which a given node trusts and depends on.
 use mc_common::{NodeID, ResponderId};
-use mc_crypto_digestible::Digestible;
+use mc_crypto_digestible::{Digestible, MerlinTranscript};
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::{HashMap, HashSet},
-    fmt::Debug,
+    cmp,
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt::{self, Debug, Display, Write},
     hash::{Hash, Hasher},
     iter::FromIterator,
+    str::FromStr,
 };
 
 use crate::{
@@ -31,15 +33,69 @@ pub enum QuorumSetMember<ID: GenericNodeId> {
 }
 
 /// The quorum set defining the trusted set of peers.
+///
+/// As JSON, a quorum set is `{"threshold": 2, "members": [...]}`, where each member is either
+/// `{"type": "Node", "args": <node id>}` or `{"type": "InnerSet", "args": <quorum set>}`.
 #[derive(Clone, Debug, Ord, PartialOrd, Serialize, Deserialize, Digestible)]
 pub struct QuorumSet<ID: GenericNodeId = NodeID> {
     /// Threshold (how many members do we need to reach quorum).
+    ///
+    /// A threshold of 0 is trivially satisfied: an inner set with threshold 0 counts towards its
+    /// parent's threshold without any of its members, contributes an empty quorum slice, and can
+    /// never be blocked.
     pub threshold: u32,
 
     /// Members.
     pub members: Vec<QuorumSetMember<ID>>,
 }
 
+/// Why a quorum set failed `QuorumSet::validate`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum QuorumSetValidationError<ID: GenericNodeId = NodeID> {
+    /// A set's threshold is larger than its number of members, so it can never be satisfied.
+    ThresholdExceedsMembers {
+        /// The set's threshold.
+        threshold: u32,
+
+        /// The set's number of members.
+        members: usize,
+    },
+
+    /// A set has no members.
+    EmptyMembers,
+
+    /// A node appears more than once.
+    DuplicateNode(ID),
+
+    /// A set has a threshold of 0, so it is satisfied by any set of nodes.
+    ThresholdIsZero,
+
+    /// Inner sets are nested deeper than `QuorumSet::MAX_DEPTH`, so the deepest are ignored.
+    TooDeep,
+}
+
+impl<ID: GenericNodeId> Display for QuorumSetValidationError<ID> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::ThresholdExceedsMembers { threshold, members } => write!(
+                f,
+                "threshold {} exceeds the number of members {}",
+                threshold, members
+            ),
+            Self::EmptyMembers => write!(f, "quorum set has no members"),
+            Self::DuplicateNode(node_id) => write!(f, "node {} appears more than once", node_id),
+            Self::ThresholdIsZero => write!(f, "threshold is 0"),
+            Self::TooDeep => write!(
+                f,
+                "inner sets are nested more than {} deep",
+                QuorumSet::<ID>::MAX_DEPTH
+            ),
+        }
+    }
+}
+
+impl<ID: GenericNodeId> std::error::Error for QuorumSetValidationError<ID> {}
+
 impl<ID: GenericNodeId> PartialEq for QuorumSet<ID> {
     fn eq(&self, other: &QuorumSet<ID>) -> bool {
         if self.threshold == other.threshold && self.members.len() == other.members.len() {
@@ -66,6 +122,12 @@ impl<ID: GenericNodeId> Hash for QuorumSet<ID> {
 }
 
 impl<ID: GenericNodeId> QuorumSet<ID> {
+    /// How deeply inner sets may be nested when searching for quorums and blocking sets.
+    ///
+    /// Inner sets below this depth are treated as unsatisfiable and never blocked, which bounds
+    /// the recursion on pathological quorum sets.
+    pub const MAX_DEPTH: usize = 4;
+
     /// Create a new quorum set.
     pub fn new(threshold: u32, members: Vec<QuorumSetMember<ID>>) -> Self {
         Self { threshold, members }
@@ -96,6 +158,8 @@ impl<ID: GenericNodeId> QuorumSet<ID> {
     }
 
     /// Check if a quorum set is valid.
+    ///
+    /// A threshold of 0 is valid, at the top level or in an inner set. See [QuorumSet::threshold].
     pub fn is_valid(&self) -> bool {
         // Must have at least `threshold` members.
         if self.threshold as usize > self.members.len() {
@@ -115,6 +179,52 @@ impl<ID: GenericNodeId> QuorumSet<ID> {
         true
     }
 
+    /// Checks that this quorum set and its inner sets are well-formed.
+    ///
+    /// This is stricter than `is_valid`: empty sets and sets with a threshold of 0 are allowed by
+    /// the protocol, but are almost certainly configuration mistakes for a node that is about to
+    /// join the network. Each node may appear at most once, including across inner sets, and
+    /// inner sets may be nested at most `MAX_DEPTH` deep.
+    pub fn validate(&self) -> Result<(), QuorumSetValidationError<ID>> {
+        self.validate_helper(&mut HashSet::default(), 0)
+    }
+
+    /// Internal helper method, recording the nodes seen so far in `seen`.
+    fn validate_helper(
+        &self,
+        seen: &mut HashSet<ID>,
+        depth: usize,
+    ) -> Result<(), QuorumSetValidationError<ID>> {
+        if depth > Self::MAX_DEPTH {
+            return Err(QuorumSetValidationError::TooDeep);
+        }
+        if self.members.is_empty() {
+            return Err(QuorumSetValidationError::EmptyMembers);
+        }
+        if self.threshold == 0 {
+            return Err(QuorumSetValidationError::ThresholdIsZero);
+        }
+        if self.threshold as usize > self.members.len() {
+            return Err(QuorumSetValidationError::ThresholdExceedsMembers {
+                threshold: self.threshold,
+                members: self.members.len(),
+            });
+        }
+
+        for member in self.members.iter() {
+            match member {
+                QuorumSetMember::Node(node_id) => {
+                    if !seen.insert(node_id.clone()) {
+                        return Err(QuorumSetValidationError::DuplicateNode(node_id.clone()));
+                    }
+                }
+                QuorumSetMember::InnerSet(qs) => qs.validate_helper(seen, depth + 1)?,
+            }
+        }
+
+        Ok(())
+    }
+
     /// Recursively sort the qs and all inner sets
     pub fn sort(&mut self) {
         for member in self.members.iter_mut() {
@@ -126,6 +236,151 @@ impl<ID: GenericNodeId> QuorumSet<ID> {
         self.members.sort();
     }
 
+    /// Removes `node_id` from this quorum set and all of its inner sets, e.g. when a node is
+    /// decommissioned. Returns true if anything changed.
+    ///
+    /// Inner sets left without members are removed as well, since an empty inner set would be
+    /// trivially satisfied. Each threshold is clamped to its set's new number of members.
+    pub fn remove_node(&mut self, node_id: &ID) -> bool {
+        let mut changed = false;
+        self.members.retain(|member| {
+            let keep = match member {
+                QuorumSetMember::Node(id) => id != node_id,
+                QuorumSetMember::InnerSet(_) => true,
+            };
+            changed |= !keep;
+            keep
+        });
+
+        for member in self.members.iter_mut() {
+            if let QuorumSetMember::InnerSet(qs) = member {
+                changed |= qs.remove_node(node_id);
+            }
+        }
+        let num_members = self.members.len();
+        self.members.retain(|member| match member {
+            QuorumSetMember::Node(_) => true,
+            QuorumSetMember::InnerSet(qs) => !qs.members.is_empty(),
+        });
+        changed |= self.members.len() != num_members;
+
+        self.threshold = cmp::min(self.threshold, self.members.len() as u32);
+        changed
+    }
+
+    /// A copy of this quorum set with its members, and those of its inner sets, in a
+    /// deterministic order.
+    ///
+    /// Equal quorum sets compare and hash equal regardless of member order, but their digests do
+    /// not. Quorum sets must be canonicalized before digesting them, or messages carrying them,
+    /// if equivalent sets should produce the same digest.
+    pub fn canonical(&self) -> Self {
+        let mut canonical = self.clone();
+        canonical.sort();
+        canonical
+    }
+
+    /// A digest identifying this quorum set. Equal quorum sets have equal fingerprints,
+    /// regardless of member order.
+    pub fn fingerprint(&self) -> [u8; 32] {
+        self.canonical()
+            .digest32::<MerlinTranscript>(b"scp_quorum_set")
+    }
+
+    /// Iterates over members in canonical order: nodes sorted by ID, followed by inner sets,
+    /// which are themselves recursively sorted.
+    pub fn iter_ordered(&self) -> impl Iterator<Item = QuorumSetMember<ID>> {
+        let mut sorted = self.clone();
+        sorted.sort();
+        sorted.members.into_iter()
+    }
+
+    /// Renders this quorum set as a GraphViz DOT graph.
+    ///
+    /// Each quorum set is a box labeled with its threshold, with an edge to each of its members.
+    /// A node that appears in several inner sets is drawn once.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph quorum_set {\n");
+        let mut num_sets = 0;
+        self.to_dot_helper(&mut dot, &mut num_sets);
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Internal helper method, writing the statements for this set and its members.
+    ///
+    /// # Arguments
+    /// * `dot` - The graph being written.
+    /// * `num_sets` - How many sets have been written, used to name them.
+    ///
+    /// # Returns
+    /// * The DOT identifier of this set.
+    fn to_dot_helper(&self, dot: &mut String, num_sets: &mut usize) -> String {
+        let set_id = format!("\"qs:{}\"", num_sets);
+        *num_sets += 1;
+        writeln!(
+            dot,
+            "    {} [shape=box, label=\"{} of {}\"];",
+            set_id,
+            self.threshold,
+            self.members.len()
+        )
+        .expect("writing to a String cannot fail");
+
+        for member in self.iter_ordered() {
+            let member_id = match member {
+                QuorumSetMember::Node(node_id) => {
+                    let label = escape_dot(&node_id.to_string());
+                    let member_id = format!("\"node:{}\"", label);
+                    writeln!(dot, "    {} [label=\"{}\"];", member_id, label)
+                        .expect("writing to a String cannot fail");
+                    member_id
+                }
+                QuorumSetMember::InnerSet(qs) => qs.to_dot_helper(dot, num_sets),
+            };
+            writeln!(dot, "    {} -> {};", set_id, member_id)
+                .expect("writing to a String cannot fail");
+        }
+
+        set_id
+    }
+
+    /// Parses a quorum set from the compact notation, such as `([2],1,2,([1],3,4))`.
+    ///
+    /// Each set is written as `([threshold],member,...)`, where a member is either an integer or
+    /// a nested set. Integers are mapped to node ids by `node_id`.
+    pub fn from_config_str(
+        src: &str,
+        node_id: &dyn Fn(u32) -> ID,
+    ) -> Result<Self, QuorumSetParseError> {
+        let tokens = tokenize_quorum_set(src)?;
+        parse_quorum_set(src, tokens, &|token| match token {
+            QuorumSetToken::Integer(value) => Ok(node_id(*value)),
+            token => Err(format!("expected an integer node id, found {}", token)),
+        })
+    }
+
+    /// Writes this quorum set in the compact notation read by `from_config_str`.
+    ///
+    /// Members are written in their current order, with each node written as `node_name(node)`.
+    pub fn to_config_str(&self, node_name: &dyn Fn(&ID) -> String) -> String {
+        let members: Vec<String> = self
+            .members
+            .iter()
+            .map(|member| match member {
+                QuorumSetMember::Node(node_id) => node_name(node_id),
+                QuorumSetMember::InnerSet(qs) => qs.to_config_str(node_name),
+            })
+            .collect();
+        let mut config = format!("([{}]", self.threshold);
+        for member in members {
+            config.push(',');
+            config.push_str(&member);
+        }
+        config.push(')');
+        config
+    }
+
     /// Returns a flattened set of all nodes contained in q and its nested QSets.
     pub fn nodes(&self) -> HashSet<ID> {
         let mut result = HashSet::<ID>::default();
@@ -142,6 +397,67 @@ impl<ID: GenericNodeId> QuorumSet<ID> {
         result
     }
 
+    /// Enumerates the quorum slices of this quorum set.
+    ///
+    /// A quorum slice is a minimal set of nodes that satisfies the threshold at each level of
+    /// the quorum set: `threshold` members, where each chosen inner set is itself satisfied by
+    /// one of its own slices. Unlike a quorum, a slice does not need to satisfy the quorum sets
+    /// of the nodes it contains.
+    pub fn slices(&self) -> Vec<BTreeSet<ID>> {
+        let member_slices: Vec<Vec<BTreeSet<ID>>> = self
+            .members
+            .iter()
+            .map(|member| match member {
+                QuorumSetMember::Node(node_id) => vec![BTreeSet::from_iter(vec![node_id.clone()])],
+                QuorumSetMember::InnerSet(qs) => qs.slices(),
+            })
+            .collect();
+
+        let mut result = Vec::new();
+        Self::slices_helper(
+            self.threshold as usize,
+            &member_slices,
+            BTreeSet::new(),
+            &mut result,
+        );
+        result
+    }
+
+    /// Internal helper method, choosing `needed` of the remaining members' slices.
+    ///
+    /// # Arguments
+    /// * `needed` - How many more members are needed to satisfy the threshold.
+    /// * `member_slices` - The slices of each remaining member.
+    /// * `slice_so_far` - Nodes collected so far for the slice under construction.
+    /// * `result` - Completed slices.
+    fn slices_helper(
+        needed: usize,
+        member_slices: &[Vec<BTreeSet<ID>>],
+        slice_so_far: BTreeSet<ID>,
+        result: &mut Vec<BTreeSet<ID>>,
+    ) {
+        if needed == 0 {
+            if !result.contains(&slice_so_far) {
+                result.push(slice_so_far);
+            }
+            return;
+        }
+
+        if needed > member_slices.len() {
+            return;
+        }
+
+        // Slices that include the first member.
+        for slice in member_slices[0].iter() {
+            let mut slice_so_far2 = slice_so_far.clone();
+            slice_so_far2.extend(slice.iter().cloned());
+            Self::slices_helper(needed - 1, &member_slices[1..], slice_so_far2, result);
+        }
+
+        // Slices that exclude the first member.
+        Self::slices_helper(needed, &member_slices[1..], slice_so_far, result);
+    }
+
     /// Gives the fraction of quorum slices containing the given node.
     /// It assumes that id appears in at most one QuorumSet
     /// (either the top level one or a single reachable nested one)
@@ -169,6 +485,55 @@ impl<ID: GenericNodeId> QuorumSet<ID> {
         (0, 1)
     }
 
+    /// Returns true if `node_ids` satisfy the threshold at each level of this quorum set, i.e.
+    /// contain one of its slices.
+    ///
+    /// Unlike `findQuorum`, this only evaluates this quorum set: the quorum sets of the nodes in
+    /// `node_ids` are not known, so they are not checked. As in `findQuorum`, inner sets nested
+    /// deeper than `MAX_DEPTH` are never satisfied.
+    pub fn is_quorum(&self, node_ids: &HashSet<ID>) -> bool {
+        self.is_quorum_helper(node_ids, 0)
+    }
+
+    /// Internal helper method, where `depth` is how deeply this set is nested.
+    fn is_quorum_helper(&self, node_ids: &HashSet<ID>, depth: usize) -> bool {
+        let satisfied = self
+            .members
+            .iter()
+            .filter(|member| match member {
+                QuorumSetMember::Node(node_id) => node_ids.contains(node_id),
+                QuorumSetMember::InnerSet(qs) => {
+                    depth < Self::MAX_DEPTH && qs.is_quorum_helper(node_ids, depth + 1)
+                }
+            })
+            .count();
+        satisfied >= self.threshold as usize
+    }
+
+    /// Returns true if `node_ids` intersect every slice of this quorum set, i.e. at least
+    /// `members.len() - threshold + 1` members are blocked, where an inner set is blocked if
+    /// `node_ids` are blocking for it. As in `findBlockingSet`, inner sets nested deeper than
+    /// `MAX_DEPTH` are never blocked.
+    pub fn is_blocking(&self, node_ids: &HashSet<ID>) -> bool {
+        self.is_blocking_helper(node_ids, 0)
+    }
+
+    /// Internal helper method, where `depth` is how deeply this set is nested.
+    fn is_blocking_helper(&self, node_ids: &HashSet<ID>, depth: usize) -> bool {
+        let needed = (self.members.len() + 1).saturating_sub(self.threshold as usize);
+        let blocked = self
+            .members
+            .iter()
+            .filter(|member| match member {
+                QuorumSetMember::Node(node_id) => node_ids.contains(node_id),
+                QuorumSetMember::InnerSet(qs) => {
+                    depth < Self::MAX_DEPTH && qs.is_blocking_helper(node_ids, depth + 1)
+                }
+            })
+            .count();
+        blocked >= needed
+    }
+
     /// Attempts to find a blocking set matching a given predicate `predicate`.
     ///
     /// # Arguments
@@ -189,6 +554,7 @@ impl<ID: GenericNodeId> QuorumSet<ID> {
             msgs,
             pred,
             HashSet::default(),
+            0,
         )
     }
 
@@ -201,12 +567,14 @@ impl<ID: GenericNodeId> QuorumSet<ID> {
     /// * `msgs` - A map of ID -> Msg holding the newest message received from each node.
     /// * `pred` - Predicate to apply to the messages.
     /// * `node_so_far` - Nodes we have collected so far in our quest for finding a blocking set.
+    /// * `depth` - How deeply `members` is nested in the quorum set.
     fn findBlockingSetHelper<V: Value, P: Predicate<V, ID>>(
         needed: u32,
         members: &[QuorumSetMember<ID>],
         msgs: &HashMap<ID, Msg<V, ID>>,
         pred: P,
         nodes_so_far: HashSet<ID>,
+        depth: usize,
     ) -> (HashSet<ID>, P) {
         // If we don't need any more nodes, we're done.
         if needed == 0 {
@@ -236,11 +604,13 @@ impl<ID: GenericNodeId> QuorumSet<ID> {
                             msgs,
                             nextPred,
                             nodes_so_far2,
+                            depth,
                         );
                     }
                 }
             }
-            QuorumSetMember::InnerSet(Q) => {
+            // Inner sets nested too deeply are never blocked.
+            QuorumSetMember::InnerSet(Q) if depth < Self::MAX_DEPTH => {
                 let (nodes_so_far2, pred2) = Self::findBlockingSetHelper(
                     // "A message reaches blocking threshold at "v" when the number of
                     //  "validators" making the statement plus (recursively) the number
@@ -251,6 +621,7 @@ impl<ID: GenericNodeId> QuorumSet<ID> {
                     msgs,
                     pred.clone(),
                     nodes_so_far.clone(),
+                    depth + 1,
                 );
                 if !nodes_so_far2.is_empty() {
                     return Self::findBlockingSetHelper(
@@ -259,13 +630,157 @@ impl<ID: GenericNodeId> QuorumSet<ID> {
                         msgs,
                         pred2,
                         nodes_so_far2,
+                        depth,
                     );
                 }
             }
+            QuorumSetMember::InnerSet(_) => {}
         }
 
         // First member didn't get us to a blocking set, move to the next member and try again.
-        Self::findBlockingSetHelper(needed, &members[1..], msgs, pred, nodes_so_far)
+        Self::findBlockingSetHelper(needed, &members[1..], msgs, pred, nodes_so_far, depth)
+    }
+
+    /// Enumerates every minimal blocking set matching a given predicate `predicate`.
+    ///
+    /// Unlike `findBlockingSet`, which stops at the first match, this explores every way of
+    /// blocking each member. Sets that contain another matching set are dropped. The search is
+    /// exponential in the size of the quorum set, so this is intended for offline analysis.
+    ///
+    /// # Arguments
+    /// * `msgs` - A map of ID -> Msg holding the newest message received from each node.
+    /// * `pred` - Predicate to apply to the messages.
+    ///
+    /// # Returns
+    /// * The distinct minimal blocking sets, sorted. Empty if no blocking set matches.
+    pub fn findAllBlockingSets<V: Value, P: Predicate<V, ID>>(
+        &self,
+        msgs: &HashMap<ID, Msg<V, ID>>,
+        pred: P,
+    ) -> Vec<BTreeSet<ID>> {
+        self.findAllBlockingSetsWithPredicates(msgs, pred)
+            .into_iter()
+            .map(|(nodes, _pred)| nodes)
+            .collect()
+    }
+
+    /// Like `findAllBlockingSets`, but also returns the predicate that accepted each blocking
+    /// set. If a set can be reached in several ways, the predicate from the first is returned.
+    ///
+    /// # Returns
+    /// * The distinct minimal blocking sets, sorted, each with its predicate.
+    pub fn findAllBlockingSetsWithPredicates<V: Value, P: Predicate<V, ID>>(
+        &self,
+        msgs: &HashMap<ID, Msg<V, ID>>,
+        pred: P,
+    ) -> Vec<(BTreeSet<ID>, P)> {
+        let mut found = Vec::new();
+        Self::findAllBlockingSetsHelper(
+            (self.members.len() as u32 + 1).saturating_sub(self.threshold),
+            &self.members,
+            msgs,
+            pred,
+            BTreeSet::new(),
+            &mut found,
+            0,
+        );
+
+        let mut result: Vec<(BTreeSet<ID>, P)> = Vec::new();
+        for (nodes, pred) in found.iter() {
+            let has_smaller = found
+                .iter()
+                .any(|(other, _)| other.len() < nodes.len() && other.is_subset(nodes));
+            if !has_smaller && !result.iter().any(|(other, _)| other == nodes) {
+                result.push((nodes.clone(), pred.clone()));
+            }
+        }
+        result.sort_by(|(a, _), (b, _)| a.cmp(b));
+        result
+    }
+
+    /// Internal helper method, collecting every blocking set reachable from `members`.
+    ///
+    /// # Arguments
+    /// * `needed` - How many more members need to be blocked.
+    /// * `members` - Array of quorum set members we are considering as potential blocking set
+    ///    members.
+    /// * `msgs` - A map of ID -> Msg holding the newest message received from each node.
+    /// * `pred` - Predicate to apply to the messages.
+    /// * `nodes_so_far` - Nodes collected so far for the blocking set under construction.
+    /// * `result` - Completed blocking sets, with the predicate that accepted them.
+    /// * `depth` - How deeply `members` is nested in the quorum set.
+    fn findAllBlockingSetsHelper<V: Value, P: Predicate<V, ID>>(
+        needed: u32,
+        members: &[QuorumSetMember<ID>],
+        msgs: &HashMap<ID, Msg<V, ID>>,
+        pred: P,
+        nodes_so_far: BTreeSet<ID>,
+        result: &mut Vec<(BTreeSet<ID>, P)>,
+        depth: usize,
+    ) {
+        if needed == 0 {
+            result.push((nodes_so_far, pred));
+            return;
+        }
+
+        if needed as usize > members.len() {
+            return;
+        }
+
+        // Blocking sets that block the first member.
+        match &members[0] {
+            QuorumSetMember::Node(N) => {
+                if let Some(nextPred) = msgs.get(N).and_then(|msg| pred.test(msg)) {
+                    let mut nodes_so_far2 = nodes_so_far.clone();
+                    nodes_so_far2.insert(N.clone());
+                    Self::findAllBlockingSetsHelper(
+                        needed - 1,
+                        &members[1..],
+                        msgs,
+                        nextPred,
+                        nodes_so_far2,
+                        result,
+                        depth,
+                    );
+                }
+            }
+            // Inner sets nested too deeply are never blocked.
+            QuorumSetMember::InnerSet(Q) if depth < Self::MAX_DEPTH => {
+                let mut inner = Vec::new();
+                Self::findAllBlockingSetsHelper(
+                    (Q.members.len() as u32 + 1).saturating_sub(Q.threshold),
+                    &Q.members,
+                    msgs,
+                    pred.clone(),
+                    nodes_so_far.clone(),
+                    &mut inner,
+                    depth + 1,
+                );
+                for (nodes_so_far2, pred2) in inner {
+                    Self::findAllBlockingSetsHelper(
+                        needed - 1,
+                        &members[1..],
+                        msgs,
+                        pred2,
+                        nodes_so_far2,
+                        result,
+                        depth,
+                    );
+                }
+            }
+            QuorumSetMember::InnerSet(_) => {}
+        }
+
+        // Blocking sets that leave the first member alone.
+        Self::findAllBlockingSetsHelper(
+            needed,
+            &members[1..],
+            msgs,
+            pred,
+            nodes_so_far,
+            result,
+            depth,
+        );
     }
 
     /// Attempts to find a quorum matching a given predicate `predicate`.
@@ -290,6 +805,7 @@ impl<ID: GenericNodeId> QuorumSet<ID> {
             msgs,
             pred,
             HashSet::from_iter(vec![node_id.clone()]),
+            0,
         )
     }
 
@@ -301,12 +817,14 @@ impl<ID: GenericNodeId> QuorumSet<ID> {
     /// * `msgs` - A map of ID -> Msg holding the newest message received from each node.
     /// * `pred` - Predicate to apply to the messages.
     /// * `node_so_far` - Nodes we have collected so far in our quest for finding a quorum.
+    /// * `depth` - How deeply `members` is nested in its quorum set.
     fn findQuorumHelper<V: Value, P: Predicate<V, ID>>(
         threshold: u32,
         members: &[QuorumSetMember<ID>],
         msgs: &HashMap<ID, Msg<V, ID>>,
         pred: P,
         nodes_so_far: HashSet<ID>,
+        depth: usize,
     ) -> (HashSet<ID>, P) {
         // If we don't need any more nodes, we're done.
         if threshold == 0 {
@@ -330,6 +848,7 @@ impl<ID: GenericNodeId> QuorumSet<ID> {
                         msgs,
                         pred,
                         nodes_so_far,
+                        depth,
                     );
                 }
 
@@ -348,6 +867,7 @@ impl<ID: GenericNodeId> QuorumSet<ID> {
                             msgs,
                             nextPred,
                             nodes_so_far_with_N,
+                            0,
                         );
                         if !nodes_so_far2.is_empty() {
                             // We can find a quorum for the node's validators, so consider it a
@@ -358,12 +878,14 @@ impl<ID: GenericNodeId> QuorumSet<ID> {
                                 msgs,
                                 pred2,
                                 nodes_so_far2,
+                                depth,
                             );
                         }
                     }
                 }
             }
-            QuorumSetMember::InnerSet(Q) => {
+            // Inner sets nested too deeply are treated as unsatisfiable.
+            QuorumSetMember::InnerSet(Q) if depth < Self::MAX_DEPTH => {
                 // See if we can find quorum for the inner set.
                 let (nodes_so_far2, pred2) = Self::findQuorumHelper(
                     Q.threshold,
@@ -371,6 +893,7 @@ impl<ID: GenericNodeId> QuorumSet<ID> {
                     msgs,
                     pred.clone(),
                     nodes_so_far.clone(),
+                    depth + 1,
                 );
                 if !nodes_so_far2.is_empty() {
                     // We found a quorum for the inner set, we need 1 validator less.
@@ -380,16 +903,67 @@ impl<ID: GenericNodeId> QuorumSet<ID> {
                         msgs,
                         pred2,
                         nodes_so_far2,
+                        depth,
                     );
                 }
             }
+            QuorumSetMember::InnerSet(_) => {}
         }
 
         // First member didn't get us to a quorum, move to the next member and try again.
-        Self::findQuorumHelper(threshold, &members[1..], msgs, pred, nodes_so_far)
+        Self::findQuorumHelper(threshold, &members[1..], msgs, pred, nodes_so_far, depth)
+    }
+}
+
+/// Escapes a string for use inside a quoted DOT identifier.
+fn escape_dot(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Nodes of a network grouped by the quorum set they use.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct QuorumSetSymmetry<ID: GenericNodeId = NodeID> {
+    /// Each distinct (sorted) quorum set and the nodes using it, largest group first.
+    pub groups: Vec<(QuorumSet<ID>, BTreeSet<ID>)>,
+}
+
+impl<ID: GenericNodeId> QuorumSetSymmetry<ID> {
+    /// True if every node uses the same quorum set.
+    pub fn is_symmetric(&self) -> bool {
+        self.groups.len() <= 1
     }
 }
 
+/// Groups nodes by their quorum sets, for decentralization analysis.
+///
+/// Quorum sets are compared after sorting, so member order does not matter. Note that a quorum
+/// set that lists every node except its owner differs from node to node.
+pub fn quorum_set_symmetry<ID: GenericNodeId>(
+    quorum_sets: &HashMap<ID, QuorumSet<ID>>,
+) -> QuorumSetSymmetry<ID> {
+    let mut groups: Vec<(QuorumSet<ID>, BTreeSet<ID>)> = Vec::new();
+    for (node_id, quorum_set) in quorum_sets {
+        let mut normalized = quorum_set.clone();
+        normalized.sort();
+        match groups.iter_mut().find(|(qs, _)| *qs == normalized) {
+            Some((_, node_ids)) => {
+                node_ids.insert(node_id.clone());
+            }
+            None => groups.push((normalized, BTreeSet::from_iter(vec![node_id.clone()]))),
+        }
+    }
+
+    groups.sort_by(|(a_qs, a_ids), (b_qs, b_ids)| {
+        b_ids
+            .len()
+            .cmp(&a_ids.len())
+            .then_with(|| a_ids.cmp(b_ids))
+            .then_with(|| a_qs.cmp(b_qs))
+    });
+
+    QuorumSetSymmetry { groups }
+}
+
 impl<ID: GenericNodeId + AsRef<ResponderId>> From<&QuorumSet<ID>> for QuorumSet<ResponderId> {
     fn from(src: &QuorumSet<ID>) -> QuorumSet<ResponderId> {
         let members = src
@@ -409,47 +983,434 @@ impl<ID: GenericNodeId + AsRef<ResponderId>> From<&QuorumSet<ID>> for QuorumSet<
     }
 }
 
-#[cfg(test)]
-mod quorum_set_tests {
-    use super::*;
-    use crate::{core_types::*, msg::*, predicates::*, test_utils::test_node_id};
-    use mc_common::ResponderId;
-    use std::collections::hash_map::DefaultHasher;
+/// The responder id of node `n` in the legacy integer quorum set format, `node{n}.test.com:8443`.
+pub fn legacy_responder_id(n: u32) -> ResponderId {
+    ResponderId(format!("node{}.test.com:8443", n))
+}
 
-    #[test]
-    // quorum sets should sort recursively
-    fn test_quorum_set_sorting() {
-        let qs = QuorumSet::new(
-            2,
-            vec![
-                QuorumSetMember::Node(test_node_id(1)),
-                QuorumSetMember::InnerSet(QuorumSet::new(
-                    2,
-                    vec![
-                        QuorumSetMember::Node(test_node_id(3)),
-                        QuorumSetMember::Node(test_node_id(2)),
-                        QuorumSetMember::InnerSet(QuorumSet::new_with_node_ids(
-                            2,
-                            vec![test_node_id(5), test_node_id(7), test_node_id(6)],
-                        )),
-                    ],
-                )),
-                QuorumSetMember::Node(test_node_id(0)),
-            ],
-        );
-        let mut qs_sorted = qs.clone();
-        qs_sorted.sort();
+impl QuorumSet<ResponderId> {
+    /// Parses a quorum set whose members are either all quoted responder ids, such as
+    /// `([2],"a.com:443","b.com:443")`, or all integers, such as `([2],1,2)`. Integers are
+    /// mapped to responder ids by `integer_id`. Mixing the two is an error.
+    pub fn from_str_with_integer_ids(
+        src: &str,
+        integer_id: &dyn Fn(u32) -> ResponderId,
+    ) -> Result<Self, QuorumSetParseError> {
+        let tokens = tokenize_quorum_set(src)?;
+
+        let mut node_ids = tokens.iter().filter(|(_, token)| {
+            matches!(
+                token,
+                QuorumSetToken::Integer(_) | QuorumSetToken::Quoted(_)
+            )
+        });
+        if let Some((_, first)) = node_ids.next() {
+            let is_integer = matches!(first, QuorumSetToken::Integer(_));
+            if let Some((offset, _)) = node_ids
+                .find(|(_, token)| matches!(token, QuorumSetToken::Integer(_)) != is_integer)
+            {
+                return Err(QuorumSetParseError::MixedNodeIds { offset: *offset });
+            }
+        }
 
-        assert_eq!(qs, qs_sorted);
+        parse_quorum_set(src, tokens, &|token| match token {
+            QuorumSetToken::Integer(value) => Ok(integer_id(*value)),
+            QuorumSetToken::Quoted(quoted) => ResponderId::from_str(quoted)
+                .map_err(|e| format!("invalid responder id \"{}\": {:?}", quoted, e)),
+            token => Err(format!("expected a node id, found {}", token)),
+        })
     }
+}
 
-    #[test]
-    // ordering of members should not matter
-    fn test_quorum_set_equality_1() {
-        let quorum_set_1 = QuorumSet::new(
-            2,
-            vec![
-                QuorumSetMember::Node(test_node_id(0)),
+/// Parses a quorum set from a string such as `([2],"a.com:443",([1],"b.com:443","c.com:443"))`.
+///
+/// Each set is written as `([threshold],member,...)`, where a member is either a quoted
+/// responder id or a nested set. For compatibility with the legacy format, members may instead
+/// all be integers, such as `([2],1,2,3)`, where `n` is `legacy_responder_id(n)`. Integer and
+/// quoted members may not be mixed.
+impl FromStr for QuorumSet<ResponderId> {
+    type Err = QuorumSetParseError;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        Self::from_str_with_integer_ids(src, &legacy_responder_id)
+    }
+}
+
+/// Why a quorum set string failed to parse.
+///
+/// Offsets are byte offsets into the parsed string.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum QuorumSetParseError {
+    /// A character that cannot start any token.
+    UnexpectedCharacter {
+        /// Where the character is.
+        offset: usize,
+
+        /// The character.
+        character: char,
+    },
+
+    /// A threshold or quoted node id that is never closed.
+    Unterminated {
+        /// Where the threshold or quoted node id starts.
+        offset: usize,
+
+        /// What is unterminated.
+        what: &'static str,
+    },
+
+    /// A threshold that is not a non-negative integer.
+    InvalidThreshold {
+        /// Where the threshold starts.
+        offset: usize,
+
+        /// The text between the brackets.
+        threshold: String,
+    },
+
+    /// A token that does not fit the grammar.
+    UnexpectedToken {
+        /// Where the token starts, or the length of the input if it ended early.
+        offset: usize,
+
+        /// What would have fit.
+        expected: &'static str,
+
+        /// The token, or None if the input ended early.
+        found: Option<String>,
+    },
+
+    /// A set whose threshold exceeds its number of members, so it can never be satisfied.
+    ThresholdExceedsMembers {
+        /// Where the set's threshold is.
+        offset: usize,
+
+        /// The set's threshold.
+        threshold: u32,
+
+        /// The set's number of members.
+        members: usize,
+    },
+
+    /// A node id that could not be mapped to a node.
+    InvalidNodeId {
+        /// Where the node id is.
+        offset: usize,
+
+        /// Why it could not be mapped.
+        message: String,
+    },
+
+    /// Integer and quoted node ids are both used.
+    MixedNodeIds {
+        /// Where the first node id of the other kind is.
+        offset: usize,
+    },
+}
+
+impl QuorumSetParseError {
+    /// The byte offset in the input where the error was found.
+    pub fn offset(&self) -> usize {
+        match self {
+            Self::UnexpectedCharacter { offset, .. }
+            | Self::Unterminated { offset, .. }
+            | Self::InvalidThreshold { offset, .. }
+            | Self::UnexpectedToken { offset, .. }
+            | Self::ThresholdExceedsMembers { offset, .. }
+            | Self::InvalidNodeId { offset, .. }
+            | Self::MixedNodeIds { offset } => *offset,
+        }
+    }
+}
+
+impl Display for QuorumSetParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnexpectedCharacter { character, .. } => {
+                write!(f, "unexpected character '{}'", character)?
+            }
+            Self::Unterminated { what, .. } => write!(f, "unterminated {}", what)?,
+            Self::InvalidThreshold { threshold, .. } => {
+                write!(f, "invalid threshold [{}]", threshold)?
+            }
+            Self::UnexpectedToken {
+                expected, found, ..
+            } => match found {
+                Some(found) => write!(f, "expected {}, found {}", expected, found)?,
+                None => write!(f, "expected {}, found end of input", expected)?,
+            },
+            Self::ThresholdExceedsMembers {
+                threshold, members, ..
+            } => write!(f, "threshold {} exceeds {} members", threshold, members)?,
+            Self::InvalidNodeId { message, .. } => write!(f, "{}", message)?,
+            Self::MixedNodeIds { .. } => write!(
+                f,
+                "integer and quoted node ids are mixed; use either integers, e.g. ([2],1,2,3), or quoted responder ids, e.g. ([2],\"a.com:443\",\"b.com:443\")"
+            )?,
+        }
+        write!(f, " at offset {}", self.offset())
+    }
+}
+
+impl std::error::Error for QuorumSetParseError {}
+
+/// A token of the quorum set string format.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum QuorumSetToken {
+    Open,
+    Close,
+    Comma,
+    Threshold(u32),
+    Integer(u32),
+    Quoted(String),
+}
+
+impl Display for QuorumSetToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Open => write!(f, "'('"),
+            Self::Close => write!(f, "')'"),
+            Self::Comma => write!(f, "','"),
+            Self::Threshold(threshold) => write!(f, "threshold [{}]", threshold),
+            Self::Integer(value) => write!(f, "node id {}", value),
+            Self::Quoted(quoted) => write!(f, "node id \"{}\"", quoted),
+        }
+    }
+}
+
+/// Splits a quorum set string into tokens and their offsets, ignoring whitespace.
+fn tokenize_quorum_set(src: &str) -> Result<Vec<(usize, QuorumSetToken)>, QuorumSetParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = src.char_indices().peekable();
+
+    while let Some((offset, c)) = chars.next() {
+        let token =
+            match c {
+                c if c.is_whitespace() => continue,
+                '(' => QuorumSetToken::Open,
+                ')' => QuorumSetToken::Close,
+                ',' => QuorumSetToken::Comma,
+                '[' => {
+                    let mut digits = String::new();
+                    loop {
+                        match chars.next() {
+                            Some((_, ']')) => break,
+                            Some((_, c)) => digits.push(c),
+                            None => {
+                                return Err(QuorumSetParseError::Unterminated {
+                                    offset,
+                                    what: "threshold",
+                                })
+                            }
+                        }
+                    }
+                    let threshold = digits.trim().parse().map_err(|_| {
+                        QuorumSetParseError::InvalidThreshold {
+                            offset,
+                            threshold: digits.clone(),
+                        }
+                    })?;
+                    QuorumSetToken::Threshold(threshold)
+                }
+                '"' => {
+                    let mut quoted = String::new();
+                    loop {
+                        match chars.next() {
+                            Some((_, '"')) => break,
+                            Some((_, c)) => quoted.push(c),
+                            None => {
+                                return Err(QuorumSetParseError::Unterminated {
+                                    offset,
+                                    what: "quoted node id",
+                                })
+                            }
+                        }
+                    }
+                    QuorumSetToken::Quoted(quoted)
+                }
+                c if c.is_ascii_digit() => {
+                    let mut digits = c.to_string();
+                    while let Some((_, c)) = chars.peek() {
+                        if !c.is_ascii_digit() {
+                            break;
+                        }
+                        digits.push(*c);
+                        chars.next();
+                    }
+                    let value = digits
+                        .parse()
+                        .map_err(|_| QuorumSetParseError::InvalidNodeId {
+                            offset,
+                            message: format!("node id {} is too large", digits),
+                        })?;
+                    QuorumSetToken::Integer(value)
+                }
+                character => {
+                    return Err(QuorumSetParseError::UnexpectedCharacter { offset, character })
+                }
+            };
+        tokens.push((offset, token));
+    }
+
+    Ok(tokens)
+}
+
+/// Parses the tokens of `src` as a single quorum set, resolving node ids with `node_id`.
+fn parse_quorum_set<ID: GenericNodeId>(
+    src: &str,
+    tokens: Vec<(usize, QuorumSetToken)>,
+    node_id: &dyn Fn(&QuorumSetToken) -> Result<ID, String>,
+) -> Result<QuorumSet<ID>, QuorumSetParseError> {
+    let mut parser = QuorumSetParser {
+        tokens,
+        pos: 0,
+        end: src.len(),
+        node_id,
+    };
+    let quorum_set = parser.parse_set()?;
+    if let Some((offset, token)) = parser.tokens.get(parser.pos) {
+        return Err(QuorumSetParseError::UnexpectedToken {
+            offset: *offset,
+            expected: "end of input",
+            found: Some(token.to_string()),
+        });
+    }
+    Ok(quorum_set)
+}
+
+/// A recursive descent parser over quorum set tokens.
+struct QuorumSetParser<'a, ID: GenericNodeId> {
+    tokens: Vec<(usize, QuorumSetToken)>,
+    pos: usize,
+    /// The length of the input, reported as the offset of errors at the end of input.
+    end: usize,
+    /// Resolves an integer or quoted token to a node id.
+    node_id: &'a dyn Fn(&QuorumSetToken) -> Result<ID, String>,
+}
+
+impl<'a, ID: GenericNodeId> QuorumSetParser<'a, ID> {
+    /// The next token and its offset, or the end of input offset.
+    fn next(&mut self) -> (usize, Option<QuorumSetToken>) {
+        let next = match self.tokens.get(self.pos) {
+            Some((offset, token)) => (*offset, Some(token.clone())),
+            None => (self.end, None),
+        };
+        self.pos += 1;
+        next
+    }
+
+    fn unexpected(
+        offset: usize,
+        expected: &'static str,
+        found: Option<QuorumSetToken>,
+    ) -> QuorumSetParseError {
+        QuorumSetParseError::UnexpectedToken {
+            offset,
+            expected,
+            found: found.map(|token| token.to_string()),
+        }
+    }
+
+    /// set := "(" threshold ("," member)* ")"
+    fn parse_set(&mut self) -> Result<QuorumSet<ID>, QuorumSetParseError> {
+        match self.next() {
+            (_, Some(QuorumSetToken::Open)) => {}
+            (offset, token) => return Err(Self::unexpected(offset, "'('", token)),
+        }
+        let (threshold_offset, threshold) = match self.next() {
+            (offset, Some(QuorumSetToken::Threshold(threshold))) => (offset, threshold),
+            (offset, token) => return Err(Self::unexpected(offset, "a threshold", token)),
+        };
+
+        let mut members = Vec::new();
+        loop {
+            match self.next() {
+                (_, Some(QuorumSetToken::Close)) => break,
+                (_, Some(QuorumSetToken::Comma)) => members.push(self.parse_member()?),
+                (offset, token) => return Err(Self::unexpected(offset, "',' or ')'", token)),
+            }
+        }
+
+        if threshold as usize > members.len() {
+            return Err(QuorumSetParseError::ThresholdExceedsMembers {
+                offset: threshold_offset,
+                threshold,
+                members: members.len(),
+            });
+        }
+
+        Ok(QuorumSet::new(threshold, members))
+    }
+
+    /// member := integer | quoted | set
+    fn parse_member(&mut self) -> Result<QuorumSetMember<ID>, QuorumSetParseError> {
+        match self.tokens.get(self.pos).cloned() {
+            Some((_, QuorumSetToken::Open)) => Ok(QuorumSetMember::InnerSet(self.parse_set()?)),
+            Some((offset, token @ QuorumSetToken::Integer(_)))
+            | Some((offset, token @ QuorumSetToken::Quoted(_))) => {
+                self.pos += 1;
+                let node_id = (self.node_id)(&token)
+                    .map_err(|message| QuorumSetParseError::InvalidNodeId { offset, message })?;
+                Ok(QuorumSetMember::Node(node_id))
+            }
+            Some((offset, token)) => {
+                Err(Self::unexpected(offset, "a quorum set member", Some(token)))
+            }
+            None => Err(Self::unexpected(self.end, "a quorum set member", None)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod quorum_set_tests {
+    use super::*;
+    use crate::{
+        core_types::*,
+        msg::*,
+        predicates::*,
+        test_utils::{
+            fig_2_network, random_quorum_set, test_node_id, test_quorum_set_from_str,
+            test_responder_id,
+        },
+    };
+    use mc_common::ResponderId;
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::collections::hash_map::DefaultHasher;
+
+    #[test]
+    // quorum sets should sort recursively
+    fn test_quorum_set_sorting() {
+        let qs = QuorumSet::new(
+            2,
+            vec![
+                QuorumSetMember::Node(test_node_id(1)),
+                QuorumSetMember::InnerSet(QuorumSet::new(
+                    2,
+                    vec![
+                        QuorumSetMember::Node(test_node_id(3)),
+                        QuorumSetMember::Node(test_node_id(2)),
+                        QuorumSetMember::InnerSet(QuorumSet::new_with_node_ids(
+                            2,
+                            vec![test_node_id(5), test_node_id(7), test_node_id(6)],
+                        )),
+                    ],
+                )),
+                QuorumSetMember::Node(test_node_id(0)),
+            ],
+        );
+        let mut qs_sorted = qs.clone();
+        qs_sorted.sort();
+
+        assert_eq!(qs, qs_sorted);
+    }
+
+    #[test]
+    // ordering of members should not matter
+    fn test_quorum_set_equality_1() {
+        let quorum_set_1 = QuorumSet::new(
+            2,
+            vec![
+                QuorumSetMember::Node(test_node_id(0)),
                 QuorumSetMember::Node(test_node_id(1)),
                 QuorumSetMember::Node(test_node_id(2)),
                 QuorumSetMember::Node(test_node_id(3)),
@@ -1087,4 +2048,883 @@ mod quorum_set_tests {
         );
         assert!(!qs.is_valid());
     }
+
+    #[test]
+    // slices should enumerate each combination of members satisfying the threshold.
+    fn test_slices() {
+        // ([2],1,2,3)
+        let qs = QuorumSet::new_with_node_ids(
+            2,
+            vec![test_node_id(1), test_node_id(2), test_node_id(3)],
+        );
+        let slices: HashSet<BTreeSet<NodeID>> = HashSet::from_iter(qs.slices());
+        let expected: HashSet<BTreeSet<NodeID>> = HashSet::from_iter(vec![
+            BTreeSet::from_iter(vec![test_node_id(1), test_node_id(2)]),
+            BTreeSet::from_iter(vec![test_node_id(1), test_node_id(3)]),
+            BTreeSet::from_iter(vec![test_node_id(2), test_node_id(3)]),
+        ]);
+        assert_eq!(slices, expected);
+
+        // ([2],1,([1],2,3)): the inner set counts as a single member, so each slice contains
+        // node 1 and one of nodes 2 or 3.
+        let qs = QuorumSet::new(
+            2,
+            vec![
+                QuorumSetMember::Node(test_node_id(1)),
+                QuorumSetMember::InnerSet(QuorumSet::new_with_node_ids(
+                    1,
+                    vec![test_node_id(2), test_node_id(3)],
+                )),
+            ],
+        );
+        let slices: HashSet<BTreeSet<NodeID>> = HashSet::from_iter(qs.slices());
+        let expected: HashSet<BTreeSet<NodeID>> = HashSet::from_iter(vec![
+            BTreeSet::from_iter(vec![test_node_id(1), test_node_id(2)]),
+            BTreeSet::from_iter(vec![test_node_id(1), test_node_id(3)]),
+        ]);
+        assert_eq!(slices, expected);
+
+        // An empty quorum set is satisfied by the empty slice.
+        assert_eq!(QuorumSet::<NodeID>::empty().slices(), vec![BTreeSet::new()]);
+    }
+
+    #[test]
+    // Fingerprints should ignore member order but distinguish different quorum sets.
+    fn test_fingerprint() {
+        let qs = test_quorum_set_from_str("([2],1,2,([1],3,4))").unwrap();
+        let reordered = test_quorum_set_from_str("([2],([1],4,3),2,1)").unwrap();
+        let different = test_quorum_set_from_str("([2],1,2,([1],3,5))").unwrap();
+        assert_eq!(qs.fingerprint(), reordered.fingerprint());
+        assert_ne!(qs.fingerprint(), different.fingerprint());
+    }
+
+    #[test]
+    // findAllBlockingSets should enumerate every minimal blocking set.
+    fn test_find_all_blocking_sets() {
+        let qs = test_quorum_set_from_str("([2],([2],2,3,4),([2],5,6,7))").unwrap();
+
+        let mut msgs = HashMap::<ResponderId, Msg<u32, ResponderId>>::default();
+        for id in 2..=7 {
+            let node_id = test_responder_id(id);
+            msgs.insert(
+                node_id.clone(),
+                Msg::new(
+                    node_id,
+                    qs.clone(),
+                    1,
+                    Topic::Nominate(NominatePayload {
+                        X: BTreeSet::default(),
+                        Y: BTreeSet::default(),
+                    }),
+                ),
+            );
+        }
+        let all = || FuncPredicate::<u32, ResponderId> {
+            test_fn: &|_msg| true,
+        };
+        let set = |ids: Vec<u32>| -> BTreeSet<ResponderId> {
+            ids.into_iter().map(test_responder_id).collect()
+        };
+
+        // Blocking either inner set blocks the outer set, and each inner set is blocked by any
+        // two of its three nodes.
+        let blocking_sets = qs.findAllBlockingSets(&msgs, all());
+        assert_eq!(
+            blocking_sets,
+            vec![
+                set(vec![2, 3]),
+                set(vec![2, 4]),
+                set(vec![3, 4]),
+                set(vec![5, 6]),
+                set(vec![5, 7]),
+                set(vec![6, 7]),
+            ]
+        );
+
+        // Sets including a node rejected by the predicate are excluded.
+        let excluded = test_responder_id(4);
+        let blocking_sets = qs.findAllBlockingSets(
+            &msgs,
+            FuncPredicate {
+                test_fn: &|msg| msg.sender_id != excluded,
+            },
+        );
+        assert_eq!(
+            blocking_sets,
+            vec![
+                set(vec![2, 3]),
+                set(vec![5, 6]),
+                set(vec![5, 7]),
+                set(vec![6, 7]),
+            ]
+        );
+
+        // Every set findBlockingSet finds is a superset of one of these.
+        let (node_ids, _) = qs.findBlockingSet(&msgs, all());
+        let node_ids: BTreeSet<ResponderId> = node_ids.into_iter().collect();
+        assert!(qs
+            .findAllBlockingSets(&msgs, all())
+            .iter()
+            .any(|blocking_set| blocking_set.is_subset(&node_ids)));
+
+        // With no messages there are no blocking sets.
+        msgs.clear();
+        assert!(qs.findAllBlockingSets(&msgs, all()).is_empty());
+    }
+
+    #[test]
+    // Quorum sets should parse from the legacy integer format.
+    fn test_from_str_integers() {
+        let qs = QuorumSet::<ResponderId>::from_str("([2],1,2,([1],3,4))").unwrap();
+        assert_eq!(qs, test_quorum_set_from_str("([2],1,2,([1],3,4))").unwrap());
+        let expected = QuorumSet::new(
+            2,
+            vec![
+                QuorumSetMember::Node(test_node_id(1).responder_id),
+                QuorumSetMember::Node(test_node_id(2).responder_id),
+                QuorumSetMember::InnerSet(QuorumSet::new_with_node_ids(
+                    1,
+                    vec![test_node_id(3).responder_id, test_node_id(4).responder_id],
+                )),
+            ],
+        );
+        assert_eq!(qs, expected);
+    }
+
+    #[test]
+    // Nodes should be grouped by their quorum sets, regardless of member order.
+    fn test_quorum_set_symmetry() {
+        let shared = |ids: Vec<u32>| {
+            QuorumSet::new_with_node_ids(2, ids.into_iter().map(test_node_id).collect())
+        };
+        let mut quorum_sets = HashMap::default();
+        quorum_sets.insert(test_node_id(1), shared(vec![1, 2, 3]));
+        quorum_sets.insert(test_node_id(2), shared(vec![3, 2, 1]));
+        quorum_sets.insert(test_node_id(3), shared(vec![2, 1, 3]));
+        quorum_sets.insert(test_node_id(4), shared(vec![1, 2, 4]));
+
+        let symmetry = quorum_set_symmetry(&quorum_sets);
+        assert!(!symmetry.is_symmetric());
+        assert_eq!(symmetry.groups.len(), 2);
+
+        let (qs, node_ids) = &symmetry.groups[0];
+        assert_eq!(*qs, shared(vec![1, 2, 3]));
+        assert_eq!(
+            *node_ids,
+            BTreeSet::from_iter(vec![test_node_id(1), test_node_id(2), test_node_id(3)])
+        );
+
+        let (qs, node_ids) = &symmetry.groups[1];
+        assert_eq!(*qs, shared(vec![1, 2, 4]));
+        assert_eq!(*node_ids, BTreeSet::from_iter(vec![test_node_id(4)]));
+
+        // Without the odd one out, the network is symmetric.
+        quorum_sets.remove(&test_node_id(4));
+        assert!(quorum_set_symmetry(&quorum_sets).is_symmetric());
+    }
+
+    #[test]
+    // An inner set with threshold 0 is valid and trivially satisfied.
+    fn test_zero_threshold_inner_set() {
+        let qs = test_quorum_set_from_str("([2],2,([0]))").unwrap();
+        assert!(qs.is_valid());
+
+        let local_id = test_responder_id(1);
+        let peer_id = test_responder_id(2);
+        let pred = || FuncPredicate::<u32, ResponderId> {
+            test_fn: &|_msg| true,
+        };
+
+        // Without node 2 there is no quorum, since the inner set only satisfies one member.
+        let mut msgs = HashMap::<ResponderId, Msg<u32, ResponderId>>::default();
+        let (node_ids, _) = qs.findQuorum(&local_id, &msgs, pred());
+        assert!(node_ids.is_empty());
+
+        // With node 2, the inner set satisfies the remaining member without any of its own.
+        msgs.insert(
+            peer_id.clone(),
+            Msg::new(
+                peer_id.clone(),
+                QuorumSet::new_with_node_ids(1, vec![local_id.clone()]),
+                1,
+                Topic::Nominate(NominatePayload {
+                    X: BTreeSet::default(),
+                    Y: BTreeSet::default(),
+                }),
+            ),
+        );
+        let (node_ids, _) = qs.findQuorum(&local_id, &msgs, pred());
+        assert_eq!(node_ids, HashSet::from_iter(vec![local_id, peer_id]));
+
+        // The inner set can never be blocked, so node 2 alone is not a blocking set for a
+        // threshold of 1.
+        let qs = test_quorum_set_from_str("([1],2,([0]))").unwrap();
+        let (node_ids, _) = qs.findBlockingSet(&msgs, pred());
+        assert!(node_ids.is_empty());
+
+        // The inner set contributes an empty slice.
+        assert!(qs.slices().contains(&BTreeSet::new()));
+        assert_eq!(qs.weight(&test_responder_id(2)), (1, 2));
+    }
+
+    #[test]
+    // is_quorum should evaluate the threshold at each level of nested quorum sets.
+    fn test_is_quorum() {
+        let node_ids =
+            |ids: &[u32]| -> HashSet<NodeID> { ids.iter().map(|id| test_node_id(*id)).collect() };
+
+        // Two of {1, 2, 3}.
+        let qs = QuorumSet::new_with_node_ids(
+            2,
+            vec![test_node_id(1), test_node_id(2), test_node_id(3)],
+        );
+        assert!(qs.is_quorum(&node_ids(&[1, 2])));
+        assert!(qs.is_quorum(&node_ids(&[1, 2, 3])));
+        assert!(!qs.is_quorum(&node_ids(&[3])));
+        assert!(!qs.is_quorum(&node_ids(&[3, 4, 5])));
+
+        // Two of: {1, 2, 3} with threshold 2, {4, 5} with threshold 1, and node 6.
+        let qs = QuorumSet::new(
+            2,
+            vec![
+                QuorumSetMember::InnerSet(QuorumSet::new_with_node_ids(
+                    2,
+                    vec![test_node_id(1), test_node_id(2), test_node_id(3)],
+                )),
+                QuorumSetMember::InnerSet(QuorumSet::new_with_node_ids(
+                    1,
+                    vec![test_node_id(4), test_node_id(5)],
+                )),
+                QuorumSetMember::Node(test_node_id(6)),
+            ],
+        );
+        assert!(qs.is_quorum(&node_ids(&[1, 2, 4])));
+        assert!(qs.is_quorum(&node_ids(&[5, 6])));
+        assert!(qs.is_quorum(&node_ids(&[1, 3, 6])));
+        // One short in the first inner set.
+        assert!(!qs.is_quorum(&node_ids(&[1, 4])));
+        // Only one member satisfied.
+        assert!(!qs.is_quorum(&node_ids(&[4, 5])));
+        assert!(!qs.is_quorum(&HashSet::default()));
+
+        // A zero threshold is always satisfied.
+        assert!(QuorumSet::<NodeID>::empty().is_quorum(&HashSet::default()));
+    }
+
+    #[test]
+    // is_blocking should find the blocking sets of the Fig. 2 network.
+    fn test_is_blocking() {
+        let node_ids =
+            |ids: &[u32]| -> HashSet<NodeID> { ids.iter().map(|id| test_node_id(*id)).collect() };
+        let ((_, qs_1), (_, qs_2), _, _) = fig_2_network();
+
+        // Node 1's slice is {1, 2, 3}, so {2}, {3} and {2, 3} are blocking sets.
+        assert!(qs_1.is_blocking(&node_ids(&[2])));
+        assert!(qs_1.is_blocking(&node_ids(&[3])));
+        assert!(qs_1.is_blocking(&node_ids(&[2, 3])));
+        assert!(!qs_1.is_blocking(&node_ids(&[4])));
+        assert!(!qs_1.is_blocking(&HashSet::default()));
+
+        // Node 2's slice is {2, 3, 4}.
+        assert!(qs_2.is_blocking(&node_ids(&[4])));
+        assert!(!qs_2.is_blocking(&node_ids(&[1])));
+
+        // With nested sets, a blocking set must block one more member than the threshold allows
+        // to be missing: two of {1, 2, 3} with threshold 2, {4, 5} with threshold 1, and node 6.
+        let qs = QuorumSet::new(
+            2,
+            vec![
+                QuorumSetMember::InnerSet(QuorumSet::new_with_node_ids(
+                    2,
+                    vec![test_node_id(1), test_node_id(2), test_node_id(3)],
+                )),
+                QuorumSetMember::InnerSet(QuorumSet::new_with_node_ids(
+                    1,
+                    vec![test_node_id(4), test_node_id(5)],
+                )),
+                QuorumSetMember::Node(test_node_id(6)),
+            ],
+        );
+        assert!(qs.is_blocking(&node_ids(&[1, 2, 6])));
+        assert!(qs.is_blocking(&node_ids(&[4, 5, 6])));
+        assert!(!qs.is_blocking(&node_ids(&[1, 4, 6])));
+        assert!(!qs.is_blocking(&node_ids(&[6])));
+
+        // A zero threshold can never be blocked.
+        assert!(!QuorumSet::<NodeID>::empty().is_blocking(&node_ids(&[1])));
+    }
+
+    #[test]
+    // validate should accept well-formed quorum sets and report what is wrong with others.
+    fn test_validate() {
+        let qs = QuorumSet::new(
+            2,
+            vec![
+                QuorumSetMember::Node(test_node_id(1)),
+                QuorumSetMember::InnerSet(QuorumSet::new_with_node_ids(
+                    1,
+                    vec![test_node_id(2), test_node_id(3)],
+                )),
+            ],
+        );
+        assert_eq!(qs.validate(), Ok(()));
+
+        // Threshold exceeds members, in an inner set.
+        let qs = QuorumSet::new(
+            1,
+            vec![
+                QuorumSetMember::Node(test_node_id(1)),
+                QuorumSetMember::InnerSet(QuorumSet::new_with_node_ids(
+                    3,
+                    vec![test_node_id(2), test_node_id(3)],
+                )),
+            ],
+        );
+        assert_eq!(
+            qs.validate(),
+            Err(QuorumSetValidationError::ThresholdExceedsMembers {
+                threshold: 3,
+                members: 2
+            })
+        );
+
+        // Empty members.
+        assert_eq!(
+            QuorumSet::<NodeID>::new(1, vec![]).validate(),
+            Err(QuorumSetValidationError::EmptyMembers)
+        );
+        let qs = QuorumSet::new_with_inner_sets(1, vec![QuorumSet::<NodeID>::empty()]);
+        assert_eq!(qs.validate(), Err(QuorumSetValidationError::EmptyMembers));
+
+        // Duplicate node, across inner sets.
+        let qs = QuorumSet::new_with_inner_sets(
+            2,
+            vec![
+                QuorumSet::new_with_node_ids(1, vec![test_node_id(1), test_node_id(2)]),
+                QuorumSet::new_with_node_ids(1, vec![test_node_id(3), test_node_id(2)]),
+            ],
+        );
+        assert_eq!(
+            qs.validate(),
+            Err(QuorumSetValidationError::DuplicateNode(test_node_id(2)))
+        );
+
+        // Threshold is zero.
+        let qs = QuorumSet::new_with_node_ids(0, vec![test_node_id(1)]);
+        assert!(qs.is_valid());
+        assert_eq!(
+            qs.validate(),
+            Err(QuorumSetValidationError::ThresholdIsZero)
+        );
+    }
+
+    #[test]
+    // Equivalent quorum sets should canonicalize to the same members in the same order.
+    fn test_canonical() {
+        let qs_1 = QuorumSet::new(
+            2,
+            vec![
+                QuorumSetMember::InnerSet(QuorumSet::new_with_node_ids(
+                    1,
+                    vec![test_node_id(4), test_node_id(3)],
+                )),
+                QuorumSetMember::Node(test_node_id(2)),
+                QuorumSetMember::Node(test_node_id(1)),
+            ],
+        );
+        let qs_2 = QuorumSet::new(
+            2,
+            vec![
+                QuorumSetMember::Node(test_node_id(1)),
+                QuorumSetMember::InnerSet(QuorumSet::new_with_node_ids(
+                    1,
+                    vec![test_node_id(3), test_node_id(4)],
+                )),
+                QuorumSetMember::Node(test_node_id(2)),
+            ],
+        );
+        assert_ne!(qs_1.members, qs_2.members);
+        assert_ne!(
+            qs_1.digest32::<MerlinTranscript>(b"test"),
+            qs_2.digest32::<MerlinTranscript>(b"test")
+        );
+
+        let (canonical_1, canonical_2) = (qs_1.canonical(), qs_2.canonical());
+        assert_eq!(canonical_1.threshold, canonical_2.threshold);
+        assert_eq!(canonical_1.members, canonical_2.members);
+        assert_eq!(
+            canonical_1.digest32::<MerlinTranscript>(b"test"),
+            canonical_2.digest32::<MerlinTranscript>(b"test")
+        );
+
+        // Canonicalizing does not change the quorum set's meaning.
+        let slices = |qs: &QuorumSet| qs.slices().into_iter().collect::<BTreeSet<_>>();
+        assert_eq!(canonical_1, qs_1);
+        assert_eq!(slices(&canonical_1), slices(&qs_1));
+    }
+
+    #[test]
+    // nodes should collect every node in nested quorum sets exactly once.
+    fn test_nodes_nested() {
+        // Node 1 appears at all three levels, and node 4 in two inner sets.
+        let innermost = QuorumSet::new(
+            1,
+            vec![
+                QuorumSetMember::Node(test_node_id(1)),
+                QuorumSetMember::Node(test_node_id(4)),
+                QuorumSetMember::Node(test_node_id(5)),
+            ],
+        );
+        let inner = QuorumSet::new(
+            2,
+            vec![
+                QuorumSetMember::Node(test_node_id(1)),
+                QuorumSetMember::Node(test_node_id(3)),
+                QuorumSetMember::InnerSet(innermost),
+            ],
+        );
+        let qs = QuorumSet::new(
+            2,
+            vec![
+                QuorumSetMember::Node(test_node_id(1)),
+                QuorumSetMember::Node(test_node_id(2)),
+                QuorumSetMember::InnerSet(inner),
+                QuorumSetMember::InnerSet(QuorumSet::new_with_node_ids(
+                    1,
+                    vec![test_node_id(4), test_node_id(6)],
+                )),
+            ],
+        );
+
+        let expected: HashSet<NodeID> = (1..=6).map(test_node_id).collect();
+        assert_eq!(qs.nodes(), expected);
+    }
+
+    #[test]
+    // remove_node should remove a node at every level and clamp thresholds.
+    fn test_remove_node() {
+        // From the top level.
+        let mut qs = QuorumSet::new_with_node_ids(
+            3,
+            vec![test_node_id(1), test_node_id(2), test_node_id(3)],
+        );
+        assert!(qs.remove_node(&test_node_id(2)));
+        assert_eq!(
+            qs,
+            QuorumSet::new_with_node_ids(2, vec![test_node_id(1), test_node_id(3)])
+        );
+        assert!(!qs.remove_node(&test_node_id(2)));
+
+        // The threshold is only lowered if it exceeds the new number of members.
+        let mut qs = QuorumSet::new_with_node_ids(
+            1,
+            vec![test_node_id(1), test_node_id(2), test_node_id(3)],
+        );
+        assert!(qs.remove_node(&test_node_id(3)));
+        assert_eq!(qs.threshold, 1);
+
+        // From an inner set, and from the top level at the same time.
+        let mut qs = QuorumSet::new(
+            2,
+            vec![
+                QuorumSetMember::Node(test_node_id(1)),
+                QuorumSetMember::InnerSet(QuorumSet::new_with_node_ids(
+                    2,
+                    vec![test_node_id(1), test_node_id(2)],
+                )),
+                QuorumSetMember::InnerSet(QuorumSet::new_with_node_ids(
+                    1,
+                    vec![test_node_id(3), test_node_id(4)],
+                )),
+            ],
+        );
+        assert!(qs.remove_node(&test_node_id(1)));
+        assert_eq!(
+            qs,
+            QuorumSet::new_with_inner_sets(
+                2,
+                vec![
+                    QuorumSet::new_with_node_ids(1, vec![test_node_id(2)]),
+                    QuorumSet::new_with_node_ids(1, vec![test_node_id(3), test_node_id(4)]),
+                ]
+            )
+        );
+
+        // An inner set left empty is removed, and the threshold clamped.
+        assert!(qs.remove_node(&test_node_id(2)));
+        assert_eq!(
+            qs,
+            QuorumSet::new_with_inner_sets(
+                1,
+                vec![QuorumSet::new_with_node_ids(
+                    1,
+                    vec![test_node_id(3), test_node_id(4)]
+                )]
+            )
+        );
+        assert!(!qs.remove_node(&test_node_id(5)));
+    }
+
+    #[test]
+    // Inner sets nested deeper than MAX_DEPTH are ignored by findQuorum and findBlockingSet.
+    fn test_max_depth() {
+        // Node 2, wrapped in `depth` inner sets of threshold 1.
+        let nested = |depth: usize| -> QuorumSet {
+            let mut qs = QuorumSet::new_with_node_ids(1, vec![test_node_id(2)]);
+            for _ in 0..depth {
+                qs = QuorumSet::new_with_inner_sets(1, vec![qs]);
+            }
+            qs
+        };
+        let local_node_id = test_node_id(1);
+        let pred = || FuncPredicate::<u32> {
+            test_fn: &|_msg| true,
+        };
+
+        let mut msgs = HashMap::<NodeID, Msg<u32>>::default();
+        msgs.insert(
+            test_node_id(2),
+            Msg::new(
+                test_node_id(2),
+                QuorumSet::new_with_node_ids(1, vec![local_node_id.clone()]),
+                1,
+                Topic::Nominate(NominatePayload {
+                    X: BTreeSet::default(),
+                    Y: BTreeSet::default(),
+                }),
+            ),
+        );
+
+        // Node 2 is found at exactly MAX_DEPTH.
+        let qs = nested(QuorumSet::<NodeID>::MAX_DEPTH);
+        let (node_ids, _) = qs.findQuorum(&local_node_id, &msgs, pred());
+        assert_eq!(
+            node_ids,
+            HashSet::from_iter(vec![local_node_id.clone(), test_node_id(2)])
+        );
+        let (node_ids, _) = qs.findBlockingSet(&msgs, pred());
+        assert_eq!(node_ids, HashSet::from_iter(vec![test_node_id(2)]));
+        assert_eq!(
+            qs.findAllBlockingSets(&msgs, pred()),
+            vec![BTreeSet::from_iter(vec![test_node_id(2)])]
+        );
+        let node_2 = HashSet::from_iter(vec![test_node_id(2)]);
+        assert!(qs.is_quorum(&node_2));
+        assert!(qs.is_blocking(&node_2));
+        assert_eq!(qs.validate(), Ok(()));
+
+        // One level deeper, the innermost set is treated as unsatisfiable.
+        let qs = nested(QuorumSet::<NodeID>::MAX_DEPTH + 1);
+        let (node_ids, _) = qs.findQuorum(&local_node_id, &msgs, pred());
+        assert!(node_ids.is_empty());
+        let (node_ids, _) = qs.findBlockingSet(&msgs, pred());
+        assert!(node_ids.is_empty());
+        assert!(qs.findAllBlockingSets(&msgs, pred()).is_empty());
+        assert!(!qs.is_quorum(&node_2));
+        assert!(!qs.is_blocking(&node_2));
+        assert_eq!(qs.validate(), Err(QuorumSetValidationError::TooDeep));
+
+        // Pathologically deep quorum sets still terminate quickly.
+        let qs = nested(1000);
+        let (node_ids, _) = qs.findQuorum(&local_node_id, &msgs, pred());
+        assert!(node_ids.is_empty());
+    }
+
+    #[test]
+    // Quorum sets, including nested inner sets, should round-trip through JSON.
+    fn test_json_round_trip() {
+        let qs = test_quorum_set_from_str("([3],1,2,3,4,([2],5,6,([1],7,8)))").unwrap();
+        let json = serde_json::to_string(&qs).expect("QuorumSet should yield JSON");
+        let qs2: QuorumSet<ResponderId> = serde_json::from_str(&json).unwrap();
+        assert_eq!(qs, qs2);
+        assert_eq!(qs2.nodes().len(), 8);
+
+        // Members are tagged with their type.
+        let member: QuorumSetMember<ResponderId> = serde_json::from_str(
+            r#"{"type":"InnerSet","args":{"threshold":1,"members":[{"type":"Node","args":"node7.test.com:8443"}]}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            member,
+            QuorumSetMember::InnerSet(QuorumSet::new_with_node_ids(1, vec![test_responder_id(7)]))
+        );
+    }
+
+    #[test]
+    // random_quorum_set should only generate valid quorum sets.
+    fn test_random_quorum_set() {
+        fn depth(qs: &QuorumSet) -> usize {
+            qs.members
+                .iter()
+                .map(|member| match member {
+                    QuorumSetMember::Node(_) => 0,
+                    QuorumSetMember::InnerSet(inner) => 1 + depth(inner),
+                })
+                .max()
+                .unwrap_or(0)
+        }
+
+        let mut rng: StdRng = SeedableRng::from_seed([7u8; 32]);
+        for i in 0..100 {
+            let num_nodes = 1 + i % 20;
+            let max_depth = i % (QuorumSet::<NodeID>::MAX_DEPTH + 1);
+            let qs = random_quorum_set(num_nodes, max_depth, &mut rng);
+            assert_eq!(qs.validate(), Ok(()), "{:?}", qs);
+            assert_eq!(qs.nodes().len(), num_nodes);
+            assert!(depth(&qs) <= max_depth);
+        }
+    }
+
+    #[test]
+    // Quorum sets should parse from quoted responder ids.
+    fn test_from_str_quoted() {
+        let qs = QuorumSet::<ResponderId>::from_str(
+            r#"([1], "a.com:443", ([2],"b.com:443","c.com:443"))"#,
+        )
+        .unwrap();
+        let responder_id = |s: &str| ResponderId::from_str(s).unwrap();
+        let expected = QuorumSet::new(
+            1,
+            vec![
+                QuorumSetMember::Node(responder_id("a.com:443")),
+                QuorumSetMember::InnerSet(QuorumSet::new_with_node_ids(
+                    2,
+                    vec![responder_id("b.com:443"), responder_id("c.com:443")],
+                )),
+            ],
+        );
+        assert_eq!(qs, expected);
+    }
+
+    #[test]
+    // from_config_str and to_config_str should map arbitrary node ids to and from integers.
+    fn test_config_str_round_trip() {
+        let responder_ids: Vec<ResponderId> = vec![
+            "validator.example.com:443",
+            "peer1.mobilecoin.com:8443",
+            "10.0.0.1:3223",
+            "localhost:8081",
+        ]
+        .into_iter()
+        .map(|s| ResponderId::from_str(s).unwrap())
+        .collect();
+        let node_id = |n: u32| responder_ids[n as usize].clone();
+        let node_name = |id: &ResponderId| {
+            responder_ids
+                .iter()
+                .position(|r| r == id)
+                .expect("unknown responder id")
+                .to_string()
+        };
+
+        let src = "([2],0,1,([1],2,3))";
+        let qs = QuorumSet::from_config_str(src, &node_id).unwrap();
+        let expected = QuorumSet::new(
+            2,
+            vec![
+                QuorumSetMember::Node(responder_ids[0].clone()),
+                QuorumSetMember::Node(responder_ids[1].clone()),
+                QuorumSetMember::InnerSet(QuorumSet::new_with_node_ids(
+                    1,
+                    vec![responder_ids[2].clone(), responder_ids[3].clone()],
+                )),
+            ],
+        );
+        assert_eq!(qs, expected);
+        assert_eq!(qs.to_config_str(&node_name), src);
+
+        // Node ids too can be written directly, e.g. by their responder id.
+        assert_eq!(
+            qs.to_config_str(&|id| format!("\"{}\"", id)),
+            r#"([2],"validator.example.com:443","peer1.mobilecoin.com:8443",([1],"10.0.0.1:3223","localhost:8081"))"#
+        );
+
+        // Works for NodeIDs, and for empty sets.
+        let qs =
+            QuorumSet::from_config_str("([3],1,2,3,4,([2],5,6,([1],7,8)),([0]))", &test_node_id)
+                .unwrap();
+        let names: HashMap<NodeID, u32> = (1..=8).map(|n| (test_node_id(n), n)).collect();
+        assert_eq!(
+            qs.to_config_str(&|id| names[id].to_string()),
+            "([3],1,2,3,4,([2],5,6,([1],7,8)),([0]))"
+        );
+
+        // Quoted node ids cannot be mapped, and malformed input is rejected.
+        assert!(QuorumSet::from_config_str(r#"([1],"a.com:443")"#, &node_id).is_err());
+        assert!(QuorumSet::from_config_str("([2],0,1", &node_id).is_err());
+    }
+
+    #[test]
+    // Quorum sets mixing integer and quoted node ids should be rejected.
+    fn test_from_str_mixed() {
+        let err =
+            QuorumSet::<ResponderId>::from_str(r#"([2],1,"node2.test.com:8443")"#).unwrap_err();
+        assert_eq!(err, QuorumSetParseError::MixedNodeIds { offset: 7 });
+        assert!(err
+            .to_string()
+            .starts_with("integer and quoted node ids are mixed"));
+
+        let err = QuorumSet::<ResponderId>::from_str(r#"([2],"node1.test.com:8443",([1],2))"#)
+            .unwrap_err();
+        assert_eq!(err, QuorumSetParseError::MixedNodeIds { offset: 32 });
+
+        // Malformed input is also rejected.
+        assert!(QuorumSet::<ResponderId>::from_str("([2],1,2").is_err());
+        assert!(QuorumSet::<ResponderId>::from_str("(1,2)").is_err());
+        assert!(QuorumSet::<ResponderId>::from_str(r#"([1],"no-port")"#).is_err());
+    }
+
+    #[test]
+    // Integer node ids should be mapped by the given function.
+    fn test_from_str_with_integer_ids() {
+        let integer_id =
+            |n: u32| ResponderId::from_str(&format!("peer{}.example.com:443", n)).unwrap();
+        let qs = QuorumSet::from_str_with_integer_ids("([1],1,([1],2))", &integer_id).unwrap();
+        let expected = QuorumSet::new(
+            1,
+            vec![
+                QuorumSetMember::Node(integer_id(1)),
+                QuorumSetMember::InnerSet(QuorumSet::new_with_node_ids(1, vec![integer_id(2)])),
+            ],
+        );
+        assert_eq!(qs, expected);
+
+        // Quoted node ids are parsed as responder ids.
+        let qs = QuorumSet::from_str_with_integer_ids(r#"([1],"a.com:443")"#, &integer_id).unwrap();
+        assert_eq!(
+            qs,
+            QuorumSet::new_with_node_ids(1, vec![ResponderId::from_str("a.com:443").unwrap()])
+        );
+    }
+
+    #[test]
+    // Parse errors should say what went wrong and where.
+    fn test_parse_error_messages() {
+        let err = |src: &str| test_quorum_set_from_str(src).unwrap_err().to_string();
+
+        // A threshold larger than the set.
+        assert_eq!(
+            err("([2],1,([5],2,3))"),
+            "threshold 5 exceeds 2 members at offset 8"
+        );
+        assert_eq!(err("([x],1)"), "invalid threshold [x] at offset 1");
+        assert_eq!(err("([1,1)"), "unterminated threshold at offset 1");
+
+        // Stray and missing brackets.
+        assert_eq!(
+            err("([2],1,2))"),
+            "expected end of input, found ')' at offset 9"
+        );
+        assert_eq!(err("([2],1,2]"), "unexpected character ']' at offset 8");
+        assert_eq!(
+            err("([2],1,2"),
+            "expected ',' or ')', found end of input at offset 8"
+        );
+        assert_eq!(
+            err("[2],1,2)"),
+            "expected '(', found threshold [2] at offset 0"
+        );
+
+        // Bad members.
+        assert_eq!(
+            err("([1],,1)"),
+            "expected a quorum set member, found ',' at offset 5"
+        );
+        let no_port = QuorumSet::<ResponderId>::from_str(r#"([1],"no-port")"#)
+            .unwrap_err()
+            .to_string();
+        assert!(no_port.starts_with(r#"invalid responder id "no-port""#));
+        assert!(no_port.ends_with("at offset 5"));
+
+        let err = QuorumSet::from_config_str(r#"([1],"a.com:443")"#, &test_node_id).unwrap_err();
+        assert_eq!(err.offset(), 5);
+        assert_eq!(
+            err.to_string(),
+            r#"expected an integer node id, found node id "a.com:443" at offset 5"#
+        );
+    }
+
+    #[test]
+    // iter_ordered should yield members in the same order regardless of insertion order.
+    fn test_iter_ordered() {
+        let inner_set_1 = QuorumSet::new_with_node_ids(1, vec![test_node_id(4), test_node_id(3)]);
+        let inner_set_1_shuffled =
+            QuorumSet::new_with_node_ids(1, vec![test_node_id(3), test_node_id(4)]);
+        let inner_set_2 = QuorumSet::new_with_node_ids(2, vec![test_node_id(6), test_node_id(5)]);
+
+        let qs_1 = QuorumSet::new(
+            2,
+            vec![
+                QuorumSetMember::InnerSet(inner_set_2.clone()),
+                QuorumSetMember::Node(test_node_id(2)),
+                QuorumSetMember::InnerSet(inner_set_1),
+                QuorumSetMember::Node(test_node_id(1)),
+            ],
+        );
+        let qs_2 = QuorumSet::new(
+            2,
+            vec![
+                QuorumSetMember::Node(test_node_id(1)),
+                QuorumSetMember::InnerSet(inner_set_1_shuffled),
+                QuorumSetMember::Node(test_node_id(2)),
+                QuorumSetMember::InnerSet(inner_set_2),
+            ],
+        );
+
+        let members_1: Vec<_> = qs_1.iter_ordered().collect();
+        let members_2: Vec<_> = qs_2.iter_ordered().collect();
+        assert_eq!(members_1, members_2);
+
+        // Nodes come before inner sets.
+        assert!(matches!(members_1[0], QuorumSetMember::Node(_)));
+        assert!(matches!(members_1[1], QuorumSetMember::Node(_)));
+        assert!(matches!(members_1[2], QuorumSetMember::InnerSet(_)));
+        assert!(matches!(members_1[3], QuorumSetMember::InnerSet(_)));
+
+        // Inner sets are sorted recursively.
+        if let QuorumSetMember::InnerSet(qs) = &members_1[2] {
+            let inner_members: Vec<_> = qs.iter_ordered().collect();
+            assert_eq!(inner_members, qs.members);
+        }
+    }
+
+    #[test]
+    // to_dot should draw each set labeled with its threshold, with an edge to each member.
+    fn test_to_dot() {
+        let inner_set =
+            QuorumSet::new_with_node_ids(1, vec![test_responder_id(3), test_responder_id(4)]);
+        let qs = QuorumSet::new(
+            2,
+            vec![
+                QuorumSetMember::Node(test_responder_id(1)),
+                QuorumSetMember::Node(test_responder_id(2)),
+                QuorumSetMember::InnerSet(inner_set),
+            ],
+        );
+
+        let dot = qs.to_dot();
+        assert!(dot.starts_with("digraph quorum_set {\n"));
+        assert!(dot.ends_with("}\n"));
+        assert!(dot.contains("\"qs:0\" [shape=box, label=\"2 of 3\"];"));
+        assert!(dot.contains("\"qs:1\" [shape=box, label=\"1 of 2\"];"));
+        assert!(dot.contains("\"qs:0\" -> \"qs:1\";"));
+        for i in 1..=4 {
+            let node = test_responder_id(i);
+            assert!(dot.contains(&format!("\"node:{}\" [label=\"{}\"];", node, node)));
+        }
+        assert!(dot.contains(&format!("\"qs:0\" -> \"node:{}\";", test_responder_id(1))));
+        assert!(dot.contains(&format!("\"qs:1\" -> \"node:{}\";", test_responder_id(4))));
+
+        // Every line between the braces is a single statement with balanced quotes.
+        let lines: Vec<&str> = dot.lines().collect();
+        for line in &lines[1..lines.len() - 1] {
+            assert!(line.ends_with(';'), "unterminated statement: {}", line);
+            assert_eq!(
+                line.matches('"').count() % 2,
+                0,
+                "unbalanced quotes: {}",
+                line
+            );
+        }
+    }
 }
@@ -1,7 +1,11 @@
 // Copyright (c) 2018-2021 The MobileCoin Foundation
 
 //! This crate provides a logging framework for recording and replaying SCP messages.
-use crate::{slot::SlotMetrics, Msg, QuorumSet, ScpNode, SlotIndex, Value};
+use crate::{
+    clock::{Clock, MockClock, SystemClock},
+    slot::SlotMetrics,
+    Msg, QuorumSet, ScpNode, SlotIndex, Value,
+};
 use mc_common::{
     logger::{log, Logger},
     NodeID,
@@ -13,12 +17,25 @@ use std::{
     io::Write,
     marker::PhantomData,
     path::PathBuf,
-    time::{Instant, SystemTime},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
 };
 
 /// Maximum number of slot state files to keep.
 const MAX_SLOT_STATE_FILES: usize = 10;
 
+/// Header that precedes a compressed log entry.
+///
+/// Each log file holds a single [StoredMsg]. An uncompressed entry is the plain
+/// `mc_util_serial` encoding of the message. A compressed entry is these four bytes
+/// followed by a zstd frame containing that same encoding. The header can't be
+/// confused with an uncompressed entry, which always begins with a map marker.
+pub const COMPRESSED_ENTRY_HEADER: &[u8; 4] = b"SCPZ";
+
+/// zstd compression level used for log entries.
+#[cfg(feature = "compression")]
+const COMPRESSION_LEVEL: i32 = 3;
+
 /// A node specifically for logging SCP messages.
 pub struct LoggingScpNode<V: Value, N: ScpNode<V>> {
     /// Output path for current slot log files.
@@ -42,6 +59,12 @@ pub struct LoggingScpNode<V: Value, N: ScpNode<V>> {
     /// List of slot state filenames that make it easy to maintain `MAX_SLOT_STATE_FILES` on disk.
     slot_state_filenames: Vec<PathBuf>,
 
+    /// Whether log entries are compressed before being written.
+    compress: bool,
+
+    /// Source of the current time, used to timestamp log entries.
+    clock: Arc<dyn Clock>,
+
     /// Logger
     logger: Logger,
 
@@ -63,7 +86,7 @@ pub enum LoggedMsg<V: Value> {
     /// A Nominate message.
     Nominate(SlotIndex, BTreeSet<V>),
 
-    /// A timeout event.
+    /// A call to `process_timeouts` that fired a timeout, and the messages it emitted.
     ProcessTimeouts(Vec<Msg<V>>),
 
     /// A message container for an arbitrary string.
@@ -137,11 +160,26 @@ impl<V: Value, N: ScpNode<V>> LoggingScpNode<V, N> {
             msg_count: 0,
             slot_start_time: Instant::now(),
             slot_state_filenames: Vec::new(),
+            compress: false,
+            clock: Arc::new(SystemClock),
             logger,
             _v: Default::default(),
         })
     }
 
+    /// Enable or disable compression of log entries written from now on.
+    #[cfg(feature = "compression")]
+    pub fn set_compression(&mut self, compress: bool) {
+        self.compress = compress;
+    }
+
+    /// Replace the clock used to timestamp log entries. To capture a run that [replay] can
+    /// reproduce, this should be the clock used by the wrapped node.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.slot_start_time = clock.now();
+        self.clock = clock;
+    }
+
     fn write(&mut self, msg: LoggedMsg<V>) -> Result<(), String> {
         let msg_slot_index = match &msg {
             LoggedMsg::IncomingMsg(msg) | LoggedMsg::OutgoingMsg(msg) => msg.slot_index,
@@ -162,7 +200,7 @@ impl<V: Value, N: ScpNode<V>> LoggingScpNode<V, N> {
 
             self.highest_slot_index = msg_slot_index;
             self.msg_count = 0;
-            self.slot_start_time = Instant::now();
+            self.slot_start_time = self.clock.now();
 
             self.write(LoggedMsg::NodeSettings(
                 self.node.node_id(),
@@ -178,11 +216,10 @@ impl<V: Value, N: ScpNode<V>> LoggingScpNode<V, N> {
 
         // Serialize and write to a log file.
         let data = StoredMsg {
-            msec_since_start: (Instant::now() - self.slot_start_time).as_millis() as u64,
+            msec_since_start: (self.clock.now() - self.slot_start_time).as_millis() as u64,
             msg,
         };
-        let bytes =
-            mc_util_serial::serialize(&data).map_err(|e| format!("failed serialize: {:?}", e))?;
+        let bytes = encode_entry(&data, self.compress)?;
 
         let mut file_path = self.cur_slot_out_path.clone();
         file_path.push(format!("{:08}", self.msg_count));
@@ -285,9 +322,16 @@ impl<V: Value, N: ScpNode<V>> ScpNode<V> for LoggingScpNode<V, N> {
     }
 
     fn process_timeouts(&mut self) -> Vec<Msg<V>> {
+        let before = self.node.get_current_slot_metrics();
         let out_msgs = self.node.process_timeouts();
-
-        if !out_msgs.is_empty() {
+        let after = self.node.get_current_slot_metrics();
+
+        // A timeout may advance the nomination round or ballot counter without emitting a
+        // message, and replaying the log must reproduce that.
+        if !out_msgs.is_empty()
+            || before.cur_nomination_round != after.cur_nomination_round
+            || before.bN != after.bN
+        {
             self.write(LoggedMsg::ProcessTimeouts(out_msgs.clone()))
                 .expect("failed writing");
         }
@@ -303,6 +347,10 @@ impl<V: Value, N: ScpNode<V>> ScpNode<V> for LoggingScpNode<V, N> {
         self.node.get_current_slot_metrics()
     }
 
+    fn is_stuck(&self, since: Duration) -> bool {
+        self.node.is_stuck(since)
+    }
+
     fn get_slot_debug_snapshot(&mut self, slot_index: SlotIndex) -> Option<String> {
         self.node.get_slot_debug_snapshot(slot_index)
     }
@@ -312,6 +360,116 @@ impl<V: Value, N: ScpNode<V>> ScpNode<V> for LoggingScpNode<V, N> {
     }
 }
 
+/// Replays logged entries against `node`, checking that it sends the logged outgoing messages.
+///
+/// `clock` must be the clock used by `node`. Before each entry, it is advanced to the entry's
+/// timestamp, so that timeouts fire at the logged points. Timestamps have millisecond
+/// resolution, so a logged timeout is replayed at the end of its millisecond, after any timer
+/// that expired during it. The clock never moves backwards.
+///
+/// `NodeSettings` entries are skipped; `node` should be created with the logged settings.
+pub fn replay<V: Value, N: ScpNode<V>>(
+    node: &mut N,
+    clock: &MockClock,
+    entries: impl IntoIterator<Item = StoredMsg<V>>,
+) -> Result<(), String> {
+    let mut elapsed = Duration::from_millis(0);
+    let mut advance_to = |target: Duration| {
+        if target > elapsed {
+            clock.advance(target - elapsed);
+            elapsed = target;
+        }
+    };
+
+    let mut sent_msgs: VecDeque<Msg<V>> = VecDeque::new();
+    let expect_sent = |sent_msgs: &mut VecDeque<Msg<V>>, msg: &Msg<V>| match sent_msgs.pop_front() {
+        Some(sent) if sent == *msg => Ok(()),
+        Some(sent) => Err(format!("Replay sent {}, but the log has {}", sent, msg)),
+        None => Err(format!("Replay sent nothing, but the log has {}", msg)),
+    };
+
+    for entry in entries {
+        let timestamp = Duration::from_millis(entry.msec_since_start);
+        match entry.msg {
+            LoggedMsg::NodeSettings(..) | LoggedMsg::Marker(_) => {}
+
+            LoggedMsg::IncomingMsg(msg) => {
+                advance_to(timestamp);
+                sent_msgs.extend(node.handle_message(&msg)?);
+            }
+
+            LoggedMsg::Nominate(_slot_index, values) => {
+                advance_to(timestamp);
+                sent_msgs.extend(node.propose_values(values)?);
+            }
+
+            LoggedMsg::OutgoingMsg(msg) => expect_sent(&mut sent_msgs, &msg)?,
+
+            LoggedMsg::ProcessTimeouts(msgs) => {
+                advance_to(timestamp + Duration::from_millis(1) - Duration::from_nanos(1));
+                sent_msgs.extend(node.process_timeouts());
+                for msg in &msgs {
+                    expect_sent(&mut sent_msgs, msg)?;
+                }
+            }
+        }
+    }
+
+    match sent_msgs.pop_front() {
+        None => Ok(()),
+        Some(sent) => Err(format!("Replay sent {}, which is not in the log", sent)),
+    }
+}
+
+/// Serialize a log entry, compressing it if `compress` is set.
+pub fn encode_entry<V: Value>(entry: &StoredMsg<V>, compress: bool) -> Result<Vec<u8>, String> {
+    let bytes =
+        mc_util_serial::serialize(entry).map_err(|e| format!("failed serialize: {:?}", e))?;
+    if compress {
+        compress_entry(&bytes)
+    } else {
+        Ok(bytes)
+    }
+}
+
+/// Deserialize a log entry written by `encode_entry`, whether compressed or not.
+pub fn decode_entry<V: serde::de::DeserializeOwned + Value>(
+    bytes: &[u8],
+) -> Result<StoredMsg<V>, String> {
+    let decompressed;
+    let bytes = if bytes.starts_with(COMPRESSED_ENTRY_HEADER) {
+        decompressed = decompress_entry(&bytes[COMPRESSED_ENTRY_HEADER.len()..])?;
+        &decompressed[..]
+    } else {
+        bytes
+    };
+    mc_util_serial::deserialize(bytes).map_err(|e| format!("failed deserialize: {:?}", e))
+}
+
+#[cfg(feature = "compression")]
+fn compress_entry(bytes: &[u8]) -> Result<Vec<u8>, String> {
+    let compressed = zstd::encode_all(bytes, COMPRESSION_LEVEL)
+        .map_err(|e| format!("failed compressing: {:?}", e))?;
+    let mut out = COMPRESSED_ENTRY_HEADER.to_vec();
+    out.extend(compressed);
+    Ok(out)
+}
+
+#[cfg(not(feature = "compression"))]
+fn compress_entry(_bytes: &[u8]) -> Result<Vec<u8>, String> {
+    Err("compression requires the `compression` feature".to_string())
+}
+
+#[cfg(feature = "compression")]
+fn decompress_entry(compressed: &[u8]) -> Result<Vec<u8>, String> {
+    zstd::decode_all(compressed).map_err(|e| format!("failed decompressing: {:?}", e))
+}
+
+#[cfg(not(feature = "compression"))]
+fn decompress_entry(_compressed: &[u8]) -> Result<Vec<u8>, String> {
+    Err("decompression requires the `compression` feature".to_string())
+}
+
 /// An SCP log reader, to read a series of SCP messages.
 pub struct ScpLogReader<V: Value> {
     /// The log files to read.
@@ -349,17 +507,31 @@ impl<V: serde::de::DeserializeOwned + Value> Iterator for ScpLogReader<V> {
     fn next(&mut self) -> Option<Self::Item> {
         let path = self.files.pop_front()?;
         let bytes = read(&path).unwrap_or_else(|_| panic!("failed reading {:?}", path));
-        let data: Self::Item = mc_util_serial::deserialize(&bytes)
-            .unwrap_or_else(|_| panic!("failed deserializing {:?}", path));
+        let data: Self::Item =
+            decode_entry(&bytes).unwrap_or_else(|e| panic!("failed decoding {:?}: {}", path, e));
         Some(data)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{node::MockScpNode, scp_log::LoggingScpNode};
-    use mc_common::logger::{test_with_logger, Logger};
-    use std::fs::create_dir_all;
+    use crate::{
+        clock::MockClock,
+        node::MockScpNode,
+        scp_log::{encode_entry, replay, LoggedMsg, LoggingScpNode, ScpLogReader, StoredMsg},
+        test_utils::{get_node, test_node_id},
+        Msg, QuorumSet, ScpNode,
+    };
+    use maplit::btreeset;
+    use mc_common::{
+        logger::{test_with_logger, Logger},
+        NodeID,
+    };
+    use std::{
+        fs::{create_dir_all, write},
+        sync::Arc,
+        time::Duration,
+    };
     use tempdir::TempDir;
 
     #[test_with_logger]
@@ -368,7 +540,7 @@ mod tests {
         let dir = TempDir::new("test").unwrap();
         let out_path = dir.path().join("debug_output");
 
-        let node = MockScpNode::<&'static str>::new();
+        let node = MockScpNode::<&'static str, NodeID>::new();
         let _logging_scp_node = LoggingScpNode::new(node, out_path.clone(), logger).unwrap();
 
         // test/debug_output/cur-slot directory should exist.
@@ -395,7 +567,145 @@ mod tests {
 
         assert!(out_path.exists());
 
-        let node = MockScpNode::<&'static str>::new();
+        let node = MockScpNode::<&'static str, NodeID>::new();
         let _logging_scp_node = LoggingScpNode::new(node, out_path.clone(), logger).unwrap();
     }
+
+    #[test]
+    // Uncompressed entries should round-trip through the log reader.
+    fn test_uncompressed_round_trip() {
+        let dir = TempDir::new("test").unwrap();
+        let entry = StoredMsg {
+            msec_since_start: 12,
+            msg: LoggedMsg::Nominate(3, btreeset! {"a".to_string(), "b".to_string()}),
+        };
+        let bytes = encode_entry(&entry, false).unwrap();
+        write(dir.path().join("00000000"), &bytes).unwrap();
+
+        let read: Vec<StoredMsg<String>> = ScpLogReader::new(&dir.path().to_path_buf())
+            .unwrap()
+            .collect();
+        assert_eq!(read.len(), 1);
+        assert_eq!(read[0].msec_since_start, 12);
+        assert_eq!(format!("{:?}", read[0].msg), format!("{:?}", entry.msg));
+    }
+
+    #[test_with_logger]
+    // A captured run that includes a nomination round timeout should replay to the same state.
+    fn test_replay_with_timeouts(logger: Logger) {
+        let dir = TempDir::new("test").unwrap();
+        let out_path = dir.path().join("debug_output");
+        let clock = Arc::new(MockClock::default());
+        let new_node = |node_id: u32, peer_id: u32, clock: Arc<MockClock>| {
+            let mut node = get_node(
+                1,
+                test_node_id(node_id),
+                QuorumSet::new_with_node_ids(1, vec![test_node_id(peer_id)]),
+                logger.clone(),
+            );
+            node.set_clock(clock);
+            node
+        };
+
+        let mut node1 = LoggingScpNode::new(
+            new_node(1, 2, clock.clone()),
+            out_path.clone(),
+            logger.clone(),
+        )
+        .unwrap();
+        node1.set_clock(clock.clone());
+        let mut node2 = new_node(2, 1, clock.clone());
+
+        // Node 1 is not a nomination leader for the first round, so it emits nothing until the
+        // round times out. Node 2 only adopts node 1's values once its own round times out.
+        assert_eq!(node1.propose_values(btreeset! {1000}), Ok(None));
+        let mut to_node1: Vec<Msg<u32>> = Vec::new();
+        let mut to_node2: Vec<Msg<u32>> = Vec::new();
+        for _ in 0..100 {
+            if to_node1.is_empty() && to_node2.is_empty() {
+                if node1.get_externalized_values(1).is_some() {
+                    break;
+                }
+                clock.advance(Duration::from_millis(1500));
+                to_node2.extend(node1.process_timeouts());
+                to_node1.extend(node2.process_timeouts());
+            }
+            clock.advance(Duration::from_millis(3));
+            let from_node1 = node1
+                .handle_messages(std::mem::take(&mut to_node1))
+                .unwrap();
+            let from_node2 = node2
+                .handle_messages(std::mem::take(&mut to_node2))
+                .unwrap();
+            to_node2 = from_node1;
+            to_node1 = from_node2;
+        }
+        assert_eq!(node1.get_externalized_values(1), Some(vec![1000]));
+
+        // Replay the log against a fresh node.
+        let mut entries: Vec<StoredMsg<u32>> = ScpLogReader::new(&out_path.join("cur-slot"))
+            .unwrap()
+            .collect();
+        assert!(entries
+            .iter()
+            .any(|entry| matches!(entry.msg, LoggedMsg::ProcessTimeouts(_))));
+        let (node_id, quorum_set, slot_index) = match entries.remove(0).msg {
+            LoggedMsg::NodeSettings(node_id, quorum_set, slot_index) => {
+                (node_id, quorum_set, slot_index)
+            }
+            _ => panic!("failed getting NodeSettings entry"),
+        };
+        let replay_clock = Arc::new(MockClock::default());
+        let mut replayed = get_node(slot_index, node_id, quorum_set, logger);
+        replayed.set_clock(replay_clock.clone());
+
+        assert_eq!(replay(&mut replayed, &replay_clock, entries), Ok(()));
+        assert_eq!(replayed.current_slot_index(), node1.current_slot_index());
+        assert_eq!(replayed.get_externalized_values(1), Some(vec![1000]));
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    // Compressed and uncompressed entries should be readable side by side.
+    fn test_compressed_round_trip() {
+        use crate::scp_log::{decode_entry, COMPRESSED_ENTRY_HEADER};
+
+        let dir = TempDir::new("test").unwrap();
+        let entries = vec![
+            StoredMsg {
+                msec_since_start: 0,
+                msg: LoggedMsg::<String>::Marker("start".repeat(100)),
+            },
+            StoredMsg {
+                msec_since_start: 5,
+                msg: LoggedMsg::Nominate(3, btreeset! {"a".to_string(), "b".to_string()}),
+            },
+        ];
+
+        let compressed = encode_entry(&entries[0], true).unwrap();
+        assert!(compressed.starts_with(COMPRESSED_ENTRY_HEADER));
+        assert!(compressed.len() < encode_entry(&entries[0], false).unwrap().len());
+        write(dir.path().join("00000000"), &compressed).unwrap();
+        write(
+            dir.path().join("00000001"),
+            &encode_entry(&entries[1], false).unwrap(),
+        )
+        .unwrap();
+
+        let read: Vec<StoredMsg<String>> = ScpLogReader::new(&dir.path().to_path_buf())
+            .unwrap()
+            .collect();
+        assert_eq!(read.len(), entries.len());
+        for (read, entry) in read.iter().zip(entries.iter()) {
+            assert_eq!(read.msec_since_start, entry.msec_since_start);
+            assert_eq!(format!("{:?}", read.msg), format!("{:?}", entry.msg));
+        }
+
+        // A compressed entry decodes directly too.
+        let decoded: StoredMsg<String> = decode_entry(&compressed).unwrap();
+        assert_eq!(
+            format!("{:?}", decoded.msg),
+            format!("{:?}", entries[0].msg)
+        );
+    }
 }
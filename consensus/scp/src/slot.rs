@@ -4,13 +4,17 @@
 //!
 //! The transactions validated in this slot determine the values to include in the next block appended to the ledger.
 use crate::{
-    core_types::{Ballot, CombineFn, SlotIndex, ValidityFn, Value},
+    clock::{Clock, SystemClock},
+    core_types::{
+        Ballot, CombineFn, GenericNodeId, NodePriorityKey, SlotIndex, TimeoutFn, ValidityFn, Value,
+    },
     msg::*,
     predicates::{
         BallotRangePredicate, BallotSetPredicate, FuncPredicate, Predicate, ValueSetPredicate,
     },
     quorum_set::QuorumSet,
     slot_state::SlotState,
+    transcript::Transcript,
     utils,
 };
 use core::cmp;
@@ -21,7 +25,7 @@ use mc_common::{
 };
 #[cfg(test)]
 use mockall::*;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::{
     collections::{BTreeSet, HashMap, HashSet},
     fmt::Display,
@@ -47,47 +51,70 @@ pub enum Phase {
 
 /// A Single slot of the SCP protocol.
 #[cfg_attr(test, automock)]
-pub trait ScpSlot<V: Value>: Send {
+pub trait ScpSlot<V: Value, ID: GenericNodeId + Send + 'static = NodeID>: Send {
     /// Get metrics about the slot.
     fn get_metrics(&self) -> SlotMetrics;
 
     /// The slot index.
     fn get_index(&self) -> SlotIndex;
 
+    /// The current ballot.
+    fn get_ballot(&self) -> Ballot<V>;
+
     /// Last message sent by this node, if any.
-    fn get_last_message_sent(&self) -> Option<Msg<V>>;
+    fn get_last_message_sent(&self) -> Option<Msg<V, ID>>;
 
     /// Processes any timeouts that may have occurred.
-    fn process_timeouts(&mut self) -> Vec<Msg<V>>;
+    fn process_timeouts(&mut self) -> Vec<Msg<V, ID>>;
 
     /// Propose values for this node to nominate.
-    fn propose_values(&mut self, values: &BTreeSet<V>) -> Result<Option<Msg<V>>, String>;
+    fn propose_values(&mut self, values: &BTreeSet<V>) -> Result<Option<Msg<V, ID>>, String>;
+
+    /// Propose values for this node to nominate that already passed the validity function, so
+    /// they are not validated again.
+    fn propose_valid_values(&mut self, values: &BTreeSet<V>) -> Result<Option<Msg<V, ID>>, String>;
 
     /// Handles an incoming message from a peer.
-    fn handle_message(&mut self, msg: &Msg<V>) -> Result<Option<Msg<V>>, String>;
+    fn handle_message(&mut self, msg: &Msg<V, ID>) -> Result<Option<Msg<V, ID>>, String>;
 
     /// Handle incoming messages from peers. Messages for other slots are ignored.
-    fn handle_messages(&mut self, msgs: &[Msg<V>]) -> Result<Option<Msg<V>>, String>;
+    fn handle_messages(&mut self, msgs: &[Msg<V, ID>]) -> Result<Option<Msg<V, ID>>, String>;
 
     /// Additional debug info, e.g. a JSON representation of the Slot's state.
     fn get_debug_snapshot(&self) -> String;
+
+    /// The slot's protocol state, from which it can be rebuilt with `Slot::from_state`.
+    fn get_state(&self) -> SlotState<V, ID>;
+
+    /// Replace the clock used for nomination and ballot timers.
+    fn set_clock(&mut self, clock: Arc<dyn Clock>);
+
+    /// Compute ballot timeouts with `timeout_fn` instead of linearly in the ballot counter.
+    fn set_timeout_fn(&mut self, timeout_fn: TimeoutFn);
+
+    /// Progress towards externalizing, in [0.0, 1.0]. Never decreases.
+    fn progress(&self) -> f64;
+
+    /// The nodes from which this slot took `value` when voting to nominate it, including the
+    /// local node for its own proposals. `None` if this slot never voted to nominate `value`.
+    fn value_provenance(&self, value: &V) -> Option<HashSet<ID>>;
 }
 
 /// The SCP slot.
 // Note: The fields representing the state of the slot are marked with pub(crate) so that they
 // could be accessed by `SlotState`.
-pub struct Slot<V: Value, ValidationError: Display> {
+pub struct Slot<V: Value, ValidationError: Display, ID: GenericNodeId = NodeID> {
     /// Current slot number.
     pub(crate) slot_index: SlotIndex,
 
     /// Local node ID.
-    pub(crate) node_id: NodeID,
+    pub(crate) node_id: ID,
 
     /// Local node quorum set.
-    pub(crate) quorum_set: QuorumSet,
+    pub(crate) quorum_set: QuorumSet<ID>,
 
     /// Map of Node ID -> highest message from each node, including the local node.
-    pub(crate) M: HashMap<NodeID, Msg<V>>,
+    pub(crate) M: HashMap<ID, Msg<V, ID>>,
 
     /// Set of values that have been proposed, but not yet voted for.
     pub(crate) W: HashSet<V>,
@@ -125,10 +152,16 @@ pub struct Slot<V: Value, ValidationError: Display> {
     pub(crate) phase: Phase,
 
     /// Last message sent by us.
-    pub(crate) last_sent_msg: Option<Msg<V>>,
+    pub(crate) last_sent_msg: Option<Msg<V, ID>>,
+
+    /// Every message sent by us, oldest first.
+    sent_msgs: Vec<Msg<V, ID>>,
+
+    /// For each value voted nominated, the nodes whose nomination it was taken from.
+    nominated_by: HashMap<V, HashSet<ID>>,
 
     /// Max priority peers - nodes from which we listen to value nominations.
-    pub(crate) max_priority_peers: HashSet<NodeID>,
+    pub(crate) max_priority_peers: HashSet<ID>,
 
     /// Current nomination round number.
     pub(crate) nominate_round: u32,
@@ -145,6 +178,12 @@ pub struct Slot<V: Value, ValidationError: Display> {
     /// Application-specific function for combining multiple values. Must be deterministic.
     combine_fn: CombineFn<V, ValidationError>,
 
+    /// Number of times `validity_fn` has been called.
+    num_validity_fn_calls: usize,
+
+    /// Number of times `combine_fn` has been called.
+    num_combine_fn_calls: usize,
+
     /// List of values that have been checked to be valid for the current slot.
     /// We can cache this and save on validation calls since the ledger doesn't change during a slot.
     pub(crate) valid_values: BTreeSet<V>,
@@ -152,6 +191,18 @@ pub struct Slot<V: Value, ValidationError: Display> {
     /// Logger.
     logger: Logger,
 
+    /// Source of the current time, used for nomination and ballot timers.
+    pub(crate) clock: Arc<dyn Clock>,
+
+    /// The most recent reading of `clock`.
+    last_clock_reading: Instant,
+
+    /// Time as seen by the timers. Only moves forward, even if `clock` steps backwards.
+    monotonic_now: Instant,
+
+    /// Reading of `clock` when the slot entered its current phase.
+    phase_entered_at: Instant,
+
     /// This parameter sets the base interval for round timeout.
     /// SCP suggests this should be one second.
     pub base_round_interval: Duration,
@@ -159,6 +210,33 @@ pub struct Slot<V: Value, ValidationError: Display> {
     /// This parameter sets the base interval for ballot timeout.
     /// SCP suggests this should be one second.
     pub base_ballot_interval: Duration,
+
+    /// Computes the ballot timeout from the ballot counter. If `None`, the timeout grows
+    /// linearly as `base_ballot_interval * counter`.
+    timeout_fn: Option<TimeoutFn>,
+
+    /// Maximum number of distinct ballots referenced by messages in `M`, or `None` for no limit.
+    pub max_tracked_ballots: Option<usize>,
+
+    /// Number of messages evicted from `M` to respect `max_tracked_ballots`.
+    num_ballot_evictions: usize,
+
+    /// Maximum `Msg::estimated_size` of an outgoing message, or `None` for no limit. The slot
+    /// stops voting to nominate new values once they would not fit, and if the combined
+    /// confirmed nominated values would not fit in a ballot, it combines a smaller set instead.
+    /// Accepted nominated values and peers' ballots cannot be trimmed, so a message that is too
+    /// large because of them is still sent, and logged.
+    pub max_msg_size: Option<usize>,
+
+    /// True once an outgoing message exceeding `max_msg_size` has been logged.
+    logged_oversized_msg: bool,
+
+    /// Ballot counter beyond which ballot timeouts no longer increment the counter, or `None` for
+    /// no limit. The counter can still rise to follow peers on higher ballots.
+    pub max_ballot_counter: Option<u32>,
+
+    /// Number of nomination and ballot timeouts that have fired.
+    num_timeouts: usize,
 }
 
 /// Metrics and information about a given slot.
@@ -178,11 +256,35 @@ pub struct SlotMetrics {
     /// Teh current nomination round.
     pub cur_nomination_round: u32,
 
-    /// The highest ballot counter.
+    /// The current ballot counter, or 0 before balloting starts.
     pub bN: u32,
+
+    /// The number of times the validity function has been called.
+    pub num_validity_fn_calls: usize,
+
+    /// The number of times the combine function has been called.
+    pub num_combine_fn_calls: usize,
+
+    /// The number of distinct ballots referenced by tracked messages.
+    pub num_tracked_ballots: usize,
+
+    /// The number of messages evicted to respect `max_tracked_ballots`.
+    pub num_ballot_evictions: usize,
+
+    /// The number of nomination and ballot timeouts that have fired.
+    pub num_timeouts: usize,
+
+    /// True if the ballot counter has reached `max_ballot_counter`, so that ballot timeouts no
+    /// longer increment it.
+    pub counter_capped: bool,
 }
 
-impl<V: Value, ValidationError: Display> ScpSlot<V> for Slot<V, ValidationError> {
+impl<
+        V: Value,
+        ValidationError: Display,
+        ID: GenericNodeId + NodePriorityKey + Serialize + DeserializeOwned + Send + 'static,
+    > ScpSlot<V, ID> for Slot<V, ValidationError, ID>
+{
     /// Get some metrics/information about the slot for debugging purposes.
     fn get_metrics(&self) -> SlotMetrics {
         SlotMetrics {
@@ -192,6 +294,12 @@ impl<V: Value, ValidationError: Display> ScpSlot<V> for Slot<V, ValidationError>
             num_confirmed_nominated: self.Z.len(),
             cur_nomination_round: self.nominate_round,
             bN: self.B.N,
+            num_validity_fn_calls: self.num_validity_fn_calls,
+            num_combine_fn_calls: self.num_combine_fn_calls,
+            num_tracked_ballots: self.tracked_ballots().len(),
+            num_ballot_evictions: self.num_ballot_evictions,
+            num_timeouts: self.num_timeouts,
+            counter_capped: self.is_counter_capped(),
         }
     }
 
@@ -199,23 +307,27 @@ impl<V: Value, ValidationError: Display> ScpSlot<V> for Slot<V, ValidationError>
         self.slot_index
     }
 
+    fn get_ballot(&self) -> Ballot<V> {
+        self.B.clone()
+    }
+
     /// Last message sent by this node, if any.
-    fn get_last_message_sent(&self) -> Option<Msg<V>> {
+    fn get_last_message_sent(&self) -> Option<Msg<V, ID>> {
         self.last_sent_msg.clone()
     }
 
     /// Processes any timeouts that may have occurred.
     /// Returns list of messages to broadcast to network.
-    fn process_timeouts(&mut self) -> Vec<Msg<V>> {
-        let mut msgs = Vec::<Msg<V>>::new();
+    fn process_timeouts(&mut self) -> Vec<Msg<V, ID>> {
+        let mut msgs = Vec::<Msg<V, ID>>::new();
 
         let mut timeout_occurred = false;
+        let now = self.now();
 
         // Nomination round timeout.
-        if self.next_nominate_round_at.is_some()
-            && Instant::now() > self.next_nominate_round_at.unwrap()
-        {
+        if self.next_nominate_round_at.is_some() && now > self.next_nominate_round_at.unwrap() {
             timeout_occurred = true;
+            self.num_timeouts += 1;
             // Canceling is required since schedule_next_nomination_round will not schedule a round
             // if one is already scheduled.
             self.cancel_next_nomination_round();
@@ -236,7 +348,7 @@ impl<V: Value, ValidationError: Display> ScpSlot<V> for Slot<V, ValidationError>
         }
 
         // Ballot timeout.
-        if self.next_ballot_at.is_some() && Instant::now() > self.next_ballot_at.unwrap() {
+        if self.next_ballot_at.is_some() && now > self.next_ballot_at.unwrap() {
             log::debug!(
                 self.logger,
                 "Ballot {} timed out in {:?} phase",
@@ -245,37 +357,46 @@ impl<V: Value, ValidationError: Display> ScpSlot<V> for Slot<V, ValidationError>
             );
 
             timeout_occurred = true;
+            self.num_timeouts += 1;
             self.cancel_next_ballot_timer();
             let next_counter = self.B.N + 1;
 
-            match self.phase {
-                Phase::NominatePrepare | Phase::Prepare => {
-                    if let Some(x) = self.get_next_ballot_values() {
+            if self.is_counter_capped() {
+                log::warn!(
+                    self.logger,
+                    "Ballot counter {} has reached its maximum, not incrementing.",
+                    self.B.N
+                );
+            } else {
+                match self.phase {
+                    Phase::NominatePrepare | Phase::Prepare => {
+                        if let Some(x) = self.get_next_ballot_values() {
+                            log::trace!(
+                                self.logger,
+                                "process_timeouts: updating B.N: {} -> {}",
+                                self.B.N,
+                                next_counter
+                            );
+                            self.B = Ballot::new(next_counter, &x);
+                        }
+                    }
+                    Phase::Commit => {
+                        // B.X can no longer change. Increment B.N
                         log::trace!(
                             self.logger,
                             "process_timeouts: updating B.N: {} -> {}",
                             self.B.N,
                             next_counter
                         );
-                        self.B = Ballot::new(next_counter, &x);
+                        self.B.N = next_counter;
+                    }
+                    Phase::Externalize => {
+                        // B no longer changes.
+                        log::warn!(
+                            self.logger,
+                            "Ballot timeout occurred during Externalize phase."
+                        );
                     }
-                }
-                Phase::Commit => {
-                    // B.X can no longer change. Increment B.N
-                    log::trace!(
-                        self.logger,
-                        "process_timeouts: updating B.N: {} -> {}",
-                        self.B.N,
-                        next_counter
-                    );
-                    self.B.N = next_counter;
-                }
-                Phase::Externalize => {
-                    // B no longer changes.
-                    log::warn!(
-                        self.logger,
-                        "Ballot timeout occurred during Externalize phase."
-                    );
                 }
             }
             self.do_ballot_protocol();
@@ -291,7 +412,7 @@ impl<V: Value, ValidationError: Display> ScpSlot<V> for Slot<V, ValidationError>
     }
 
     /// Propose values for this node to nominate.
-    fn propose_values(&mut self, values: &BTreeSet<V>) -> Result<Option<Msg<V>>, String> {
+    fn propose_values(&mut self, values: &BTreeSet<V>) -> Result<Option<Msg<V, ID>>, String> {
         // Only accept values during the Nominate phase and if no other values have been confirmed nominated.
         if !(self.phase == Phase::NominatePrepare && self.Z.is_empty()) {
             return Ok(self.out_msg());
@@ -314,15 +435,20 @@ impl<V: Value, ValidationError: Display> ScpSlot<V> for Slot<V, ValidationError>
         Ok(self.out_msg())
     }
 
+    fn propose_valid_values(&mut self, values: &BTreeSet<V>) -> Result<Option<Msg<V, ID>>, String> {
+        self.valid_values.extend(values.iter().cloned());
+        self.propose_values(values)
+    }
+
     /// Handle an incoming message from a peer.
-    fn handle_message(&mut self, msg: &Msg<V>) -> Result<Option<Msg<V>>, String> {
+    fn handle_message(&mut self, msg: &Msg<V, ID>) -> Result<Option<Msg<V, ID>>, String> {
         self.handle_messages(&[msg.clone()])
     }
 
     /// Handle incoming messages from peers. Messages for other slots are ignored.
-    fn handle_messages(&mut self, msgs: &[Msg<V>]) -> Result<Option<Msg<V>>, String> {
+    fn handle_messages(&mut self, msgs: &[Msg<V, ID>]) -> Result<Option<Msg<V, ID>>, String> {
         // Ignore messages from self.
-        let msgs: Vec<&Msg<V>> = msgs
+        let msgs: Vec<&Msg<V, ID>> = msgs
             .iter()
             .filter(|&msg| msg.sender_id != self.node_id)
             .collect();
@@ -377,6 +503,8 @@ impl<V: Value, ValidationError: Display> ScpSlot<V> for Slot<V, ValidationError>
         }
 
         if has_higher_messages {
+            self.enforce_ballot_cap();
+
             if self.phase == Phase::NominatePrepare {
                 self.do_nominate_phase();
             }
@@ -391,22 +519,84 @@ impl<V: Value, ValidationError: Display> ScpSlot<V> for Slot<V, ValidationError>
     fn get_debug_snapshot(&self) -> String {
         serde_json::to_string(&SlotState::from(self)).expect("SlotState should yield JSON")
     }
+
+    fn get_state(&self) -> SlotState<V, ID> {
+        SlotState::from(self)
+    }
+
+    fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        let now = clock.now();
+        self.clock = clock;
+        self.last_clock_reading = now;
+        self.monotonic_now = now;
+        self.phase_entered_at = now;
+    }
+
+    fn set_timeout_fn(&mut self, timeout_fn: TimeoutFn) {
+        self.timeout_fn = Some(timeout_fn);
+    }
+
+    /// Each phase is mapped to its own range, and progress within a phase is the fraction of
+    /// quorum set peers heard from for this slot:
+    /// * NominatePrepare: [0.0, 0.4], with 0.1 each for voting, accepting and confirming
+    ///   nominated values, plus up to 0.1 for peers.
+    /// * Prepare: [0.5, 0.6].
+    /// * Commit: [0.75, 0.85].
+    /// * Externalize: 1.0.
+    fn progress(&self) -> f64 {
+        let peers: Vec<ID> = self
+            .quorum_set
+            .nodes()
+            .into_iter()
+            .filter(|node_id| *node_id != self.node_id)
+            .collect();
+        let participation = if peers.is_empty() {
+            1.0
+        } else {
+            let heard = peers.iter().filter(|node_id| self.M.contains_key(node_id));
+            heard.count() as f64 / peers.len() as f64
+        };
+
+        match self.phase {
+            Phase::NominatePrepare => {
+                let nomination_steps = [&self.X, &self.Y, &self.Z]
+                    .iter()
+                    .filter(|values| !values.is_empty())
+                    .count();
+                0.1 * nomination_steps as f64 + 0.1 * participation
+            }
+            Phase::Prepare => 0.5 + 0.1 * participation,
+            Phase::Commit => 0.75 + 0.1 * participation,
+            Phase::Externalize => 1.0,
+        }
+    }
+
+    fn value_provenance(&self, value: &V) -> Option<HashSet<ID>> {
+        self.nominated_by.get(value).cloned()
+    }
 }
 
-impl<V: Value, ValidationError: Display> Slot<V, ValidationError> {
+impl<
+        V: Value,
+        ValidationError: Display,
+        ID: GenericNodeId + NodePriorityKey + Serialize + DeserializeOwned + Send + 'static,
+    > Slot<V, ValidationError, ID>
+{
     ///////////////////////////////////////////////////////////////////////////
     // Public methods (how the Slot interfaces with the Node)
     ///////////////////////////////////////////////////////////////////////////
 
     /// Create a new slot.
     pub fn new(
-        node_id: NodeID,
-        quorum_set: QuorumSet,
+        node_id: ID,
+        quorum_set: QuorumSet<ID>,
         slot_index: SlotIndex,
         validity_fn: ValidityFn<V, ValidationError>,
         combine_fn: CombineFn<V, ValidationError>,
         logger: Logger,
     ) -> Self {
+        let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+        let now = clock.now();
         let mut slot = Slot {
             slot_index,
             node_id,
@@ -423,16 +613,31 @@ impl<V: Value, ValidationError: Display> Slot<V, ValidationError> {
             H: None,
             phase: Phase::NominatePrepare,
             last_sent_msg: None,
+            sent_msgs: Vec::new(),
+            nominated_by: HashMap::default(),
             max_priority_peers: HashSet::default(),
             nominate_round: 1,
             next_nominate_round_at: None,
             next_ballot_at: None,
             validity_fn,
             combine_fn,
+            num_validity_fn_calls: 0,
+            num_combine_fn_calls: 0,
             valid_values: BTreeSet::default(),
             logger: logger.new(o!("mc.scp.slot" => slot_index)),
+            clock,
+            last_clock_reading: now,
+            monotonic_now: now,
+            phase_entered_at: now,
             base_round_interval: Duration::from_millis(1000),
             base_ballot_interval: Duration::from_millis(1000),
+            timeout_fn: None,
+            max_tracked_ballots: None,
+            num_ballot_evictions: 0,
+            max_msg_size: None,
+            logged_oversized_msg: false,
+            max_ballot_counter: None,
+            num_timeouts: 0,
         };
 
         let max_priority_peer = slot.find_max_priority_peer(slot.nominate_round);
@@ -441,11 +646,245 @@ impl<V: Value, ValidationError: Display> Slot<V, ValidationError> {
         slot
     }
 
+    /// Create a slot that has already externalized `values`, e.g. when restoring history.
+    pub fn new_externalized(
+        node_id: ID,
+        quorum_set: QuorumSet<ID>,
+        slot_index: SlotIndex,
+        values: &[V],
+        validity_fn: ValidityFn<V, ValidationError>,
+        combine_fn: CombineFn<V, ValidationError>,
+        logger: Logger,
+    ) -> Self {
+        let mut slot = Self::new(
+            node_id,
+            quorum_set,
+            slot_index,
+            validity_fn,
+            combine_fn,
+            logger,
+        );
+
+        let ballot = Ballot::new(1, values);
+        slot.B = ballot.clone();
+        slot.P = Some(ballot.clone());
+        slot.H = Some(ballot.clone());
+        slot.C = Some(ballot);
+        slot.phase = Phase::Externalize;
+        slot.check_externalize_phase_invariants();
+
+        // Sets `last_sent_msg` to the Externalize message.
+        slot.out_msg();
+
+        slot
+    }
+
+    /// Creates a slot from previously saved state, e.g. after a crash.
+    ///
+    /// Timers are re-armed relative to now. The transcript and the provenance of nominated
+    /// values are not part of `SlotState`, so they start out empty.
+    pub fn from_state(
+        state: SlotState<V, ID>,
+        quorum_set: QuorumSet<ID>,
+        validity_fn: ValidityFn<V, ValidationError>,
+        combine_fn: CombineFn<V, ValidationError>,
+        logger: Logger,
+    ) -> Self {
+        let mut slot = Self::new(
+            state.node_id,
+            quorum_set,
+            state.slot_index,
+            validity_fn,
+            combine_fn,
+            logger,
+        );
+
+        slot.M = state
+            .M
+            .into_iter()
+            .map(|msg| (msg.sender_id.clone(), msg))
+            .collect();
+        slot.W = state.W;
+        slot.X = state.X;
+        slot.Y = state.Y;
+        slot.Z = state.Z;
+        slot.B = state.B;
+        slot.P = state.P;
+        slot.PP = state.PP;
+        slot.H = state.H;
+        slot.C = state.C;
+        slot.phase = state.phase;
+        slot.last_sent_msg = state.last_sent_msg;
+        slot.max_priority_peers = state.max_priority_peers;
+        slot.nominate_round = state.nominate_round;
+        slot.valid_values = state.valid_values;
+
+        if slot.phase == Phase::NominatePrepare && slot.last_sent_msg.is_some() {
+            slot.schedule_next_nomination_round();
+        }
+        slot.maybe_set_ballot_timer();
+
+        slot
+    }
+
+    /// Replaces the nodes whose nominations this node echoes in the first nomination round.
+    ///
+    /// Each later round adds one leader by priority, as usual. This should be called before the
+    /// slot starts nominating.
+    pub fn set_leaders(&mut self, leaders: impl IntoIterator<Item = ID>) {
+        self.max_priority_peers = leaders.into_iter().collect();
+    }
+
+    /// The nodes whose nominations this node echoes by the given nomination round.
+    ///
+    /// Each round adds one leader to those of earlier rounds. The result depends only on the
+    /// slot index, this node's ID and its quorum set, so it matches `max_priority_peers` once
+    /// the slot has reached `round`, unless `set_leaders` was called.
+    pub fn nomination_leaders(&self, round: u32) -> BTreeSet<ID> {
+        let mut leaders = HashSet::default();
+        for r in 1..=round {
+            let leader = self.max_priority_peer_excluding(r, &leaders);
+            leaders.insert(leader);
+        }
+        leaders.into_iter().collect()
+    }
+
+    /// The messages this slot has emitted so far, in order.
+    pub fn transcript(&self) -> Transcript<V, ID> {
+        Transcript {
+            slot_index: self.slot_index,
+            node_id: self.node_id.clone(),
+            msgs: self.sent_msgs.clone(),
+        }
+    }
+
+    /// How long the slot has been in its current phase.
+    pub fn time_in_current_phase(&self) -> Duration {
+        self.clock
+            .now()
+            .saturating_duration_since(self.phase_entered_at)
+    }
+
+    /// True if ballot timeouts may no longer increment the ballot counter.
+    fn is_counter_capped(&self) -> bool {
+        self.max_ballot_counter
+            .map_or(false, |max_ballot_counter| self.B.N >= max_ballot_counter)
+    }
+
+    /// How long to wait on a ballot with the given counter before moving to the next counter.
+    pub fn ballot_timeout(&self, counter: u32) -> Duration {
+        match &self.timeout_fn {
+            Some(timeout_fn) => timeout_fn(counter),
+            None => self.base_ballot_interval * counter,
+        }
+    }
+
+    /// The latest message from each peer, which is the input to this slot's quorum and blocking
+    /// set calculations. Ordered by sender.
+    pub fn tracked_messages(&self) -> Vec<Msg<V, ID>> {
+        let mut msgs: Vec<Msg<V, ID>> = self.M.values().cloned().collect();
+        msgs.sort_by(|a, b| a.sender_id.cmp(&b.sender_id));
+        msgs
+    }
+
+    /// The distinct ballots that tracked messages vote or accept prepared.
+    fn tracked_ballots(&self) -> HashSet<Ballot<V>> {
+        self.M
+            .values()
+            .flat_map(|msg| msg.votes_or_accepts_prepared())
+            .collect()
+    }
+
+    /// Evicts peer messages from `M`, lowest ballot counter first, until at most
+    /// `max_tracked_ballots` distinct ballots are tracked.
+    ///
+    /// Only Prepare messages from senders outside the local quorum set are evicted, so
+    /// nominations and the messages of quorum set members are always kept. A message is kept if
+    /// any of its ballots has the same values as one of this node's own ballots (B, P, PP, H, C).
+    /// The cap can therefore be exceeded if no message is eligible for eviction.
+    fn enforce_ballot_cap(&mut self) {
+        let max_tracked_ballots = match self.max_tracked_ballots {
+            Some(max_tracked_ballots) => max_tracked_ballots,
+            None => return,
+        };
+
+        // The number of tracked messages that reference each ballot.
+        let mut ballot_refs: HashMap<Ballot<V>, usize> = HashMap::default();
+        for msg in self.M.values() {
+            for ballot in msg.votes_or_accepts_prepared() {
+                *ballot_refs.entry(ballot).or_insert(0) += 1;
+            }
+        }
+        if ballot_refs.len() <= max_tracked_ballots {
+            return;
+        }
+
+        let local_values: HashSet<Vec<V>> = vec![
+            Some(self.B.clone()),
+            self.P.clone(),
+            self.PP.clone(),
+            self.H.clone(),
+            self.C.clone(),
+        ]
+        .into_iter()
+        .flatten()
+        .map(|ballot| ballot.X)
+        .collect();
+        let quorum_set_nodes = self.quorum_set.nodes();
+
+        let mut evictable: Vec<(u32, ID)> = self
+            .M
+            .iter()
+            .filter(|(node_id, msg)| {
+                **node_id != self.node_id
+                    && !quorum_set_nodes.contains(node_id)
+                    && msg.topic.is_prepare()
+                    && msg
+                        .votes_or_accepts_prepared()
+                        .iter()
+                        .all(|ballot| !local_values.contains(&ballot.X))
+            })
+            .map(|(node_id, msg)| (msg.bN(), node_id.clone()))
+            .collect();
+        evictable.sort();
+
+        for (_counter, node_id) in evictable {
+            if ballot_refs.len() <= max_tracked_ballots {
+                return;
+            }
+
+            // Evicting a message only helps if it is the last to reference one of its ballots.
+            let ballots = self.M[&node_id].votes_or_accepts_prepared();
+            if ballots.iter().all(|ballot| ballot_refs[ballot] > 1) {
+                continue;
+            }
+
+            self.M.remove(&node_id);
+            self.num_ballot_evictions += 1;
+            for ballot in ballots {
+                let refs = ballot_refs.get_mut(&ballot).expect("ballot is tracked");
+                *refs -= 1;
+                if *refs == 0 {
+                    ballot_refs.remove(&ballot);
+                }
+            }
+        }
+
+        if ballot_refs.len() > max_tracked_ballots {
+            log::warn!(
+                self.logger,
+                "Tracking more than {} ballots, but no message can be evicted.",
+                max_tracked_ballots,
+            );
+        }
+    }
+
     fn is_valid(&mut self, value: &V) -> Result<(), String> {
         if self.valid_values.contains(value) {
             return Ok(());
         }
 
+        self.num_validity_fn_calls += 1;
         match (self.validity_fn)(value) {
             Ok(()) => {
                 self.valid_values.insert(value.clone());
@@ -467,7 +906,7 @@ impl<V: Value, ValidationError: Display> Slot<V, ValidationError> {
     ///
     /// # Returns
     /// * (numerator, denominator) representing the node's weight.
-    fn weight(&self, node_id: &NodeID) -> (u32, u32) {
+    fn weight(&self, node_id: &ID) -> (u32, u32) {
         if node_id == &self.node_id {
             (1, 1)
         } else {
@@ -479,11 +918,11 @@ impl<V: Value, ValidationError: Display> Slot<V, ValidationError> {
     /// Neighbors are nodes that the current node is willing to accept nomination values from.
     /// See p.10 of the [IETF draft](https://tools.ietf.org/pdf/draft-mazieres-dinrg-scp-04.pdf).
     /// See p.20 of the [Whitepaper](https://www.stellar.org/papers/stellar-consensus-protocol.pdf).
-    fn neighbors(&self, slot_index: SlotIndex, nomination_round: u32) -> Vec<NodeID> {
+    fn neighbors(&self, slot_index: SlotIndex, nomination_round: u32) -> Vec<ID> {
         let mut self_and_peers = vec![self.node_id.clone()];
         self_and_peers.extend(self.quorum_set.nodes());
 
-        let mut result = Vec::<NodeID>::new();
+        let mut result = Vec::<ID>::new();
         for node_id in self_and_peers.iter() {
             // weight256 is the node's weight, scaled to 0..<max uint256>
             // (weight256 = <max uint256> * <num> / <denom>)
@@ -497,7 +936,7 @@ impl<V: Value, ValidationError: Display> Slot<V, ValidationError> {
                 slot_index,
                 1,
                 nomination_round,
-                node_id.public_key.as_ref(),
+                &node_id.priority_key(),
             );
 
             if gi_one < weight256 {
@@ -509,7 +948,12 @@ impl<V: Value, ValidationError: Display> Slot<V, ValidationError> {
     }
 
     /// The max priority peer for a given nomination round.
-    fn find_max_priority_peer(&self, round: u32) -> NodeID {
+    fn find_max_priority_peer(&self, round: u32) -> ID {
+        self.max_priority_peer_excluding(round, &self.max_priority_peers)
+    }
+
+    /// The max priority peer for a given nomination round, skipping peers in `excluded`.
+    fn max_priority_peer_excluding(&self, round: u32, excluded: &HashSet<ID>) -> ID {
         let neighbors = self.neighbors(self.slot_index, round);
         let mut result = self.node_id.clone();
         let mut max_priority = bigint::U256::zero();
@@ -517,16 +961,12 @@ impl<V: Value, ValidationError: Display> Slot<V, ValidationError> {
         for node_id in neighbors.iter() {
             // NOTE: this deviates from the spec. Without doing this we may have nomination rounds
             // where no new peers gets added, so nothing changes which slows the protocol down.
-            if self.max_priority_peers.contains(node_id) {
+            if excluded.contains(node_id) {
                 continue;
             }
 
-            let node_priority = utils::slot_round_salted_keccak(
-                self.slot_index,
-                2,
-                round,
-                node_id.public_key.as_ref(),
-            );
+            let node_priority =
+                utils::slot_round_salted_keccak(self.slot_index, 2, round, &node_id.priority_key());
             if node_priority > max_priority {
                 max_priority = node_priority;
                 result = node_id.clone();
@@ -536,11 +976,36 @@ impl<V: Value, ValidationError: Display> Slot<V, ValidationError> {
         result
     }
 
+    /// Move to `phase`, noting when it was entered.
+    fn set_phase(&mut self, phase: Phase) {
+        self.phase = phase;
+        self.phase_entered_at = self.clock.now();
+    }
+
+    /// The current time for timer purposes.
+    ///
+    /// Readings of `clock` are only trusted to move forward: if the clock steps backwards
+    /// (e.g. an NTP adjustment), time stands still until it moves forward again, so pending
+    /// deadlines keep their remaining duration rather than being pushed out.
+    fn now(&mut self) -> Instant {
+        let reading = self.clock.now();
+        match reading.checked_duration_since(self.last_clock_reading) {
+            Some(elapsed) => self.monotonic_now += elapsed,
+            None => log::warn!(
+                self.logger,
+                "Clock went backwards by {:?}",
+                self.last_clock_reading - reading
+            ),
+        }
+        self.last_clock_reading = reading;
+        self.monotonic_now
+    }
+
     /// Set the timer for the next nomination round.
     fn schedule_next_nomination_round(&mut self) {
         if self.next_nominate_round_at.is_none() {
             self.next_nominate_round_at =
-                Some(Instant::now() + self.base_round_interval * self.nominate_round);
+                Some(self.now() + self.base_round_interval * self.nominate_round);
         }
     }
 
@@ -559,7 +1024,7 @@ impl<V: Value, ValidationError: Display> Slot<V, ValidationError> {
         // If no values have been confirmed nominated, the node may add new values to its voted set.
         if self.Z.is_empty() {
             // Gather all nominate payloads from other nodes.
-            let mut nominate_payloads: HashMap<NodeID, &NominatePayload<V>> = Default::default();
+            let mut nominate_payloads: HashMap<ID, &NominatePayload<V>> = Default::default();
             for (node_id, msg) in &self.M {
                 if *node_id == self.node_id {
                     continue;
@@ -573,11 +1038,24 @@ impl<V: Value, ValidationError: Display> Slot<V, ValidationError> {
                 }
             }
 
+            // Bytes left for new values in outgoing messages, if max_msg_size is set.
+            let mut room = self.nomination_room();
+            let mut num_values_without_room = 0;
+
             // This node may nominate new values when it is among max_priority_peers.
             if self.max_priority_peers.contains(&self.node_id) {
                 for value in &self.W {
-                    if !self.Y.contains(value) {
+                    if self.Y.contains(value) {
+                        continue;
+                    }
+                    if !self.X.contains(value) && !take_room(&mut room, value) {
+                        num_values_without_room += 1;
+                    } else {
                         self.X.insert(value.clone());
+                        self.nominated_by
+                            .entry(value.clone())
+                            .or_default()
+                            .insert(self.node_id.clone());
                     }
                 }
             }
@@ -586,12 +1064,30 @@ impl<V: Value, ValidationError: Display> Slot<V, ValidationError> {
             for (node_id, payload) in &nominate_payloads {
                 if self.max_priority_peers.contains(node_id) {
                     for value in payload.X.iter().chain(payload.Y.iter()) {
-                        if !self.Y.contains(value) {
+                        if self.Y.contains(value) {
+                            continue;
+                        }
+                        if !self.X.contains(value) && !take_room(&mut room, value) {
+                            num_values_without_room += 1;
+                        } else {
                             self.X.insert(value.clone());
+                            self.nominated_by
+                                .entry(value.clone())
+                                .or_default()
+                                .insert(node_id.clone());
                         }
                     }
                 }
             }
+
+            if num_values_without_room > 0 {
+                log::warn!(
+                    self.logger,
+                    "Not voting to nominate {} values, which would exceed max_msg_size.",
+                    num_values_without_room
+                );
+            }
+
             // Invariant: X and Y are disjoint.
             assert!(self.X.is_disjoint(&self.Y));
         }
@@ -600,14 +1096,97 @@ impl<V: Value, ValidationError: Display> Slot<V, ValidationError> {
         self.update_YZ();
 
         if !self.Z.is_empty() && self.B.is_zero() {
-            let z_as_vec: Vec<V> = self.Z.iter().cloned().collect();
-            match (self.combine_fn)(&z_as_vec) {
-                Ok(values) => self.B = Ballot::new(1, &values),
-                Err(_e) => log::error!(self.logger, "Failed to combine Z: {:?}", &z_as_vec),
+            if let Some(values) = self.combine_Z() {
+                self.B = Ballot::new(1, &values);
+            }
+        }
+    }
+
+    /// Applies the combine function to the confirmed nominated values.
+    ///
+    /// If the result would not fit in a ballot within `max_msg_size`, the combine function is
+    /// applied to the longest prefix of Z whose result fits instead.
+    fn combine_Z(&mut self) -> Option<Vec<V>> {
+        // Z is the same on every node that confirms it, but its iteration order is not.
+        let mut z_as_vec: Vec<V> = self.Z.iter().cloned().collect();
+        z_as_vec.sort();
+        let values = self.combine(&z_as_vec)?;
+        if self.ballot_fits(&values) {
+            return Some(values);
+        }
+
+        // Binary search for the longest prefix of Z that combines into a ballot that fits.
+        let (mut fits_len, mut too_long_len) = (0, z_as_vec.len());
+        let mut trimmed_values = None;
+        while too_long_len - fits_len > 1 {
+            let len = (fits_len + too_long_len) / 2;
+            match self.combine(&z_as_vec[..len]) {
+                Some(values) if self.ballot_fits(&values) => {
+                    fits_len = len;
+                    trimmed_values = Some(values);
+                }
+                _ => too_long_len = len,
+            }
+        }
+
+        match &trimmed_values {
+            Some(values) => log::warn!(
+                self.logger,
+                "Combined {} of {} confirmed nominated values into {} values, to fit in max_msg_size.",
+                fits_len,
+                z_as_vec.len(),
+                values.len()
+            ),
+            None => log::error!(
+                self.logger,
+                "No confirmed nominated values combine into a ballot that fits in max_msg_size."
+            ),
+        }
+        trimmed_values
+    }
+
+    /// Applies the combine function to `values`, returning None if it fails or returns nothing.
+    fn combine(&mut self, values: &[V]) -> Option<Vec<V>> {
+        self.num_combine_fn_calls += 1;
+        match (self.combine_fn)(values) {
+            Ok(combined) if combined.is_empty() => {
+                // A ballot must have values.
+                log::error!(self.logger, "Combining Z gave no values: {:?}", values);
+                None
+            }
+            Ok(combined) => Some(combined),
+            Err(_e) => {
+                log::error!(self.logger, "Failed to combine Z: {:?}", values);
+                None
             }
         }
     }
 
+    /// True if a ballot with `values` fits within `max_msg_size`, alongside this slot's
+    /// nomination statement.
+    fn ballot_fits(&self, values: &[V]) -> bool {
+        let max_msg_size = match self.max_msg_size {
+            Some(max_msg_size) => max_msg_size,
+            None => return true,
+        };
+        let msg = Msg::<V, ID>::new(
+            self.node_id.clone(),
+            self.quorum_set.clone(),
+            self.slot_index,
+            Topic::NominatePrepare(
+                NominatePayload::new(&self.X, &self.Y),
+                PreparePayload {
+                    B: Ballot::new(1, values),
+                    P: None,
+                    PP: None,
+                    CN: 0,
+                    HN: 0,
+                },
+            ),
+        );
+        msg.estimated_size() <= max_msg_size
+    }
+
     /// Update Y (values accepted-nominated) and Z (values confirmed-nominated).
     fn update_YZ(&mut self) {
         for value in self.additional_values_accepted_nominated().into_iter() {
@@ -758,7 +1337,7 @@ impl<V: Value, ValidationError: Display> Slot<V, ValidationError> {
             if self.phase == Phase::NominatePrepare {
                 // Nominate ends when some ballot has been confirmed prepared.
                 self.cancel_next_nomination_round();
-                self.phase = Phase::Prepare;
+                self.set_phase(Phase::Prepare);
             }
 
             // self.H should not decrease.
@@ -908,7 +1487,7 @@ impl<V: Value, ValidationError: Display> Slot<V, ValidationError> {
                 self.B = Ballot::new(core::cmp::max(self.B.N, h.N), &h.X);
             }
 
-            self.phase = Phase::Commit;
+            self.set_phase(Phase::Commit);
             self.cancel_next_nomination_round();
 
             // In the commit phase, P must have the same value as B.
@@ -1051,7 +1630,7 @@ impl<V: Value, ValidationError: Display> Slot<V, ValidationError> {
             // Ballot timeouts are not performed during the Externalize phase.
             self.cancel_next_nomination_round();
             self.cancel_next_ballot_timer();
-            self.phase = Phase::Externalize;
+            self.set_phase(Phase::Externalize);
             return;
         }
 
@@ -1145,7 +1724,7 @@ impl<V: Value, ValidationError: Display> Slot<V, ValidationError> {
         let mut unblocking_counter = self.B.N;
         loop {
             let (blocking_set, _) = self.find_blocking_set(FuncPredicate {
-                test_fn: &|msg: &Msg<V>| msg.bN() > unblocking_counter,
+                test_fn: &|msg: &Msg<V, ID>| msg.bN() > unblocking_counter,
             });
             if blocking_set.is_empty() {
                 break;
@@ -1180,19 +1759,19 @@ impl<V: Value, ValidationError: Display> Slot<V, ValidationError> {
             // "ballot.counter" is greater than or equal to the local "ballot.counter", the node
             // arms a timer to fire in a number of seconds equal to its "ballot.counter + 1""
             // See p.14 of the [IETF draft](https://tools.ietf.org/pdf/draft-mazieres-dinrg-scp-04.pdf).
-            let (quorum_ids, _) = self.find_quorum(FuncPredicate::<V> {
-                test_fn: &|msg: &Msg<V>| msg.bN() >= self.B.N,
+            let (quorum_ids, _) = self.find_quorum(FuncPredicate::<V, ID> {
+                test_fn: &|msg: &Msg<V, ID>| msg.bN() >= self.B.N,
             });
 
             if !quorum_ids.is_empty() {
                 self.next_ballot_at =
-                    Some(Instant::now() + self.base_ballot_interval * self.B.N.saturating_add(1));
+                    Some(self.now() + self.ballot_timeout(self.B.N.saturating_add(1)));
             }
         }
     }
 
     /// The values, if any, for the next ballot.
-    fn get_next_ballot_values(&self) -> Option<Vec<V>> {
+    fn get_next_ballot_values(&mut self) -> Option<Vec<V>> {
         // "If any ballot has been confirmed prepared, then "ballot.value" is taken to to be
         // "h.value" for the highest confirmed prepared ballot "h"."
         if let Some(h) = self.ballots_confirmed_prepared().into_iter().max() {
@@ -1203,10 +1782,8 @@ impl<V: Value, ValidationError: Display> Slot<V, ValidationError> {
         // then "ballot.value" is taken as the output of the deterministic combining function
         // applied to all confirmed nominated values."
         if !self.Z.is_empty() {
-            let z_as_vec: Vec<V> = self.Z.iter().cloned().collect();
-            match (self.combine_fn)(&z_as_vec) {
-                Ok(values) => return Some(values),
-                Err(_e) => log::error!(self.logger, "Failed to combine Z: {:?}", &z_as_vec),
+            if let Some(values) = self.combine_Z() {
+                return Some(values);
             }
         }
 
@@ -1228,7 +1805,38 @@ impl<V: Value, ValidationError: Display> Slot<V, ValidationError> {
 
     /// Calculate the message to send to the network based on our current state.
     /// Any duplicate messages are suppressed.
-    fn out_msg(&mut self) -> Option<Msg<V>> {
+    ///
+    /// A message that exceeds `max_msg_size` is still sent, since withholding it would stall the
+    /// slot. The first such message is logged.
+    fn out_msg(&mut self) -> Option<Msg<V, ID>> {
+        let msg = self.build_msg()?;
+        assert_eq!(msg.validate(), Ok(()));
+
+        if let Some(max_msg_size) = self.max_msg_size {
+            let size = msg.estimated_size();
+            if size > max_msg_size && !self.logged_oversized_msg {
+                log::error!(
+                    self.logger,
+                    "Outgoing message is {} bytes, exceeding the maximum of {}: {}",
+                    size,
+                    max_msg_size,
+                    msg
+                );
+                self.logged_oversized_msg = true;
+            }
+        }
+
+        // Suppress duplicate outgoing messages.
+        if self.last_sent_msg.as_ref() == Some(&msg) {
+            return None;
+        }
+        self.last_sent_msg = Some(msg.clone());
+        self.sent_msgs.push(msg.clone());
+        Some(msg)
+    }
+
+    /// The message that describes our current state, if any.
+    fn build_msg(&self) -> Option<Msg<V, ID>> {
         // Prepared is " the highest accepted prepared ballot not exceeding the "ballot" field...
         // if "ballot = <n, x>" and the highest prepared ballot is "<n, y>" where "x < y",
         // then the "prepared" field in sent messages must be set to "<n-1, y>" instead of "<n, y>""
@@ -1379,46 +1987,42 @@ impl<V: Value, ValidationError: Display> Slot<V, ValidationError> {
             })),
         };
 
-        let msg_opt = topic_opt.map(|topic| {
+        topic_opt.map(|topic| {
             Msg::new(
                 self.node_id.clone(),
                 self.quorum_set.clone(),
                 self.slot_index,
                 topic,
             )
-        });
-
-        // Suppress duplicate outgoing messages.
-        if let Some(msg) = msg_opt {
-            assert_eq!(msg.validate(), Ok(()));
-
-            if let Some(last_msg) = &self.last_sent_msg {
-                if msg != *last_msg {
-                    self.last_sent_msg = Some(msg.clone());
-                    return Some(msg);
-                } else {
-                    // Ignore duplicate outgoing message.
-                    return None;
-                }
-            } else {
-                // The first emitted message.
-                self.last_sent_msg = Some(msg.clone());
-                return Some(msg);
-            }
-        }
+        })
+    }
 
-        None
+    /// Bytes left for new values in outgoing messages before they exceed `max_msg_size`, or
+    /// `None` for no limit.
+    fn nomination_room(&self) -> Option<usize> {
+        let max_msg_size = self.max_msg_size?;
+        let size = match self.build_msg() {
+            Some(msg) => msg.estimated_size(),
+            None => Msg::<V, ID>::new(
+                self.node_id.clone(),
+                self.quorum_set.clone(),
+                self.slot_index,
+                Topic::Nominate(NominatePayload::new(&self.X, &self.Y)),
+            )
+            .estimated_size(),
+        };
+        Some(max_msg_size.saturating_sub(size))
     }
 
     /// Checks that at least one node in each quorum slice satisfies pred
     /// (excluding the slot's node).
-    fn find_blocking_set<P: Predicate<V>>(&self, pred: P) -> (HashSet<NodeID>, P) {
+    fn find_blocking_set<P: Predicate<V, ID>>(&self, pred: P) -> (HashSet<ID>, P) {
         self.quorum_set.findBlockingSet(&self.M, pred)
     }
 
     /// Finds a quorum in which every node satisfies the given predicate.
     /// The slot's node itself is presumed to satisfy the predicate.
-    fn find_quorum<P: Predicate<V>>(&self, pred: P) -> (HashSet<NodeID>, P) {
+    fn find_quorum<P: Predicate<V, ID>>(&self, pred: P) -> (HashSet<ID>, P) {
         self.quorum_set.findQuorum(&self.node_id, &self.M, pred)
     }
 
@@ -1442,7 +2046,7 @@ impl<V: Value, ValidationError: Display> Slot<V, ValidationError> {
             // Test if a blocking set has issued "accept nominate" for each value.
             for value in candidates {
                 // Test if a blocking set has issued "accept nominate(v)".
-                let predicate = ValueSetPredicate::<V> {
+                let predicate = ValueSetPredicate::<V, ID> {
                     values: btreeset! {value.clone()},
                     test_fn: Arc::new(|msg, values| match msg.accepts_nominated() {
                         None => BTreeSet::default(),
@@ -1465,7 +2069,7 @@ impl<V: Value, ValidationError: Display> Slot<V, ValidationError> {
         let mut accepted_from_quorum: BTreeSet<V> = {
             // Predicate for identifying values in self.X that can be moved to self.Y because
             // a quorum of nodes has issued "vote nominate" or "accept nominate".
-            let votes_or_accepts_predicate = ValueSetPredicate::<V> {
+            let votes_or_accepts_predicate = ValueSetPredicate::<V, ID> {
                 values: self.X.iter().cloned().collect(),
                 test_fn: Arc::new(|msg, values| match msg.votes_or_accepts_nominated() {
                     None => BTreeSet::default(),
@@ -1494,7 +2098,7 @@ impl<V: Value, ValidationError: Display> Slot<V, ValidationError> {
 
     /// "Confirmed Nominated" values that are not yet in self.Z.
     fn additional_values_confirmed_nominated(&self) -> BTreeSet<V> {
-        let (quorum_ids, pred) = self.find_quorum(ValueSetPredicate::<V> {
+        let (quorum_ids, pred) = self.find_quorum(ValueSetPredicate::<V, ID> {
             values: self.Y.difference(&self.Z).cloned().collect(),
             test_fn: Arc::new(|msg, values| match msg.accepts_nominated() {
                 None => BTreeSet::default(),
@@ -1527,7 +2131,7 @@ impl<V: Value, ValidationError: Display> Slot<V, ValidationError> {
             let mut results: HashSet<Ballot<V>> = Default::default();
 
             for ballot in candidates.into_iter() {
-                let predicate = BallotSetPredicate::<V> {
+                let predicate = BallotSetPredicate::<V, ID> {
                     ballots: hashset! { ballot.clone()},
                     test_fn: Arc::new(|msg, candidates| {
                         let mut intersections: HashSet<Ballot<V>> = HashSet::default();
@@ -1569,7 +2173,7 @@ impl<V: Value, ValidationError: Display> Slot<V, ValidationError> {
                     }
                 }
 
-                BallotSetPredicate::<V> {
+                BallotSetPredicate::<V, ID> {
                     ballots: candidates,
                     test_fn: Arc::new(|msg, candidates| {
                         let mut intersections: HashSet<Ballot<V>> = HashSet::default();
@@ -1659,22 +2263,12 @@ impl<V: Value, ValidationError: Display> Slot<V, ValidationError> {
             let mut results: HashMap<Vec<V>, (u32, u32)> = Default::default();
 
             for (values, range) in candidates {
-                let mut ballot_ranges: HashMap<Vec<V>, (u32, u32)> = Default::default();
-                ballot_ranges.insert(values.clone(), range);
-                let accepts_predicate = BallotRangePredicate::<V> {
-                    ballot_ranges,
-                    test_fn: Arc::new(|msg, ballot_ranges| {
-                        let mut intersection: HashMap<Vec<V>, (u32, u32)> = Default::default();
-                        for (values, &(min, max)) in ballot_ranges {
-                            assert!(min <= max);
-                            if let Some((a, b)) = msg.accepts_commits(values, min, max) {
-                                assert!(a <= b);
-                                intersection.insert(values.clone(), (a, b));
-                            }
-                        }
-                        intersection
-                    }),
-                };
+                let accepts_predicate = BallotRangePredicate::<V, ID>::for_value(
+                    values.clone(),
+                    range.0,
+                    range.1,
+                    BallotRangePredicate::intersect_commits(),
+                );
 
                 let (nodeIDs, _) = self.find_blocking_set(accepts_predicate);
                 if !nodeIDs.is_empty() {
@@ -1693,7 +2287,7 @@ impl<V: Value, ValidationError: Display> Slot<V, ValidationError> {
                     candidates.insert(self.B.X.clone(), (C.N, H.N));
                 }
 
-                BallotRangePredicate::<V> {
+                BallotRangePredicate::<V, ID> {
                     ballot_ranges: candidates,
                     test_fn: Arc::new(|msg, ballot_ranges| {
                         let mut intersection: HashMap<Vec<V>, (u32, u32)> = Default::default();
@@ -1763,19 +2357,9 @@ impl<V: Value, ValidationError: Display> Slot<V, ValidationError> {
         }
 
         let accepts_predicate = {
-            BallotRangePredicate::<V> {
+            BallotRangePredicate::<V, ID> {
                 ballot_ranges: candidates,
-                test_fn: Arc::new(|msg, ballot_ranges| {
-                    let mut intersection: HashMap<Vec<V>, (u32, u32)> = Default::default();
-                    for (values, &(min, max)) in ballot_ranges {
-                        assert!(min <= max);
-                        if let Some((a, b)) = msg.accepts_commits(values, min, max) {
-                            assert!(a <= b);
-                            intersection.insert(values.clone(), (a, b));
-                        }
-                    }
-                    intersection
-                }),
+                test_fn: BallotRangePredicate::intersect_commits(),
             }
         };
 
@@ -1789,6 +2373,23 @@ impl<V: Value, ValidationError: Display> Slot<V, ValidationError> {
     }
 }
 
+/// Takes room for `value` from `room`, the bytes left in outgoing messages, allowing a byte for
+/// the growth of the set's length prefix. Returns false if there is not enough room.
+fn take_room<V: Value>(room: &mut Option<usize>, value: &V) -> bool {
+    match room {
+        None => true,
+        Some(bytes_left) => {
+            let size = serialized_size(value) + 1;
+            if size <= *bytes_left {
+                *bytes_left -= size;
+                true
+            } else {
+                false
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod nominate_protocol_tests {
     use super::*;
@@ -2365,12 +2966,89 @@ mod nominate_protocol_tests {
             assert_eq!(emitted, expected);
         }
     }
-}
+
+    #[test_with_logger]
+    /// The slot should stop voting to nominate new values once they would not fit in
+    /// max_msg_size, combine fewer confirmed nominated values if their ballot would not fit, and
+    /// still send a message that is too large anyway.
+    fn test_max_msg_size(logger: Logger) {
+        let (local_node, _node_2, _node_3) = three_node_cycle();
+        let values: BTreeSet<u32> = (1000..2000).collect();
+
+        let get_nominating_slot = |max_msg_size: Option<usize>| {
+            let mut slot = Slot::<u32, TransactionValidationError>::new(
+                local_node.0.clone(),
+                local_node.1.clone(),
+                2,
+                Arc::new(trivial_validity_fn),
+                Arc::new(trivial_combine_fn),
+                logger.clone(),
+            );
+            slot.max_priority_peers.insert(local_node.0.clone());
+            slot.max_msg_size = max_msg_size;
+            slot
+        };
+
+        // Without a limit, every proposed value is nominated.
+        let unlimited = get_nominating_slot(None)
+            .propose_values(&values)
+            .expect("slot.propose_values failed")
+            .expect("No message emitted");
+        assert_eq!(unlimited.votes_or_accepts_nominated(), Some(values.clone()));
+
+        // With a limit, the slot votes for as many values as fit.
+        let max_msg_size = unlimited.estimated_size() / 10;
+        let mut slot = get_nominating_slot(Some(max_msg_size));
+        let emitted = slot
+            .propose_values(&values)
+            .expect("slot.propose_values failed")
+            .expect("No message emitted");
+        let voted = emitted.votes_or_accepts_nominated().unwrap();
+        assert!(emitted.estimated_size() <= max_msg_size);
+        assert!(voted.len() > 10 && voted.len() < values.len());
+        assert_eq!(slot.X, voted.iter().cloned().collect::<HashSet<u32>>());
+
+        // Values voted for are never withdrawn as more values are proposed.
+        let more_values: BTreeSet<u32> = (0..1000).collect();
+        if let Some(emitted) = slot
+            .propose_values(&more_values)
+            .expect("slot.propose_values failed")
+        {
+            assert!(emitted.estimated_size() <= max_msg_size);
+        }
+        assert!(voted.iter().all(|value| slot.X.contains(value)));
+
+        // Confirmed nominated values are combined into a smaller ballot that fits.
+        let mut slot = get_nominating_slot(Some(max_msg_size));
+        slot.Z = values.iter().cloned().collect();
+        let combined = slot.combine_Z().expect("No values combined");
+        assert!(!combined.is_empty() && combined.len() < values.len());
+        assert!(slot.ballot_fits(&combined));
+        assert_eq!(
+            combined,
+            values
+                .iter()
+                .cloned()
+                .take(combined.len())
+                .collect::<Vec<u32>>()
+        );
+
+        // Accepted nominated values are not trimmed, so a message that is too large is still
+        // sent.
+        let mut slot = get_nominating_slot(Some(max_msg_size));
+        slot.Y = values.iter().cloned().collect();
+        let emitted = slot
+            .propose_values(&btreeset! {1})
+            .expect("slot.propose_values failed")
+            .expect("No message emitted");
+        assert!(emitted.estimated_size() > max_msg_size);
+    }
+}
 
 #[cfg(test)]
 mod ballot_protocol_tests {
     use super::*;
-    use crate::{core_types::*, quorum_set::*, test_utils::*};
+    use crate::{clock::MockClock, core_types::*, quorum_set::*, test_utils::*};
     use maplit::{btreeset, hashset};
     use mc_common::logger::test_with_logger;
     use pretty_assertions::assert_eq;
@@ -2612,6 +3290,575 @@ mod ballot_protocol_tests {
         }
     }
 
+    #[test_with_logger]
+    // The slot should track the latest message from each peer.
+    fn test_tracked_messages(logger: Logger) {
+        let (node_2, node_3) = (test_node_id(2), test_node_id(3));
+        let mut slot = get_slot(
+            1,
+            &test_node_id(1),
+            &QuorumSet::new_with_node_ids(2, vec![node_2.clone(), node_3.clone()]),
+            logger,
+        );
+        assert!(slot.tracked_messages().is_empty());
+
+        let nominate = |node_id: &NodeID, values: Vec<u32>| {
+            Msg::new(
+                node_id.clone(),
+                QuorumSet::new_with_node_ids(1, vec![test_node_id(1)]),
+                1,
+                Topic::Nominate(NominatePayload {
+                    X: BTreeSet::from_iter(values),
+                    Y: BTreeSet::default(),
+                }),
+            )
+        };
+
+        slot.handle_message(&nominate(&node_2, vec![1000])).unwrap();
+        slot.handle_message(&nominate(&node_3, vec![2000])).unwrap();
+        slot.handle_message(&nominate(&node_2, vec![1000, 3000]))
+            .unwrap();
+
+        let mut expected = vec![
+            nominate(&node_2, vec![1000, 3000]),
+            nominate(&node_3, vec![2000]),
+        ];
+        expected.sort_by(|a, b| a.sender_id.cmp(&b.sender_id));
+        assert_eq!(slot.tracked_messages(), expected);
+    }
+
+    #[test_with_logger]
+    // Flooding a slot with distinct ballots should not grow the tracked ballots beyond the cap,
+    // but should keep nominations, messages from quorum set members, and messages for the local
+    // node's values.
+    fn test_max_tracked_ballots(logger: Logger) {
+        let members: Vec<NodeID> = (2..5).map(test_node_id).collect();
+        let strangers: Vec<NodeID> = (5..35).map(test_node_id).collect();
+        let mut slot = get_slot(
+            1,
+            &test_node_id(1),
+            &QuorumSet::new_with_node_ids(3, members.clone()),
+            logger,
+        );
+        slot.max_tracked_ballots = Some(10);
+
+        // Pretend the local node accepted a ballot.
+        let accepted = Ballot::new(1, &[1000]);
+        slot.B = accepted.clone();
+        slot.P = Some(accepted.clone());
+
+        let prepare = |ballot: Ballot<u32>| {
+            Topic::Prepare(PreparePayload {
+                B: ballot,
+                P: None,
+                PP: None,
+                CN: 0,
+                HN: 0,
+            })
+        };
+        let send = |slot: &mut Slot<u32, TransactionValidationError>, sender: &NodeID, topic| {
+            let msg = Msg::new(
+                sender.clone(),
+                QuorumSet::new_with_node_ids(1, vec![test_node_id(1)]),
+                1,
+                topic,
+            );
+            slot.handle_message(&msg).unwrap();
+        };
+
+        // A member, a stranger on a higher ballot with the local node's values, and a stranger
+        // that is still nominating.
+        send(&mut slot, &members[0], prepare(Ballot::new(1, &[2000])));
+        send(&mut slot, &strangers[0], prepare(Ballot::new(5, &[1000])));
+        send(
+            &mut slot,
+            &strangers[1],
+            Topic::NominatePrepare(
+                NominatePayload {
+                    X: btreeset! {3000},
+                    Y: BTreeSet::default(),
+                },
+                PreparePayload {
+                    B: Ballot::new(1, &[3000]),
+                    P: None,
+                    PP: None,
+                    CN: 0,
+                    HN: 0,
+                },
+            ),
+        );
+
+        // The remaining strangers flood the slot.
+        for (i, stranger) in strangers.iter().enumerate().skip(2) {
+            let ballot = Ballot::new(i as u32 + 1, &[5000 + i as u32]);
+            send(&mut slot, stranger, prepare(ballot));
+            assert!(slot.get_metrics().num_tracked_ballots <= 10);
+        }
+
+        let metrics = slot.get_metrics();
+        assert_eq!(metrics.num_tracked_ballots, 10);
+        assert_eq!(metrics.num_ballot_evictions, strangers.len() - 9);
+        assert!(slot.M.contains_key(&members[0]));
+        assert!(slot.M.contains_key(&strangers[0]));
+        assert!(slot.M.contains_key(&strangers[1]));
+    }
+
+    #[test_with_logger]
+    // Z should be combined in ascending order, however widely each value was nominated, since
+    // peers track different messages.
+    fn test_combine_Z_in_ascending_order(logger: Logger) {
+        let (node_2, node_3, node_4) = (test_node_id(2), test_node_id(3), test_node_id(4));
+        let mut slot = Slot::<u32, TransactionValidationError>::new(
+            test_node_id(1),
+            QuorumSet::new_with_node_ids(2, vec![node_2.clone(), node_3.clone(), node_4.clone()]),
+            1,
+            Arc::new(trivial_validity_fn),
+            Arc::new(|values: &[u32]| Ok(values[..1].to_vec())),
+            logger,
+        );
+
+        for (node_id, value) in vec![(node_2, 2000), (node_3, 2000), (node_4, 1000)] {
+            let msg = Msg::new(
+                node_id,
+                QuorumSet::empty(),
+                1,
+                Topic::Nominate(NominatePayload {
+                    X: btreeset! {value},
+                    Y: BTreeSet::default(),
+                }),
+            );
+            slot.M.insert(msg.sender_id.clone(), msg);
+        }
+        slot.Z = hashset! {1000, 2000};
+
+        assert_eq!(slot.combine_Z(), Some(vec![1000]));
+    }
+
+    #[test_with_logger]
+    // A combine_fn that returns no values should not produce a ballot, since a ballot must have
+    // values.
+    fn test_combine_Z_empty(logger: Logger) {
+        let node_2 = test_node_id(2);
+        let mut slot = Slot::<u32, TransactionValidationError>::new(
+            test_node_id(1),
+            QuorumSet::new_with_node_ids(1, vec![node_2.clone()]),
+            1,
+            Arc::new(trivial_validity_fn),
+            Arc::new(|_values: &[u32]| Ok(vec![])),
+            logger,
+        );
+
+        let msg = Msg::new(
+            node_2,
+            QuorumSet::new_with_node_ids(1, vec![test_node_id(1)]),
+            1,
+            Topic::Nominate(NominatePayload {
+                X: BTreeSet::default(),
+                Y: btreeset! {1000},
+            }),
+        );
+        let emitted = slot
+            .handle_message(&msg)
+            .expect("failed handling msg")
+            .expect("slot should emit a message");
+        assert_eq!(slot.Z, hashset! {1000});
+        assert_eq!(slot.combine_Z(), None);
+        assert!(slot.B.X.is_empty());
+        assert_eq!(emitted.votes_or_accepts_nominated(), Some(btreeset! {1000}));
+    }
+
+    #[test_with_logger]
+    // Calling process_timeouts again before the clock advances should not fire the timers again.
+    fn test_process_timeouts_is_idempotent(logger: Logger) {
+        let clock = Arc::new(MockClock::default());
+        let node_2 = test_node_id(2);
+        let mut slot = Slot::<u32, TransactionValidationError>::new(
+            test_node_id(1),
+            QuorumSet::new_with_node_ids(1, vec![node_2]),
+            1,
+            Arc::new(trivial_validity_fn),
+            Arc::new(trivial_combine_fn),
+            logger,
+        );
+        slot.set_clock(clock.clone());
+
+        slot.Z = HashSet::from_iter(vec![1234, 5678]);
+        slot.B = Ballot::new(1, &[1234, 5678]);
+        slot.next_ballot_at = Some(clock.now() + slot.base_ballot_interval);
+
+        // Nothing is due yet.
+        assert!(slot.process_timeouts().is_empty());
+        assert_eq!(slot.B.N, 1);
+
+        clock.advance(slot.base_ballot_interval * 2);
+
+        // The ballot timer fires once.
+        let msgs = slot.process_timeouts();
+        assert_eq!(msgs.len(), 1);
+        assert_eq!(slot.B.N, 2);
+
+        // Without advancing the clock, a second call does nothing.
+        assert!(slot.process_timeouts().is_empty());
+        assert_eq!(slot.B.N, 2);
+    }
+
+    #[test_with_logger]
+    // Slots with the same configuration should agree on the nomination leaders.
+    fn test_nomination_leaders(logger: Logger) {
+        let quorum_set = QuorumSet::new_with_node_ids(
+            2,
+            vec![test_node_id(2), test_node_id(3), test_node_id(4)],
+        );
+        let new_slot = || {
+            Slot::<u32, TransactionValidationError>::new(
+                test_node_id(1),
+                quorum_set.clone(),
+                1,
+                Arc::new(trivial_validity_fn),
+                Arc::new(trivial_combine_fn),
+                logger.clone(),
+            )
+        };
+        let slot_a = new_slot();
+        let mut slot_b = new_slot();
+
+        for round in 1..=4 {
+            assert_eq!(
+                slot_a.nomination_leaders(round),
+                slot_b.nomination_leaders(round)
+            );
+        }
+
+        // The first round's leader is the one the slot starts with.
+        assert_eq!(
+            slot_a.nomination_leaders(1),
+            slot_a.max_priority_peers.iter().cloned().collect()
+        );
+
+        // After a nomination timeout, the slot's leaders match those for round 2.
+        slot_b.next_nominate_round_at = Some(Instant::now() - Duration::from_secs(1));
+        slot_b.process_timeouts();
+        assert_eq!(slot_b.nominate_round, 2);
+        assert_eq!(
+            slot_b.nomination_leaders(2),
+            slot_b.max_priority_peers.iter().cloned().collect()
+        );
+        assert_eq!(slot_a.nomination_leaders(2), slot_b.nomination_leaders(2));
+    }
+
+    #[test_with_logger]
+    // Transcripts should capture emitted messages and pinpoint where two slots diverge.
+    fn test_transcript(logger: Logger) {
+        let node_1 = test_node_id(1);
+        let node_2 = test_node_id(2);
+        let new_slot = |combine_fn: CombineFn<u32, TransactionValidationError>| {
+            let mut slot = Slot::<u32, TransactionValidationError>::new(
+                node_1.clone(),
+                QuorumSet::new_with_node_ids(1, vec![node_2.clone()]),
+                1,
+                Arc::new(trivial_validity_fn),
+                combine_fn,
+                logger.clone(),
+            );
+            slot.max_priority_peers.insert(node_2.clone());
+            slot
+        };
+        let run = |slot: &mut Slot<u32, TransactionValidationError>| {
+            for (X, Y) in vec![
+                (btreeset! {1000, 2000}, btreeset! {}),
+                (btreeset! {}, btreeset! {1000, 2000}),
+            ] {
+                let msg = Msg::new(
+                    node_2.clone(),
+                    QuorumSet::new_with_node_ids(1, vec![node_1.clone()]),
+                    1,
+                    Topic::Nominate(NominatePayload { X, Y }),
+                );
+                slot.handle_messages(&[msg]).expect("failed handling msg");
+            }
+        };
+
+        let mut slot = new_slot(Arc::new(trivial_combine_fn));
+        run(&mut slot);
+        let transcript = slot.transcript();
+        assert_eq!(transcript.slot_index, 1);
+        assert_eq!(transcript.msgs.len(), 2);
+        assert_eq!(transcript.msgs.last(), slot.last_sent_msg.as_ref());
+
+        // Transcripts survive serialization.
+        let bytes = mc_util_serial::serialize(&transcript).unwrap();
+        assert_eq!(
+            mc_util_serial::deserialize::<Transcript<u32>>(&bytes).unwrap(),
+            transcript
+        );
+
+        // An identical slot produces an identical transcript.
+        let mut same = new_slot(Arc::new(trivial_combine_fn));
+        run(&mut same);
+        assert_eq!(transcript.diff(&same.transcript()), None);
+
+        // A slot that combines values differently nominates the same values, but prepares a
+        // different ballot.
+        let combine_max: CombineFn<u32, TransactionValidationError> =
+            Arc::new(|values: &[u32]| Ok(values.iter().max().into_iter().cloned().collect()));
+        let mut modified = new_slot(combine_max);
+        run(&mut modified);
+        let diff = transcript
+            .diff(&modified.transcript())
+            .expect("transcripts should differ");
+        let ballot_values = |msg: Option<Msg<u32>>| match msg.map(|msg| msg.topic) {
+            Some(Topic::NominatePrepare(_, prepare)) => Some(prepare.B.X),
+            _ => None,
+        };
+        assert_eq!(diff.step, 1);
+        assert_eq!(ballot_values(diff.ours), Some(vec![1000, 2000]));
+        assert_eq!(ballot_values(diff.theirs), Some(vec![2000]));
+
+        // A truncated transcript differs at the first missing message.
+        let mut truncated = transcript.clone();
+        truncated.msgs.pop();
+        assert_eq!(transcript.diff(&truncated).map(|diff| diff.step), Some(1));
+    }
+
+    #[test_with_logger]
+    // time_in_current_phase should measure from when the slot entered its phase.
+    fn test_time_in_current_phase(logger: Logger) {
+        let clock = Arc::new(MockClock::default());
+        let node_1 = test_node_id(1);
+        let node_2 = test_node_id(2);
+        let mut slot = Slot::<u32, TransactionValidationError>::new(
+            node_1.clone(),
+            QuorumSet::new_with_node_ids(1, vec![node_2.clone()]),
+            1,
+            Arc::new(trivial_validity_fn),
+            Arc::new(trivial_combine_fn),
+            logger,
+        );
+        slot.set_clock(clock.clone());
+
+        clock.advance(Duration::from_secs(3));
+        assert_eq!(slot.phase, Phase::NominatePrepare);
+        assert_eq!(slot.time_in_current_phase(), Duration::from_secs(3));
+
+        // Node 2 has accepted prepare(b), which is enough for node 1 to confirm it.
+        let ballot = Ballot::new(1, &[1234]);
+        let msg = Msg::new(
+            node_2,
+            QuorumSet::new_with_node_ids(1, vec![node_1]),
+            1,
+            Topic::Prepare(PreparePayload {
+                B: ballot.clone(),
+                P: Some(ballot),
+                PP: None,
+                CN: 0,
+                HN: 0,
+            }),
+        );
+        slot.handle_messages(&[msg]).expect("failed handling msg");
+        assert_eq!(slot.phase, Phase::Prepare);
+        assert_eq!(slot.time_in_current_phase(), Duration::from_secs(0));
+
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(slot.time_in_current_phase(), Duration::from_secs(5));
+    }
+
+    #[test_with_logger]
+    // A supplied timeout_fn should replace the linear ballot timeout.
+    fn test_timeout_fn(logger: Logger) {
+        let clock = Arc::new(MockClock::default());
+        let node_2 = test_node_id(2);
+        let mut slot = Slot::<u32, TransactionValidationError>::new(
+            test_node_id(1),
+            QuorumSet::new_with_node_ids(1, vec![node_2.clone()]),
+            1,
+            Arc::new(trivial_validity_fn),
+            Arc::new(trivial_combine_fn),
+            logger,
+        );
+        slot.set_clock(clock.clone());
+
+        // By default, the timeout grows linearly.
+        assert_eq!(slot.ballot_timeout(1), Duration::from_secs(1));
+        assert_eq!(slot.ballot_timeout(5), Duration::from_secs(5));
+        assert_eq!(slot.ballot_timeout(10), Duration::from_secs(10));
+
+        // Exponential backoff from 100ms.
+        slot.set_timeout_fn(Arc::new(|counter| {
+            Duration::from_millis(100) * 2u32.pow(counter.saturating_sub(1))
+        }));
+        assert_eq!(slot.ballot_timeout(1), Duration::from_millis(100));
+        assert_eq!(slot.ballot_timeout(5), Duration::from_millis(1600));
+        assert_eq!(slot.ballot_timeout(10), Duration::from_millis(51200));
+
+        // The ballot timer is armed with the supplied timeout.
+        slot.Z = HashSet::from_iter(vec![1234]);
+        slot.B = Ballot::new(4, &[1234]);
+        let msg = Msg::new(
+            node_2,
+            QuorumSet::new_with_node_ids(1, vec![test_node_id(1)]),
+            1,
+            Topic::Prepare(PreparePayload {
+                B: Ballot::new(4, &[1234]),
+                P: None,
+                PP: None,
+                CN: 0,
+                HN: 0,
+            }),
+        );
+        slot.handle_message(&msg).expect("Failed handling msg");
+        assert_eq!(
+            slot.next_ballot_at,
+            Some(clock.now() + Duration::from_millis(1600))
+        );
+    }
+
+    #[test_with_logger]
+    // Ballot timeouts should stop incrementing the counter once it reaches max_ballot_counter.
+    fn test_max_ballot_counter(logger: Logger) {
+        let clock = Arc::new(MockClock::default());
+        let mut slot = Slot::<u32, TransactionValidationError>::new(
+            test_node_id(1),
+            QuorumSet::new_with_node_ids(1, vec![test_node_id(2)]),
+            1,
+            Arc::new(trivial_validity_fn),
+            Arc::new(trivial_combine_fn),
+            logger,
+        );
+        slot.set_clock(clock.clone());
+        slot.max_ballot_counter = Some(3);
+
+        slot.Z = HashSet::from_iter(vec![1234, 5678]);
+        slot.B = Ballot::new(1, &[1234, 5678]);
+
+        // Each timeout increments the counter until it reaches the cap.
+        for expected_counter in 2..=3 {
+            assert!(!slot.get_metrics().counter_capped);
+            slot.next_ballot_at = Some(clock.now() + slot.base_ballot_interval);
+            clock.advance(slot.base_ballot_interval * 2);
+            assert_eq!(slot.process_timeouts().len(), 1);
+            assert_eq!(slot.B.N, expected_counter);
+        }
+        assert!(slot.get_metrics().counter_capped);
+
+        // Further timeouts leave the counter at the cap.
+        for _ in 0..3 {
+            slot.next_ballot_at = Some(clock.now() + slot.base_ballot_interval);
+            clock.advance(slot.base_ballot_interval * 2);
+            slot.process_timeouts();
+            assert_eq!(slot.B.N, 3);
+        }
+
+        let metrics = slot.get_metrics();
+        assert!(metrics.counter_capped);
+        assert_eq!(metrics.bN, 3);
+        assert_eq!(metrics.num_timeouts, 5);
+    }
+
+    #[test_with_logger]
+    // The phase and ballot counter in the slot's metrics should follow the ballot protocol.
+    fn test_metrics_phase_transitions(logger: Logger) {
+        let node_1 = test_node_id(1);
+        let node_2 = test_node_id(2);
+        let mut slot = Slot::<u32, TransactionValidationError>::new(
+            node_1.clone(),
+            QuorumSet::new_with_node_ids(1, vec![node_2.clone()]),
+            1,
+            Arc::new(trivial_validity_fn),
+            Arc::new(trivial_combine_fn),
+            logger,
+        );
+        let node_2_quorum_set = QuorumSet::new_with_node_ids(1, vec![node_1]);
+        let ballot = Ballot::new(1, &[1234]);
+
+        let metrics = slot.get_metrics();
+        assert_eq!(metrics.phase, Phase::NominatePrepare);
+        assert_eq!(metrics.bN, 0);
+
+        // Node 2 accepts the ballot prepared, so the local node confirms it prepared.
+        let msg = Msg::new(
+            node_2.clone(),
+            node_2_quorum_set.clone(),
+            1,
+            Topic::Prepare(PreparePayload {
+                B: ballot.clone(),
+                P: Some(ballot.clone()),
+                PP: None,
+                CN: 0,
+                HN: 0,
+            }),
+        );
+        slot.handle_message(&msg).expect("Failed handling msg");
+        let metrics = slot.get_metrics();
+        assert_eq!(metrics.phase, Phase::Prepare);
+        assert_eq!(metrics.bN, 1);
+
+        // Node 2 votes to commit the ballot, so the local node accepts it committed.
+        let msg = Msg::new(
+            node_2.clone(),
+            node_2_quorum_set.clone(),
+            1,
+            Topic::Prepare(PreparePayload {
+                B: ballot.clone(),
+                P: Some(ballot.clone()),
+                PP: None,
+                CN: 1,
+                HN: 1,
+            }),
+        );
+        slot.handle_message(&msg).expect("Failed handling msg");
+        let metrics = slot.get_metrics();
+        assert_eq!(metrics.phase, Phase::Commit);
+        assert_eq!(metrics.bN, 1);
+
+        // Node 2 accepts the ballot committed, so the local node confirms it committed.
+        let msg = Msg::new(
+            node_2,
+            node_2_quorum_set,
+            1,
+            Topic::Commit(CommitPayload {
+                B: ballot,
+                PN: 1,
+                CN: 1,
+                HN: 1,
+            }),
+        );
+        slot.handle_message(&msg).expect("Failed handling msg");
+        let metrics = slot.get_metrics();
+        assert_eq!(metrics.phase, Phase::Externalize);
+        assert_eq!(metrics.bN, 1);
+    }
+
+    #[test_with_logger]
+    // A clock stepping backwards should not delay timers that are already pending.
+    fn test_process_timeouts_clock_goes_backwards(logger: Logger) {
+        let clock = Arc::new(MockClock::default());
+        let node_2 = test_node_id(2);
+        let mut slot = Slot::<u32, TransactionValidationError>::new(
+            test_node_id(1),
+            QuorumSet::new_with_node_ids(1, vec![node_2]),
+            1,
+            Arc::new(trivial_validity_fn),
+            Arc::new(trivial_combine_fn),
+            logger,
+        );
+        slot.set_clock(clock.clone());
+
+        slot.Z = HashSet::from_iter(vec![1234, 5678]);
+        slot.B = Ballot::new(1, &[1234, 5678]);
+        slot.next_ballot_at = Some(clock.now() + slot.base_ballot_interval);
+
+        // The clock jumps back by more than the ballot interval.
+        clock.rewind(slot.base_ballot_interval * 10);
+        assert!(slot.process_timeouts().is_empty());
+        assert_eq!(slot.B.N, 1);
+
+        // Once the clock has moved forward by the ballot interval, the timer fires.
+        clock.advance(slot.base_ballot_interval * 2);
+        let msgs = slot.process_timeouts();
+        assert_eq!(msgs.len(), 1);
+        assert_eq!(slot.B.N, 2);
+    }
+
     #[test_with_logger]
     // A node that has issued "accept prepare(b)" but not "confirm prepare(b)" should include
     // confirmed nominated values when it advances to the next ballot.
@@ -4402,6 +5649,7 @@ mod tests {
     use super::*;
     use crate::{core_types::*, test_utils::*};
     use mc_common::logger::test_with_logger;
+    use std::iter::FromIterator;
 
     #[test_with_logger]
     // `ballots_accepted_prepared` should return all ballots accepted prepared by any blocking set.
@@ -4466,6 +5714,44 @@ mod tests {
         }
     }
 
+    #[test_with_logger]
+    // The validity function should be called once for each distinct value validated.
+    fn test_validity_fn_call_count(logger: Logger) {
+        let (local_node, node_2, _node_3, _node_4) = fig_2_network();
+
+        let slot_index = 2;
+        let mut slot = Slot::<u32, TransactionValidationError>::new(
+            local_node.0.clone(),
+            local_node.1,
+            slot_index,
+            Arc::new(trivial_validity_fn),
+            Arc::new(trivial_combine_fn),
+            logger,
+        );
+        assert_eq!(slot.get_metrics().num_validity_fn_calls, 0);
+
+        let values = BTreeSet::from_iter(vec![1000, 2000, 3000]);
+        slot.propose_values(&values).expect("propose_values failed");
+        assert_eq!(slot.get_metrics().num_validity_fn_calls, 3);
+
+        // A peer votes for one previously validated value and one new value.
+        let msg = Msg::new(
+            node_2.0.clone(),
+            node_2.1,
+            slot_index,
+            Topic::Nominate(NominatePayload {
+                X: BTreeSet::from_iter(vec![2000, 4000]),
+                Y: BTreeSet::default(),
+            }),
+        );
+        slot.handle_message(&msg).expect("handle_message failed");
+        assert_eq!(slot.get_metrics().num_validity_fn_calls, 4);
+
+        // Proposing the same values again does not call the validity function.
+        slot.propose_values(&values).expect("propose_values failed");
+        assert_eq!(slot.get_metrics().num_validity_fn_calls, 4);
+    }
+
     // TODO: test_ballots_accepted_prepared_quorum
 
     // TODO: test_ballots_confirmed_prepared
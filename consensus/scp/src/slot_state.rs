@@ -5,7 +5,7 @@
 //! inside a `Slot`.
 
 use crate::{
-    core_types::{Ballot, SlotIndex, Value},
+    core_types::{Ballot, GenericNodeId, SlotIndex, Value},
     msg::*,
     slot::{Phase, Slot},
 };
@@ -16,70 +16,72 @@ use std::{
     fmt::Display,
 };
 
-/// Serializable slot state used for debugging purposes.
+/// Serializable slot state used for debugging and crash recovery.
 #[derive(Clone, Serialize, Deserialize)]
-pub struct SlotState<V: Value> {
+pub struct SlotState<V: Value, ID: GenericNodeId = NodeID> {
     /// Current slot number.
-    slot_index: SlotIndex,
+    pub(crate) slot_index: SlotIndex,
 
     /// Local node ID.
-    node_id: NodeID,
+    pub(crate) node_id: ID,
 
     /// List of highest messages from each node.
     /// This is not stored as a HashMap since it simplifies serialization. The node id is part of
     /// the message so that can be derived.
-    M: Vec<Msg<V>>,
+    pub(crate) M: Vec<Msg<V, ID>>,
 
     /// Set of values that have been proposed, but not yet voted for.
-    W: HashSet<V>,
+    pub(crate) W: HashSet<V>,
 
     /// Set of values we have voted to nominate.
-    X: HashSet<V>,
+    pub(crate) X: HashSet<V>,
 
     /// Set of values we have accepted as nominated.
-    Y: HashSet<V>,
+    pub(crate) Y: HashSet<V>,
 
     /// Set of values we have confirmed as nominated.
-    Z: HashSet<V>,
+    pub(crate) Z: HashSet<V>,
 
     /// Current ballot we are trying to pass.
-    B: Ballot<V>,
+    pub(crate) B: Ballot<V>,
 
     /// The highest accepted prepared ballot, if any.
-    P: Option<Ballot<V>>,
+    pub(crate) P: Option<Ballot<V>>,
 
     /// The highest accepted prepared ballot that is less-than-and-incompatible with P.
-    PP: Option<Ballot<V>>,
+    pub(crate) PP: Option<Ballot<V>>,
 
     /// In Prepare: the highest ballot that this node confirms prepared, if any.
     /// In Commit: the highest ballot that this node accepts committed, if any.
     /// In Externalize: The highest ballot that this node confirms committed.
-    H: Option<Ballot<V>>,
+    pub(crate) H: Option<Ballot<V>>,
 
     /// In Prepare: The lowest ballot that this node votes to commit, if any.
     /// In Commit: The lowest ballot that this node accepts committed, if any.
     /// In Externalize: The lowest ballot that this node confirms committed.
     /// Invariant: if C is Some, C \lesssim H \lesssim B
-    C: Option<Ballot<V>>,
+    pub(crate) C: Option<Ballot<V>>,
 
     /// Current phase of the protocol.
-    phase: Phase,
+    pub(crate) phase: Phase,
 
     /// Last message sent by us.
-    last_sent_msg: Option<Msg<V>>,
+    pub(crate) last_sent_msg: Option<Msg<V, ID>>,
 
     /// Max priority peers - nodes from which we listen to value nominations.
-    max_priority_peers: HashSet<NodeID>,
+    pub(crate) max_priority_peers: HashSet<ID>,
 
     /// Current nomination round number.
-    nominate_round: u32,
+    pub(crate) nominate_round: u32,
 
     /// List of values that have been checked to be valid for the current slot.
     /// We can cache this and save on validation calls since the ledger doesn't change during a slot.
-    valid_values: BTreeSet<V>,
+    pub(crate) valid_values: BTreeSet<V>,
 }
-impl<V: Value, ValidationError: Display> From<&Slot<V, ValidationError>> for SlotState<V> {
-    fn from(src: &Slot<V, ValidationError>) -> Self {
+impl<V: Value, ValidationError: Display, ID: GenericNodeId> From<&Slot<V, ValidationError, ID>>
+    for SlotState<V, ID>
+{
+    fn from(src: &Slot<V, ValidationError, ID>) -> Self {
         Self {
             slot_index: src.slot_index,
             node_id: src.node_id.clone(),
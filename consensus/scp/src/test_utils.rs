@@ -1,13 +1,20 @@
 // Copyright (c) 2018-2021 The MobileCoin Foundation
 
 //! Utilities for Stellar Consensus Protocol tests.
-use crate::{core_types::Value, slot::Slot, QuorumSet, SlotIndex};
+use crate::{
+    core_types::{GenericNodeId, NodePriorityKey, Value},
+    node::Node,
+    quorum_set::legacy_responder_id,
+    slot::Slot,
+    QuorumSet, QuorumSetMember, QuorumSetParseError, SlotIndex,
+};
 use mc_common::{logger::Logger, NodeID, ResponderId};
 use mc_crypto_keys::Ed25519Pair;
 use mc_util_from_random::FromRandom;
-use rand::SeedableRng;
+use rand::{seq::SliceRandom, Rng, SeedableRng};
 use rand_hc::Hc128Rng as FixedRng;
-use std::{fmt, str::FromStr, sync::Arc};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{fmt, sync::Arc};
 
 /// Error for transaction validation
 #[derive(Clone)]
@@ -44,6 +51,17 @@ pub fn get_bounded_combine_fn<V: Value>(
     }
 }
 
+/// Creates ResponderId from integer for testing.
+pub fn test_responder_id(node_id: u32) -> ResponderId {
+    legacy_responder_id(node_id)
+}
+
+/// Parses a quorum set in the integer test format, such as `([2],1,2,([1],3,4))`, where `n` is
+/// the responder id `test_responder_id(n)`.
+pub fn test_quorum_set_from_str(src: &str) -> Result<QuorumSet<ResponderId>, QuorumSetParseError> {
+    QuorumSet::from_config_str(src, &test_responder_id)
+}
+
 /// Creates NodeID from integer for testing.
 pub fn test_node_id(node_id: u32) -> NodeID {
     let (node_id, _signer) = test_node_id_and_signer(node_id);
@@ -60,7 +78,7 @@ pub fn test_node_id_and_signer(node_id: u32) -> (NodeID, Ed25519Pair) {
     let signer_keypair = Ed25519Pair::from_random(&mut seeded_rng);
     (
         NodeID {
-            responder_id: ResponderId::from_str(&format!("node{}.test.com:8443", node_id)).unwrap(),
+            responder_id: test_responder_id(node_id),
             public_key: signer_keypair.public_key(),
         },
         signer_keypair,
@@ -68,13 +86,15 @@ pub fn test_node_id_and_signer(node_id: u32) -> (NodeID, Ed25519Pair) {
 }
 
 /// Creates a new slot.
-pub fn get_slot(
+pub fn get_slot<
+    ID: GenericNodeId + NodePriorityKey + Serialize + DeserializeOwned + Send + 'static,
+>(
     slot_index: SlotIndex,
-    node_id: &NodeID,
-    quorum_set: &QuorumSet,
+    node_id: &ID,
+    quorum_set: &QuorumSet<ID>,
     logger: Logger,
-) -> Slot<u32, TransactionValidationError> {
-    Slot::<u32, TransactionValidationError>::new(
+) -> Slot<u32, TransactionValidationError, ID> {
+    Slot::<u32, TransactionValidationError, ID>::new(
         node_id.clone(),
         quorum_set.clone(),
         slot_index,
@@ -84,6 +104,25 @@ pub fn get_slot(
     )
 }
 
+/// Creates a new node.
+pub fn get_node<
+    ID: GenericNodeId + NodePriorityKey + Serialize + DeserializeOwned + Send + 'static,
+>(
+    slot_index: SlotIndex,
+    node_id: ID,
+    quorum_set: QuorumSet<ID>,
+    logger: Logger,
+) -> Node<u32, TransactionValidationError, ID> {
+    Node::<u32, TransactionValidationError, ID>::new(
+        node_id,
+        quorum_set,
+        Arc::new(trivial_validity_fn),
+        Arc::new(trivial_combine_fn),
+        slot_index,
+        logger,
+    )
+}
+
 /// Three nodes that form a three-node cycle.
 ///
 /// * Node 1 has the quorum slice {1,2}, where {2} is a blocking set.
@@ -162,3 +201,81 @@ pub fn three_node_dense_graph() -> (
     );
     (node_1, node_2, node_3)
 }
+
+/// A network of `num_nodes` nodes where each node's quorum set is every other node at
+/// `threshold`.
+///
+/// * Node `i` has the quorum slice containing itself and any `threshold` other nodes.
+/// * Any two quorums intersect, because `threshold` is required to satisfy
+///   `2 * (threshold + 1) > num_nodes`.
+pub fn symmetric_network(num_nodes: u32, threshold: u32) -> Vec<(NodeID, QuorumSet)> {
+    assert!(
+        threshold < num_nodes,
+        "threshold {} must be less than the number of nodes {}",
+        threshold,
+        num_nodes
+    );
+    assert!(
+        2 * (threshold + 1) > num_nodes,
+        "threshold {} does not guarantee quorum intersection among {} nodes",
+        threshold,
+        num_nodes
+    );
+
+    (1..=num_nodes)
+        .map(|i| {
+            let peers = (1..=num_nodes)
+                .filter(|j| *j != i)
+                .map(test_node_id)
+                .collect();
+            (
+                test_node_id(i),
+                QuorumSet::new_with_node_ids(threshold, peers),
+            )
+        })
+        .collect()
+}
+
+/// A random valid quorum set over `test_node_id(1)` to `test_node_id(num_nodes)`.
+///
+/// * Each node appears exactly once, and inner sets are nested at most `max_depth` deep.
+/// * Each set's threshold is a random majority of its members.
+pub fn random_quorum_set<R: Rng>(num_nodes: usize, max_depth: usize, rng: &mut R) -> QuorumSet {
+    assert!(num_nodes > 0, "a quorum set needs at least one node");
+    let mut ids: Vec<u32> = (1..=num_nodes as u32).collect();
+    ids.shuffle(rng);
+    random_quorum_set_helper(&ids, max_depth, rng)
+}
+
+fn random_quorum_set_helper<R: Rng>(ids: &[u32], max_depth: usize, rng: &mut R) -> QuorumSet {
+    let mut members = Vec::new();
+    let mut remaining = ids;
+    while !remaining.is_empty() {
+        // Split off a chunk, but never all of `ids`, so that each set has several members.
+        let max_chunk = if remaining.len() == ids.len() {
+            (remaining.len() - 1).max(1)
+        } else {
+            remaining.len()
+        };
+        let chunk_len = rng.gen_range(1, max_chunk + 1);
+        let (chunk, rest) = remaining.split_at(chunk_len);
+        remaining = rest;
+
+        if chunk.len() > 1 && max_depth > 0 {
+            members.push(QuorumSetMember::InnerSet(random_quorum_set_helper(
+                chunk,
+                max_depth - 1,
+                rng,
+            )));
+        } else {
+            members.extend(
+                chunk
+                    .iter()
+                    .map(|id| QuorumSetMember::Node(test_node_id(*id))),
+            );
+        }
+    }
+
+    let threshold = rng.gen_range(members.len() / 2 + 1, members.len() + 1) as u32;
+    QuorumSet::new(threshold, members)
+}
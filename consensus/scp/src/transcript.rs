@@ -0,0 +1,53 @@
+// Copyright (c) 2018-2021 The MobileCoin Foundation
+
+//! The sequence of messages emitted by a slot, for comparing protocol behavior across runs or
+//! implementations.
+
+use crate::{
+    core_types::{GenericNodeId, SlotIndex, Value},
+    msg::Msg,
+};
+use mc_common::NodeID;
+use serde::{Deserialize, Serialize};
+
+/// The messages a slot emitted, in the order it emitted them.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Transcript<V: Value, ID: GenericNodeId = NodeID> {
+    /// The slot that emitted the messages.
+    pub slot_index: SlotIndex,
+
+    /// The local node ID.
+    pub node_id: ID,
+
+    /// Emitted messages, oldest first.
+    pub msgs: Vec<Msg<V, ID>>,
+}
+
+/// The first point at which two transcripts diverge.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TranscriptDiff<V: Value, ID: GenericNodeId = NodeID> {
+    /// Index of the first message that differs.
+    pub step: usize,
+
+    /// The message at `step` in the first transcript, if it has one.
+    pub ours: Option<Msg<V, ID>>,
+
+    /// The message at `step` in the second transcript, if it has one.
+    pub theirs: Option<Msg<V, ID>>,
+}
+
+impl<V: Value, ID: GenericNodeId> Transcript<V, ID> {
+    /// Finds the first message at which `self` and `other` differ, or `None` if they emitted the
+    /// same messages. A transcript that is a prefix of the other differs at the first message
+    /// it lacks.
+    pub fn diff(&self, other: &Self) -> Option<TranscriptDiff<V, ID>> {
+        let len = self.msgs.len().max(other.msgs.len());
+        (0..len)
+            .find(|step| self.msgs.get(*step) != other.msgs.get(*step))
+            .map(|step| TranscriptDiff {
+                step,
+                ours: self.msgs.get(step).cloned(),
+                theirs: other.msgs.get(step).cloned(),
+            })
+    }
+}
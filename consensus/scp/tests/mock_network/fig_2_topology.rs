@@ -0,0 +1,47 @@
+// Copyright (c) 2018-2021 The MobileCoin Foundation
+
+// The example network from the SCP whitepaper.
+
+// We allow dead code because not all integration tests use all of the common code.
+// https://github.com/rust-lang/rust/issues/46379
+#![allow(dead_code)]
+
+use crate::mock_network;
+use mc_common::NodeID;
+use mc_consensus_scp::test_utils;
+use std::collections::HashSet;
+
+///////////////////////////////////////////////////////////////////////////////
+// Figure 2 in the SCP whitepaper
+///////////////////////////////////////////////////////////////////////////////
+
+/// Constructs the four node network of `test_utils::fig_2_network`.
+pub fn fig_2() -> mock_network::NetworkConfig {
+    let (node_1, node_2, node_3, node_4) = test_utils::fig_2_network();
+    let node_ids: Vec<NodeID> = vec![
+        node_1.0.clone(),
+        node_2.0.clone(),
+        node_3.0.clone(),
+        node_4.0.clone(),
+    ];
+
+    let nodes = vec![node_1, node_2, node_3, node_4]
+        .into_iter()
+        .enumerate()
+        .map(|(node_index, (node_id, quorum_set))| {
+            let peers = node_ids
+                .iter()
+                .filter(|other_node_id| **other_node_id != node_id)
+                .cloned()
+                .collect::<HashSet<NodeID>>();
+            mock_network::NodeConfig::new(
+                format!("f{}", node_index + 1),
+                node_id,
+                peers,
+                quorum_set,
+            )
+        })
+        .collect();
+
+    mock_network::NetworkConfig::new("fig2".to_string(), nodes)
+}
@@ -38,3 +38,11 @@ pub fn dense_mesh(
 
     mock_network::NetworkConfig::new(format!("m{}k{}", n, k), nodes)
 }
+
+/// Constructs a mesh network, where each node has all of its peers as validators with a
+/// majority threshold.
+pub fn build(num_nodes: usize) -> mock_network::NetworkConfig {
+    let num_peers = num_nodes.saturating_sub(1);
+    let k = if num_peers == 0 { 0 } else { num_peers / 2 + 1 };
+    dense_mesh(num_nodes, k)
+}
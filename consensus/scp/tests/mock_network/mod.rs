@@ -7,14 +7,17 @@ use mc_common::{
     NodeID,
 };
 use mc_consensus_scp::{
-    core_types::{CombineFn, SlotIndex, ValidityFn},
-    msg::Msg,
+    core_types::{Ballot, CombineFn, SlotIndex, ValidityFn},
+    msg::{Msg, NominatePayload, Topic},
     node::{Node, ScpNode},
     quorum_set::QuorumSet,
     test_utils,
 };
+use rand::{Rng, SeedableRng};
+use rand_hc::Hc128Rng as FixedRng;
 use std::{
     collections::{BTreeSet, HashMap, HashSet},
+    ops::Range,
     sync::{Arc, Mutex},
     thread,
     thread::JoinHandle,
@@ -22,12 +25,38 @@ use std::{
 };
 
 pub mod cyclic_topology;
+pub mod fig_2_topology;
 pub mod mesh_topology;
 pub mod metamesh_topology;
+pub mod star_topology;
 
 // Test values are random strings of this length.
 const CHARACTERS_PER_VALUE: usize = 10;
 
+// When messages can be dropped, nodes retain and retransmit this many externalized slots.
+const RETRANSMIT_EXTERNALIZED_SLOTS: usize = 8;
+
+/// A range of rounds, where a round is one `scp_timebase` interval since the network started.
+pub type RoundRange = Range<u64>;
+
+/// How a Byzantine node deviates from the protocol. The node runs SCP honestly, but its
+/// outgoing messages are altered.
+// Not all integration tests construct every variant.
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ByzantineBehavior {
+    /// Sends its real messages to half of its peers, and messages for conflicting values to the
+    /// other half.
+    Equivocate,
+
+    /// Sends each ballot message only once it has a newer one, so peers always see its previous
+    /// ballot state. Nomination messages are sent as usual.
+    SendStaleBallots,
+
+    /// Sends nothing.
+    Silent,
+}
+
 // Controls test parameters
 #[derive(Clone)]
 pub struct TestOptions {
@@ -61,6 +90,38 @@ pub struct TestOptions {
 
     /// The values combine function to use (typically trivial)
     pub combine_fn: CombineFn<String, test_utils::TransactionValidationError>,
+
+    /// During each range of rounds, nodes are split into the given groups, and messages between
+    /// nodes in different groups are dropped. Nodes not listed in any group are unaffected.
+    pub partition_schedule: Vec<(RoundRange, Vec<Vec<NodeID>>)>,
+
+    /// Rounds during which no node may externalize a block, e.g. while a partition leaves no
+    /// group with a quorum.
+    pub stalled_rounds: RoundRange,
+
+    /// Delivery latency for messages sent from the first node to the second. Links are
+    /// directional, so a link can be slower one way than the other. Links not listed deliver
+    /// messages immediately.
+    pub link_latencies: HashMap<(NodeID, NodeID), Duration>,
+
+    /// Probability, between 0 and 1, that any one message delivery to a peer is dropped.
+    /// When messages can be dropped, every node re-broadcasts its latest message and its
+    /// Externalize messages for recent slots once per `scp_timebase`, as a real network layer
+    /// would retry.
+    pub drop_rate: f64,
+
+    /// Each message delivery to a peer is delayed by a random duration of up to this much, in
+    /// addition to its link latency. Random delays also reorder messages.
+    pub max_delay: Duration,
+
+    /// Seed for every random decision in the test: the submitted values, and each node's drops
+    /// and delays. Defaults to the seed of `mc_util_test_helper::get_seeded_rng`, so the same
+    /// values are submitted as before the seed was configurable.
+    pub rng_seed: [u8; 32],
+
+    /// Nodes that deviate from the protocol. Values are submitted only to the other, honest
+    /// nodes, and only their ledgers are checked.
+    pub byzantine_nodes: HashMap<NodeID, ByzantineBehavior>,
 }
 
 impl TestOptions {
@@ -75,6 +136,13 @@ impl TestOptions {
             scp_timebase: Duration::from_millis(1000),
             validity_fn: Arc::new(test_utils::trivial_validity_fn::<String>),
             combine_fn: Arc::new(test_utils::get_bounded_combine_fn::<String>(100)),
+            partition_schedule: Vec::new(),
+            stalled_rounds: 0..0,
+            link_latencies: HashMap::default(),
+            drop_rate: 0.0,
+            max_delay: Duration::from_millis(0),
+            rng_seed: [7u8; 32],
+            byzantine_nodes: HashMap::default(),
         }
     }
 }
@@ -124,9 +192,24 @@ pub struct SCPNetwork {
     names_map: HashMap<NodeID, String>,
     nodes_map: Arc<Mutex<HashMap<NodeID, SCPNode>>>,
     shared_data_map: HashMap<NodeID, Arc<Mutex<SCPNodeSharedData>>>,
+    deliveries_map: HashMap<NodeID, Arc<Mutex<Vec<(NodeID, Option<Duration>)>>>>,
+    rng_seed: [u8; 32],
+    start: Instant,
     logger: Logger,
 }
 
+/// The random decisions made during a test, which depend only on `TestOptions::rng_seed`.
+#[allow(dead_code)]
+pub struct MessageTrace {
+    /// The values submitted to the network.
+    pub values: Vec<String>,
+
+    /// For each node, the peer and fate of each message delivery, in the order drawn: None if
+    /// the message was dropped, or its random delay. Threads are scheduled nondeterministically,
+    /// so runs with the same seed may make different numbers of deliveries.
+    pub deliveries: HashMap<NodeID, Vec<(NodeID, Option<Duration>)>>,
+}
+
 impl SCPNetwork {
     // creates a new network simulation
     pub fn new(network_config: &NetworkConfig, test_options: &TestOptions, logger: Logger) -> Self {
@@ -135,20 +218,71 @@ impl SCPNetwork {
             names_map: HashMap::default(),
             nodes_map: Arc::new(Mutex::new(HashMap::default())),
             shared_data_map: HashMap::default(),
+            deliveries_map: HashMap::default(),
+            rng_seed: test_options.rng_seed,
+            start: Instant::now(),
             logger: logger.clone(),
         };
 
+        let start = scp_network.start;
+        let partition_schedule = Arc::new(test_options.partition_schedule.clone());
+        let link_latencies = Arc::new(test_options.link_latencies.clone());
+        let scp_timebase_millis = test_options.scp_timebase.as_millis().max(1);
+
+        // Each node's drops and delays are drawn from its own generator, seeded from this one.
+        let mut seed_rng = FixedRng::from_seed(scp_network.rng_seed);
+
         for node_config in network_config.nodes.iter() {
             assert!(!node_config.peers.contains(&node_config.id));
 
             let nodes_map_clone = Arc::clone(&scp_network.nodes_map);
             let peers_clone = node_config.peers.clone();
+            let partition_schedule_clone = Arc::clone(&partition_schedule);
+            let link_latencies_clone = Arc::clone(&link_latencies);
+            let byzantine_behavior = test_options.byzantine_nodes.get(&node_config.id).cloned();
+            let last_ballot_msg: Mutex<Option<Msg<String>>> = Mutex::new(None);
+            let link_faults = LinkFaults {
+                drop_rate: test_options.drop_rate,
+                max_delay: test_options.max_delay,
+                rng: Mutex::new(FixedRng::from_seed(seed_rng.gen())),
+                deliveries: Arc::new(Mutex::new(Vec::new())),
+            };
+            scp_network
+                .deliveries_map
+                .insert(node_config.id.clone(), Arc::clone(&link_faults.deliveries));
 
             let (node, join_handle) = SCPNode::new(
                 node_config.clone(),
                 test_options,
                 Arc::new(move |logger, msg| {
-                    SCPNetwork::broadcast_msg(logger, &nodes_map_clone, &peers_clone, msg)
+                    let round = (start.elapsed().as_millis() / scp_timebase_millis) as u64;
+                    let reachable_peers: HashSet<NodeID> = peers_clone
+                        .iter()
+                        .filter(|peer_id| {
+                            !SCPNetwork::is_partitioned(
+                                &partition_schedule_clone,
+                                round,
+                                &msg.sender_id,
+                                peer_id,
+                            )
+                        })
+                        .cloned()
+                        .collect();
+                    for (peers, msg) in SCPNetwork::deviate(
+                        byzantine_behavior,
+                        &last_ballot_msg,
+                        reachable_peers,
+                        msg,
+                    ) {
+                        SCPNetwork::broadcast_msg(
+                            logger.clone(),
+                            &nodes_map_clone,
+                            &peers,
+                            &link_latencies_clone,
+                            &link_faults,
+                            msg,
+                        )
+                    }
                 }),
                 0, // first slot index
                 logger.clone(),
@@ -219,6 +353,20 @@ impl SCPNetwork {
             .clone()
     }
 
+    /// The round in which each block in the node's ledger was externalized.
+    fn get_externalized_rounds(&self, node_id: &NodeID, scp_timebase: Duration) -> Vec<u64> {
+        let scp_timebase_millis = scp_timebase.as_millis().max(1);
+        self.shared_data_map
+            .get(node_id)
+            .expect("could not find node_id in shared_data_map")
+            .lock()
+            .expect("lock failed on shared_data getting externalized rounds")
+            .externalized_at
+            .iter()
+            .map(|at| (at.duration_since(self.start).as_millis() / scp_timebase_millis) as u64)
+            .collect()
+    }
+
     fn get_ledger_size(&self, node_id: &NodeID) -> usize {
         self.shared_data_map
             .get(node_id)
@@ -228,29 +376,164 @@ impl SCPNetwork {
             .ledger_size()
     }
 
+    /// Returns true if messages from `from` to `to` are dropped during `round`.
+    fn is_partitioned(
+        partition_schedule: &[(RoundRange, Vec<Vec<NodeID>>)],
+        round: u64,
+        from: &NodeID,
+        to: &NodeID,
+    ) -> bool {
+        partition_schedule
+            .iter()
+            .filter(|(rounds, _groups)| rounds.contains(&round))
+            .any(|(_rounds, groups)| {
+                let from_group = groups.iter().position(|group| group.contains(from));
+                let to_group = groups.iter().position(|group| group.contains(to));
+                match (from_group, to_group) {
+                    (Some(from_group), Some(to_group)) => from_group != to_group,
+                    _ => false,
+                }
+            })
+    }
+
+    /// The messages a node with the given behavior sends in place of `msg`, and to whom.
+    fn deviate(
+        behavior: Option<ByzantineBehavior>,
+        last_ballot_msg: &Mutex<Option<Msg<String>>>,
+        peers: HashSet<NodeID>,
+        msg: Msg<String>,
+    ) -> Vec<(HashSet<NodeID>, Msg<String>)> {
+        match behavior {
+            None => vec![(peers, msg)],
+            Some(ByzantineBehavior::Silent) => Vec::new(),
+            Some(ByzantineBehavior::Equivocate) => {
+                let mut sorted_peers: Vec<NodeID> = peers.into_iter().collect();
+                sorted_peers.sort();
+                let other_half = sorted_peers.split_off(sorted_peers.len() / 2);
+                let mut conflicting_msg = msg.clone();
+                conflicting_msg.topic = Self::conflicting_topic(&msg.topic);
+                vec![
+                    (sorted_peers.into_iter().collect(), msg),
+                    (other_half.into_iter().collect(), conflicting_msg),
+                ]
+            }
+            Some(ByzantineBehavior::SendStaleBallots) => {
+                if let Topic::Nominate(_) = msg.topic {
+                    return vec![(peers, msg)];
+                }
+                let mut last_ballot_msg = last_ballot_msg
+                    .lock()
+                    .expect("lock failed on last_ballot_msg");
+                match last_ballot_msg.replace(msg) {
+                    Some(stale_msg) => vec![(peers, stale_msg)],
+                    None => Vec::new(),
+                }
+            }
+        }
+    }
+
+    /// `topic` with each of its ballots and value sets reduced to their first value.
+    fn conflicting_topic(topic: &Topic<String>) -> Topic<String> {
+        let ballot = |ballot: &Ballot<String>| Ballot {
+            N: ballot.N,
+            X: ballot.X.iter().take(1).cloned().collect(),
+        };
+        let nominate = |payload: &NominatePayload<String>| NominatePayload {
+            X: payload.X.iter().take(1).cloned().collect(),
+            Y: payload.Y.iter().take(1).cloned().collect(),
+        };
+
+        let mut topic = topic.clone();
+        match &mut topic {
+            Topic::Nominate(nominate_payload) => *nominate_payload = nominate(nominate_payload),
+            Topic::NominatePrepare(nominate_payload, prepare_payload) => {
+                *nominate_payload = nominate(nominate_payload);
+                prepare_payload.B = ballot(&prepare_payload.B);
+                prepare_payload.P = prepare_payload.P.as_ref().map(ballot);
+                prepare_payload.PP = prepare_payload.PP.as_ref().map(ballot);
+            }
+            Topic::Prepare(prepare_payload) => {
+                prepare_payload.B = ballot(&prepare_payload.B);
+                prepare_payload.P = prepare_payload.P.as_ref().map(ballot);
+                prepare_payload.PP = prepare_payload.PP.as_ref().map(ballot);
+            }
+            Topic::Commit(commit_payload) => commit_payload.B = ballot(&commit_payload.B),
+            Topic::Externalize(externalize_payload) => {
+                externalize_payload.C = ballot(&externalize_payload.C)
+            }
+        }
+        topic
+    }
+
     fn broadcast_msg(
         logger: Logger,
         nodes_map: &Arc<Mutex<HashMap<NodeID, SCPNode>>>,
         peers: &HashSet<NodeID>,
+        link_latencies: &HashMap<(NodeID, NodeID), Duration>,
+        link_faults: &LinkFaults,
         msg: Msg<String>,
     ) {
         let mut nodes_map = nodes_map
             .lock()
             .expect("lock failed on nodes_map in broadcast");
+        let mut rng = link_faults
+            .rng
+            .lock()
+            .expect("lock failed on rng in broadcast");
+        let mut deliveries = link_faults
+            .deliveries
+            .lock()
+            .expect("lock failed on deliveries in broadcast");
 
         log::trace!(logger, "(broadcast) {}", msg);
 
         let amsg = Arc::new(msg);
 
+        // Draw each peer's fate in a fixed order, so that it depends only on the seed.
+        let mut peers: Vec<&NodeID> = peers.iter().collect();
+        peers.sort();
+
         for peer_id in peers {
-            nodes_map
-                .get_mut(&peer_id)
-                .expect("failed to get peer from nodes_map")
-                .send_msg(amsg.clone());
+            if link_faults.drop_rate > 0.0 && rng.gen_bool(link_faults.drop_rate) {
+                log::trace!(logger, "(  drop   ) {} to {}", amsg, peer_id);
+                deliveries.push((peer_id.clone(), None));
+                continue;
+            }
+
+            let mut delay = Duration::from_millis(0);
+            if link_faults.max_delay > Duration::from_millis(0) {
+                delay = link_faults.max_delay.mul_f64(rng.gen::<f64>());
+            }
+            deliveries.push((peer_id.clone(), Some(delay)));
+
+            let latency = link_latencies
+                .get(&(amsg.sender_id.clone(), peer_id.clone()))
+                .cloned()
+                .unwrap_or_default()
+                + delay;
+
+            let node = nodes_map
+                .get_mut(peer_id)
+                .expect("failed to get peer from nodes_map");
+            if latency > Duration::from_millis(0) {
+                node.send_delayed_msg(amsg.clone(), Instant::now() + latency);
+            } else {
+                node.send_msg(amsg.clone());
+            }
         }
     }
 }
 
+// Random message loss and delay on a node's outgoing links.
+struct LinkFaults {
+    drop_rate: f64,
+    max_delay: Duration,
+    rng: Mutex<FixedRng>,
+
+    // The peer and fate of each delivery drawn from `rng`, for `MessageTrace`.
+    deliveries: Arc<Mutex<Vec<(NodeID, Option<Duration>)>>>,
+}
+
 impl Drop for SCPNetwork {
     fn drop(&mut self) {
         self.stop_all();
@@ -260,6 +543,7 @@ impl Drop for SCPNetwork {
 enum SCPNodeTaskMessage {
     Value(String),
     Msg(Arc<Msg<String>>),
+    DelayedMsg(Arc<Msg<String>>, Instant),
     StopTrigger,
 }
 
@@ -267,6 +551,9 @@ enum SCPNodeTaskMessage {
 #[derive(Clone)]
 struct SCPNodeSharedData {
     pub ledger: Vec<Vec<String>>,
+
+    /// When each block in the ledger was externalized.
+    pub externalized_at: Vec<Instant>,
 }
 
 impl SCPNodeSharedData {
@@ -293,7 +580,10 @@ impl SCPNode {
 
         let scp_node = Self {
             sender,
-            shared_data: Arc::new(Mutex::new(SCPNodeSharedData { ledger: Vec::new() })),
+            shared_data: Arc::new(Mutex::new(SCPNodeSharedData {
+                ledger: Vec::new(),
+                externalized_at: Vec::new(),
+            })),
         };
 
         let mut thread_local_node = Node::new(
@@ -308,6 +598,11 @@ impl SCPNode {
 
         let thread_shared_data = Arc::clone(&scp_node.shared_data);
         let max_slot_proposed_values: usize = test_options.max_slot_proposed_values;
+        let retransmit = test_options.drop_rate > 0.0;
+        let scp_timebase = test_options.scp_timebase;
+        if retransmit {
+            thread_local_node.set_max_externalized_slots(RETRANSMIT_EXTERNALIZED_SLOTS);
+        }
 
         let mut current_slot: usize = 0;
         let mut total_broadcasts: u32 = 0;
@@ -319,6 +614,13 @@ impl SCPNode {
                     // All values that have not yet been externalized.
                     let mut pending_values: Vec<String> = Vec::default();
 
+                    // The last message this node broadcast, and when it last retransmitted.
+                    let mut last_sent_msg: Option<Msg<String>> = None;
+                    let mut last_retransmit = Instant::now();
+
+                    // Messages that are in flight on a slow link, with their delivery times.
+                    let mut delayed_msgs: Vec<(Instant, Arc<Msg<String>>)> = Vec::default();
+
                     'main_loop: loop {
                         // Compare to byzantine_ledger::tick()
                         // there pending values are proposed before incoming msg is handled
@@ -337,6 +639,11 @@ impl SCPNode {
                                     incoming_msg_option = Some(msg);
                                 }
 
+                                // Hold a message until its delivery time
+                                SCPNodeTaskMessage::DelayedMsg(msg, deliver_at) => {
+                                    delayed_msgs.push((deliver_at, msg));
+                                }
+
                                 // Stop the thread
                                 SCPNodeTaskMessage::StopTrigger => {
                                     break 'main_loop;
@@ -348,6 +655,20 @@ impl SCPNode {
                             }
                         };
 
+                        // Otherwise, deliver the earliest delayed message that is due
+                        if incoming_msg_option.is_none() {
+                            let now = Instant::now();
+                            let due = delayed_msgs
+                                .iter()
+                                .enumerate()
+                                .filter(|(_, (deliver_at, _))| *deliver_at <= now)
+                                .min_by_key(|(_, (deliver_at, _))| *deliver_at)
+                                .map(|(index, _)| index);
+                            if let Some(index) = due {
+                                incoming_msg_option = Some(delayed_msgs.remove(index).1);
+                            }
+                        }
+
                         // Propose pending values submitted to our node
                         if !pending_values.is_empty() {
                             let values_to_propose: BTreeSet<String> = pending_values
@@ -361,6 +682,7 @@ impl SCPNode {
                                 .expect("propose_values() failed");
 
                             if let Some(outgoing_msg) = outgoing_msg {
+                                last_sent_msg = Some(outgoing_msg.clone());
                                 (broadcast_msg_fn)(logger.clone(), outgoing_msg);
                                 total_broadcasts += 1;
                             }
@@ -373,6 +695,7 @@ impl SCPNode {
                                 .expect("handle_message() failed");
 
                             if let Some(outgoing_msg) = outgoing_msg {
+                                last_sent_msg = Some(outgoing_msg.clone());
                                 (broadcast_msg_fn)(logger.clone(), outgoing_msg);
                                 total_broadcasts += 1;
                             }
@@ -383,10 +706,35 @@ impl SCPNode {
                             thread_local_node.process_timeouts().into_iter().collect();
 
                         for outgoing_msg in timeout_msgs {
+                            last_sent_msg = Some(outgoing_msg.clone());
                             (broadcast_msg_fn)(logger.clone(), outgoing_msg);
                             total_broadcasts += 1;
                         }
 
+                        // Retransmit periodically if messages may have been lost. Receivers
+                        // ignore messages they have already seen. Peers that missed the end of a
+                        // recent slot need its Externalize message.
+                        if retransmit && last_retransmit.elapsed() >= scp_timebase {
+                            last_retransmit = Instant::now();
+                            let current_slot_index = thread_local_node.current_slot_index();
+                            let mut retransmit_msgs: Vec<Msg<String>> = (current_slot_index
+                                .saturating_sub(RETRANSMIT_EXTERNALIZED_SLOTS as SlotIndex)
+                                ..current_slot_index)
+                                .filter_map(|slot_index| {
+                                    thread_local_node.re_externalize(slot_index)
+                                })
+                                .collect();
+                            if let Some(msg) = &last_sent_msg {
+                                if !retransmit_msgs.contains(msg) {
+                                    retransmit_msgs.push(msg.clone());
+                                }
+                            }
+                            for outgoing_msg in retransmit_msgs {
+                                (broadcast_msg_fn)(logger.clone(), outgoing_msg);
+                                total_broadcasts += 1;
+                            }
+                        }
+
                         // Check if the current slot is done
                         if let Some(new_block) =
                             thread_local_node.get_externalized_values(current_slot as SlotIndex)
@@ -402,6 +750,7 @@ impl SCPNode {
                                 .expect("thread_shared_data lock failed");
 
                             locked_shared_data.ledger.push(new_block);
+                            locked_shared_data.externalized_at.push(Instant::now());
 
                             let ledger_size = locked_shared_data.ledger_size();
 
@@ -463,6 +812,23 @@ impl SCPNode {
         }
     }
 
+    /// Feed message from the network to this node's consensus task, to be handled no earlier
+    /// than `deliver_at`.
+    pub fn send_delayed_msg(&self, msg: Arc<Msg<String>>, deliver_at: Instant) {
+        match self
+            .sender
+            .try_send(SCPNodeTaskMessage::DelayedMsg(msg, deliver_at))
+        {
+            Ok(_) => {}
+            Err(err) => match err {
+                crossbeam_channel::TrySendError::Disconnected(_) => {}
+                _ => {
+                    panic!("send_delayed_msg failed: {:?}", err);
+                }
+            },
+        }
+    }
+
     pub fn send_stop(&self) {
         match self.sender.try_send(SCPNodeTaskMessage::StopTrigger) {
             Ok(_) => {}
@@ -486,9 +852,21 @@ pub fn skip_slow_tests() -> bool {
 }
 
 /// Injects values to a network and waits for completion
-pub fn build_and_test(network_config: &NetworkConfig, test_options: &TestOptions, logger: Logger) {
+pub fn build_and_test(
+    network_config: &NetworkConfig,
+    test_options: &TestOptions,
+    logger: Logger,
+) -> MessageTrace {
     let simulation = SCPNetwork::new(network_config, test_options, logger.clone());
 
+    // log the seed so that a failure can be replayed with TestOptions::rng_seed
+    log::info!(
+        logger,
+        "( testing ) rng_seed for {}: {:?}",
+        network_config.name,
+        simulation.rng_seed,
+    );
+
     if test_options.submit_in_parallel {
         log::info!(
             logger,
@@ -507,7 +885,7 @@ pub fn build_and_test(network_config: &NetworkConfig, test_options: &TestOptions
 
     let start = Instant::now();
 
-    let mut rng = mc_util_test_helper::get_seeded_rng();
+    let mut rng = FixedRng::from_seed(simulation.rng_seed);
     let mut values = Vec::<String>::with_capacity(test_options.values_to_submit);
     for _i in 0..test_options.values_to_submit {
         let value = mc_util_test_helper::random_str(&mut rng, CHARACTERS_PER_VALUE);
@@ -520,11 +898,17 @@ pub fn build_and_test(network_config: &NetworkConfig, test_options: &TestOptions
         test_options.values_to_submit
     );
 
-    // get a vector of the node_ids
-    let node_ids: Vec<NodeID> = network_config.nodes.iter().map(|n| n.id.clone()).collect();
+    // get a vector of the node_ids of honest nodes
+    let node_ids: Vec<NodeID> = network_config
+        .nodes
+        .iter()
+        .map(|n| n.id.clone())
+        .filter(|node_id| !test_options.byzantine_nodes.contains_key(node_id))
+        .collect();
+    assert!(!node_ids.is_empty(), "the network has no honest nodes");
 
     // check that all ledgers start empty
-    for n in 0..network_config.nodes.len() {
+    for n in 0..node_ids.len() {
         assert!(simulation.get_ledger_size(&node_ids[n]) == 0);
     }
 
@@ -535,12 +919,12 @@ pub fn build_and_test(network_config: &NetworkConfig, test_options: &TestOptions
 
         if test_options.submit_in_parallel {
             // simulate broadcast of values to all nodes in parallel
-            for n in 0..network_config.nodes.len() {
+            for n in 0..node_ids.len() {
                 simulation.push_value(&node_ids[n], &values[i]);
             }
         } else {
             // submit values to nodes in sequence
-            let n = i % network_config.nodes.len();
+            let n = i % node_ids.len();
             simulation.push_value(&node_ids[n], &values[i]);
         }
 
@@ -698,7 +1082,31 @@ pub fn build_and_test(network_config: &NetworkConfig, test_options: &TestOptions
         }
     }
 
+    // Check that no blocks were externalized while the network was expected to stall
+    for node_id in node_ids.iter() {
+        let externalized_rounds =
+            simulation.get_externalized_rounds(node_id, test_options.scp_timebase);
+        if let Some(round) = externalized_rounds
+            .iter()
+            .find(|round| test_options.stalled_rounds.contains(round))
+        {
+            log::error!(
+                simulation.logger,
+                "node {} externalized a block in round {}, during stalled rounds {:?}",
+                simulation
+                    .names_map
+                    .get(node_id)
+                    .expect("could not find node_id"),
+                round,
+                test_options.stalled_rounds,
+            );
+            // panic
+            panic!("test failed due to progress while the network should stall");
+        }
+    }
+
     // drop the simulation here so that MESSAGES log statements appear before results
+    let deliveries_map = simulation.deliveries_map.clone();
     drop(simulation);
 
     // csv for scripting use
@@ -724,4 +1132,18 @@ pub fn build_and_test(network_config: &NetworkConfig, test_options: &TestOptions
 
     // allow log to flush
     std::thread::sleep(test_options.log_flush_delay);
+
+    MessageTrace {
+        values,
+        deliveries: deliveries_map
+            .into_iter()
+            .map(|(node_id, deliveries)| {
+                let deliveries = deliveries
+                    .lock()
+                    .expect("lock failed on deliveries building trace")
+                    .clone();
+                (node_id, deliveries)
+            })
+            .collect(),
+    }
 }
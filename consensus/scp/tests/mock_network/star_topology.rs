@@ -0,0 +1,48 @@
+// Copyright (c) 2018-2021 The MobileCoin Foundation
+
+// Hub-and-spoke network topologies.
+
+// We allow dead code because not all integration tests use all of the common code.
+// https://github.com/rust-lang/rust/issues/46379
+#![allow(dead_code)]
+
+use crate::mock_network;
+use mc_common::NodeID;
+use mc_consensus_scp::{test_utils, QuorumSet};
+use std::collections::HashSet;
+
+///////////////////////////////////////////////////////////////////////////////
+// Star Topology
+///////////////////////////////////////////////////////////////////////////////
+
+/// Constructs a star network, where node 0 is the hub and nodes 1 to `num_leaves` are leaves.
+///
+/// Each leaf trusts only the hub, and the hub trusts a majority of the leaves.
+pub fn build(num_leaves: usize) -> mock_network::NetworkConfig {
+    assert!(num_leaves > 0, "a star network needs at least one leaf");
+    let num_nodes = num_leaves + 1;
+    let hub_id = test_utils::test_node_id(0);
+
+    let mut nodes = Vec::<mock_network::NodeConfig>::new();
+    for node_index in 0..num_nodes {
+        let peers_vector = (0..num_nodes)
+            .filter(|other_node_index| other_node_index != &node_index)
+            .map(|other_node_index| test_utils::test_node_id(other_node_index as u32))
+            .collect::<Vec<NodeID>>();
+
+        let quorum_set = if node_index == 0 {
+            QuorumSet::new_with_node_ids(num_leaves as u32 / 2 + 1, peers_vector.clone())
+        } else {
+            QuorumSet::new_with_node_ids(1, vec![hub_id.clone()])
+        };
+
+        nodes.push(mock_network::NodeConfig::new(
+            format!("s{}", node_index),
+            test_utils::test_node_id(node_index as u32),
+            peers_vector.iter().cloned().collect::<HashSet<NodeID>>(),
+            quorum_set,
+        ));
+    }
+
+    mock_network::NetworkConfig::new(format!("star{}", num_leaves), nodes)
+}
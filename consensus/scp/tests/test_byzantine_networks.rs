@@ -0,0 +1,46 @@
+// Copyright (c) 2018-2021 The MobileCoin Foundation
+
+mod mock_network;
+
+use mc_common::logger::{test_with_logger, Logger};
+use mc_consensus_scp::test_utils;
+use mock_network::ByzantineBehavior;
+use serial_test_derive::serial;
+use std::time::Duration;
+
+/// Performs a consensus test for a 4-node mesh where node 0 has the given behavior.
+///
+/// Each node requires 2 of its 3 peers, so any two quorums share an honest node.
+fn byzantine_test_helper(behavior: ByzantineBehavior, logger: Logger) {
+    if mock_network::skip_slow_tests() {
+        return;
+    }
+
+    let mut test_options = mock_network::TestOptions::new();
+    test_options.values_to_submit = 1000;
+    test_options.scp_timebase = Duration::from_millis(20);
+    test_options
+        .byzantine_nodes
+        .insert(test_utils::test_node_id(0), behavior);
+
+    let network_config = mock_network::mesh_topology::dense_mesh(4, 2);
+    mock_network::build_and_test(&network_config, &test_options, logger);
+}
+
+#[test_with_logger]
+#[serial]
+fn byzantine_4k2_equivocate(logger: Logger) {
+    byzantine_test_helper(ByzantineBehavior::Equivocate, logger);
+}
+
+#[test_with_logger]
+#[serial]
+fn byzantine_4k2_stale_ballots(logger: Logger) {
+    byzantine_test_helper(ByzantineBehavior::SendStaleBallots, logger);
+}
+
+#[test_with_logger]
+#[serial]
+fn byzantine_4k2_silent(logger: Logger) {
+    byzantine_test_helper(ByzantineBehavior::Silent, logger);
+}
@@ -0,0 +1,74 @@
+// Copyright (c) 2018-2021 The MobileCoin Foundation
+
+mod mock_network;
+
+use mc_common::logger::{test_with_logger, Logger};
+use serial_test_derive::serial;
+use std::time::Duration;
+
+#[test_with_logger]
+#[serial]
+fn fig_2_drop_20_percent(logger: Logger) {
+    if mock_network::skip_slow_tests() {
+        return;
+    }
+
+    let mut test_options = mock_network::TestOptions::new();
+    test_options.values_to_submit = 1000;
+    test_options.scp_timebase = Duration::from_millis(20);
+    test_options.drop_rate = 0.2;
+    test_options.allowed_test_time = Duration::from_secs(60);
+
+    let network_config = mock_network::fig_2_topology::fig_2();
+    mock_network::build_and_test(&network_config, &test_options, logger);
+}
+
+#[test_with_logger]
+#[serial]
+fn mesh_5k3_random_delays(logger: Logger) {
+    if mock_network::skip_slow_tests() {
+        return;
+    }
+
+    let mut test_options = mock_network::TestOptions::new();
+    test_options.values_to_submit = 1000;
+    test_options.scp_timebase = Duration::from_millis(20);
+    test_options.max_delay = Duration::from_millis(10);
+
+    let network_config = mock_network::mesh_topology::dense_mesh(5, 3);
+    mock_network::build_and_test(&network_config, &test_options, logger);
+}
+
+#[test_with_logger]
+#[serial]
+fn mesh_4k3_same_seed_same_trace(logger: Logger) {
+    if mock_network::skip_slow_tests() {
+        return;
+    }
+
+    let mut test_options = mock_network::TestOptions::new();
+    test_options.values_to_submit = 200;
+    test_options.scp_timebase = Duration::from_millis(20);
+    test_options.drop_rate = 0.1;
+    test_options.max_delay = Duration::from_millis(5);
+    test_options.rng_seed = [42u8; 32];
+
+    let network_config = mock_network::mesh_topology::dense_mesh(4, 3);
+    let first = mock_network::build_and_test(&network_config, &test_options, logger.clone());
+    let second = mock_network::build_and_test(&network_config, &test_options, logger.clone());
+
+    // Thread scheduling decides how many messages are sent, so one run's deliveries at each node
+    // must be a prefix of the other's.
+    assert_eq!(first.values, second.values);
+    assert_eq!(first.deliveries.len(), second.deliveries.len());
+    for (node_id, first_deliveries) in first.deliveries.iter() {
+        let second_deliveries = &second.deliveries[node_id];
+        let len = first_deliveries.len().min(second_deliveries.len());
+        assert!(len > 0);
+        assert_eq!(first_deliveries[..len], second_deliveries[..len]);
+    }
+
+    test_options.rng_seed = [43u8; 32];
+    let other = mock_network::build_and_test(&network_config, &test_options, logger);
+    assert_ne!(first.values, other.values);
+}
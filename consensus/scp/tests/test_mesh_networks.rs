@@ -3,7 +3,9 @@
 mod mock_network;
 
 use mc_common::logger::{test_with_logger, Logger};
+use mc_consensus_scp::test_utils;
 use serial_test_derive::serial;
+use std::{collections::HashMap, time::Duration};
 
 /// Performs a consensus test for a mesh network of (n) nodes.
 fn mesh_test_helper(
@@ -64,3 +66,101 @@ fn mesh_5k3(logger: Logger) {
 fn mesh_5k4(logger: Logger) {
     mesh_test_helper(5, 4, logger);
 }
+
+#[test_with_logger]
+#[serial]
+fn mesh_5_majority(logger: Logger) {
+    if mock_network::skip_slow_tests() {
+        return;
+    }
+
+    let mut test_options = mock_network::TestOptions::new();
+    test_options.values_to_submit = 10000;
+    let network_config = mock_network::mesh_topology::build(5);
+    mock_network::build_and_test(&network_config, &test_options, logger);
+}
+
+#[test_with_logger]
+#[serial]
+fn mesh_5k3_partitioned_then_healed(logger: Logger) {
+    if mock_network::skip_slow_tests() {
+        return;
+    }
+
+    let mut test_options = mock_network::TestOptions::new();
+    test_options.values_to_submit = 1000;
+    test_options.scp_timebase = Duration::from_millis(20);
+
+    // Neither group contains a quorum, so no values are externalized until the partition heals.
+    let group = |indexes: &[u32]| {
+        indexes
+            .iter()
+            .map(|i| test_utils::test_node_id(*i))
+            .collect()
+    };
+    test_options.partition_schedule = vec![(0..25, vec![group(&[0, 1]), group(&[2, 3, 4])])];
+    test_options.stalled_rounds = 0..25;
+
+    let network_config = mock_network::mesh_topology::dense_mesh(5, 3);
+    mock_network::build_and_test(&network_config, &test_options, logger);
+}
+
+#[test_with_logger]
+#[serial]
+fn mesh_4k3_split_in_half_then_healed(logger: Logger) {
+    if mock_network::skip_slow_tests() {
+        return;
+    }
+
+    let mut test_options = mock_network::TestOptions::new();
+    test_options.values_to_submit = 1000;
+    test_options.scp_timebase = Duration::from_millis(20);
+
+    // Each half has two of the three nodes a quorum needs, so the network stalls until it heals.
+    let group = |indexes: &[u32]| {
+        indexes
+            .iter()
+            .map(|i| test_utils::test_node_id(*i))
+            .collect()
+    };
+    test_options.partition_schedule = vec![(0..25, vec![group(&[0, 1]), group(&[2, 3])])];
+    test_options.stalled_rounds = 0..25;
+
+    let network_config = mock_network::mesh_topology::dense_mesh(4, 3);
+    mock_network::build_and_test(&network_config, &test_options, logger);
+}
+
+#[test_with_logger]
+#[serial]
+fn mesh_5k3_asymmetric_latency(logger: Logger) {
+    if mock_network::skip_slow_tests() {
+        return;
+    }
+
+    let mut test_options = mock_network::TestOptions::new();
+    test_options.values_to_submit = 1000;
+    test_options.scp_timebase = Duration::from_millis(20);
+
+    // Every link gets a fixed latency of 0-12ms, and each direction of a link differs.
+    let mut link_latencies = HashMap::default();
+    for from in 0..5u32 {
+        for to in 0..5u32 {
+            if from != to {
+                let latency = Duration::from_millis(u64::from((from * 7 + to * 3) % 13));
+                link_latencies.insert(
+                    (test_utils::test_node_id(from), test_utils::test_node_id(to)),
+                    latency,
+                );
+            }
+        }
+    }
+    assert_ne!(
+        link_latencies[&(test_utils::test_node_id(0), test_utils::test_node_id(1))],
+        link_latencies[&(test_utils::test_node_id(1), test_utils::test_node_id(0))]
+    );
+    test_options.link_latencies = link_latencies;
+
+    // build_and_test checks that every node externalizes every value, in identical blocks.
+    let network_config = mock_network::mesh_topology::dense_mesh(5, 3);
+    mock_network::build_and_test(&network_config, &test_options, logger);
+}
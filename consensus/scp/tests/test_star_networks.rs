@@ -0,0 +1,37 @@
+// Copyright (c) 2018-2021 The MobileCoin Foundation
+
+mod mock_network;
+
+use mc_common::logger::{test_with_logger, Logger};
+use serial_test_derive::serial;
+
+/// Performs a consensus test for a star network with `num_leaves` leaves around one hub.
+fn star_test_helper(num_leaves: usize, logger: Logger) {
+    if num_leaves > 2 && mock_network::skip_slow_tests() {
+        return;
+    }
+
+    let mut test_options = mock_network::TestOptions::new();
+    test_options.values_to_submit = 10000;
+
+    let network_config = mock_network::star_topology::build(num_leaves);
+    mock_network::build_and_test(&network_config, &test_options, logger.clone());
+}
+
+#[test_with_logger]
+#[serial]
+fn star_1(logger: Logger) {
+    star_test_helper(1, logger);
+}
+
+#[test_with_logger]
+#[serial]
+fn star_2(logger: Logger) {
+    star_test_helper(2, logger);
+}
+
+#[test_with_logger]
+#[serial]
+fn star_4(logger: Logger) {
+    star_test_helper(4, logger);
+}